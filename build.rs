@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use a bundled protoc, so that building doesn't need one installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/galileo/faucet/v1/faucet.proto")?;
+    Ok(())
+}
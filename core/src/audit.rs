@@ -0,0 +1,87 @@
+//! A trail of everything the faucet does on behalf of users and administrators, for moderators to
+//! review: every dispense result, every rejected request, and every admin command.
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::responder::Origin;
+
+/// Where to forward audit entries, and the sequence number of the next one.
+static TRAIL: Mutex<(u64, Option<mpsc::UnboundedSender<Entry>>)> = Mutex::new((0, None));
+
+/// Something the faucet did, as recorded in the audit trail.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// The position of the entry in the trail since the faucet started, so that a missing entry
+    /// shows up as a gap.
+    pub seq: u64,
+    /// When it happened.
+    pub time: DateTime<Utc>,
+    /// What kind of thing happened, like `dispense`, `rejected`, or `admin`.
+    pub kind: &'static str,
+    /// The user who made the request or ran the command, if any.
+    pub user_id: Option<u64>,
+    /// The channel the request or command was made in, if any.
+    pub channel_id: Option<u64>,
+    /// The message containing the request, if any.
+    pub message_id: Option<u64>,
+    /// What happened.
+    pub detail: String,
+}
+
+impl Entry {
+    /// The entry, as a message for the audit channel.
+    pub fn message(&self) -> String {
+        format!(
+            "```json\n{}\n```",
+            serde_json::to_string_pretty(self).unwrap_or_default()
+        )
+    }
+}
+
+/// Record that something happened in the audit trail: it's logged, and forwarded to whoever
+/// subscribed to the trail, if anyone.
+pub fn record(
+    kind: &'static str,
+    user_id: Option<u64>,
+    channel_id: Option<u64>,
+    message_id: Option<u64>,
+    detail: impl Into<String>,
+) {
+    let mut trail = TRAIL.lock().unwrap();
+    let entry = Entry {
+        seq: trail.0,
+        time: Utc::now(),
+        kind,
+        user_id,
+        channel_id,
+        message_id,
+        detail: detail.into(),
+    };
+    trail.0 += 1;
+    tracing::debug!(?entry, "audit");
+    if let Some(subscriber) = trail.1.as_ref() {
+        let _ = subscriber.send(entry);
+    }
+}
+
+/// Record something which happened in answer to the request from the given origin.
+pub fn record_request(kind: &'static str, origin: Origin, detail: impl Into<String>) {
+    record(
+        kind,
+        Some(origin.user_id.0),
+        Some(origin.channel_id.0),
+        Some(origin.message_id.0),
+        detail,
+    );
+}
+
+/// Receive every entry recorded in the audit trail from now on.
+pub fn subscribe() -> mpsc::UnboundedReceiver<Entry> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    TRAIL.lock().unwrap().1 = Some(tx);
+    rx
+}
@@ -0,0 +1,59 @@
+//! Identifiers for the things the faucet keeps track of on a chat platform.
+//!
+//! These are numeric, and message ids are expected to increase over time like Discord's
+//! "snowflakes" (see [`MessageId::at`]); frontends for other platforms map their own identifiers
+//! onto them.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+macro_rules! id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub u64);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+id! {
+    /// A user who can make requests.
+    UserId
+}
+
+id! {
+    /// A channel in which requests can be made.
+    ChannelId
+}
+
+id! {
+    /// A message, which might contain a request.
+    MessageId
+}
+
+id! {
+    /// A role a user can have in a server, which can affect how they are treated.
+    RoleId
+}
+
+id! {
+    /// A server (on Discord, a guild), whose administrators can change some settings.
+    ServerId
+}
+
+impl MessageId {
+    /// The smallest possible id of a message sent at the given time, which can be used as a bound
+    /// when walking back through the history of a channel.
+    pub fn at(time: DateTime<Utc>) -> Self {
+        // Discord ids are "snowflakes", whose top bits are milliseconds since the Discord epoch
+        const DISCORD_EPOCH_MILLIS: i64 = 1_420_070_400_000;
+        let millis = (time.timestamp_millis() - DISCORD_EPOCH_MILLIS).max(0) as u64;
+        MessageId(millis << 22)
+    }
+}
@@ -0,0 +1,816 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc::error::SendError, oneshot},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    audit,
+    id::{ChannelId, MessageId, RoleId, ServerId, UserId},
+    locale, metrics,
+    responder::{
+        address_matches, AddressOrAlmost, BatchSchedule, Counterparties, Origin, Request, Response,
+        Routes,
+    },
+    store::{Pending, Undelivered},
+    transport::{self, Transport},
+    Lifecycle, Locale, Store,
+};
+
+mod reply_limit;
+pub use reply_limit::{ReplyLimits, RoleReplyLimit};
+
+mod overrides;
+pub use overrides::{Override, Overrides};
+
+mod sybil;
+pub use sybil::{Cluster, Sybil, SybilPolicy};
+
+mod ownership;
+pub use ownership::{is_answer, signed_message, OwnershipProof, CHALLENGE_TTL, PROVE_COMMAND};
+
+/// The number of recent messages for which we remember which addresses were already handled, so
+/// that edits to those messages can be re-scanned without dispensing twice.
+const SEEN_MESSAGES: usize = 4096;
+
+/// A message which might contain a request, as far as the faucet is concerned, whatever chat
+/// platform it came from.
+#[derive(Debug, Clone)]
+pub struct Incoming {
+    /// The message's id.
+    pub id: MessageId,
+    /// The channel the message was posted in.
+    pub channel_id: ChannelId,
+    /// The server the channel is in, on platforms which have them.
+    pub server_id: Option<ServerId>,
+    /// The author of the message.
+    pub author_id: UserId,
+    /// The author's name, for logging.
+    pub author_name: String,
+    /// The author's roles in the server the message was posted in.
+    pub author_roles: Vec<RoleId>,
+    /// The text of the message.
+    pub content: String,
+}
+
+/// A quick acknowledgement of a message, shown as a reaction to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reaction {
+    /// The message contained a request, which is being processed.
+    Received,
+    /// The author has to wait before making another request.
+    RateLimited,
+    /// Sending to at least one address in the message failed.
+    Failed,
+}
+
+impl Reaction {
+    /// Every kind of reaction.
+    pub const ALL: [Reaction; 3] = [Reaction::Received, Reaction::RateLimited, Reaction::Failed];
+
+    /// The name of this kind of reaction, as used in commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            Reaction::Received => "received",
+            Reaction::RateLimited => "rate-limited",
+            Reaction::Failed => "failed",
+        }
+    }
+
+    /// The emoji to react with, unless a server chooses another.
+    pub fn default_emoji(self) -> &'static str {
+        match self {
+            Reaction::Received => "👀",
+            Reaction::RateLimited => "⏳",
+            Reaction::Failed => "❌",
+        }
+    }
+}
+
+impl FromStr for Reaction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Reaction::ALL
+            .into_iter()
+            .find(|reaction| reaction.name() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown kind of reaction: {}", s))
+    }
+}
+
+/// The conversation on a chat platform in which a message was posted, through which the faucet
+/// answers it. Each frontend implements this for its platform.
+#[async_trait]
+pub trait ChatPlatform: Send + Sync {
+    /// Reply to the message with some text.
+    async fn reply(&self, text: String) -> anyhow::Result<()>;
+
+    /// Show that we're working on a request made in the message.
+    async fn typing(&self) -> anyhow::Result<()>;
+
+    /// Acknowledge the message at a glance, without replying to it.
+    async fn react(&self, reaction: Reaction) -> anyhow::Result<()>;
+
+    /// Tell the author how a slow request is coming along: the first time, by replying to the
+    /// message, and after that by editing that reply, so as not to flood the channel.
+    async fn progress(&self, text: String) -> anyhow::Result<()>;
+
+    /// Keep the author from posting more requests until the given time, because they kept at it
+    /// after we stopped telling them about their rate limit (if the platform has a way to, and
+    /// we're set up to use it).
+    async fn cool_down(&self, until: DateTime<Utc>) -> anyhow::Result<()>;
+
+    /// The transports by which to deliver the result of a request made in the message: replying to
+    /// it, and sending the author receipts directly if asked to.
+    fn transports(&self, dm_receipts: bool) -> Vec<Box<dyn Transport>>;
+
+    /// Whether the message stays in its channel's history, where it can be caught up on and
+    /// replied to later. If not (say, an address submitted through a form), handling it moves no
+    /// checkpoint, and it's never handed off to the next instance.
+    fn in_history(&self) -> bool {
+        true
+    }
+}
+
+/// The default minimum duration between dispensing tokens to a user, shared so that administrators
+/// can change it while the faucet runs.
+#[derive(Debug, Clone)]
+pub struct RateLimit(Arc<Mutex<Duration>>);
+
+impl RateLimit {
+    pub fn new(rate_limit: Duration) -> Self {
+        RateLimit(Arc::new(Mutex::new(rate_limit)))
+    }
+
+    /// The rate limit as it stands.
+    pub fn get(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+
+    /// Change the rate limit, from the next request on.
+    pub fn set(&self, rate_limit: Duration) {
+        *self.0.lock().unwrap() = rate_limit;
+    }
+}
+
+/// Where requests come in: decides which messages to dispense to (rate limiting, and making sure no
+/// message is handled twice), queues them for the responder, and delivers the results.
+pub struct Intake {
+    /// The minimum duration between dispensing tokens to a user.
+    rate_limit: RateLimit,
+    /// Limit of the number of times, per user, we will inform that user of their rate limit.
+    reply_limits: ReplyLimits,
+    /// Limits which differ in particular servers and channels.
+    overrides: Overrides,
+    /// The language to reply in, unless overridden where a message was posted.
+    locale: Locale,
+    /// The other chains whose addresses we look for, to withdraw tokens to over IBC.
+    counterparties: Counterparties,
+    /// History of requests we answered for token dispersal, with a timestamp and the number of
+    /// times we've told the user about the rate limit (so that eventually we can stop replying if
+    /// they keep asking).
+    send_history: Arc<Mutex<VecDeque<(UserId, Instant, usize)>>>,
+    /// Recently handled messages, so that we can tell what's new when one of them is edited.
+    seen: Arc<Mutex<IndexMap<MessageId, Seen>>>,
+    /// Persistent state, where we record the last message handled in each channel.
+    store: Store,
+    /// Whether to send users a receipt by direct message after dispensing to them.
+    dm_receipts: bool,
+    /// Where else to deliver the result of every request, besides replying to it.
+    transports: Vec<Box<dyn Transport>>,
+    /// Whether we're still accepting requests, and which are in flight.
+    lifecycle: Lifecycle,
+    /// The queues of requests for the responders, by the channel they're made in.
+    requests: Routes,
+    /// How long a request can take before we tell its author we're still working on it (and how
+    /// often to update them after that), if at all.
+    progress_after: Option<Duration>,
+    /// When requests are dispensed, if they're collected into batches.
+    batch: Option<BatchSchedule>,
+    /// Watches for many accounts requesting tokens for the same addresses, if asked to.
+    sybil: Option<Sybil>,
+    /// The roles whose members' requests jump ahead of others waiting in the queue.
+    priority_roles: Vec<RoleId>,
+    /// Requires proof that the addresses in large requests are their author's own, if asked to.
+    ownership: Option<OwnershipProof>,
+}
+
+/// The counter of chat events received, by kind and channel (named from when the faucet only ran
+/// on Discord, so as not to break existing dashboards).
+pub const EVENTS: &str = "galileo_discord_events_total";
+
+/// The counter of chat events ignored, by channel and the rule which caused them to be ignored.
+pub const FILTERED: &str = "galileo_discord_events_filtered_total";
+
+/// The counter of requests which took longer than the progress threshold, by channel.
+pub const SLOW_DISPENSES: &str = "galileo_slow_dispenses_total";
+
+/// Count an event of the given kind in a channel.
+pub fn count_event(kind: &'static str, channel_id: ChannelId) {
+    metrics::increment(
+        EVENTS,
+        &[
+            ("kind", kind.to_string()),
+            ("channel", channel_id.to_string()),
+        ],
+    );
+}
+
+/// Count an event in a channel being ignored because of the given rule.
+pub fn count_filtered(rule: &'static str, channel_id: ChannelId) {
+    tracing::trace!(?rule, ?channel_id, "ignoring event");
+    metrics::increment(
+        FILTERED,
+        &[
+            ("channel", channel_id.to_string()),
+            ("rule", rule.to_string()),
+        ],
+    );
+}
+
+/// What we know about a message we've already handled.
+#[derive(Debug, Clone, Default)]
+struct Seen {
+    /// Everything in the message which looked like an address, across all its edits so far.
+    matches: HashSet<String>,
+}
+
+impl Intake {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        rate_limit: RateLimit,
+        reply_limits: ReplyLimits,
+        overrides: Overrides,
+        locale: Locale,
+        counterparties: Counterparties,
+        store: Store,
+        dm_receipts: bool,
+        transports: Vec<Box<dyn Transport>>,
+        lifecycle: Lifecycle,
+        requests: Routes,
+        progress_after: Option<Duration>,
+        batch: Option<BatchSchedule>,
+        sybil: Option<SybilPolicy>,
+        priority_roles: Vec<RoleId>,
+        ownership: Option<OwnershipProof>,
+    ) -> Self {
+        Intake {
+            rate_limit,
+            reply_limits,
+            overrides,
+            locale,
+            counterparties,
+            store,
+            dm_receipts,
+            transports,
+            lifecycle,
+            requests,
+            progress_after,
+            batch,
+            sybil: sybil.map(Sybil::new),
+            priority_roles,
+            ownership,
+            send_history: Arc::new(Mutex::new(VecDeque::new())),
+            seen: Arc::new(Mutex::new(IndexMap::new())),
+        }
+    }
+
+    /// The language to reply in where a message is posted.
+    pub fn locale(&self, server_id: Option<ServerId>, channel_id: ChannelId) -> Locale {
+        self.overrides
+            .resolve(server_id, channel_id)
+            .locale
+            .unwrap_or(self.locale)
+    }
+
+    /// Returns `true` if the message was handled since we started.
+    pub fn has_seen(&self, message_id: MessageId) -> bool {
+        self.seen.lock().unwrap().contains_key(&message_id)
+    }
+
+    /// Check a direct message answering a challenge to prove ownership of addresses, returning
+    /// the reply to it and, once the proof holds, the message whose request was waiting on it, to
+    /// handle again. Returns `None` if the message isn't an answer at all.
+    ///
+    /// Answers are only taken by direct message, and neither logged nor echoed, since the proof
+    /// includes the full viewing key of the wallet.
+    pub fn prove(&self, answer: &Incoming) -> Option<(String, Option<Incoming>)> {
+        let ownership = self.ownership.as_ref()?;
+        match ownership.answer(answer)? {
+            Ok(waiting) => {
+                tracing::info!(user_id = ?answer.author_id.to_string(), "ownership proven");
+                let locale = self.locale(waiting.server_id, waiting.channel_id);
+                Some((locale.text().ownership_proven.to_string(), Some(waiting)))
+            }
+            Err(reason) => {
+                count_filtered("ownership-unproven", answer.channel_id);
+                let reply = locale::fill(
+                    self.locale.text().ownership_unproven,
+                    &[("reason", &reason)],
+                );
+                Some((reply, None))
+            }
+        }
+    }
+
+    /// Handle a new or edited message, dispensing tokens to any addresses in it which we haven't
+    /// already handled.
+    pub async fn handle(&self, chat: &dyn ChatPlatform, message: Incoming, edited: bool) {
+        // Proofs of ownership are taken by direct message: one posted in a channel has already
+        // exposed its full viewing key, so only warn its author
+        if self.ownership.is_some() && is_answer(&message.content) {
+            count_filtered("ownership-public", message.channel_id);
+            let locale = self.locale(message.server_id, message.channel_id);
+            let reply = locale::fill(
+                locale.text().ownership_public,
+                &[("command", &PROVE_COMMAND)],
+            );
+            if let Err(e) = chat.reply(reply).await {
+                tracing::error!(error = ?e, "failed to reply");
+            }
+            return;
+        }
+        // A message whose request was waiting on a proof, which has since been given, is handled
+        // without challenging its author again
+        let proven = self
+            .ownership
+            .as_ref()
+            .map_or(false, |ownership| ownership.take_proven(&message));
+        let channel_id = message.channel_id;
+        let user_id = message.author_id;
+        let user_name = message.author_name.clone();
+        let limits = self.overrides.resolve(message.server_id, channel_id);
+        let default_rate_limit = self.rate_limit.get();
+        let rate_limit = limits.rate_limit.unwrap_or(default_rate_limit);
+        let locale = limits.locale.unwrap_or(self.locale);
+
+        // Once we're shutting down, leave messages for the next instance to catch up on
+        if !self.lifecycle.is_accepting() {
+            count_filtered("stopping", channel_id);
+            return;
+        }
+
+        // Moderators can ban users, in which case we don't even acknowledge them until it expires
+        if self.store.ban_of(user_id, Utc::now()).is_some() {
+            count_filtered("banned", channel_id);
+            return;
+        }
+
+        // Prune the send history of all expired rate limit timeouts (which, with rate limits
+        // overridden in places, means those past the longest rate limit)
+        let longest_rate_limit = self
+            .overrides
+            .longest_rate_limit()
+            .unwrap_or(default_rate_limit)
+            .max(default_rate_limit);
+        {
+            tracing::trace!("pruning send history");
+            // scoped to prevent deadlock on send_history
+            let mut send_history = self.send_history.lock().unwrap();
+            while let Some((user, last_fulfilled, _)) = send_history.front() {
+                if last_fulfilled.elapsed() >= longest_rate_limit {
+                    tracing::debug!(?user, ?last_fulfilled, "rate limit expired");
+                    send_history.pop_front();
+                } else {
+                    break;
+                }
+            }
+            tracing::trace!("finished pruning send history");
+        }
+
+        // Remember what this message looked like, and what it looked like before if it was edited
+        let previously = {
+            let mut seen = self.seen.lock().unwrap();
+            let previously = seen.get(&message.id).cloned();
+            let entry = seen.entry(message.id).or_default();
+            entry.matches.extend(
+                address_matches(&message.content)
+                    .into_iter()
+                    .chain(self.counterparties.address_matches(&message.content))
+                    .map(String::from),
+            );
+            if seen.len() > SEEN_MESSAGES {
+                seen.shift_remove_index(0);
+            }
+            previously
+        };
+
+        // Check if the message contains a penumbra address and create a request for it if so,
+        // skipping any addresses we already handled before the message was edited
+        let exclude = previously
+            .as_ref()
+            .map(|seen| seen.matches.clone())
+            .unwrap_or_default();
+        let origin = Origin {
+            user_id,
+            channel_id,
+            message_id: message.id,
+        };
+        let (response, mut request) = if let Some(parsed) =
+            Request::try_new_excluding(&message.content, origin, &exclude, &self.counterparties)
+        {
+            parsed
+        } else {
+            tracing::trace!("no new addresses found in message");
+            count_filtered("no-addresses", channel_id);
+            return;
+        };
+        if let Some(max_addresses) = limits.max_addresses {
+            request.limit_addresses(max_addresses);
+        }
+        request.set_locale(locale);
+        if !chat.in_history() {
+            request.keep_local();
+        }
+        // Members with a priority role (say, server boosters) jump ahead of any backlog
+        if message
+            .author_roles
+            .iter()
+            .any(|role_id| self.priority_roles.contains(role_id))
+        {
+            request.prioritize();
+        }
+
+        // If the message author was in the send history, don't send them tokens
+        let rate_limited = self
+            .send_history
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|(user, last_fulfilled, _)| {
+                *user == user_id && last_fulfilled.elapsed() < rate_limit
+            })
+            .map(|(_, last_fulfilled, notified)| {
+                // Increase the notification count by one and return the previous count:
+                let old_notified = *notified;
+                *notified += 1;
+                (*last_fulfilled, old_notified)
+            });
+
+        if let Some((last_fulfilled, notified)) = rate_limited {
+            tracing::info!(
+                ?user_name,
+                ?notified,
+                user_id = ?user_id.to_string(),
+                ?last_fulfilled,
+                "rate-limited user"
+            );
+
+            count_filtered("rate-limited", channel_id);
+            audit::record_request(
+                "rejected",
+                origin,
+                format!(
+                    "rate-limited, last fulfilled {} ago",
+                    humantime::format_duration(Duration::from_secs(
+                        last_fulfilled.elapsed().as_secs()
+                    ))
+                ),
+            );
+
+            // Let the user know at a glance, even if we've stopped replying to them
+            if notified > 0 {
+                react(chat, Reaction::RateLimited).await;
+            }
+
+            // If we already notified the user, don't reply again
+            let reply_limit = match limits.reply_limit {
+                Some(default) => self.reply_limits.with_default(default),
+                None => self.reply_limits.clone(),
+            }
+            .for_author(user_id, &message.author_roles, &self.store);
+            if notified > reply_limit + 1 {
+                // The first time they carry on regardless, cool them down until they can ask again
+                if notified == reply_limit + 2 {
+                    let remaining = rate_limit.saturating_sub(last_fulfilled.elapsed());
+                    let until = Utc::now()
+                        + chrono::Duration::from_std(remaining)
+                            .unwrap_or_else(|_| chrono::Duration::max_value());
+                    tracing::info!(?user_name, user_id = ?user_id.to_string(), %until, "cooling down user");
+                    if let Err(e) = chat.cool_down(until).await {
+                        tracing::error!(error = ?e, "failed to cool down user");
+                    }
+                }
+                return;
+            }
+
+            let response = locale::fill(
+                locale.text().rate_limited,
+                &[("wait", &format_remaining_time(last_fulfilled, rate_limit))],
+            );
+            if let Err(e) = chat.reply(response).await {
+                tracing::error!(error = ?e, "failed to reply");
+            }
+
+            // Setting the notified count to zero "un-rate-limits" an entry, which we do when a
+            // request fails, so we don't have to traverse the entire list:
+            if notified > 0 {
+                // So therefore we only prevent the request when the notification count is greater
+                // than zero
+                return;
+            }
+        }
+
+        // Accounts sharing addresses with too many others are flagged, and may be made to wait as
+        // though they were one account
+        if let Some(sybil) = &self.sybil {
+            let addresses: Vec<String> = request
+                .addresses()
+                .iter()
+                .filter_map(|address| match address {
+                    AddressOrAlmost::Address(address) => Some(address.to_string()),
+                    _ => None,
+                })
+                .collect();
+            if let Some(wait) = sybil.check(&self.store, origin, &addresses) {
+                tracing::info!(?user_name, user_id = ?user_id.to_string(), ?wait, "throttled sybil cluster");
+                count_filtered("sybil", channel_id);
+                audit::record_request(
+                    "rejected",
+                    origin,
+                    format!(
+                        "throttled as part of a suspected sybil cluster for another {}",
+                        humantime::format_duration(Duration::from_secs(wait.as_secs()))
+                    ),
+                );
+                // Don't explain: that would only tell whoever's behind the cluster what to avoid
+                react(chat, Reaction::RateLimited).await;
+                return;
+            }
+        }
+
+        // Asking for a lot at once takes proof that the addresses are the author's own, until which
+        // the request waits
+        if let Some(ownership) = self.ownership.as_ref().filter(|_| !proven) {
+            let addresses = request
+                .addresses()
+                .iter()
+                .filter(|address| matches!(address, AddressOrAlmost::Address(_)))
+                .count();
+            if ownership.required(addresses) {
+                // Forget this message's addresses, so that it's handled afresh once proven
+                {
+                    let mut seen = self.seen.lock().unwrap();
+                    match previously {
+                        Some(previously) => {
+                            seen.insert(message.id, previously);
+                        }
+                        None => {
+                            seen.shift_remove(&message.id);
+                        }
+                    }
+                }
+                tracing::info!(?user_name, user_id = ?user_id.to_string(), addresses, "challenged to prove ownership");
+                count_filtered("ownership-challenged", channel_id);
+                audit::record_request(
+                    "challenged",
+                    origin,
+                    format!("asked to prove ownership of {} addresses", addresses),
+                );
+                let challenge = ownership.issue(message);
+                let reply = locale::fill(
+                    locale.text().ownership_challenge,
+                    &[
+                        ("challenge", &challenge),
+                        ("command", &PROVE_COMMAND),
+                        ("wait", &humantime::format_duration(CHALLENGE_TTL)),
+                    ],
+                );
+                if let Err(e) = chat.reply(reply).await {
+                    tracing::error!(error = ?e, "failed to reply");
+                }
+                return;
+            }
+        }
+
+        // Keep track of this request until we've replied to it, so that we don't exit before then
+        let _in_flight = self.lifecycle.begin();
+
+        // Make sure no one else (a catch-up worker, or this bot before a restart) has handled this
+        // message already; edits are exempt, since their new addresses were deduplicated above.
+        // Only a claim made here is ours to release if the request fails: an edit's message was
+        // claimed for the addresses it had before
+        let claimed = !edited
+            && match self.store.claim(message.id) {
+                Ok(true) => true,
+                Ok(false) => {
+                    tracing::debug!(message_id = ?message.id, "message already processed");
+                    count_filtered("already-processed", channel_id);
+                    return;
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "failed to record message as processed");
+                    false
+                }
+            };
+
+        // Push the user into the send history queue for rate-limiting in the future
+        tracing::trace!(?user_name, user_id = ?user_id.to_string(), "pushing user into send history");
+        self.send_history
+            .lock()
+            .unwrap()
+            .push_back((user_id, Instant::now(), 1));
+
+        // Send the message to the queue, to be processed asynchronously
+        tracing::trace!("sending message to worker queue");
+        if let Err(SendError(request)) = self.requests.queue(channel_id).send(request).await {
+            // The responder stopped taking requests since we checked, so this one is handed off
+            // along with the rest of the queue
+            if chat.in_history() {
+                tracing::debug!(message_id = ?message.id, "responder stopped, handing off request");
+                self.hand_off(&request, claimed);
+            } else {
+                react(chat, Reaction::Failed).await;
+            }
+            return;
+        }
+
+        // Acknowledge the request, and broadcast to the channel that we are typing, so users know
+        // something is happening
+        react(chat, Reaction::Received).await;
+        if let Some(batch) = self.batch {
+            // It'll be a while, so say when rather than just typing
+            let now = Utc::now();
+            let reply = locale::fill(
+                locale.text().batched,
+                &[
+                    ("time", &batch.next(now).format("%H:%M")),
+                    (
+                        "wait",
+                        &humantime::format_duration(Duration::from_secs(
+                            batch.until_next(now).as_secs(),
+                        )),
+                    ),
+                ],
+            );
+            if let Err(e) = chat.reply(reply).await {
+                tracing::error!(error = ?e, "failed to reply");
+            }
+        } else if let Err(e) = chat.typing().await {
+            tracing::error!(error = ?e, "failed to broadcast typing");
+        }
+
+        // Reply to the user with the response from the responder
+        if let Ok(response) = self.wait(chat, channel_id, locale, response).await {
+            // Record that we've handled this message, so that catch-up after a restart resumes
+            // after it
+            if chat.in_history() {
+                if let Err(e) = self.store.checkpoint(message.channel_id, message.id) {
+                    tracing::error!(error = ?e, "failed to record checkpoint");
+                }
+            }
+            // Only count the request against the user's allowance if they actually received
+            // something (so that, for instance, correcting a typo in the address isn't penalized)
+            if response.succeeded().is_empty() && response.withdrawn().is_empty() {
+                tracing::debug!(?user_name, user_id = ?user_id.to_string(), "nothing dispensed, releasing rate limit");
+                self.release_rate_limit(user_id);
+            }
+            if !response.failed().is_empty() || !response.failed_withdrawals().is_empty() {
+                react(chat, Reaction::Failed).await;
+            }
+            let chat_transports = chat.transports(self.dm_receipts);
+            let transports: Vec<&dyn Transport> = chat_transports
+                .iter()
+                .chain(self.transports.iter())
+                .map(|transport| &**transport)
+                .collect();
+            // If we stop before the reply is out, the next instance delivers it
+            if chat.in_history() {
+                self.store
+                    .delivering(Undelivered::new(origin, response.plain_summary()));
+            }
+            transport::deliver_all(&transports, &response).await;
+            self.store.delivered(message.id);
+        } else if self.lifecycle.is_stopping() && chat.in_history() {
+            // The request was handed off to the next instance, which will reply to it, so it stays
+            // claimed and counts against the rate limit
+            tracing::debug!(message_id = ?message.id, "request handed off");
+        } else {
+            react(chat, Reaction::Failed).await;
+            self.release_rate_limit(user_id);
+            // Let catch-up try this message again after a restart
+            if claimed {
+                if let Err(e) = self.store.unclaim(message.id) {
+                    tracing::error!(error = ?e, "failed to release message");
+                }
+            }
+        }
+    }
+
+    /// Wait for the response to a request, keeping its author posted if it takes longer than the
+    /// progress threshold (proving can be slow), so they don't assume we've died and ask again.
+    async fn wait(
+        &self,
+        chat: &dyn ChatPlatform,
+        channel_id: ChannelId,
+        locale: Locale,
+        mut response: oneshot::Receiver<Response>,
+    ) -> Result<Response, oneshot::error::RecvError> {
+        // Batches are slow by design, and the author was already told when theirs goes out
+        let progress_after = match self.progress_after.filter(|_| self.batch.is_none()) {
+            Some(progress_after) => progress_after,
+            None => return response.await,
+        };
+
+        let start = Instant::now();
+        let mut updates = tokio::time::interval_at(start + progress_after, progress_after);
+        let mut slow = false;
+        loop {
+            tokio::select! {
+                result = &mut response => {
+                    if slow {
+                        let elapsed = format_elapsed(start);
+                        let done = locale::fill(locale.text().done_after, &[("elapsed", &elapsed)]);
+                        progress(chat, done).await;
+                    }
+                    return result;
+                }
+                _ = updates.tick() => {
+                    if !slow {
+                        slow = true;
+                        tracing::info!(?channel_id, "slow dispense");
+                        metrics::increment(SLOW_DISPENSES, &[("channel", channel_id.to_string())]);
+                    }
+                    let elapsed = format_elapsed(start);
+                    let update = locale::fill(locale.text().still_working, &[("elapsed", &elapsed)]);
+                    progress(chat, update).await;
+                    // The typing indicator only lasts a few seconds
+                    if let Err(e) = chat.typing().await {
+                        tracing::debug!(error = ?e, "failed to broadcast typing");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Save a request which the responder won't take up for the next instance, like those still
+    /// queued when it stopped, or if that fails, release its message to be caught up on instead
+    /// (if it was `claimed` for this request, rather than edited after an earlier one).
+    fn hand_off(&self, request: &Request, claimed: bool) {
+        let message_id = request.origin().message_id;
+        if let Err(e) = self.store.save_pending(vec![Pending::of(request)]) {
+            tracing::error!(error = ?e, ?message_id, "failed to hand off request");
+            if !claimed {
+                return;
+            }
+            if let Err(e) = self.store.unclaim(message_id) {
+                tracing::error!(error = ?e, "failed to release message");
+            }
+        }
+    }
+
+    /// Make the rate limit not apply to the next request from this user, releasing the request
+    /// they made last (which, with rate limits overridden in places, may not be their only one).
+    fn release_rate_limit(&self, user_id: UserId) {
+        if let Some((_, _, notified)) = self
+            .send_history
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .rev()
+            .find(|(user, _, _)| *user == user_id)
+        {
+            // If nothing was dispensed, we set the notification count to zero, so that the rate
+            // limit will not apply to future requests
+            *notified = notified.saturating_sub(1);
+        }
+    }
+}
+
+/// React to a message, logging rather than failing if we can't.
+async fn react(chat: &dyn ChatPlatform, reaction: Reaction) {
+    if let Err(e) = chat.react(reaction).await {
+        tracing::warn!(error = ?e, ?reaction, "failed to react to message");
+    }
+}
+
+/// Post or update a progress message, logging rather than failing if we can't.
+async fn progress(chat: &dyn ChatPlatform, text: String) {
+    if let Err(e) = chat.progress(text).await {
+        tracing::warn!(error = ?e, "failed to report progress");
+    }
+}
+
+/// How long it's been since the given instant, to the second.
+fn format_elapsed(start: Instant) -> String {
+    humantime::format_duration(Duration::from_secs(start.elapsed().as_secs())).to_string()
+}
+
+fn format_remaining_time(last_fulfilled: Instant, rate_limit: Duration) -> String {
+    humantime::Duration::from(rate_limit - last_fulfilled.elapsed())
+        .to_string()
+        .split(' ')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,209 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    id::{ChannelId, ServerId},
+    Locale,
+};
+
+/// Limits which differ from the faucet's defaults in one server or channel, written as
+/// `<id>:<setting>=<value>,...`, where the settings are `rate-limit` (a duration, like `10m`),
+/// `reply-limit`, `max-addresses`, and `locale` (a language code, like `es`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Override {
+    /// The id of the server or channel.
+    pub id: u64,
+    /// The minimum duration between dispensing tokens to a user.
+    pub rate_limit: Option<Duration>,
+    /// How many times to tell a user about their rate limit, in place of the default and
+    /// first-time limits (limits for roles still take precedence).
+    pub reply_limit: Option<usize>,
+    /// The maximum number of addresses per message to dispense to.
+    pub max_addresses: Option<usize>,
+    /// The language to reply in.
+    pub locale: Option<Locale>,
+}
+
+impl FromStr for Override {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, settings) = s
+            .split_once(':')
+            .context("override must be written as <id>:<setting>=<value>,...")?;
+        let mut result = Override {
+            id: id.trim().parse().context("invalid id")?,
+            ..Override::default()
+        };
+        for setting in settings.split(',') {
+            let (name, value) = setting.split_once('=').with_context(|| {
+                format!("setting must be written as <setting>=<value>: {}", setting)
+            })?;
+            let value = value.trim();
+            match name.trim() {
+                "rate-limit" => {
+                    result.rate_limit =
+                        Some(humantime::parse_duration(value).context("invalid rate limit")?)
+                }
+                "reply-limit" => {
+                    result.reply_limit = Some(value.parse().context("invalid reply limit")?)
+                }
+                "max-addresses" => {
+                    result.max_addresses = Some(value.parse().context("invalid max addresses")?)
+                }
+                "locale" => result.locale = Some(value.parse()?),
+                name => anyhow::bail!(
+                    "unknown setting {} (expected rate-limit, reply-limit, max-addresses, or \
+                    locale)",
+                    name
+                ),
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Override {
+    /// Fill in anything this doesn't set from a less specific override.
+    fn or(self, fallback: Override) -> Override {
+        Override {
+            id: self.id,
+            rate_limit: self.rate_limit.or(fallback.rate_limit),
+            reply_limit: self.reply_limit.or(fallback.reply_limit),
+            max_addresses: self.max_addresses.or(fallback.max_addresses),
+            locale: self.locale.or(fallback.locale),
+        }
+    }
+}
+
+/// The limits overridden for particular servers and channels.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    servers: Vec<Override>,
+    channels: Vec<Override>,
+}
+
+impl Overrides {
+    pub fn new(servers: Vec<Override>, channels: Vec<Override>) -> Self {
+        Overrides { servers, channels }
+    }
+
+    /// The overrides which apply to a message posted in the given channel (of the given server, if
+    /// it's in one): the channel's take precedence over the server's.
+    pub fn resolve(&self, server_id: Option<ServerId>, channel_id: ChannelId) -> Override {
+        let find = |overrides: &[Override], id: u64| {
+            overrides
+                .iter()
+                .find(|candidate| candidate.id == id)
+                .copied()
+                .unwrap_or_default()
+        };
+        let server = server_id
+            .map(|server_id| find(&self.servers, server_id.0))
+            .unwrap_or_default();
+        find(&self.channels, channel_id.0).or(server)
+    }
+
+    /// The longest rate limit set by any override, if any.
+    pub fn longest_rate_limit(&self) -> Option<Duration> {
+        self.servers
+            .iter()
+            .chain(&self.channels)
+            .filter_map(|entry| entry.rate_limit)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Override {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        assert_eq!(
+            parse("42: rate-limit = 10m, reply-limit=2,max-addresses=3,locale=pt-BR"),
+            Override {
+                id: 42,
+                rate_limit: Some(Duration::from_secs(600)),
+                reply_limit: Some(2),
+                max_addresses: Some(3),
+                locale: Some(Locale::Pt),
+            }
+        );
+        assert_eq!(
+            parse("42:locale=es"),
+            Override {
+                id: 42,
+                locale: Some(Locale::Es),
+                ..Override::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_overrides() {
+        for invalid in [
+            "rate-limit=10m",
+            "general:rate-limit=10m",
+            "42:rate-limit",
+            "42:rate-limit=soon",
+            "42:reply-limit=-1",
+            "42:max-addresses=many",
+            "42:locale=xx",
+            "42:cooldown=10m",
+            "42:",
+        ] {
+            assert!(invalid.parse::<Override>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn channel_takes_precedence_over_server() {
+        let overrides = Overrides::new(
+            vec![parse("1:rate-limit=1h,locale=fr"), parse("2:reply-limit=5")],
+            vec![parse("10:rate-limit=5m,max-addresses=1")],
+        );
+
+        let resolved = overrides.resolve(Some(ServerId(1)), ChannelId(10));
+        assert_eq!(resolved.rate_limit, Some(Duration::from_secs(300)));
+        assert_eq!(resolved.max_addresses, Some(1));
+        assert_eq!(resolved.locale, Some(Locale::Fr));
+        assert_eq!(resolved.reply_limit, None);
+
+        let resolved = overrides.resolve(Some(ServerId(1)), ChannelId(11));
+        assert_eq!(resolved.rate_limit, Some(Duration::from_secs(3600)));
+        assert_eq!(resolved.locale, Some(Locale::Fr));
+
+        let resolved = overrides.resolve(Some(ServerId(2)), ChannelId(10));
+        assert_eq!(resolved.rate_limit, Some(Duration::from_secs(300)));
+        assert_eq!(resolved.reply_limit, Some(5));
+    }
+
+    #[test]
+    fn unlisted_places_get_no_overrides() {
+        let overrides = Overrides::new(vec![parse("1:rate-limit=1h")], vec![]);
+        assert_eq!(
+            overrides.resolve(Some(ServerId(3)), ChannelId(10)),
+            Override::default()
+        );
+        assert_eq!(overrides.resolve(None, ChannelId(10)), Override::default());
+    }
+
+    #[test]
+    fn longest_rate_limit_covers_servers_and_channels() {
+        assert_eq!(Overrides::default().longest_rate_limit(), None);
+        let overrides = Overrides::new(
+            vec![parse("1:rate-limit=1h"), parse("2:locale=es")],
+            vec![parse("10:rate-limit=1day")],
+        );
+        assert_eq!(
+            overrides.longest_rate_limit(),
+            Some(Duration::from_secs(24 * 60 * 60))
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use decaf377_rdsa::{Signature, SpendAuth};
+use penumbra_asset::Value;
+use penumbra_keys::{Address, FullViewingKey};
+use rand::RngCore;
+use tokio::sync::watch;
+
+use super::Incoming;
+use crate::{
+    id::{MessageId, UserId},
+    responder::address_matches,
+};
+
+/// How long a user has to prove they own their addresses after being challenged to.
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The command with which users answer a challenge, followed by their full viewing key and the
+/// signature (as printed by `galileo prove-address`). Answers are only taken by direct message,
+/// since the full viewing key shows everything the wallet does.
+pub const PROVE_COMMAND: &str = "!faucet prove";
+
+/// The proof in a message, if it's an answer to a challenge at all.
+fn proof_in(content: &str) -> Option<&str> {
+    let content = content.trim();
+    content
+        .get(..PROVE_COMMAND.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(PROVE_COMMAND))
+        .map(|_| &content[PROVE_COMMAND.len()..])
+}
+
+/// Whether a message answers a challenge, and so holds a full viewing key which mustn't be
+/// posted, logged or echoed anywhere.
+pub fn is_answer(content: &str) -> bool {
+    proof_in(content).is_some()
+}
+
+/// What a user signs to answer a challenge, so that the signature can't be passed off as anything
+/// else signed with their spend key.
+pub fn signed_message(challenge: &str) -> Vec<u8> {
+    format!("galileo address ownership proof: {}", challenge).into_bytes()
+}
+
+/// Requires users asking for a lot at once to prove that they control the addresses they gave,
+/// so that tokens aren't farmed into addresses held on behalf of others.
+///
+/// Penumbra addresses don't carry a key to check a signature against, so the proof is the full
+/// viewing key which views every address, with a signature over a random challenge by the spend
+/// key that key belongs to.
+pub struct OwnershipProof {
+    /// The totals (across every address in a request) at or above which proof is required.
+    thresholds: Vec<Value>,
+    /// The values sent to each address, as administrators change them.
+    values: watch::Receiver<Vec<Value>>,
+    /// The challenges issued and not yet answered, by the user they were issued to.
+    outstanding: Mutex<HashMap<UserId, Challenge>>,
+    /// The messages whose challenges were answered, with the content the proof was checked
+    /// against, until they're handled again.
+    proven: Mutex<HashMap<MessageId, (String, Instant)>>,
+}
+
+/// A challenge issued to a user, with the message whose request waits on it.
+struct Challenge {
+    challenge: String,
+    message: Incoming,
+    issued: Instant,
+}
+
+impl OwnershipProof {
+    pub fn new(thresholds: Vec<Value>, values: watch::Receiver<Vec<Value>>) -> Self {
+        OwnershipProof {
+            thresholds,
+            values,
+            outstanding: Default::default(),
+            proven: Default::default(),
+        }
+    }
+
+    /// Whether sending to this many addresses takes proof that they're the requester's own.
+    pub fn required(&self, addresses: usize) -> bool {
+        let values = self.values.borrow();
+        self.thresholds.iter().any(|threshold| {
+            let each: u128 = values
+                .iter()
+                .filter(|value| value.asset_id == threshold.asset_id)
+                .map(|value| value.amount.value())
+                .sum();
+            each > 0 && each.saturating_mul(addresses as u128) >= threshold.amount.value()
+        })
+    }
+
+    /// Challenge the author of the message to prove they own its addresses, holding on to it
+    /// until they do (in place of any earlier challenge to them). Returns the challenge.
+    pub fn issue(&self, message: Incoming) -> String {
+        let mut outstanding = self.outstanding.lock().unwrap();
+        outstanding.retain(|_, challenge| challenge.issued.elapsed() < CHALLENGE_TTL);
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let challenge = hex::encode(bytes);
+        outstanding.insert(
+            message.author_id,
+            Challenge {
+                challenge: challenge.clone(),
+                message,
+                issued: Instant::now(),
+            },
+        );
+        challenge
+    }
+
+    /// If the direct message answers a challenge, the message whose request was waiting on it,
+    /// once the proof holds, or why it doesn't. Returns `None` if the message isn't an answer at
+    /// all.
+    ///
+    /// The waiting message counts as proven (see [`take_proven`](Self::take_proven)) until it's
+    /// handled again or the challenge would have expired.
+    pub fn answer(&self, message: &Incoming) -> Option<Result<Incoming, String>> {
+        let proof = proof_in(&message.content)?;
+
+        let mut outstanding = self.outstanding.lock().unwrap();
+        let challenge = match outstanding.get(&message.author_id) {
+            Some(challenge) if challenge.issued.elapsed() < CHALLENGE_TTL => challenge,
+            _ => return Some(Err("there's no challenge waiting for you".to_string())),
+        };
+        if let Err(reason) = verify(&challenge.challenge, &challenge.message.content, proof) {
+            return Some(Err(reason));
+        }
+        let waiting = outstanding.remove(&message.author_id)?.message;
+
+        let mut proven = self.proven.lock().unwrap();
+        proven.retain(|_, (_, at)| at.elapsed() < CHALLENGE_TTL);
+        proven.insert(waiting.id, (waiting.content.clone(), Instant::now()));
+        Some(Ok(waiting))
+    }
+
+    /// Whether the message's request was proven to be to the author's own addresses, as it reads
+    /// now, which it no longer is once asked.
+    pub fn take_proven(&self, message: &Incoming) -> bool {
+        match self.proven.lock().unwrap().remove(&message.id) {
+            Some((content, at)) => content == message.content && at.elapsed() < CHALLENGE_TTL,
+            None => false,
+        }
+    }
+}
+
+/// Check a proof (a full viewing key and a signature, in hex) that whoever answered the challenge
+/// holds the spend key for every address in the text.
+fn verify(challenge: &str, content: &str, proof: &str) -> Result<(), String> {
+    let (fvk, signature) = match proof.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [fvk, signature] => (*fvk, *signature),
+        _ => {
+            return Err(format!(
+                "write `{} <full viewing key> <signature>`",
+                PROVE_COMMAND
+            ))
+        }
+    };
+    let fvk = FullViewingKey::from_str(fvk).map_err(|_| "invalid full viewing key".to_string())?;
+    let signature: [u8; 64] = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "invalid signature".to_string())?;
+    fvk.spend_verification_key()
+        .verify(
+            &signed_message(challenge),
+            &Signature::<SpendAuth>::from(signature),
+        )
+        .map_err(|_| "the signature doesn't match the challenge and key".to_string())?;
+
+    for address in address_matches(content) {
+        let parsed = match Address::from_str(address) {
+            Ok(parsed) => parsed,
+            // Addresses which don't parse won't be sent anything anyway
+            Err(_) => continue,
+        };
+        if fvk.address_index(&parsed).is_none() {
+            return Err(format!("`{}` isn't viewed by that key", address));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use penumbra_keys::keys::{SeedPhrase, SpendKey};
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::id::{ChannelId, ServerId};
+
+    fn spend_key() -> SpendKey {
+        SpendKey::from_seed_phrase(SeedPhrase::generate(OsRng), 0)
+    }
+
+    fn address(spend_key: &SpendKey) -> String {
+        spend_key
+            .full_viewing_key()
+            .payment_address(0.into())
+            .0
+            .to_string()
+    }
+
+    /// What `galileo prove-address` prints for the challenge, less the command.
+    fn proof(spend_key: &SpendKey, challenge: &str) -> String {
+        let signature = spend_key
+            .spend_auth_key()
+            .sign(OsRng, &signed_message(challenge));
+        format!(
+            "{} {}",
+            spend_key.full_viewing_key(),
+            hex::encode(signature.to_bytes())
+        )
+    }
+
+    fn message(id: u64, content: String) -> Incoming {
+        Incoming {
+            id: MessageId(id),
+            channel_id: ChannelId(1),
+            server_id: Some(ServerId(1)),
+            author_id: UserId(1),
+            author_name: "user-1".to_string(),
+            author_roles: Vec::new(),
+            content,
+        }
+    }
+
+    fn ownership(values: &[&str]) -> (watch::Sender<Vec<Value>>, OwnershipProof) {
+        let values = values.iter().map(|value| value.parse().unwrap()).collect();
+        let (sender, receiver) = watch::channel(values);
+        let ownership = OwnershipProof::new(vec!["500penumbra".parse().unwrap()], receiver);
+        (sender, ownership)
+    }
+
+    #[test]
+    fn proof_holds_for_addresses_the_key_views() {
+        let spend_key = spend_key();
+        let content = format!("please send to {}", address(&spend_key));
+        assert_eq!(verify("abc", &content, &proof(&spend_key, "abc")), Ok(()));
+    }
+
+    #[test]
+    fn proof_fails_for_another_challenge() {
+        let spend_key = spend_key();
+        let content = format!("please send to {}", address(&spend_key));
+        assert!(verify("abc", &content, &proof(&spend_key, "def")).is_err());
+    }
+
+    #[test]
+    fn proof_fails_for_addresses_of_another_wallet() {
+        let (spend_key, other) = (spend_key(), spend_key());
+        let content = format!("{} {}", address(&spend_key), address(&other));
+        assert!(verify("abc", &content, &proof(&spend_key, "abc")).is_err());
+    }
+
+    #[test]
+    fn proof_fails_when_malformed() {
+        let spend_key = spend_key();
+        let content = address(&spend_key);
+        assert!(verify("abc", &content, "").is_err());
+        assert!(verify("abc", &content, "not-a-key 00").is_err());
+        let fvk = spend_key.full_viewing_key().to_string();
+        assert!(verify("abc", &content, &format!("{} 00", fvk)).is_err());
+    }
+
+    #[test]
+    fn answer_releases_the_waiting_message_once() {
+        let (_values, ownership) = ownership(&["100penumbra"]);
+        let spend_key = spend_key();
+        let waiting = message(1, format!("please send to {}", address(&spend_key)));
+        let challenge = ownership.issue(waiting.clone());
+
+        let answer = message(
+            2,
+            format!("{} {}", PROVE_COMMAND, proof(&spend_key, &challenge)),
+        );
+        let proven = ownership.answer(&answer).unwrap().unwrap();
+        assert_eq!(proven.id, waiting.id);
+        assert!(ownership.take_proven(&waiting));
+        assert!(!ownership.take_proven(&waiting));
+
+        // The challenge was used up
+        assert!(ownership.answer(&answer).unwrap().is_err());
+    }
+
+    #[test]
+    fn answer_rejects_a_bad_proof_and_keeps_the_challenge() {
+        let (_values, ownership) = ownership(&["100penumbra"]);
+        let spend_key = spend_key();
+        let waiting = message(1, format!("please send to {}", address(&spend_key)));
+        let challenge = ownership.issue(waiting.clone());
+
+        let wrong = message(2, format!("{} {}", PROVE_COMMAND, proof(&spend_key, "abc")));
+        assert!(ownership.answer(&wrong).unwrap().is_err());
+        assert!(!ownership.take_proven(&waiting));
+
+        let right = message(
+            3,
+            format!("{} {}", PROVE_COMMAND, proof(&spend_key, &challenge)),
+        );
+        assert!(ownership.answer(&right).unwrap().is_ok());
+    }
+
+    #[test]
+    fn answer_ignores_other_messages_and_needs_a_challenge() {
+        let (_values, ownership) = ownership(&["100penumbra"]);
+        assert!(ownership.answer(&message(1, "hello".to_string())).is_none());
+        let answer = message(2, format!("{} key signature", PROVE_COMMAND));
+        assert!(ownership.answer(&answer).unwrap().is_err());
+    }
+
+    #[test]
+    fn edited_message_is_no_longer_proven() {
+        let (_values, ownership) = ownership(&["100penumbra"]);
+        let spend_key = spend_key();
+        let waiting = message(1, format!("please send to {}", address(&spend_key)));
+        let challenge = ownership.issue(waiting.clone());
+        let answer = message(
+            2,
+            format!("{} {}", PROVE_COMMAND, proof(&spend_key, &challenge)),
+        );
+        ownership.answer(&answer).unwrap().unwrap();
+
+        let edited = message(
+            1,
+            format!("{} and {}", waiting.content, address(&spend_key())),
+        );
+        assert!(!ownership.take_proven(&edited));
+    }
+
+    #[test]
+    fn required_follows_the_live_values() {
+        let (values, ownership) = ownership(&["100penumbra"]);
+        assert!(!ownership.required(4));
+        assert!(ownership.required(5));
+        values.send_replace(vec!["250penumbra".parse().unwrap()]);
+        assert!(ownership.required(2));
+        values.send_replace(Vec::new());
+        assert!(!ownership.required(1000));
+    }
+}
@@ -0,0 +1,81 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+
+use crate::{
+    id::{RoleId, UserId},
+    Store,
+};
+
+/// How many times to tell a user about their rate limit before going silent, depending on who
+/// they are.
+#[derive(Debug, Clone)]
+pub struct ReplyLimits {
+    /// The limit for users not covered by any of the more specific limits.
+    default: usize,
+    /// The limit for users who have never been sent tokens, if different from the default.
+    first_time: Option<usize>,
+    /// The limits for users with particular roles.
+    roles: Vec<RoleReplyLimit>,
+}
+
+/// The reply limit for users with a particular role, written as `<role_id>=<limit>`.
+#[derive(Debug, Clone, Copy)]
+pub struct RoleReplyLimit {
+    pub role_id: RoleId,
+    pub limit: usize,
+}
+
+impl FromStr for RoleReplyLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (role_id, limit) = s
+            .split_once('=')
+            .context("role reply limit must be written as <role_id>=<limit>")?;
+        Ok(RoleReplyLimit {
+            role_id: RoleId(role_id.parse().context("invalid role id")?),
+            limit: limit.parse().context("invalid reply limit")?,
+        })
+    }
+}
+
+impl ReplyLimits {
+    pub fn new(default: usize, first_time: Option<usize>, roles: Vec<RoleReplyLimit>) -> Self {
+        ReplyLimits {
+            default,
+            first_time,
+            roles,
+        }
+    }
+
+    /// The same limits, but with the given default in place of the default and first-time limits.
+    pub fn with_default(&self, default: usize) -> Self {
+        ReplyLimits {
+            default,
+            first_time: None,
+            roles: self.roles.clone(),
+        }
+    }
+
+    /// The reply limit for the author of a message, who has the given roles.
+    ///
+    /// Role limits take precedence (the most patient one, if the author has several roles with
+    /// limits), then the limit for first-time users, then the default.
+    pub fn for_author(&self, user_id: UserId, author_roles: &[RoleId], store: &Store) -> usize {
+        let role_limit = self
+            .roles
+            .iter()
+            .filter(|role| author_roles.contains(&role.role_id))
+            .map(|role| role.limit)
+            .max();
+        if let Some(limit) = role_limit {
+            return limit;
+        }
+
+        match self.first_time {
+            Some(limit) if !store.has_received(user_id) => limit,
+            _ => self.default,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{audit, id::UserId, responder::Origin, Store};
+
+/// When to treat the accounts requesting tokens for the same addresses as one person using many
+/// accounts.
+#[derive(Debug, Clone, Copy)]
+pub struct SybilPolicy {
+    /// How far back in the dispense ledger to look for accounts sharing addresses.
+    pub window: Duration,
+    /// Flag a cluster once more than this many accounts have asked for tokens for its addresses.
+    pub max_accounts: usize,
+    /// Once a cluster is flagged, dispense to it at most this often, as if all its accounts were
+    /// one, if at all.
+    pub throttle: Option<Duration>,
+}
+
+/// Accounts linked by requesting tokens for the same addresses (directly, or through other
+/// accounts in the cluster), and those addresses.
+///
+/// Different addresses of the same wallet can't be linked without its viewing key, so this only
+/// catches addresses which were reused across accounts.
+#[derive(Debug, Clone, Default)]
+pub struct Cluster {
+    pub users: BTreeSet<UserId>,
+    pub addresses: BTreeSet<String>,
+    /// When the latest dispense to any of the addresses was made, if any was.
+    pub last_dispensed: Option<DateTime<Utc>>,
+}
+
+/// Watches for clusters of accounts sharing addresses, flagging them in the audit trail, and
+/// throttling them if asked to.
+pub struct Sybil {
+    policy: SybilPolicy,
+    /// The accounts already flagged, so that a cluster is only flagged again when it grows.
+    flagged: Mutex<HashSet<UserId>>,
+}
+
+impl Sybil {
+    pub fn new(policy: SybilPolicy) -> Self {
+        Sybil {
+            policy,
+            flagged: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Check a request for the given addresses against the dispense ledger, flagging the cluster it
+    /// belongs to if that's grown too large.
+    ///
+    /// Returns how much longer the author must wait, if their cluster is flagged and throttled.
+    pub fn check(&self, store: &Store, origin: Origin, addresses: &[String]) -> Option<Duration> {
+        let now = Utc::now();
+        let since = now - chrono::Duration::from_std(self.policy.window).ok()?;
+        let cluster = cluster(store, since, origin.user_id, addresses);
+        if cluster.users.len() <= self.policy.max_accounts {
+            return None;
+        }
+
+        let new_members = {
+            let mut flagged = self.flagged.lock().unwrap();
+            cluster
+                .users
+                .iter()
+                .filter(|user| flagged.insert(**user))
+                .count()
+        };
+        if new_members > 0 {
+            tracing::warn!(
+                accounts = cluster.users.len(),
+                addresses = cluster.addresses.len(),
+                "flagged suspected sybil cluster"
+            );
+            audit::record_request(
+                "sybil",
+                origin,
+                format!(
+                    "{} accounts requested tokens for {} shared addresses in the last {}: {}",
+                    cluster.users.len(),
+                    cluster.addresses.len(),
+                    humantime::format_duration(self.policy.window),
+                    cluster
+                        .users
+                        .iter()
+                        .map(|user| user.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+
+        let throttle = self.policy.throttle?;
+        let elapsed = (now - cluster.last_dispensed?).to_std().unwrap_or_default();
+        throttle.checked_sub(elapsed).filter(|wait| !wait.is_zero())
+    }
+}
+
+/// Find the cluster of accounts and addresses the request belongs to, among the dispenses since
+/// the given time.
+fn cluster(store: &Store, since: DateTime<Utc>, user_id: UserId, addresses: &[String]) -> Cluster {
+    let dispenses: Vec<_> = store
+        .dispenses()
+        .into_iter()
+        .filter(|dispense| dispense.time >= since)
+        .filter_map(|dispense| {
+            let user_id = UserId(dispense.user_id?);
+            Some((user_id, dispense.address, dispense.time))
+        })
+        .collect();
+    let mut by_user = HashMap::<UserId, Vec<&str>>::new();
+    let mut by_address = HashMap::<&str, Vec<UserId>>::new();
+    for (user_id, address, _) in &dispenses {
+        by_user.entry(*user_id).or_default().push(address);
+        by_address.entry(address).or_default().push(*user_id);
+    }
+
+    // Walk the graph of accounts and the addresses they were sent tokens to, from the request
+    let mut cluster = Cluster::default();
+    let mut users = vec![user_id];
+    let mut pending_addresses: Vec<&str> = addresses.iter().map(String::as_str).collect();
+    while !users.is_empty() || !pending_addresses.is_empty() {
+        for user in users.drain(..) {
+            if cluster.users.insert(user) {
+                pending_addresses.extend(by_user.get(&user).into_iter().flatten());
+            }
+        }
+        for address in pending_addresses.drain(..) {
+            if cluster.addresses.insert(address.to_string()) {
+                users.extend(by_address.get(address).into_iter().flatten());
+            }
+        }
+    }
+
+    cluster.last_dispensed = dispenses
+        .iter()
+        .filter(|(_, address, _)| cluster.addresses.contains(address))
+        .map(|(_, _, time)| *time)
+        .max();
+    cluster
+}
@@ -0,0 +1,35 @@
+//! The faucet engine behind Galileo: finding addresses in messages, rate limiting, queueing
+//! requests, and sending tokens, independent of any chat platform.
+//!
+//! A frontend (such as the Discord bot) turns the messages it receives into [`intake::Incoming`]
+//! messages, implements [`intake::ChatPlatform`] to answer them, and feeds them to an
+//! [`intake::Intake`], whose requests a [`Responder`] dispenses to.
+
+pub mod audit;
+
+pub mod id;
+
+pub mod intake;
+
+pub mod lifecycle;
+pub use lifecycle::Lifecycle;
+
+pub mod locale;
+pub use locale::Locale;
+
+pub mod metrics;
+
+pub mod report;
+
+pub mod responder;
+pub use responder::Responder;
+
+pub mod sender;
+pub use sender::Sender;
+
+pub mod simulate;
+
+pub mod store;
+pub use store::Store;
+
+pub mod transport;
@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+/// Shared state describing whether the bot is still accepting requests, so that it can stop
+/// cleanly, and how many requests are still in flight.
+#[derive(Debug, Clone)]
+pub struct Lifecycle {
+    /// The phase the bot is in.
+    phase: Arc<watch::Sender<Phase>>,
+    /// Kept so that the channel is never closed, and to read the current value.
+    phase_rx: watch::Receiver<Phase>,
+    /// The number of requests accepted from users which haven't been replied to yet.
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// The phases of the bot's life, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    /// Accepting and processing requests.
+    Running,
+    /// No longer accepting requests, but still processing those already queued.
+    Draining,
+    /// No longer processing requests: any still queued are saved for the next instance.
+    Stopping,
+}
+
+/// A request which has been accepted but not yet replied to; dropping it marks it as finished.
+#[derive(Debug)]
+pub struct InFlight {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        let (phase, phase_rx) = watch::channel(Phase::Running);
+        Lifecycle {
+            phase: Arc::new(phase),
+            phase_rx,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Lifecycle {
+    fn advance(&self, phase: Phase) {
+        if *self.phase_rx.borrow() < phase {
+            // This can't fail, because we hold a receiver
+            let _ = self.phase.send(phase);
+        }
+    }
+
+    /// Stop accepting new requests, but carry on processing those already queued.
+    pub fn drain(&self) {
+        self.advance(Phase::Draining);
+    }
+
+    /// Stop accepting new requests, and stop processing those already queued.
+    pub fn stop(&self) {
+        self.advance(Phase::Stopping);
+    }
+
+    /// Returns `true` while the bot is accepting new requests.
+    pub fn is_accepting(&self) -> bool {
+        *self.phase_rx.borrow() == Phase::Running
+    }
+
+    /// Returns `true` once the bot has stopped processing requests.
+    pub fn is_stopping(&self) -> bool {
+        *self.phase_rx.borrow() == Phase::Stopping
+    }
+
+    /// Wait until the bot stops processing requests.
+    pub async fn stopped(&self) {
+        let mut phase = self.phase_rx.clone();
+        while *phase.borrow() != Phase::Stopping {
+            if phase.changed().await.is_err() {
+                // Can't happen, because we hold the sender, but if it did we'd never stop
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Mark a request as in flight until the returned guard is dropped.
+    pub fn begin(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// The number of requests accepted but not yet replied to.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Wait until no requests are in flight.
+    pub async fn idle(&self) {
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
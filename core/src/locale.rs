@@ -0,0 +1,532 @@
+//! The languages the faucet replies to requests in, and the text of those replies in each.
+//!
+//! Every reply a user sees is written as a template in a [`Catalog`], with its variable parts as
+//! `{name}` placeholders to [`fill`] in, and each [`Locale`] has a catalog bundled here. To add a
+//! language, add a variant to [`Locale`] and translate [`EN`] into a new catalog.
+
+use std::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+/// A language to reply in, chosen per server or channel (see [`crate::intake::Override`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    /// English.
+    #[default]
+    En,
+    /// Spanish.
+    Es,
+    /// Portuguese (as spoken in Brazil).
+    Pt,
+    /// French.
+    Fr,
+}
+
+impl Locale {
+    /// Every language there are bundled translations for.
+    pub const ALL: [Locale; 4] = [Locale::En, Locale::Es, Locale::Pt, Locale::Fr];
+
+    /// The language's code, as used in options (`en`, `es`, ...).
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+            Locale::Pt => "pt",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// The text of the replies in this language.
+    pub fn text(self) -> &'static Catalog {
+        match self {
+            Locale::En => &EN,
+            Locale::Es => &ES,
+            Locale::Pt => &PT,
+            Locale::Fr => &FR,
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// Parse a language code, ignoring any region after it (so `pt-BR` is Portuguese).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s
+            .split(|c| c == '-' || c == '_')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code() == language)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unsupported locale {} (expected one of {})",
+                    s,
+                    Locale::ALL.map(Locale::code).join(", ")
+                )
+            })
+    }
+}
+
+/// Fill in the `{name}` placeholders in a template with the given values, leaving any braces which
+/// don't name one of them as they are.
+pub fn fill(template: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let arg = after.find('}').and_then(|end| {
+            args.iter()
+                .find(|(name, _)| *name == &after[..end])
+                .map(|(_, value)| (value, end))
+        });
+        match arg {
+            Some((value, end)) => {
+                write!(filled, "{}", value).unwrap();
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// The templates for every reply the faucet makes to a request, in one language.
+#[derive(Debug)]
+pub struct Catalog {
+    /// Telling a user to wait out their rate limit: `{wait}`.
+    pub rate_limited: &'static str,
+    /// Challenging the author of a large request to prove they own its addresses: `{challenge}`,
+    /// `{command}`, `{wait}`.
+    pub ownership_challenge: &'static str,
+    /// Rejecting an answer to that challenge: `{reason}`.
+    pub ownership_unproven: &'static str,
+    /// Accepting an answer to that challenge, by direct message.
+    pub ownership_proven: &'static str,
+    /// Refusing an answer posted in a channel rather than sent by direct message: `{command}`.
+    pub ownership_public: &'static str,
+    /// Acknowledging a request which will go out in the next batch: `{time}`, `{wait}`.
+    pub batched: &'static str,
+    /// Updating the author of a slow request: `{elapsed}`.
+    pub still_working: &'static str,
+    /// Telling the author of a slow request it's finished: `{elapsed}`.
+    pub done_after: &'static str,
+    /// A receipt for a dry run: `{values}`, `{address}`.
+    pub receipt_simulated: &'static str,
+    /// A receipt: `{values}`, `{address}`, `{id}`, `{height}`.
+    pub receipt: &'static str,
+    /// Heading the addresses which would have been sent to in a dry run.
+    pub simulated: &'static str,
+    /// Heading the addresses which were sent to.
+    pub succeeded: &'static str,
+    /// An address which was sent to: `{address}`, `{values}`, `{id}`.
+    pub succeeded_entry: &'static str,
+    /// Heading the addresses on other chains which were withdrawn to.
+    pub withdrawn: &'static str,
+    /// An address on another chain which would have been withdrawn to: `{address}`, `{values}`.
+    pub withdrawn_simulated: &'static str,
+    /// An address on another chain which was withdrawn to: `{address}`, `{values}`, `{id}`.
+    pub withdrawn_entry: &'static str,
+    /// The total sent across every address: `{values}`.
+    pub total: &'static str,
+    /// Heading the addresses which couldn't be sent to.
+    pub failed: &'static str,
+    /// An address which couldn't be sent to: `{address}`, `{error}`.
+    pub failed_entry: &'static str,
+    /// Why sending failed, when the faucet couldn't reach the node or its services.
+    pub failure_unavailable: &'static str,
+    /// Why sending failed, when the faucet doesn't have enough to send.
+    pub failure_funds: &'static str,
+    /// Why sending failed, when custody wouldn't authorize the transaction.
+    pub failure_custody: &'static str,
+    /// Why sending failed, when the chain rejected the transaction.
+    pub failure_rejected: &'static str,
+    /// Why sending failed, when it's not clear why.
+    pub failure_unknown: &'static str,
+    /// Heading the addresses skipped because they were given twice, or were already being sent
+    /// tokens.
+    pub duplicates: &'static str,
+    /// Asking the administrators to look into a failure: `{admins}`.
+    pub investigate: &'static str,
+    /// Heading the things which looked like addresses but weren't.
+    pub unparsed: &'static str,
+    /// Heading the addresses left over after the maximum per message: `{count}`.
+    pub remaining: &'static str,
+    /// Why an address wasn't sent anything, when everything asked for was sent too recently.
+    pub nothing_left: &'static str,
+    /// Why an address wasn't sent anything, when the budget is spent: `{budget}`, `{wait}`.
+    pub over_budget: &'static str,
+    /// Why an address wasn't sent anything, when the user has been sent as much as they may be
+    /// for now: `{cap}`, `{wait}`.
+    pub over_user_cap: &'static str,
+    /// Why an address on another chain wasn't withdrawn to, past the maximum per message:
+    /// `{count}`.
+    pub too_many_withdrawals: &'static str,
+    /// Why an asset on the menu wasn't sent: `{name}`, `{wait}`.
+    pub sent_recently: &'static str,
+    /// Which assets asked for aren't on the menu: `{unknown}`, `{menu}`.
+    pub not_on_menu: &'static str,
+    /// Diagnosing a validator's identity key.
+    pub validator_key: &'static str,
+    /// Diagnosing an address on another chain.
+    pub other_chain: &'static str,
+    /// Diagnosing an address from another testnet: `{prefix}`, `{current}`.
+    pub wrong_prefix: &'static str,
+    /// Diagnosing characters addresses never contain: `{invalid}`.
+    pub invalid_characters: &'static str,
+    /// What spaces are called, among invalid characters.
+    pub spaces: &'static str,
+    /// What line breaks are called, among invalid characters.
+    pub line_breaks: &'static str,
+    /// Diagnosing an address which was cut off.
+    pub truncated: &'static str,
+    /// Diagnosing an address with something pasted onto the end.
+    pub too_long: &'static str,
+    /// Diagnosing an address with a typo.
+    pub checksum: &'static str,
+    /// Diagnosing something nothing like an address.
+    pub not_an_address: &'static str,
+    /// The claim message, above its button.
+    pub claim_prompt: &'static str,
+    /// The label of the claim button, and the title of the form it opens.
+    pub claim_button: &'static str,
+    /// The label of the address field in that form.
+    pub claim_field: &'static str,
+}
+
+/// English, which the others are translated from.
+pub const EN: Catalog = Catalog {
+    rate_limited: "Please wait for another {wait} before requesting more tokens. Thanks!",
+    ownership_challenge: "That's a lot of tokens, so first please prove the addresses are yours: \
+        run `galileo prove-address {challenge}` with your wallet's custody file, and within \
+        {wait} send me what it prints (starting with `{command}`) by direct message.",
+    ownership_unproven: "That doesn't prove the addresses are yours: {reason}.",
+    ownership_proven: "Thanks, that proves the addresses are yours: your tokens are on the way.",
+    ownership_public: "Please don't post `{command}` here, where everyone can see it: it holds \
+        your full viewing key, which shows every transaction of your wallet. Send it to me by \
+        direct message instead, and consider that wallet's history public from now on.",
+    batched: "Got it! Tokens go out in batches, and yours will be in the next one, at {time} UTC \
+        (in {wait}).",
+    still_working: "Still working on it… ({elapsed} so far)",
+    done_after: "Done after {elapsed}.",
+    receipt_simulated: "[Simulated] The Penumbra faucet is in dry-run mode: it would have sent \
+        {values} to `{address}`, but nothing was sent.",
+    receipt: "Here's your receipt from the Penumbra faucet:\n\
+        Sent {values} to `{address}`\n\
+        Transaction `{id}`, included at block height {height}\n\
+        \n\
+        Not seeing the funds in your wallet? Your wallet only shows them once it has synced past \
+        block {height}:\n\
+        - with `pcli`, run any command that syncs (e.g. `pcli view balance`) and wait for it to \
+        catch up\n\
+        - with the web extension, keep it open until it reports it's synced\n\
+        If your wallet was created for an older testnet, reset it (`pcli view reset`) and sync \
+        again.",
+    simulated: "[Simulated] Dry run: would have sent tokens to the following addresses, but \
+        nothing was sent:",
+    succeeded: "Successfully sent tokens to the following addresses:",
+    succeeded_entry: "`{address}`: {values}\ntry `pcli v tx {id}`\n\
+        or visit https://app.testnet.penumbra.zone/tx/?hash={id}",
+    withdrawn: "Withdrew tokens over IBC to the following addresses:",
+    withdrawn_simulated: "`{address}`: {values} ([Simulated] nothing was sent)",
+    withdrawn_entry: "`{address}`: {values}\nthey'll arrive once a relayer delivers `{id}`",
+    total: "Total sent: {values}",
+    failed: "Failed to send tokens to the following addresses:",
+    failed_entry: "`{address}` (error: {error})",
+    failure_unavailable: "the faucet couldn't reach the network; please try again in a few minutes",
+    failure_funds: "the faucet is running low on funds; please try again later",
+    failure_custody: "the faucet couldn't sign the transaction; please try again later",
+    failure_rejected: "the network rejected the transaction; please try again later",
+    failure_unknown: "something went wrong on the faucet's end; please try again later",
+    duplicates: "Skipped these addresses, which were given twice or are already being sent tokens:",
+    investigate: "{admins}: you may want to investigate this error :)",
+    unparsed: "The following _look like_ addresses, but I couldn't send to them:",
+    remaining: "I'm only allowed to send tokens to addresses {count} at a time; try again later \
+        to get tokens for the following addresses:",
+    nothing_left: "nothing left to send you for now",
+    over_budget: "the faucet has given out its budget of {budget}; please try again in {wait}",
+    over_user_cap: "you've been sent as much as you can get ({cap}); please try again in {wait}",
+    too_many_withdrawals: "only {count} addresses are sent tokens at a time; try again later",
+    sent_recently: "You were sent `{name}` recently; you can ask for it again in {wait}.",
+    not_on_menu: "I don't hand out {unknown} (you can ask for {menu}).",
+    validator_key: "that's a validator's identity key: use an address from your own wallet \
+        instead",
+    other_chain: "that's an address on another chain: use a Penumbra address from your wallet",
+    wrong_prefix: "`{prefix}` addresses aren't used on this testnet (current ones start \
+        `{current}1`): update your wallet, and copy your address again",
+    invalid_characters: "it contains characters addresses never do ({invalid}): look for a typo",
+    spaces: "spaces",
+    line_breaks: "line breaks",
+    truncated: "it's too short, as if it was cut off: copy the whole address again",
+    too_long: "it's too long, as if something was pasted onto the end: check where it stops",
+    checksum: "its checksum doesn't match, so there's probably a typo: copy it from your wallet \
+        again",
+    not_an_address: "it doesn't look like an address at all",
+    claim_prompt: "Need testnet tokens? Press the button and paste your address: only you will \
+        see it, and the result.",
+    claim_button: "Request testnet funds",
+    claim_field: "Your Penumbra address",
+};
+
+/// Spanish.
+pub const ES: Catalog = Catalog {
+    rate_limited: "Espera {wait} más antes de pedir más tokens. ¡Gracias!",
+    ownership_challenge: "Son muchos tokens, así que primero demuestra que estas direcciones son \
+        tuyas: ejecuta `galileo prove-address {challenge}` con el archivo de custodia de tu \
+        billetera y, dentro de {wait}, envíame por mensaje directo lo que imprime (empieza por \
+        `{command}`).",
+    ownership_unproven: "Eso no demuestra que las direcciones sean tuyas: {reason}.",
+    ownership_proven: "Gracias, eso demuestra que las direcciones son tuyas: tus tokens están en \
+        camino.",
+    ownership_public: "No publiques `{command}` aquí, donde todos pueden verlo: contiene tu clave \
+        de visualización completa, que muestra todas las transacciones de tu billetera. \
+        Envíamelo por mensaje directo, y considera públicas las transacciones de esa billetera a \
+        partir de ahora.",
+    batched: "¡Recibido! Los tokens se envían por lotes, y los tuyos irán en el próximo, a las \
+        {time} UTC (dentro de {wait}).",
+    still_working: "Sigo en ello… (llevo {elapsed})",
+    done_after: "Listo tras {elapsed}.",
+    receipt_simulated: "[Simulado] El faucet de Penumbra está en modo de prueba: habría enviado \
+        {values} a `{address}`, pero no se envió nada.",
+    receipt: "Aquí tienes tu recibo del faucet de Penumbra:\n\
+        Se enviaron {values} a `{address}`\n\
+        Transacción `{id}`, incluida en el bloque {height}\n\
+        \n\
+        ¿No ves los fondos en tu billetera? Tu billetera solo los muestra cuando se ha \
+        sincronizado más allá del bloque {height}:\n\
+        - con `pcli`, ejecuta cualquier comando que sincronice (p. ej. `pcli view balance`) y \
+        espera a que se ponga al día\n\
+        - con la extensión web, mantenla abierta hasta que indique que está sincronizada\n\
+        Si tu billetera se creó para una testnet anterior, restablécela (`pcli view reset`) y \
+        vuelve a sincronizar.",
+    simulated: "[Simulado] Prueba: se habrían enviado tokens a las siguientes direcciones, pero \
+        no se envió nada:",
+    succeeded: "Se enviaron tokens a las siguientes direcciones:",
+    succeeded_entry: "`{address}`: {values}\nprueba `pcli v tx {id}`\n\
+        o visita https://app.testnet.penumbra.zone/tx/?hash={id}",
+    withdrawn: "Se retiraron tokens por IBC a las siguientes direcciones:",
+    withdrawn_simulated: "`{address}`: {values} ([Simulado] no se envió nada)",
+    withdrawn_entry: "`{address}`: {values}\nllegarán cuando un relayer entregue `{id}`",
+    total: "Total enviado: {values}",
+    failed: "No se pudieron enviar tokens a las siguientes direcciones:",
+    failed_entry: "`{address}` (error: {error})",
+    failure_unavailable: "el faucet no pudo conectarse a la red; inténtalo en unos minutos",
+    failure_funds: "al faucet le quedan pocos fondos; inténtalo de nuevo más tarde",
+    failure_custody: "el faucet no pudo firmar la transacción; inténtalo de nuevo más tarde",
+    failure_rejected: "la red rechazó la transacción; inténtalo de nuevo más tarde",
+    failure_unknown: "algo falló en el faucet; inténtalo de nuevo más tarde",
+    duplicates: "Se omitieron estas direcciones, que se dieron dos veces o ya están recibiendo \
+        tokens:",
+    investigate: "{admins}: quizá queráis investigar este error :)",
+    unparsed: "Lo siguiente _parecen_ direcciones, pero no pude enviarles tokens:",
+    remaining: "Solo puedo enviar tokens a {count} direcciones a la vez; vuelve a intentarlo más \
+        tarde para recibir tokens en las siguientes direcciones:",
+    nothing_left: "por ahora no queda nada que enviarte",
+    over_budget: "el faucet ha agotado su presupuesto de {budget}; vuelve a intentarlo dentro de \
+        {wait}",
+    over_user_cap: "ya se te envió todo lo que puedes recibir ({cap}); vuelve a intentarlo \
+        dentro de {wait}",
+    too_many_withdrawals: "solo se envían tokens a {count} direcciones a la vez; vuelve a \
+        intentarlo más tarde",
+    sent_recently: "Se te envió `{name}` hace poco; puedes volver a pedirlo dentro de {wait}.",
+    not_on_menu: "No reparto {unknown} (puedes pedir {menu}).",
+    validator_key: "es la clave de identidad de un validador: usa una dirección de tu propia \
+        billetera",
+    other_chain: "es una dirección de otra cadena: usa una dirección de Penumbra de tu billetera",
+    wrong_prefix: "las direcciones `{prefix}` no se usan en esta testnet (las actuales empiezan \
+        por `{current}1`): actualiza tu billetera y vuelve a copiar tu dirección",
+    invalid_characters: "contiene caracteres que las direcciones nunca tienen ({invalid}): busca \
+        un error tipográfico",
+    spaces: "espacios",
+    line_breaks: "saltos de línea",
+    truncated: "es demasiado corta, como si se hubiera cortado: vuelve a copiar la dirección \
+        completa",
+    too_long: "es demasiado larga, como si se hubiera pegado algo al final: comprueba dónde \
+        termina",
+    checksum: "su checksum no coincide, así que probablemente hay un error tipográfico: vuelve a \
+        copiarla de tu billetera",
+    not_an_address: "no se parece en nada a una dirección",
+    claim_prompt: "¿Necesitas tokens de testnet? Pulsa el botón y pega tu dirección: solo tú la \
+        verás, y también el resultado.",
+    claim_button: "Pedir fondos de testnet",
+    claim_field: "Tu dirección de Penumbra",
+};
+
+/// Portuguese (as spoken in Brazil).
+pub const PT: Catalog = Catalog {
+    rate_limited: "Aguarde mais {wait} antes de pedir mais tokens. Obrigado!",
+    ownership_challenge:
+        "São muitos tokens, então primeiro prove que esses endereços são seus: \
+        execute `galileo prove-address {challenge}` com o arquivo de custódia da sua carteira e, \
+        em até {wait}, me envie por mensagem direta o que ele imprimir (começando com `{command}`).",
+    ownership_unproven: "Isso não prova que os endereços são seus: {reason}.",
+    ownership_proven: "Obrigado, isso prova que os endereços são seus: seus tokens estão a \
+        caminho.",
+    ownership_public: "Não publique `{command}` aqui, onde todos podem ver: isso contém sua chave \
+        de visualização completa, que mostra todas as transações da sua carteira. Envie para mim \
+        por mensagem direta e considere públicas as transações dessa carteira daqui em diante.",
+    batched: "Entendido! Os tokens são enviados em lotes, e os seus irão no próximo, às {time} \
+        UTC (em {wait}).",
+    still_working: "Ainda estou trabalhando nisso… ({elapsed} até agora)",
+    done_after: "Concluído após {elapsed}.",
+    receipt_simulated: "[Simulado] O faucet da Penumbra está em modo de teste: teria enviado \
+        {values} para `{address}`, mas nada foi enviado.",
+    receipt: "Aqui está o seu recibo do faucet da Penumbra:\n\
+        Enviado {values} para `{address}`\n\
+        Transação `{id}`, incluída no bloco {height}\n\
+        \n\
+        Não está vendo os fundos na sua carteira? Ela só os mostra depois de sincronizar além do \
+        bloco {height}:\n\
+        - com o `pcli`, execute qualquer comando que sincronize (por exemplo, `pcli view \
+        balance`) e espere ele terminar\n\
+        - com a extensão web, mantenha-a aberta até que ela indique que está sincronizada\n\
+        Se a sua carteira foi criada para uma testnet anterior, redefina-a (`pcli view reset`) e \
+        sincronize novamente.",
+    simulated: "[Simulado] Teste: teria enviado tokens para os seguintes endereços, mas nada foi \
+        enviado:",
+    succeeded: "Tokens enviados com sucesso para os seguintes endereços:",
+    succeeded_entry: "`{address}`: {values}\ntente `pcli v tx {id}`\n\
+        ou acesse https://app.testnet.penumbra.zone/tx/?hash={id}",
+    withdrawn: "Tokens sacados via IBC para os seguintes endereços:",
+    withdrawn_simulated: "`{address}`: {values} ([Simulado] nada foi enviado)",
+    withdrawn_entry: "`{address}`: {values}\neles chegarão quando um relayer entregar `{id}`",
+    total: "Total enviado: {values}",
+    failed: "Falha ao enviar tokens para os seguintes endereços:",
+    failed_entry: "`{address}` (erro: {error})",
+    failure_unavailable: "o faucet não conseguiu acessar a rede; tente novamente em alguns minutos",
+    failure_funds: "o faucet está com poucos fundos; tente novamente mais tarde",
+    failure_custody: "o faucet não conseguiu assinar a transação; tente novamente mais tarde",
+    failure_rejected: "a rede rejeitou a transação; tente novamente mais tarde",
+    failure_unknown: "algo deu errado no faucet; tente novamente mais tarde",
+    duplicates: "Estes endereços foram ignorados, pois foram informados duas vezes ou já estão \
+        recebendo tokens:",
+    investigate: "{admins}: talvez vocês queiram investigar este erro :)",
+    unparsed: "Os seguintes _parecem_ endereços, mas não consegui enviar para eles:",
+    remaining: "Só posso enviar tokens para {count} endereços por vez; tente novamente mais \
+        tarde para receber tokens nos seguintes endereços:",
+    nothing_left: "nada mais para enviar a você por enquanto",
+    over_budget: "o faucet esgotou seu orçamento de {budget}; tente novamente em {wait}",
+    over_user_cap: "você já recebeu tudo o que pode receber ({cap}); tente novamente em {wait}",
+    too_many_withdrawals: "só são enviados tokens para {count} endereços por vez; tente \
+        novamente mais tarde",
+    sent_recently: "Você recebeu `{name}` recentemente; pode pedir de novo em {wait}.",
+    not_on_menu: "Não distribuo {unknown} (você pode pedir {menu}).",
+    validator_key: "isso é a chave de identidade de um validador: use um endereço da sua própria \
+        carteira",
+    other_chain: "isso é um endereço de outra chain: use um endereço da Penumbra da sua carteira",
+    wrong_prefix: "endereços `{prefix}` não são usados nesta testnet (os atuais começam com \
+        `{current}1`): atualize sua carteira e copie seu endereço novamente",
+    invalid_characters: "contém caracteres que endereços nunca têm ({invalid}): procure um erro \
+        de digitação",
+    spaces: "espaços",
+    line_breaks: "quebras de linha",
+    truncated: "é curto demais, como se tivesse sido cortado: copie o endereço inteiro novamente",
+    too_long: "é longo demais, como se algo tivesse sido colado no final: verifique onde ele \
+        termina",
+    checksum: "o checksum não confere, então provavelmente há um erro de digitação: copie-o da \
+        sua carteira novamente",
+    not_an_address: "não se parece nada com um endereço",
+    claim_prompt: "Precisa de tokens da testnet? Aperte o botão e cole seu endereço: só você vai \
+        vê-lo, assim como o resultado.",
+    claim_button: "Pedir fundos da testnet",
+    claim_field: "Seu endereço da Penumbra",
+};
+
+/// French.
+pub const FR: Catalog = Catalog {
+    rate_limited: "Merci d'attendre encore {wait} avant de redemander des tokens !",
+    ownership_challenge: "Cela fait beaucoup de tokens : prouvez d'abord que ces adresses sont \
+        à vous en lançant `galileo prove-address {challenge}` avec le fichier de garde de votre \
+        portefeuille, puis envoyez-moi en message privé dans les {wait} ce qu'il affiche \
+        (commençant par `{command}`).",
+    ownership_unproven: "Cela ne prouve pas que les adresses sont à vous : {reason}.",
+    ownership_proven: "Merci, cela prouve que les adresses sont à vous : vos tokens arrivent.",
+    ownership_public: "Ne publiez pas `{command}` ici, où tout le monde peut le voir : cela \
+        contient votre clé de visualisation complète, qui montre toutes les transactions de votre \
+        portefeuille. Envoyez-le-moi en message privé, et considérez désormais l'historique de ce \
+        portefeuille comme public.",
+    batched: "C'est noté ! Les tokens sont envoyés par lots, et les vôtres partiront avec le \
+        prochain, à {time} UTC (dans {wait}).",
+    still_working: "Toujours en cours… ({elapsed} jusqu'ici)",
+    done_after: "Terminé en {elapsed}.",
+    receipt_simulated: "[Simulation] Le faucet Penumbra est en mode simulation : il aurait \
+        envoyé {values} à `{address}`, mais rien n'a été envoyé.",
+    receipt: "Voici votre reçu du faucet Penumbra :\n\
+        {values} envoyés à `{address}`\n\
+        Transaction `{id}`, incluse à la hauteur de bloc {height}\n\
+        \n\
+        Vous ne voyez pas les fonds dans votre portefeuille ? Il ne les affiche qu'une fois \
+        synchronisé au-delà du bloc {height} :\n\
+        - avec `pcli`, lancez n'importe quelle commande qui synchronise (par exemple `pcli view \
+        balance`) et attendez qu'elle rattrape son retard\n\
+        - avec l'extension web, gardez-la ouverte jusqu'à ce qu'elle indique être synchronisée\n\
+        Si votre portefeuille a été créé pour un ancien testnet, réinitialisez-le (`pcli view \
+        reset`) et synchronisez-le à nouveau.",
+    simulated: "[Simulation] Des tokens auraient été envoyés aux adresses suivantes, mais rien \
+        n'a été envoyé :",
+    succeeded: "Tokens envoyés aux adresses suivantes :",
+    succeeded_entry: "`{address}`: {values}\nessayez `pcli v tx {id}`\n\
+        ou consultez https://app.testnet.penumbra.zone/tx/?hash={id}",
+    withdrawn: "Tokens retirés via IBC vers les adresses suivantes :",
+    withdrawn_simulated: "`{address}`: {values} ([Simulation] rien n'a été envoyé)",
+    withdrawn_entry: "`{address}`: {values}\nils arriveront dès qu'un relayer aura transmis `{id}`",
+    total: "Total envoyé : {values}",
+    failed: "Échec de l'envoi de tokens aux adresses suivantes :",
+    failed_entry: "`{address}` (erreur : {error})",
+    failure_unavailable: "le faucet n'a pas pu joindre le réseau ; réessayez dans quelques minutes",
+    failure_funds: "le faucet manque de fonds ; réessayez plus tard",
+    failure_custody: "le faucet n'a pas pu signer la transaction ; réessayez plus tard",
+    failure_rejected: "le réseau a rejeté la transaction ; réessayez plus tard",
+    failure_unknown: "un problème est survenu du côté du faucet ; réessayez plus tard",
+    duplicates: "Ces adresses ont été ignorées, car données deux fois ou recevant déjà des \
+        tokens :",
+    investigate: "{admins} : vous voudrez peut-être examiner cette erreur :)",
+    unparsed: "Les éléments suivants _ressemblent_ à des adresses, mais je n'ai pas pu y envoyer \
+        de tokens :",
+    remaining: "Je ne peux envoyer des tokens qu'à {count} adresses à la fois ; réessayez plus \
+        tard pour obtenir des tokens aux adresses suivantes :",
+    nothing_left: "plus rien à vous envoyer pour le moment",
+    over_budget: "le faucet a épuisé son budget de {budget} ; réessayez dans {wait}",
+    over_user_cap: "vous avez déjà reçu tout ce que vous pouvez recevoir ({cap}) ; réessayez \
+        dans {wait}",
+    too_many_withdrawals: "seules {count} adresses reçoivent des tokens à la fois ; réessayez \
+        plus tard",
+    sent_recently: "Vous avez reçu `{name}` récemment ; vous pourrez le redemander dans {wait}.",
+    not_on_menu: "Je ne distribue pas {unknown} (vous pouvez demander {menu}).",
+    validator_key: "c'est la clé d'identité d'un validateur : utilisez plutôt une adresse de \
+        votre propre portefeuille",
+    other_chain: "c'est une adresse d'une autre chaîne : utilisez une adresse Penumbra de votre \
+        portefeuille",
+    wrong_prefix: "les adresses `{prefix}` ne sont pas utilisées sur ce testnet (les actuelles \
+        commencent par `{current}1`) : mettez à jour votre portefeuille et copiez à nouveau \
+        votre adresse",
+    invalid_characters: "elle contient des caractères qu'une adresse ne contient jamais \
+        ({invalid}) : cherchez une faute de frappe",
+    spaces: "des espaces",
+    line_breaks: "des retours à la ligne",
+    truncated: "elle est trop courte, comme si elle avait été coupée : copiez à nouveau \
+        l'adresse entière",
+    too_long: "elle est trop longue, comme si quelque chose avait été collé à la fin : vérifiez \
+        où elle s'arrête",
+    checksum: "sa somme de contrôle ne correspond pas, il y a donc sans doute une faute de frappe \
+        : copiez-la à nouveau depuis votre portefeuille",
+    not_an_address: "cela ne ressemble pas du tout à une adresse",
+    claim_prompt: "Besoin de tokens du testnet ? Appuyez sur le bouton et collez votre adresse : \
+        vous seul la verrez, ainsi que le résultat.",
+    claim_button: "Demander des fonds du testnet",
+    claim_field: "Votre adresse Penumbra",
+};
@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+/// The labels distinguishing the different series of a metric, as `(name, value)` pairs.
+pub type Labels = Vec<(&'static str, String)>;
+
+/// Every counter, by name and labels.
+static COUNTERS: Mutex<BTreeMap<(&'static str, Labels), u64>> = Mutex::new(BTreeMap::new());
+
+/// Increment the counter with the given name and labels by one.
+pub fn increment(name: &'static str, labels: &[(&'static str, String)]) {
+    add(name, labels, 1);
+}
+
+/// Add to the counter with the given name and labels.
+pub fn add(name: &'static str, labels: &[(&'static str, String)], value: u64) {
+    *COUNTERS
+        .lock()
+        .unwrap()
+        .entry((name, labels.to_vec()))
+        .or_default() += value;
+}
+
+/// The current value of every series of the counter with the given name.
+pub fn counters(name: &'static str) -> Vec<(Labels, u64)> {
+    COUNTERS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((counter, _), _)| *counter == name)
+        .map(|((_, labels), &value)| (labels.clone(), value))
+        .collect()
+}
+
+/// Every gauge, by name and labels.
+static GAUGES: Mutex<BTreeMap<(&'static str, Labels), i64>> = Mutex::new(BTreeMap::new());
+
+/// Set the gauge with the given name and labels.
+pub fn set(name: &'static str, labels: &[(&'static str, String)], value: i64) {
+    GAUGES
+        .lock()
+        .unwrap()
+        .insert((name, labels.to_vec()), value);
+}
+
+/// The current value of every series of the gauge with the given name.
+pub fn gauges(name: &'static str) -> Vec<(Labels, i64)> {
+    GAUGES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((gauge, _), _)| *gauge == name)
+        .map(|((_, labels), &value)| (labels.clone(), value))
+        .collect()
+}
+
+/// The upper bounds of the buckets of every histogram, in seconds: from a fast node's round trip up
+/// to a slow block.
+pub const BUCKETS: [f64; 12] = [
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// The distribution of the durations observed for a histogram.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    /// How many observations fell in each bucket of [`BUCKETS`] (not counting the smaller ones).
+    pub buckets: [u64; BUCKETS.len()],
+    /// How many observations there were in all, including any above the largest bucket.
+    pub count: u64,
+    /// The sum of all observations, in seconds.
+    pub sum: f64,
+}
+
+/// Every histogram, by name and labels.
+static HISTOGRAMS: Mutex<BTreeMap<(&'static str, Labels), Histogram>> = Mutex::new(BTreeMap::new());
+
+/// Record a duration in the histogram with the given name and labels.
+pub fn observe(name: &'static str, labels: &[(&'static str, String)], duration: Duration) {
+    let seconds = duration.as_secs_f64();
+    let mut histograms = HISTOGRAMS.lock().unwrap();
+    let histogram = histograms.entry((name, labels.to_vec())).or_default();
+    if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+        histogram.buckets[bucket] += 1;
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+/// The current distribution of every series of the histogram with the given name.
+pub fn histograms(name: &'static str) -> Vec<(Labels, Histogram)> {
+    HISTOGRAMS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((histogram, _), _)| *histogram == name)
+        .map(|((_, labels), histogram)| (labels.clone(), histogram.clone()))
+        .collect()
+}
+
+/// Render every metric in the Prometheus text exposition format, for scraping.
+pub fn render() -> String {
+    let mut out = String::new();
+    // Series are sorted by name, so each name's type is written before its first series
+    let mut previous = None;
+    for ((name, labels), value) in COUNTERS.lock().unwrap().iter() {
+        if previous.replace(*name) != Some(*name) {
+            writeln!(out, "# TYPE {} counter", name).unwrap();
+        }
+        writeln!(out, "{}{} {}", name, format_labels(labels, None), value).unwrap();
+    }
+    for ((name, labels), value) in GAUGES.lock().unwrap().iter() {
+        if previous.replace(*name) != Some(*name) {
+            writeln!(out, "# TYPE {} gauge", name).unwrap();
+        }
+        writeln!(out, "{}{} {}", name, format_labels(labels, None), value).unwrap();
+    }
+    for ((name, labels), histogram) in HISTOGRAMS.lock().unwrap().iter() {
+        if previous.replace(*name) != Some(*name) {
+            writeln!(out, "# TYPE {} histogram", name).unwrap();
+        }
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let bucket_labels = format_labels(labels, Some(&bound.to_string()));
+            writeln!(out, "{}_bucket{} {}", name, bucket_labels, cumulative).unwrap();
+        }
+        let bucket_labels = format_labels(labels, Some("+Inf"));
+        writeln!(out, "{}_bucket{} {}", name, bucket_labels, histogram.count).unwrap();
+        let labels = format_labels(labels, None);
+        writeln!(out, "{}_sum{} {}", name, labels, histogram.sum).unwrap();
+        writeln!(out, "{}_count{} {}", name, labels, histogram.count).unwrap();
+    }
+    out
+}
+
+/// Format the labels of a series (and its bucket's upper bound, for a histogram), like
+/// `{channel="123",le="0.5"}`.
+fn format_labels(labels: &[(&'static str, String)], le: Option<&str>) -> String {
+    let mut pairs = labels
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect::<Vec<_>>();
+    if let Some(le) = le {
+        pairs.push(("le", le));
+    }
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let pairs = pairs
+        .into_iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", pairs.join(","))
+}
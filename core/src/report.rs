@@ -0,0 +1,140 @@
+//! Summaries of what the faucet did over a period, for administrators.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use penumbra_asset::{asset, Value};
+
+use crate::Store;
+
+/// How many of the most common kinds of failure to list.
+const TOP_ERRORS: usize = 3;
+
+/// What the faucet did over a period, according to the dispense and failure ledgers.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The length of the period.
+    pub period: Duration,
+    /// The number of requests which were sent tokens.
+    pub requests: usize,
+    /// The number of different users who were sent tokens.
+    pub users: usize,
+    /// The number of addresses sent tokens.
+    pub sent: usize,
+    /// The number of addresses which couldn't be sent tokens.
+    pub failed: usize,
+    /// The total sent of each asset.
+    pub totals: BTreeMap<asset::Id, u128>,
+    /// The most common kinds of failure, with how many of each, most common first.
+    pub top_errors: Vec<(String, usize)>,
+}
+
+impl Report {
+    /// Summarize the period of the given length up to the given time.
+    pub fn generate(store: &Store, period: Duration, now: DateTime<Utc>) -> Self {
+        let since = now
+            - chrono::Duration::from_std(period).unwrap_or_else(|_| chrono::Duration::max_value());
+
+        // Sweeps and self-tests aren't given out to anyone, so they don't count
+        let dispenses = store
+            .dispenses()
+            .into_iter()
+            .filter(|dispense| dispense.time > since && !dispense.is_internal())
+            .collect::<Vec<_>>();
+        let failures = store
+            .failures()
+            .into_iter()
+            .filter(|failure| failure.time > since)
+            .collect::<Vec<_>>();
+
+        let mut totals = BTreeMap::new();
+        for value in dispenses
+            .iter()
+            .flat_map(|dispense| &dispense.values)
+            .filter_map(|value| value.parse::<Value>().ok())
+        {
+            *totals.entry(value.asset_id).or_default() += value.amount.value();
+        }
+
+        let mut errors = HashMap::<String, usize>::new();
+        for failure in failures.iter() {
+            *errors.entry(failure.category()).or_default() += 1;
+        }
+        let mut top_errors = errors.into_iter().collect::<Vec<_>>();
+        top_errors.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        top_errors.truncate(TOP_ERRORS);
+
+        Report {
+            period,
+            requests: dispenses
+                .iter()
+                .filter_map(|dispense| dispense.message_id)
+                .collect::<HashSet<_>>()
+                .len(),
+            users: dispenses
+                .iter()
+                .filter_map(|dispense| dispense.user_id)
+                .collect::<HashSet<_>>()
+                .len(),
+            sent: dispenses.len(),
+            failed: failures.len(),
+            totals,
+            top_errors,
+        }
+    }
+
+    /// The share of addresses which couldn't be sent tokens, as a percentage.
+    pub fn failure_rate(&self) -> f64 {
+        let attempts = self.sent + self.failed;
+        if attempts == 0 {
+            return 0.0;
+        }
+        self.failed as f64 * 100.0 / attempts as f64
+    }
+
+    /// The report, written for posting to administrators.
+    pub fn summary(&self) -> String {
+        let cache = asset::Cache::with_known_assets();
+        let mut summary = format!(
+            "**Faucet report for the last {}**\n\
+            Requests served: {}\n\
+            Unique users: {}\n\
+            Addresses sent tokens: {} ({} failed, {:.1}% failure rate)\n",
+            humantime::format_duration(self.period),
+            self.requests,
+            self.users,
+            self.sent,
+            self.failed,
+            self.failure_rate(),
+        );
+
+        if !self.totals.is_empty() {
+            let totals = self
+                .totals
+                .iter()
+                .map(|(asset_id, amount)| {
+                    Value {
+                        amount: (*amount).into(),
+                        asset_id: *asset_id,
+                    }
+                    .format(&cache)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(summary, "Total sent: {}", totals).unwrap();
+        }
+
+        if !self.top_errors.is_empty() {
+            summary.push_str("Top errors:");
+            for (category, count) in self.top_errors.iter() {
+                write!(summary, "\n- {} ({})", category, count).unwrap();
+            }
+        }
+
+        summary.trim_end().to_string()
+    }
+}
@@ -0,0 +1,920 @@
+use std::{collections::VecDeque, fmt::Write, time::Duration};
+
+use chrono::Utc;
+use futures::{future::BoxFuture, stream::FuturesOrdered, FutureExt, StreamExt};
+use penumbra_asset::Value;
+use penumbra_keys::Address;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::Instrument;
+
+use crate::{
+    audit, locale, metrics,
+    sender::Dispenser,
+    store::{Dispense, Failure, Pending},
+    Lifecycle, Locale, Store,
+};
+
+mod request;
+pub use request::{address_matches, AddressOrAlmost, Origin, Request};
+
+mod response;
+pub use response::{Receipt, Response};
+
+mod control;
+pub use control::{fan_out, Control};
+
+mod queue;
+pub use queue::{queue, RequestQueue, RequestReceiver, Routes};
+
+mod batch;
+pub use batch::BatchSchedule;
+
+mod rotation;
+pub use rotation::Rotation;
+
+mod budget;
+pub use budget::Budget;
+
+mod counterparty;
+pub use counterparty::{Counterparties, Counterparty};
+
+mod delegation;
+pub use delegation::Delegation;
+
+mod menu;
+pub use menu::{Menu, MenuItem};
+
+mod validation;
+pub use validation::{validate, Diagnosis};
+
+mod failure;
+pub use failure::Cause;
+
+/// The histogram of how long requests wait in the queue before the responder takes them up.
+pub const QUEUE_SECONDS: &str = "galileo_dispense_queue_seconds";
+
+/// How long to wait, after sweeping the old wallet's funds to the new one on rotating the spend
+/// key, for the new wallet to see them before dispensing from it anyway.
+const ROTATION_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Worker transforming lists of addresses to responses describing whether they were successfully
+/// dispensed tokens.
+pub struct Responder<D: Dispenser> {
+    /// Maximum number of addresses to handle per message.
+    max_addresses: usize,
+    /// Actions to perform.
+    actions: RequestReceiver,
+    /// Administrative requests to handle.
+    control: mpsc::Receiver<Control>,
+    /// Switches to a new dispenser to make, and the sending end of the same queue.
+    rotations: (mpsc::Sender<Rotation<D>>, mpsc::Receiver<Rotation<D>>),
+    /// Values to send each time, watched by whatever else depends on them.
+    values: watch::Sender<Vec<Value>>,
+    /// Delegation tokens to send to requests which ask for them, if any.
+    delegation: Option<Delegation>,
+    /// The assets users can ask for by name, instead of the usual values.
+    menu: Menu,
+    /// Caps on the total amount sent in any window of time, across all users.
+    budgets: Vec<Budget>,
+    /// Caps on the total amount sent to any one user in any window of time, however many
+    /// addresses they give.
+    user_caps: Vec<Budget>,
+    /// When to dispense batches of requests, if not as they come in.
+    batch: Option<BatchSchedule>,
+    /// The other chains to whose addresses we withdraw tokens over IBC.
+    counterparties: Counterparties,
+    /// The transaction sender.
+    sender: D,
+    /// Persistent state, where we record every dispense in the ledger.
+    store: Store,
+    /// Whether we should stop consuming requests and hand them off to the next instance.
+    lifecycle: Lifecycle,
+    /// The address of the wallet we rotated to, if we did: rotating to it again would only sweep it
+    /// to itself.
+    rotated_to: Option<Address>,
+    /// Whether an administrator paused dispensing.
+    paused: bool,
+    /// Requests whose transactions are built and being broadcast, in the order they came in, to
+    /// answer once they're confirmed.
+    confirming: FuturesOrdered<BoxFuture<'static, Confirmed>>,
+    /// The values being sent to each request in `confirming`, which count against the budgets
+    /// (and the caps of the user who made it) along with the ledger, and the addresses they're
+    /// being sent to, which aren't sent to again until they're confirmed.
+    unconfirmed: VecDeque<(Origin, Vec<Value>, Vec<Address>)>,
+}
+
+/// A request whose transactions have been broadcast, with how each went.
+struct Confirmed {
+    reply: oneshot::Sender<Response>,
+    origin: Origin,
+    values: Vec<Value>,
+    response: Response,
+    /// The result of the send to each address.
+    sends: Vec<(Address, anyhow::Result<(penumbra_transaction::Id, u64)>)>,
+}
+
+/// A request waiting for the next batch, with what to send it.
+struct Queued {
+    request: Request,
+    /// The values to send to each address.
+    values: Vec<Value>,
+    /// Notes on anything asked for which won't be sent.
+    notes: Vec<String>,
+}
+
+impl<D: Dispenser> Responder<D> {
+    /// Create a new responder, returning the queues for requests and for administrative control.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sender: D,
+        max_addresses: usize,
+        values: Vec<Value>,
+        delegation: Option<Delegation>,
+        menu: Menu,
+        budgets: Vec<Budget>,
+        user_caps: Vec<Budget>,
+        batch: Option<BatchSchedule>,
+        counterparties: Counterparties,
+        store: Store,
+        lifecycle: Lifecycle,
+    ) -> (RequestQueue, mpsc::Sender<Control>, Self) {
+        let (tx, rx) = queue();
+        let (control_tx, control_rx) = mpsc::channel(10);
+        (
+            tx,
+            control_tx,
+            Responder {
+                sender,
+                max_addresses,
+                actions: rx,
+                control: control_rx,
+                rotations: mpsc::channel(1),
+                values: watch::channel(values).0,
+                delegation,
+                menu,
+                budgets,
+                user_caps,
+                batch,
+                counterparties,
+                store,
+                lifecycle,
+                rotated_to: None,
+                paused: false,
+                confirming: FuturesOrdered::new(),
+                unconfirmed: VecDeque::new(),
+            },
+        )
+    }
+
+    /// The values currently sent each time, as administrators change them.
+    pub fn values(&self) -> watch::Receiver<Vec<Value>> {
+        self.values.subscribe()
+    }
+
+    /// The queue through which to switch the responder to a new dispenser (see [`Rotation`]).
+    pub fn rotations(&self) -> mpsc::Sender<Rotation<D>> {
+        self.rotations.0.clone()
+    }
+
+    /// Run the responder.
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        // Requests waiting for the next batch, if dispensing in batches
+        let mut batch = Vec::<Queued>::new();
+        loop {
+            let schedule = self.batch;
+            let next_batch = async move {
+                match schedule {
+                    Some(schedule) => tokio::time::sleep(schedule.until_next(Utc::now())).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            tokio::select! {
+                request = self.actions.recv(), if !self.paused => match request {
+                    Some(request) => {
+                        metrics::observe(QUEUE_SECONDS, &[], request.queued.elapsed());
+                        let (values, notes) = self.values_for(&request);
+                        if self.batch.is_some() {
+                            batch.push(Queued { request, values, notes });
+                            continue;
+                        }
+                        self.dispense(request, values, notes).await;
+                    }
+                    None => break,
+                },
+                Some(confirmed) = self.confirming.next(), if !self.confirming.is_empty() => {
+                    self.unconfirmed.pop_front();
+                    self.answer(confirmed);
+                }
+                _ = next_batch, if !batch.is_empty() && !self.paused => {
+                    self.dispense_batch(std::mem::take(&mut batch)).await;
+                }
+                Some(control) = self.control.recv() => self.handle_control(control).await,
+                Some(rotation) = self.rotations.1.recv() => self.rotate(rotation).await,
+                _ = self.lifecycle.stopped() => {
+                    self.drain().await;
+                    return self.hand_off(batch);
+                }
+            }
+        }
+
+        // Nothing more is coming, so don't keep what's already arrived waiting
+        self.drain().await;
+        if !batch.is_empty() {
+            self.dispense_batch(batch).await;
+        }
+        Ok(())
+    }
+
+    /// The values to send for a request (which may have asked for assets from the menu, or
+    /// delegation tokens), and notes on anything asked for which won't be sent.
+    fn values_for(&self, request: &Request) -> (Vec<Value>, Vec<String>) {
+        let recent = match self.menu.longest_rate_limit() {
+            Some(rate_limit) if !request.assets.is_empty() => self.recent(rate_limit),
+            _ => Vec::new(),
+        };
+        let (chosen, notes) = self.menu.select(
+            &request.assets,
+            request.origin.user_id,
+            &recent,
+            Utc::now(),
+            request.locale,
+        );
+        let values = chosen.unwrap_or_else(|| self.values.borrow().clone());
+        let values = match (&self.delegation, request.delegate) {
+            (Some(delegation), true) => delegation.values(&values),
+            _ => values,
+        };
+        (values, notes)
+    }
+
+    /// Stop consuming requests, saving those still queued (or waiting for the next batch) to the
+    /// store for the next instance to pick up.
+    fn hand_off(&mut self, batch: Vec<Queued>) -> anyhow::Result<()> {
+        let mut requests = batch
+            .into_iter()
+            .map(|queued| queued.request)
+            .collect::<Vec<_>>();
+        while let Some(request) = self.actions.try_recv() {
+            requests.push(request);
+        }
+
+        self.save_pending(&requests)?;
+
+        // Only now that they're saved, drop the requests, letting their senders know that they
+        // won't be answered by this instance
+        drop(requests);
+        Ok(())
+    }
+
+    /// Save requests for the next instance to pick up.
+    fn save_pending(&self, requests: &[Request]) -> anyhow::Result<()> {
+        let pending = requests
+            .iter()
+            .filter(|request| request.can_hand_off())
+            .map(Pending::of)
+            .collect::<Vec<_>>();
+        tracing::info!(count = pending.len(), "handing off queued requests");
+        self.store.save_pending(pending)
+    }
+
+    /// Handle an administrative request.
+    async fn handle_control(&mut self, control: Control) {
+        match control {
+            Control::SelfTest(response) => {
+                tracing::info!("running self-test");
+                self.drain().await;
+                // The values are checked to be non-empty when the bot starts
+                let asset_id = self.values.borrow()[0].asset_id;
+                let report = self.sender.self_test(asset_id).await;
+                tracing::info!(?report, "self-test complete");
+                if let (Ok(id), false) = (&report.result, self.sender.is_dry_run()) {
+                    self.record(Dispense::new(None, &report.address, id, &[]));
+                }
+                let _ = response.send(report);
+            }
+            Control::Pause => {
+                tracing::warn!("paused: requests will wait in the queue until resumed");
+                self.paused = true;
+            }
+            Control::Resume => {
+                tracing::info!("resumed");
+                self.paused = false;
+            }
+            Control::SetValues(values) => {
+                tracing::info!(?values, "changed values to send");
+                self.values.send_replace(values);
+            }
+            Control::Sweep { address, result } => {
+                tracing::info!(%address, "sweeping funds");
+                self.drain().await;
+                let swept = self.sender.sweep(address).await;
+                if let Ok(Some((tx_id, values))) = &swept {
+                    if !self.sender.is_dry_run() {
+                        self.record(Dispense::new(None, &address, tx_id, values));
+                    }
+                }
+                let _ = result.send(swept.map(|swept| swept.map(|(tx_id, _)| tx_id)));
+            }
+            Control::Ping(reply) => {
+                let _ = reply.send(());
+            }
+        }
+    }
+
+    /// Sweep the old wallet's funds to the new one, and switch to the new dispenser, keeping the
+    /// old one if the sweep fails.
+    async fn rotate(&mut self, rotation: Rotation<D>) {
+        let Rotation {
+            mut dispenser,
+            address,
+            result,
+        } = rotation;
+        if self.rotated_to == Some(address) {
+            tracing::warn!(%address, "already dispensing from the new wallet, not rotating again");
+            let _ = result.send(Err(anyhow::anyhow!(
+                "already dispensing from {}: give a custody file for a new key before rotating again",
+                address
+            )));
+            return;
+        }
+        tracing::info!(%address, "rotating spend key: sweeping funds to the new wallet");
+        self.drain().await;
+        let swept = match self.sender.sweep(address).await {
+            Ok(swept) => swept,
+            Err(e) => {
+                tracing::error!(error = ?e, "failed to sweep funds, keeping the old spend key");
+                let _ = result.send(Err(e));
+                return;
+            }
+        };
+
+        if let Some((tx_id, values)) = &swept {
+            if !self.sender.is_dry_run() {
+                self.record(Dispense::new(None, &address, tx_id, values));
+            }
+            // Give the new wallet's view a chance to see the funds, so the next request doesn't
+            // fail for want of them
+            let deadline = tokio::time::Instant::now() + ROTATION_SYNC_TIMEOUT;
+            while !self.sender.is_dry_run() && tokio::time::Instant::now() < deadline {
+                match dispenser.balance().await {
+                    Ok(balance) if !balance.is_empty() => break,
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = ?e, "failed to check new wallet's balance"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+
+        self.sender = dispenser;
+        self.rotated_to = Some(address);
+        tracing::info!(%address, "rotated spend key");
+        let _ = result.send(Ok(swept.map(|(tx_id, _)| tx_id)));
+    }
+
+    /// Try to dispense tokens to the addresses in the request, building a transaction for each and
+    /// moving on once they're broadcasting: the request is answered with a [`Response`] describing
+    /// what happened once they're confirmed.
+    async fn dispense(&mut self, request: Request, values: Vec<Value>, notes: Vec<String>) {
+        if !self.holds_store() {
+            self.give_up(vec![request]);
+            return;
+        }
+        let max_addresses = request.max_addresses.unwrap_or(self.max_addresses);
+        let origin = request.origin;
+        // The values being sent to requests still in flight count against the budgets too
+        let mut committed = self
+            .unconfirmed
+            .iter()
+            .flat_map(|(_, values, _)| values)
+            .cloned()
+            .collect::<Vec<_>>();
+        let in_flight = committed.len();
+        // And those being sent to the same user count against their caps
+        let claimed = self
+            .unconfirmed
+            .iter()
+            .filter(|(sent_to, _, _)| sent_to.user_id == origin.user_id)
+            .flat_map(|(_, values, _)| values)
+            .cloned()
+            .collect::<Vec<_>>();
+        // Nor are the addresses being sent to sent to again
+        let pending = self
+            .unconfirmed
+            .iter()
+            .flat_map(|(_, _, addresses)| addresses)
+            .copied()
+            .collect::<Vec<_>>();
+        let (outputs, mut response) = self
+            .triage(
+                request.addresses,
+                origin,
+                max_addresses,
+                &values,
+                &mut committed,
+                claimed,
+                &pending,
+                request.locale,
+            )
+            .await;
+        response.notes = notes;
+        response.duplicates = [request.duplicates, response.duplicates].concat();
+
+        let mut broadcasts = Vec::new();
+        for addr in outputs.iter().copied() {
+            // Reply to the originating message with the address
+            let span = tracing::info_span!("send", address = %addr);
+            span.in_scope(|| {
+                tracing::info!("processing send request, waiting for readiness");
+            });
+            let prepared = self
+                .sender
+                .prepare_send(addr, values.clone(), origin)
+                .instrument(span.clone())
+                .await;
+            match prepared {
+                Ok(broadcast) => {
+                    span.in_scope(|| tracing::info!("submitted send request"));
+                    broadcasts.push((addr, broadcast.instrument(span)));
+                }
+                Err(e) => response.fail(addr, &e),
+            }
+        }
+
+        let confirmed = async move {
+            let mut sends = Vec::new();
+            for (addr, broadcast) in broadcasts {
+                sends.push((addr, broadcast.await));
+            }
+            Confirmed {
+                reply: request.response,
+                origin,
+                values,
+                response,
+                sends,
+            }
+        }
+        .boxed();
+        // Answer right away if there's nothing to wait for, rather than behind the requests in
+        // flight
+        match confirmed.now_or_never() {
+            Some(confirmed) => self.answer(confirmed),
+            None => {
+                self.confirming.push_back(confirmed);
+                self.unconfirmed
+                    .push_back((origin, committed.split_off(in_flight), outputs));
+            }
+        }
+    }
+
+    /// Check that we still hold the store firmly enough to dispense, stopping if the lease on it is
+    /// running out, since another instance may take it over.
+    fn holds_store(&mut self) -> bool {
+        if !self.store.lease_ending() {
+            return true;
+        }
+        tracing::error!("lease on the store is running out, not dispensing");
+        self.lifecycle.stop();
+        false
+    }
+
+    /// Hand off requests we were about to dispense when we stopped, while the lease on the store
+    /// lasts long enough to save them for the next instance.
+    fn give_up(&self, requests: Vec<Request>) {
+        if let Err(e) = self.save_pending(&requests) {
+            // Nor can their messages be released to be caught up on, without the store
+            let message_ids = requests
+                .iter()
+                .map(|request| request.origin.message_id)
+                .collect::<Vec<_>>();
+            tracing::error!(error = ?e, ?message_ids, "failed to hand off requests, which must be answered by hand");
+        }
+        // Only now that they're saved, drop the requests, letting their senders know that they
+        // won't be answered by this instance
+        drop(requests);
+    }
+
+    /// Answer a request whose transactions have been broadcast, recording what was sent.
+    fn answer(&mut self, confirmed: Confirmed) {
+        let Confirmed {
+            reply,
+            origin,
+            values,
+            mut response,
+            sends,
+        } = confirmed;
+        for (addr, result) in sends {
+            match result {
+                Ok((id, height)) => {
+                    tracing::info!(address = %addr, id = %id, height, "send request succeeded");
+                    let simulated = self.sender.is_dry_run();
+                    if !simulated {
+                        self.record(Dispense::new(Some(origin), &addr, &id, &values));
+                    }
+                    response.succeeded.push((
+                        addr,
+                        Receipt {
+                            id,
+                            height,
+                            values: values.clone(),
+                            simulated,
+                        },
+                    ));
+                }
+                Err(e) => response.fail(addr, &e),
+            }
+        }
+        self.record_response(origin, &response);
+        let _ = reply.send(response);
+    }
+
+    /// Wait for every request in flight to be confirmed, and answer them, before doing anything
+    /// which needs the whole wallet.
+    async fn drain(&mut self) {
+        while let Some(confirmed) = self.confirming.next().await {
+            self.unconfirmed.pop_front();
+            self.answer(confirmed);
+        }
+    }
+
+    /// Dispense to every address in a batch of requests in a single transaction, answering each
+    /// request.
+    async fn dispense_batch(&mut self, batch: Vec<Queued>) {
+        if !self.holds_store() {
+            self.give_up(batch.into_iter().map(|queued| queued.request).collect());
+            return;
+        }
+        tracing::info!(requests = batch.len(), "dispensing batch");
+        self.drain().await;
+
+        // The values promised to earlier addresses in the batch count against the budgets too, and
+        // those addresses aren't sent to twice
+        let mut committed = Vec::new();
+        let mut pending = Vec::new();
+        let mut triaged = Vec::<(
+            oneshot::Sender<Response>,
+            Origin,
+            Vec<Value>,
+            Vec<Address>,
+            Response,
+        )>::new();
+        for Queued {
+            request,
+            values,
+            notes,
+        } in batch
+        {
+            let max_addresses = request.max_addresses.unwrap_or(self.max_addresses);
+            // What earlier requests in the batch from the same user are to be sent counts against
+            // their caps
+            let claimed = triaged
+                .iter()
+                .filter(|(_, origin, _, _, _)| origin.user_id == request.origin.user_id)
+                .flat_map(|(_, _, values, outputs, _)| {
+                    outputs.iter().flat_map(move |_| values.iter().cloned())
+                })
+                .collect::<Vec<_>>();
+            let (outputs, mut response) = self
+                .triage(
+                    request.addresses,
+                    request.origin,
+                    max_addresses,
+                    &values,
+                    &mut committed,
+                    claimed,
+                    &pending,
+                    request.locale,
+                )
+                .await;
+            response.notes = notes;
+            response.duplicates = [request.duplicates, response.duplicates].concat();
+            pending.extend_from_slice(&outputs);
+            triaged.push((request.response, request.origin, values, outputs, response));
+        }
+
+        let outputs = triaged
+            .iter()
+            .flat_map(|(_, _, values, outputs, _)| {
+                outputs.iter().map(move |addr| (*addr, values.clone()))
+            })
+            .collect::<Vec<_>>();
+        let result = if outputs.is_empty() {
+            Ok(None)
+        } else {
+            let count = outputs.len();
+            match self.sender.send_batch(outputs).await {
+                Ok((id, height)) => {
+                    tracing::info!(id = %id, height, outputs = count, "batch send succeeded");
+                    Ok(Some((id, height)))
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, outputs = count, "batch send failed");
+                    Err(e)
+                }
+            }
+        };
+
+        let simulated = self.sender.is_dry_run();
+        for (reply, origin, values, outputs, mut response) in triaged {
+            for addr in outputs {
+                match &result {
+                    Ok(Some((id, height))) => {
+                        if !simulated {
+                            self.record(Dispense::new(Some(origin), &addr, id, &values));
+                        }
+                        response.succeeded.push((
+                            addr,
+                            Receipt {
+                                id: *id,
+                                height: *height,
+                                values: values.clone(),
+                                simulated,
+                            },
+                        ));
+                    }
+                    Ok(None) => {}
+                    Err(error) => response.fail(addr, error),
+                }
+            }
+            self.record_response(origin, &response);
+            let _ = reply.send(response);
+        }
+    }
+
+    /// Sort the addresses in a request into those to send the values to, and those which can't be
+    /// sent to (described in the response), withdrawing to any on other chains along the way.
+    /// Values promised to the addresses to send to are added to `committed`, which counts against
+    /// the budgets along with the ledger; those promised to this request count against the user's
+    /// caps too, along with those already `claimed` for them by other requests not yet in the
+    /// ledger. Addresses in `pending`, which are already being sent to, are skipped as duplicates.
+    /// The response is described in the given language.
+    async fn triage(
+        &mut self,
+        mut addresses: Vec<AddressOrAlmost>,
+        origin: Origin,
+        max_addresses: usize,
+        values: &[Value],
+        committed: &mut Vec<Value>,
+        mut claimed: Vec<Value>,
+        pending: &[Address],
+        locale: Locale,
+    ) -> (Vec<Address>, Response) {
+        let text = locale.text();
+
+        // Addresses to send to
+        let mut outputs = Vec::<Address>::new();
+
+        // Track addresses (and associated errors) which we can't send tokens to
+        let mut failed = Vec::<(Address, String)>::new();
+
+        // Track addresses which couldn't be parsed, and why
+        let mut unparsed = Vec::<(String, Diagnosis)>::new();
+
+        // Track addresses which another request is already sending to
+        let mut duplicates = Vec::<String>::new();
+
+        // Track addresses on other chains to which we withdrew tokens, or failed to (with the full
+        // errors behind failures described only by their cause)
+        let mut withdrawn = Vec::<(String, Receipt)>::new();
+        let mut failed_withdrawals = Vec::<(String, String)>::new();
+        let mut errors = Vec::<(String, String)>::new();
+
+        // Extract up to the maximum number of permissible valid addresses from the list
+        let mut count = 0;
+        while count <= max_addresses {
+            count += 1;
+            match addresses.pop() {
+                Some(AddressOrAlmost::Address(addr)) => {
+                    // Two requests for the same address moments apart only get it sent to once
+                    if pending.contains(&*addr) {
+                        tracing::info!(address = %addr, "already being sent to");
+                        duplicates.push(addr.to_string());
+                        continue;
+                    }
+
+                    // Everything asked for from the menu was sent to the user too recently
+                    if values.is_empty() {
+                        failed.push((*addr, text.nothing_left.to_string()));
+                        continue;
+                    }
+
+                    // Once the budget for the window is spent, put off sending until it rolls over
+                    if let Some(reason) =
+                        self.over_budget(&[committed.as_slice(), values].concat(), locale)
+                    {
+                        tracing::info!(address = %addr, %reason, "over budget");
+                        failed.push((*addr, reason));
+                        continue;
+                    }
+
+                    // Likewise once the user has been sent as much as they may be, however many
+                    // addresses they give
+                    if let Some(reason) =
+                        self.over_user_cap(origin, &[claimed.as_slice(), values].concat(), locale)
+                    {
+                        tracing::info!(address = %addr, %reason, "over user cap");
+                        failed.push((*addr, reason));
+                        continue;
+                    }
+
+                    committed.extend_from_slice(values);
+                    claimed.extend_from_slice(values);
+                    outputs.push(*addr);
+                }
+                Some(AddressOrAlmost::Almost(addr, diagnosis)) => {
+                    unparsed.push((addr, diagnosis));
+                }
+                Some(AddressOrAlmost::External(addr)) => {
+                    let counterparty = match self.counterparties.find(&addr) {
+                        Some(counterparty) => counterparty.clone(),
+                        None => {
+                            unparsed.push((addr, Diagnosis::OtherChain));
+                            continue;
+                        }
+                    };
+                    if values.is_empty() {
+                        failed_withdrawals.push((addr, text.nothing_left.to_string()));
+                        continue;
+                    }
+                    if let Some(reason) =
+                        self.over_budget(&[committed.as_slice(), values].concat(), locale)
+                    {
+                        tracing::info!(address = %addr, %reason, "over budget");
+                        failed_withdrawals.push((addr, reason));
+                        continue;
+                    }
+                    if let Some(reason) =
+                        self.over_user_cap(origin, &[claimed.as_slice(), values].concat(), locale)
+                    {
+                        tracing::info!(address = %addr, %reason, "over user cap");
+                        failed_withdrawals.push((addr, reason));
+                        continue;
+                    }
+                    match self.withdraw(&addr, counterparty, origin, values).await {
+                        Ok(receipt) => {
+                            claimed.extend_from_slice(values);
+                            withdrawn.push((addr, receipt))
+                        }
+                        Err(e) => {
+                            let (reason, detail) = failure::explain(&addr, &e, locale);
+                            errors.push((addr.clone(), detail));
+                            failed_withdrawals.push((addr, reason));
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+
+        // Separate the rest of the list into unparsed and remaining valid ones
+        let mut remaining = Vec::<Address>::new();
+        for addr in addresses {
+            match addr {
+                AddressOrAlmost::Address(addr) => remaining.push(*addr),
+                AddressOrAlmost::Almost(addr, diagnosis) => unparsed.push((addr, diagnosis)),
+                AddressOrAlmost::External(addr) if self.counterparties.find(&addr).is_some() => {
+                    failed_withdrawals.push((
+                        addr,
+                        locale::fill(text.too_many_withdrawals, &[("count", &max_addresses)]),
+                    ))
+                }
+                AddressOrAlmost::External(addr) => unparsed.push((addr, Diagnosis::OtherChain)),
+            }
+        }
+
+        (
+            outputs,
+            Response {
+                succeeded: Vec::new(),
+                failed,
+                errors,
+                unparsed,
+                remaining,
+                duplicates,
+                withdrawn,
+                failed_withdrawals,
+                notes: Vec::new(),
+                locale,
+            },
+        )
+    }
+
+    /// Withdraw the values over IBC to an address on another chain, returning a receipt, or the
+    /// error which stopped it.
+    async fn withdraw(
+        &mut self,
+        addr: &str,
+        counterparty: Counterparty,
+        origin: Origin,
+        values: &[Value],
+    ) -> anyhow::Result<Receipt> {
+        let span =
+            tracing::info_span!("withdraw", address = %addr, channel = %counterparty.channel);
+        let result = self
+            .sender
+            .withdraw(addr.to_string(), counterparty, values.to_vec(), origin)
+            .instrument(span.clone())
+            .await;
+        let (id, height) = result?;
+        span.in_scope(|| {
+            tracing::info!(id = %id, height, "withdrawal succeeded");
+        });
+        let simulated = self.sender.is_dry_run();
+        if !simulated {
+            self.record(Dispense::new(Some(origin), &addr, &id, values));
+        }
+        Ok(Receipt {
+            id,
+            height,
+            values: values.to_vec(),
+            simulated,
+        })
+    }
+
+    /// If sending the values now would go over any of the budgets, why, and when to try again, in
+    /// the given language.
+    fn over_budget(&self, values: &[Value], locale: Locale) -> Option<String> {
+        if self.budgets.is_empty() {
+            return None;
+        }
+        let longest = self.budgets.iter().map(|budget| budget.window).max()?;
+        let dispenses = self.recent(longest);
+        let now = Utc::now();
+        self.budgets.iter().find_map(|budget| {
+            budget.wait(values, &dispenses, now).map(|wait| {
+                let wait = humantime::format_duration(Duration::from_secs(wait.as_secs().max(1)));
+                locale::fill(
+                    locale.text().over_budget,
+                    &[("budget", &budget.describe()), ("wait", &wait)],
+                )
+            })
+        })
+    }
+
+    /// If sending the values now would take the user who made the request over any of the per-user
+    /// caps, why, and when to try again, in the given language.
+    fn over_user_cap(&self, origin: Origin, values: &[Value], locale: Locale) -> Option<String> {
+        // Requests made through the APIs aren't made by any chat user, and are limited by the APIs
+        if self.user_caps.is_empty() || origin.user_id.0 == 0 {
+            return None;
+        }
+        let longest = self.user_caps.iter().map(|cap| cap.window).max()?;
+        let dispenses = self
+            .recent(longest)
+            .into_iter()
+            .filter(|dispense| dispense.user_id == Some(origin.user_id.0))
+            .collect::<Vec<_>>();
+        let now = Utc::now();
+        self.user_caps.iter().find_map(|cap| {
+            cap.wait(values, &dispenses, now).map(|wait| {
+                let wait = humantime::format_duration(Duration::from_secs(wait.as_secs().max(1)));
+                locale::fill(
+                    locale.text().over_user_cap,
+                    &[("cap", &cap.describe()), ("wait", &wait)],
+                )
+            })
+        })
+    }
+
+    /// The dispenses made within the window, as far back as any budget, cap or rate limit looks,
+    /// rather than the whole ledger.
+    fn recent(&self, window: Duration) -> Vec<Dispense> {
+        let now = Utc::now();
+        match chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| now.checked_sub_signed(window))
+        {
+            Some(since) => self.store.dispenses_since(since),
+            None => self.store.dispenses(),
+        }
+    }
+
+    /// Record a response in the audit trail, and its failures in the failure ledger, for reports.
+    fn record_response(&self, origin: Origin, response: &Response) {
+        // Administrators get the full errors behind failures, which users only see the cause of
+        let mut summary = response.summary_in(Locale::En, None);
+        for (addr, error) in response.errors.iter() {
+            write!(summary, "\n`{}`: {}", addr, error).unwrap();
+        }
+        audit::record_request("dispense", origin, summary);
+        let failed = response.failed.iter().map(|(addr, reason)| {
+            let error = response.error(&addr.to_string()).unwrap_or(reason);
+            Failure::new(Some(origin), addr, error)
+        });
+        let failed_withdrawals = response.failed_withdrawals.iter().map(|(addr, reason)| {
+            Failure::new(Some(origin), addr, response.error(addr).unwrap_or(reason))
+        });
+        for failure in failed.chain(failed_withdrawals) {
+            if let Err(e) = self.store.record_failure(failure) {
+                tracing::error!(error = ?e, "failed to record failure in ledger");
+            }
+        }
+    }
+
+    /// Record a dispense in the ledger, logging rather than failing if it can't be written, since
+    /// the tokens have already been sent.
+    fn record(&self, dispense: Dispense) {
+        if let Err(e) = self.store.record_dispense(dispense) {
+            tracing::error!(error = ?e, "failed to record dispense in ledger");
+        }
+    }
+}
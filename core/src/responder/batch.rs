@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// When requests are dispensed, if they're collected into batches (each sent in a single
+/// transaction with an output for every address) rather than dispensed as they come in: trading
+/// latency for far fewer transactions, and so fees.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchSchedule {
+    /// How often to dispense a batch.
+    interval: Duration,
+    /// When the schedule started, from which batches are dispensed every interval.
+    start: DateTime<Utc>,
+}
+
+impl BatchSchedule {
+    /// Dispense a batch every interval, starting from now.
+    pub fn new(interval: Duration) -> Self {
+        BatchSchedule {
+            interval,
+            start: Utc::now(),
+        }
+    }
+
+    /// When the next batch after the given time will be dispensed.
+    pub fn next(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = (self.interval.as_millis() as i64).max(1);
+        let elapsed = (now - self.start).num_milliseconds().max(0);
+        let intervals = elapsed / interval + 1;
+        self.start + chrono::Duration::milliseconds(interval.saturating_mul(intervals))
+    }
+
+    /// How long from the given time until the next batch.
+    pub fn until_next(&self, now: DateTime<Utc>) -> Duration {
+        (self.next(now) - now).to_std().unwrap_or_default()
+    }
+}
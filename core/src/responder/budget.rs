@@ -0,0 +1,176 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use penumbra_asset::{asset, Value};
+
+use crate::store::Dispense;
+
+/// A cap on the total amount of an asset the faucet gives out in any window of time (e.g. at most
+/// 500penumbra in any hour), bounding how quickly it can be drained however many users ask.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    /// The most that may be sent in the window.
+    pub limit: Value,
+    /// The length of the window.
+    pub window: Duration,
+}
+
+impl FromStr for Budget {
+    type Err = anyhow::Error;
+
+    /// Parse a budget written as `<value>/<duration>`, such as `500penumbra/1h`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (limit, window) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("budget must be written like 500penumbra/1h"))?;
+        let limit = limit.trim().parse::<Value>()?;
+        if limit.amount.value() == 0 {
+            anyhow::bail!("budget must be non-zero");
+        }
+        let window = humantime::parse_duration(window.trim())?;
+        Ok(Budget { limit, window })
+    }
+}
+
+impl Budget {
+    /// How long until sending the values would stay within the budget, according to the dispense
+    /// ledger, or `None` if it would already. Transfers the faucet makes for itself, such as sweeps
+    /// and self-tests, don't count: they aren't given out.
+    pub fn wait(
+        &self,
+        values: &[Value],
+        dispenses: &[Dispense],
+        now: DateTime<Utc>,
+    ) -> Option<Duration> {
+        let needed: u128 = values
+            .iter()
+            .filter(|value| value.asset_id == self.limit.asset_id)
+            .map(|value| value.amount.value())
+            .sum();
+        if needed == 0 {
+            return None;
+        }
+        let limit = self.limit.amount.value();
+        if needed > limit {
+            // This will never fit, so the best we can say is to try again once the window is over
+            return Some(self.window);
+        }
+
+        // Everything sent in the window, oldest first
+        let window = chrono::Duration::from_std(self.window)
+            .unwrap_or_else(|_| chrono::Duration::max_value());
+        let mut sent = dispenses
+            .iter()
+            .filter(|dispense| now - dispense.time < window && !dispense.is_internal())
+            .filter_map(|dispense| {
+                let amount: u128 = dispense
+                    .values
+                    .iter()
+                    .filter_map(|value| value.parse::<Value>().ok())
+                    .filter(|value| value.asset_id == self.limit.asset_id)
+                    .map(|value| value.amount.value())
+                    .sum();
+                (amount > 0).then_some((dispense.time, amount))
+            })
+            .collect::<Vec<_>>();
+        sent.sort_by_key(|(time, _)| *time);
+
+        let mut total: u128 = sent.iter().map(|(_, amount)| amount).sum();
+        if total + needed <= limit {
+            return None;
+        }
+        // Wait until enough of what was sent falls out of the window
+        for (time, amount) in sent {
+            total -= amount;
+            if total + needed <= limit {
+                return (time + window - now).to_std().ok();
+            }
+        }
+        Some(self.window)
+    }
+
+    /// The budget, written like `500penumbra per 1h`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} per {}",
+            self.limit.format(&asset::Cache::with_known_assets()),
+            humantime::format_duration(self.window)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dispense(minutes_ago: i64, values: &[&str], user_id: Option<u64>) -> Dispense {
+        Dispense {
+            time: Utc::now() - chrono::Duration::minutes(minutes_ago),
+            tx_id: String::new(),
+            address: String::new(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+            user_id,
+            channel_id: None,
+            message_id: None,
+        }
+    }
+
+    fn values(values: &[&str]) -> Vec<Value> {
+        values.iter().map(|value| value.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_limit_and_window() {
+        let budget = "500penumbra/1h".parse::<Budget>().unwrap();
+        assert_eq!(budget.window, Duration::from_secs(60 * 60));
+        assert!("0penumbra/1h".parse::<Budget>().is_err());
+        assert!("500penumbra".parse::<Budget>().is_err());
+    }
+
+    #[test]
+    fn no_wait_within_the_budget() {
+        let budget = "100penumbra/1h".parse::<Budget>().unwrap();
+        let dispenses = [
+            dispense(10, &["50penumbra"], Some(1)),
+            // Outside the window
+            dispense(120, &["100penumbra"], Some(2)),
+        ];
+        let now = Utc::now();
+        assert_eq!(budget.wait(&values(&["50penumbra"]), &dispenses, now), None);
+        // Other assets don't count
+        assert_eq!(budget.wait(&values(&["1000gm"]), &dispenses, now), None);
+    }
+
+    #[test]
+    fn waits_until_enough_falls_out_of_the_window() {
+        let budget = "100penumbra/1h".parse::<Budget>().unwrap();
+        let dispenses = [
+            dispense(50, &["40penumbra"], Some(1)),
+            dispense(20, &["40penumbra"], Some(2)),
+        ];
+        let wait = budget
+            .wait(&values(&["50penumbra"]), &dispenses, Utc::now())
+            .unwrap();
+        // The older dispense leaves the window in about ten minutes
+        assert!(wait > Duration::from_secs(9 * 60) && wait <= Duration::from_secs(10 * 60));
+    }
+
+    #[test]
+    fn waits_a_whole_window_for_more_than_the_limit() {
+        let budget = "100penumbra/1h".parse::<Budget>().unwrap();
+        assert_eq!(
+            budget.wait(&values(&["101penumbra"]), &[], Utc::now()),
+            Some(budget.window)
+        );
+    }
+
+    #[test]
+    fn sweeps_do_not_count_against_the_budget() {
+        let budget = "100penumbra/1h".parse::<Budget>().unwrap();
+        let dispenses = [dispense(1, &["1000000penumbra"], None)];
+        assert_eq!(
+            budget.wait(&values(&["100penumbra"]), &dispenses, Utc::now()),
+            None
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use penumbra_asset::Value;
+use penumbra_keys::Address;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::sender::SelfTest;
+
+/// An administrative request to the responder, handled in between requests to dispense tokens.
+#[derive(Debug)]
+pub enum Control {
+    /// Send a zero-value transaction to the faucet's own address, reporting how long each stage
+    /// of the dispense path took.
+    SelfTest(oneshot::Sender<SelfTest>),
+    /// Stop dispensing until resumed, leaving requests waiting in the queue in the meantime.
+    Pause,
+    /// Dispense again after being paused.
+    Resume,
+    /// Send these values to each address from now on, instead of those the faucet started with.
+    SetValues(Vec<Value>),
+    /// Send everything in the wallet to the address (say, to retire the faucet), once the
+    /// transactions in flight are confirmed, answering with the hash of the sweep transaction, or
+    /// `None` if there was nothing to sweep.
+    Sweep {
+        address: Address,
+        result: oneshot::Sender<anyhow::Result<Option<penumbra_transaction::Id>>>,
+    },
+    /// Answer as soon as the responder gets to it, to show that it isn't stuck (for a watchdog).
+    Ping(oneshot::Sender<()>),
+}
+
+/// Pass control messages on to the responders for every chain the faucet serves (see
+/// [`Routes`](super::Routes)). Pausing and resuming applies to all of them, and a watchdog ping is
+/// answered once all of them answer; everything else concerns one wallet, so goes to the main one.
+pub fn fan_out(
+    main: mpsc::Sender<Control>,
+    others: Vec<mpsc::Sender<Control>>,
+) -> mpsc::Sender<Control> {
+    if others.is_empty() {
+        return main;
+    }
+    let (tx, mut rx) = mpsc::channel(10);
+    tokio::spawn(async move {
+        while let Some(control) = rx.recv().await {
+            let sent = match control {
+                Control::Pause => broadcast(&main, &others, || Control::Pause).await,
+                Control::Resume => broadcast(&main, &others, || Control::Resume).await,
+                Control::Ping(reply) => {
+                    let mut answers = Vec::new();
+                    let sent = broadcast(&main, &others, || {
+                        let (reply, answered) = oneshot::channel();
+                        answers.push(answered);
+                        Control::Ping(reply)
+                    })
+                    .await;
+                    // Don't hold up other control messages while a responder is busy
+                    tokio::spawn(async move {
+                        if futures::future::join_all(answers)
+                            .await
+                            .into_iter()
+                            .all(|answer| answer.is_ok())
+                        {
+                            let _ = reply.send(());
+                        }
+                    });
+                    sent
+                }
+                control => main.send(control).await.is_ok(),
+            };
+            // Once any responder is gone, let callers know that they aren't all running
+            if !sent {
+                return;
+            }
+        }
+    });
+    tx
+}
+
+/// Send a control message made by `control` to each responder, returning `false` if any is gone.
+async fn broadcast(
+    main: &mpsc::Sender<Control>,
+    others: &[mpsc::Sender<Control>],
+    mut control: impl FnMut() -> Control,
+) -> bool {
+    for queue in std::iter::once(main).chain(others) {
+        if queue.send(control()).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
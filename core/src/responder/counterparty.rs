@@ -0,0 +1,130 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use regex::Regex;
+
+/// A chain connected to Penumbra over IBC, to whose addresses the faucet dispenses by withdrawing
+/// over a channel, written as `<prefix>=<channel>` (e.g. `osmo=channel-0`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterparty {
+    /// The bech32 prefix of the chain's addresses, like `osmo`.
+    pub prefix: String,
+    /// The Penumbra end of the channel to withdraw over, like `channel-0`.
+    pub channel: String,
+}
+
+impl FromStr for Counterparty {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, channel) = s
+            .split_once('=')
+            .context("chain must be written as <address prefix>=<channel>, like osmo=channel-0")?;
+        let prefix = prefix.trim().to_lowercase();
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric()) {
+            anyhow::bail!("invalid address prefix: {}", prefix);
+        }
+        if prefix.starts_with("penumbra") {
+            anyhow::bail!("Penumbra addresses are always sent to directly");
+        }
+        let channel = channel.trim().to_string();
+        match channel.strip_prefix("channel-") {
+            Some(number) if number.parse::<u64>().is_ok() => {}
+            _ => anyhow::bail!("invalid channel (expected channel-<n>): {}", channel),
+        }
+        Ok(Counterparty { prefix, channel })
+    }
+}
+
+/// The chains the faucet dispenses to over IBC, if any.
+#[derive(Debug, Clone, Default)]
+pub struct Counterparties {
+    chains: Vec<Counterparty>,
+    /// Matches an address on any of the chains, built once up front (`None` if there are none).
+    address_regex: Option<Regex>,
+}
+
+impl Counterparties {
+    pub fn new(chains: Vec<Counterparty>) -> Self {
+        let address_regex = (!chains.is_empty()).then(|| {
+            let prefixes = chains
+                .iter()
+                .map(|chain| regex::escape(&chain.prefix))
+                .collect::<Vec<_>>()
+                .join("|");
+            Regex::new(&format!(
+                r"\b(?:{})1[qpzry9x8gf2tvdw0s3jn54khce6mua7l]{{38,}}",
+                prefixes
+            ))
+            .unwrap()
+        });
+        Counterparties {
+            chains,
+            address_regex,
+        }
+    }
+
+    /// Find every substring of the text which looks like an address on one of the chains.
+    pub fn address_matches<'a>(&self, content: &'a str) -> Vec<&'a str> {
+        match &self.address_regex {
+            Some(address_regex) => address_regex
+                .find_iter(content)
+                .map(|m| m.as_str())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The chain the address is on, if it's one of them.
+    pub fn find(&self, address: &str) -> Option<&Counterparty> {
+        self.chains.iter().find(|chain| {
+            address
+                .strip_prefix(chain.prefix.as_str())
+                .map_or(false, |rest| rest.starts_with('1'))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prefix_and_channel() {
+        let counterparty = " Osmo = channel-12 ".parse::<Counterparty>().unwrap();
+        assert_eq!(counterparty.prefix, "osmo");
+        assert_eq!(counterparty.channel, "channel-12");
+    }
+
+    #[test]
+    fn rejects_malformed_chains() {
+        for chain in [
+            "osmo",
+            "=channel-0",
+            "os-mo=channel-0",
+            "penumbra=channel-0",
+            "osmo=0",
+            "osmo=channel-x",
+        ] {
+            assert!(chain.parse::<Counterparty>().is_err(), "{}", chain);
+        }
+    }
+
+    #[test]
+    fn finds_the_chain_of_an_address() {
+        let counterparties = Counterparties::new(vec![
+            "osmo=channel-0".parse().unwrap(),
+            "cosmos=channel-1".parse().unwrap(),
+        ]);
+        let address = format!("cosmos1{}", "q".repeat(38));
+        assert_eq!(
+            counterparties.address_matches(&format!("send to {} please", address)),
+            vec![address.as_str()]
+        );
+        assert_eq!(counterparties.find(&address).unwrap().channel, "channel-1");
+        assert!(counterparties.find("juno1qqqq").is_none());
+        assert!(Counterparties::default()
+            .address_matches(&address)
+            .is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use penumbra_asset::Value;
+
+/// Delegation tokens of one validator, handed out to requests which ask for them by saying
+/// "delegate", so that stakers can try undelegating without delegating first. Written as
+/// `<amount>@<validator identity key>`, like `10@penumbravalid1...`.
+#[derive(Debug, Clone)]
+pub struct Delegation {
+    /// The delegation tokens to send.
+    pub value: Value,
+    /// Whether to send them instead of the usual values, rather than as well.
+    pub instead: bool,
+}
+
+impl FromStr for Delegation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, validator) = s
+            .split_once('@')
+            .context("delegation must be written as <amount>@<validator identity key>")?;
+        let validator = validator.trim();
+        if !validator.starts_with("penumbravalid1") {
+            anyhow::bail!("invalid validator identity key: {}", validator);
+        }
+        let value = format!("{}delegation_{}", amount.trim(), validator)
+            .parse::<Value>()
+            .context("invalid delegation amount")?;
+        if value.amount.value() == 0 {
+            anyhow::bail!("delegation amount must be non-zero");
+        }
+        Ok(Delegation {
+            value,
+            instead: false,
+        })
+    }
+}
+
+impl Delegation {
+    /// The values to send to a request which asked for delegation tokens.
+    pub fn values(&self, values: &[Value]) -> Vec<Value> {
+        if self.instead {
+            vec![self.value]
+        } else {
+            values.iter().copied().chain([self.value]).collect()
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use tonic::{Code, Status};
+
+use crate::Locale;
+
+/// What went wrong sending to an address, in terms a user can act on. The errors behind these come
+/// from the node, the view service, custody, and the planner: those from gRPC services carry a
+/// status code, but the rest tell us little beyond their text, so they're told apart by what they
+/// say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// The faucet couldn't reach the node (or its view or custody service), or it took too long.
+    Unavailable,
+    /// The faucet doesn't have enough of what it was asked to send.
+    Funds,
+    /// Custody wouldn't authorize the transaction.
+    Custody,
+    /// The chain rejected the transaction.
+    Rejected,
+    /// Anything else.
+    Unknown,
+}
+
+impl Cause {
+    /// The cause of an error from sending, going by the status of any gRPC call behind it, or
+    /// failing that, by what it and the errors it wraps say.
+    pub fn of(error: &anyhow::Error) -> Self {
+        for source in error.chain() {
+            if let Some(status) = source.downcast_ref::<Status>() {
+                match status.code() {
+                    Code::Unavailable | Code::DeadlineExceeded => return Cause::Unavailable,
+                    Code::PermissionDenied | Code::Unauthenticated => return Cause::Custody,
+                    _ => {}
+                }
+            }
+            if source.is::<tonic::transport::Error>() {
+                return Cause::Unavailable;
+            }
+        }
+        Self::from_text(&format!("{:#}", error))
+    }
+
+    /// The cause of an error going by its text alone. Failing to reach a service is checked for
+    /// first, since the message around it often says what we were trying to do (like checking the
+    /// balance) rather than what went wrong.
+    fn from_text(text: &str) -> Self {
+        let text = text.to_lowercase();
+        let says = |phrases: &[&str]| phrases.iter().any(|phrase| text.contains(phrase));
+        if says(&[
+            "transport error",
+            "unavailable",
+            "connection refused",
+            "connection reset",
+            "broken pipe",
+            "dns error",
+            "deadline",
+            "timed out",
+        ]) {
+            Cause::Unavailable
+        } else if says(&["insufficient", "not enough"]) {
+            Cause::Funds
+        } else if says(&["custody", "authoriz", "passphrase"]) {
+            Cause::Custody
+        } else if says(&[
+            "rejected",
+            "check_tx",
+            "checktx",
+            "mempool",
+            "invalid transaction",
+        ]) {
+            Cause::Rejected
+        } else {
+            Cause::Unknown
+        }
+    }
+
+    /// A short name for the cause, for logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Cause::Unavailable => "unavailable",
+            Cause::Funds => "funds",
+            Cause::Custody => "custody",
+            Cause::Rejected => "rejected",
+            Cause::Unknown => "unknown",
+        }
+    }
+
+    /// Describe the cause to the user, in the given language.
+    pub fn describe(self, locale: Locale) -> &'static str {
+        let text = locale.text();
+        match self {
+            Cause::Unavailable => text.failure_unavailable,
+            Cause::Funds => text.failure_funds,
+            Cause::Custody => text.failure_custody,
+            Cause::Rejected => text.failure_rejected,
+            Cause::Unknown => text.failure_unknown,
+        }
+    }
+}
+
+/// Describe an error from sending to an address to the user by its cause, logging it in full.
+/// Returns the description, and the full error for the ledger and the audit trail.
+pub(super) fn explain(address: &str, error: &anyhow::Error, locale: Locale) -> (String, String) {
+    let cause = Cause::of(error);
+    let detail = format!("{:#}", error);
+    tracing::warn!(%address, cause = cause.name(), error = %detail, "failed to send");
+    (cause.describe(locale).to_string(), detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn status_codes_decide_the_cause() {
+        let error = Err::<(), _>(Status::unavailable("node is down"))
+            .context("failed to check balance")
+            .unwrap_err();
+        assert_eq!(Cause::of(&error), Cause::Unavailable);
+        let error = anyhow::Error::new(Status::deadline_exceeded("too slow"));
+        assert_eq!(Cause::of(&error), Cause::Unavailable);
+        let error = Err::<(), _>(Status::permission_denied("over the limit"))
+            .context("insufficient funds")
+            .unwrap_err();
+        assert_eq!(Cause::of(&error), Cause::Custody);
+    }
+
+    #[test]
+    fn other_status_codes_fall_back_to_the_text() {
+        let error = anyhow::Error::new(Status::invalid_argument("insufficient funds for spend"));
+        assert_eq!(Cause::of(&error), Cause::Funds);
+        let error = anyhow::Error::new(Status::internal("CheckTx failed"));
+        assert_eq!(Cause::of(&error), Cause::Rejected);
+    }
+
+    #[test]
+    fn transport_errors_are_not_mistaken_for_funds() {
+        let error = anyhow::anyhow!("failed to check balance: transport error");
+        assert_eq!(Cause::of(&error), Cause::Unavailable);
+    }
+
+    #[test]
+    fn text_decides_the_cause_without_a_status() {
+        assert_eq!(
+            Cause::of(&anyhow::anyhow!("not enough penumbra")),
+            Cause::Funds
+        );
+        assert_eq!(
+            Cause::of(&anyhow::anyhow!("custody refused to sign")),
+            Cause::Custody
+        );
+        assert_eq!(Cause::of(&anyhow::anyhow!("something odd")), Cause::Unknown);
+    }
+}
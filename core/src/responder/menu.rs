@@ -0,0 +1,161 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use penumbra_asset::Value;
+
+use crate::{
+    id::UserId,
+    locale::{self, Locale},
+    store::Dispense,
+};
+
+/// An asset users can ask for by name, written as `<value>` or `<value>/<rate limit>`, like
+/// `10gm/1h`: the name is the denomination the value is written in.
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    /// The name users ask for it by, like `gm`.
+    pub name: String,
+    /// How much of it to send.
+    pub value: Value,
+    /// The minimum duration between sending it to the same user, if any.
+    pub rate_limit: Option<Duration>,
+}
+
+impl FromStr for MenuItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, rate_limit) = match s.split_once('/') {
+            Some((value, rate_limit)) => (
+                value.trim(),
+                Some(humantime::parse_duration(rate_limit.trim()).context("invalid rate limit")?),
+            ),
+            None => (s.trim(), None),
+        };
+        let name = value
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .to_lowercase();
+        if name.is_empty() {
+            anyhow::bail!("menu item must be written like 10gm or 10gm/1h");
+        }
+        let value = value.parse::<Value>()?;
+        if value.amount.value() == 0 {
+            anyhow::bail!("menu item must be non-zero");
+        }
+        Ok(MenuItem {
+            name,
+            value,
+            rate_limit,
+        })
+    }
+}
+
+/// The assets users can choose from, instead of receiving the usual values.
+#[derive(Debug, Clone, Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+impl Menu {
+    pub fn new(items: Vec<MenuItem>) -> Self {
+        Menu { items }
+    }
+
+    /// The longest rate limit on any item, which is as far back as [`Menu::select`] looks in the
+    /// ledger, if any item has one.
+    pub fn longest_rate_limit(&self) -> Option<Duration> {
+        self.items.iter().filter_map(|item| item.rate_limit).max()
+    }
+
+    /// The values to send to a user who asked for the named assets (or `None` if nothing on the
+    /// menu was asked for, so the usual values should be sent), and notes explaining any which
+    /// won't be sent because they aren't on the menu, or the user was sent them recently (in the
+    /// given language).
+    pub fn select(
+        &self,
+        names: &[String],
+        user_id: UserId,
+        dispenses: &[Dispense],
+        now: DateTime<Utc>,
+        locale: Locale,
+    ) -> (Option<Vec<Value>>, Vec<String>) {
+        if self.items.is_empty() || names.is_empty() {
+            return (None, Vec::new());
+        }
+
+        let mut values = Vec::new();
+        let mut notes = Vec::new();
+        let mut unknown = Vec::new();
+        let mut chosen = false;
+        for name in names {
+            let item = match self.items.iter().find(|item| &item.name == name) {
+                Some(item) => item,
+                None => {
+                    unknown.push(format!("`{}`", name));
+                    continue;
+                }
+            };
+            chosen = true;
+            if values.contains(&item.value) {
+                continue;
+            }
+            match item
+                .rate_limit
+                .and_then(|rate_limit| self.wait(item, rate_limit, user_id, dispenses, now))
+            {
+                Some(wait) => {
+                    let wait =
+                        humantime::format_duration(Duration::from_secs(wait.as_secs().max(1)));
+                    notes.push(locale::fill(
+                        locale.text().sent_recently,
+                        &[("name", &item.name), ("wait", &wait)],
+                    ))
+                }
+                None => values.push(item.value),
+            }
+        }
+
+        if !unknown.is_empty() {
+            let menu = self
+                .items
+                .iter()
+                .map(|item| format!("`{}`", item.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            notes.push(locale::fill(
+                locale.text().not_on_menu,
+                &[("unknown", &unknown.join(", ")), ("menu", &menu)],
+            ));
+        }
+        (chosen.then_some(values), notes)
+    }
+
+    /// How long until the item can be sent to the user again, according to the dispense ledger,
+    /// or `None` if it can be now.
+    fn wait(
+        &self,
+        item: &MenuItem,
+        rate_limit: Duration,
+        user_id: UserId,
+        dispenses: &[Dispense],
+        now: DateTime<Utc>,
+    ) -> Option<Duration> {
+        let rate_limit = chrono::Duration::from_std(rate_limit)
+            .unwrap_or_else(|_| chrono::Duration::max_value());
+        dispenses
+            .iter()
+            .filter(|dispense| dispense.user_id == Some(user_id.0))
+            .filter(|dispense| now - dispense.time < rate_limit)
+            .filter(|dispense| {
+                dispense
+                    .values
+                    .iter()
+                    .filter_map(|value| value.parse::<Value>().ok())
+                    .any(|value| value.asset_id == item.value.asset_id)
+            })
+            .map(|dispense| dispense.time)
+            .max()
+            .and_then(|last| (last + rate_limit - now).to_std().ok())
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{mpsc::error::SendError, Notify};
+
+use super::Request;
+use crate::id::ChannelId;
+
+/// The queue of requests for the responder. Unlike a plain channel, requests with priority (see
+/// [`Request::prioritize`]) are taken up before any backlog of others; otherwise requests are taken
+/// up in the order they came in. It's unbounded, so that a backlog waits in the queue, where
+/// priority requests can overtake it.
+#[derive(Debug)]
+pub struct RequestQueue {
+    shared: Arc<Shared>,
+}
+
+/// The responder's end of a [`RequestQueue`].
+#[derive(Debug)]
+pub struct RequestReceiver {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Wakes the receiver when a request arrives, or the last sender is dropped.
+    arrived: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The requests waiting to be taken up.
+    waiting: BinaryHeap<Waiting>,
+    /// How many requests have been queued, to order requests of the same priority.
+    sequence: u64,
+    /// How many senders there are: once there are none, nothing more can arrive.
+    senders: usize,
+    /// Whether the receiver has been dropped, so that nothing more will be taken up.
+    closed: bool,
+}
+
+/// A request waiting in the queue, ordered so that the next to take up is the greatest.
+#[derive(Debug)]
+struct Waiting {
+    request: Request,
+    sequence: u64,
+}
+
+impl Waiting {
+    fn key(&self) -> (bool, Reverse<u64>) {
+        (self.request.priority, Reverse(self.sequence))
+    }
+}
+
+impl PartialEq for Waiting {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Waiting {}
+
+impl PartialOrd for Waiting {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiting {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Make a new queue of requests, returning its sending and receiving ends.
+pub fn queue() -> (RequestQueue, RequestReceiver) {
+    let shared = Arc::new(Shared::default());
+    shared.state.lock().unwrap().senders = 1;
+    (
+        RequestQueue {
+            shared: shared.clone(),
+        },
+        RequestReceiver { shared },
+    )
+}
+
+impl RequestQueue {
+    /// Queue a request, failing (and giving it back) if the responder has stopped taking them up.
+    pub async fn send(&self, request: Request) -> Result<(), SendError<Request>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return Err(SendError(request));
+        }
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.waiting.push(Waiting { request, sequence });
+        drop(state);
+        self.shared.arrived.notify_one();
+        Ok(())
+    }
+}
+
+impl Clone for RequestQueue {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        RequestQueue {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for RequestQueue {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.arrived.notify_one();
+        }
+    }
+}
+
+impl RequestReceiver {
+    /// Wait for the next request to take up, or `None` once the queue is empty and every sender is
+    /// gone. Nothing is lost if this is cancelled.
+    pub async fn recv(&mut self) -> Option<Request> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(waiting) = state.waiting.pop() {
+                    return Some(waiting.request);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            self.shared.arrived.notified().await;
+        }
+    }
+
+    /// Take up the next request, if there's one waiting.
+    pub fn try_recv(&mut self) -> Option<Request> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.waiting.pop().map(|waiting| waiting.request)
+    }
+}
+
+impl Drop for RequestReceiver {
+    fn drop(&mut self) {
+        // Drop whatever's still waiting, letting its senders know it won't be answered
+        let waiting = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.closed = true;
+            std::mem::take(&mut state.waiting)
+        };
+        drop(waiting);
+    }
+}
+
+/// The queues of several responders (one for each chain the faucet serves), with the channels whose
+/// requests go to each: requests from any other channel go to the main one.
+#[derive(Debug, Clone)]
+pub struct Routes {
+    main: RequestQueue,
+    channels: HashMap<ChannelId, RequestQueue>,
+}
+
+impl Routes {
+    /// Send every request to the one queue, until channels are routed elsewhere.
+    pub fn new(main: RequestQueue) -> Self {
+        Routes {
+            main,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Send requests made in the channel to the given queue.
+    pub fn route(&mut self, channel_id: ChannelId, queue: RequestQueue) {
+        self.channels.insert(channel_id, queue);
+    }
+
+    /// The queue for requests made in the channel.
+    pub fn queue(&self, channel_id: ChannelId) -> &RequestQueue {
+        self.channels.get(&channel_id).unwrap_or(&self.main)
+    }
+}
@@ -0,0 +1,289 @@
+use std::{collections::HashSet, fmt, time::Instant};
+
+use once_cell::sync::Lazy;
+use penumbra_keys::Address;
+use regex::Regex;
+use tokio::sync::oneshot;
+
+use super::{Counterparties, Diagnosis, Response};
+use crate::{
+    id::{ChannelId, MessageId, UserId},
+    Locale,
+};
+
+/// A request to be fulfilled by the responder service.
+#[derive(Debug)]
+pub struct Request {
+    /// The addresses matched in the originating message.
+    pub(super) addresses: Vec<AddressOrAlmost>,
+    /// The addresses which were given again later in the message, and skipped.
+    pub(super) duplicates: Vec<String>,
+    /// The sender for the response.
+    pub(super) response: oneshot::Sender<Response>,
+    /// Where the request came from.
+    pub(super) origin: Origin,
+    /// The maximum number of addresses to dispense to, if not the responder's.
+    pub(super) max_addresses: Option<usize>,
+    /// Whether the request asked for delegation tokens.
+    pub(super) delegate: bool,
+    /// The assets asked for by name, if any.
+    pub(super) assets: Vec<String>,
+    /// When the request was made, to time how long it waits in the queue.
+    pub(super) queued: Instant,
+    /// The language to answer the request in.
+    pub(super) locale: Locale,
+    /// Whether the request jumps ahead of others waiting in the queue.
+    pub(super) priority: bool,
+    /// Whether the request is dropped rather than handed off to the next instance, if this one
+    /// stops before processing it.
+    pub(super) local: bool,
+}
+
+/// The user and message from which a request originated.
+#[derive(Debug, Clone, Copy)]
+pub struct Origin {
+    /// The user who made the request.
+    pub user_id: UserId,
+    /// The channel in which the request was made.
+    pub channel_id: ChannelId,
+    /// The message containing the request.
+    pub message_id: MessageId,
+}
+
+/// Either a correctly parsed address, something that looks almost like it (with what's wrong with
+/// it), or an address on another chain (to which tokens are withdrawn over IBC).
+#[derive(Debug, Clone)]
+pub enum AddressOrAlmost {
+    Address(Box<Address>),
+    Almost(String, Diagnosis),
+    External(String),
+}
+
+impl fmt::Display for AddressOrAlmost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressOrAlmost::Address(address) => address.fmt(f),
+            AddressOrAlmost::Almost(almost, _) => almost.fmt(f),
+            AddressOrAlmost::External(external) => external.fmt(f),
+        }
+    }
+}
+
+impl Request {
+    /// Get the parsed addresses from this request.
+    pub fn addresses(&self) -> &[AddressOrAlmost] {
+        &self.addresses
+    }
+
+    /// Get the addresses which were given again later in the message, and skipped.
+    pub fn duplicates(&self) -> &[String] {
+        &self.duplicates
+    }
+
+    /// Get the user and message from which this request originated.
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    /// Dispense to at most this many of the addresses, instead of the responder's maximum (e.g.
+    /// because it's overridden where the request was made).
+    pub fn limit_addresses(&mut self, max_addresses: usize) {
+        self.max_addresses = Some(max_addresses);
+    }
+
+    /// Get the maximum number of addresses to dispense to, if not the responder's.
+    pub fn max_addresses(&self) -> Option<usize> {
+        self.max_addresses
+    }
+
+    /// Returns `true` if the request asked for delegation tokens.
+    pub fn wants_delegation(&self) -> bool {
+        self.delegate
+    }
+
+    /// Ask for delegation tokens along with (or instead of) the usual values, if the faucet hands
+    /// any out.
+    pub fn request_delegation(&mut self) {
+        self.delegate = true;
+    }
+
+    /// Get the assets asked for by name.
+    pub fn assets(&self) -> &[String] {
+        &self.assets
+    }
+
+    /// Ask for the named assets, in place of the usual values, if they're on the faucet's menu.
+    pub fn request_assets(&mut self, assets: Vec<String>) {
+        self.assets = assets;
+    }
+
+    /// Get the language to answer this request in.
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Answer the request in the given language (e.g. the one set where it was made).
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
+    /// Returns `true` if the request jumps ahead of others waiting in the queue.
+    pub fn has_priority(&self) -> bool {
+        self.priority
+    }
+
+    /// Let the request jump ahead of others waiting in the queue (e.g. because its author has a
+    /// priority role).
+    pub fn prioritize(&mut self) {
+        self.priority = true;
+    }
+
+    /// Returns `true` if the request can be handed off to the next instance.
+    pub fn can_hand_off(&self) -> bool {
+        !self.local
+    }
+
+    /// Drop the request rather than handing it off to the next instance if this one stops before
+    /// processing it (e.g. because only this instance can answer it).
+    pub fn keep_local(&mut self) {
+        self.local = true;
+    }
+
+    /// Create a new request by scanning the contents of a message.
+    ///
+    /// Returns a receiver for the response to this request, as well as the request itself.
+    pub fn try_new(
+        content: &str,
+        origin: Origin,
+    ) -> Option<(oneshot::Receiver<Response>, Request)> {
+        Self::try_new_excluding(content, origin, &HashSet::new(), &Counterparties::default())
+    }
+
+    /// Create a new request by scanning the contents of a message (for addresses on the given
+    /// other chains, too), skipping any matches which are in the `exclude` set (e.g. because they
+    /// were already handled before the message was edited).
+    ///
+    /// Returns a receiver for the response to this request, as well as the request itself.
+    pub fn try_new_excluding(
+        content: &str,
+        origin: Origin,
+        exclude: &HashSet<String>,
+        counterparties: &Counterparties,
+    ) -> Option<(oneshot::Receiver<Response>, Request)> {
+        // Collect all the matches into a struct, bundled with the original message
+        tracing::trace!("collecting addresses from message");
+        let all_matches: Vec<&str> = address_matches(content)
+            .into_iter()
+            .chain(counterparties.address_matches(content))
+            .collect();
+        let matches: Vec<&str> = all_matches
+            .iter()
+            .copied()
+            .filter(|m| !exclude.contains(*m))
+            .collect();
+
+        // If no addresses were found, don't bother sending the message to the queue
+        if matches.is_empty() {
+            None
+        } else {
+            let (response, mut request) = Self::new(matches, origin);
+            if mentions_delegation(content) {
+                request.request_delegation();
+            }
+            request.request_assets(asset_names(content, &all_matches));
+            Some((response, request))
+        }
+    }
+
+    /// Create a new request for the given addresses (or things that look like them), as written in
+    /// a message. An address given more than once is only sent to once.
+    ///
+    /// Returns a receiver for the response to this request, as well as the request itself.
+    pub fn new<'a>(
+        matches: impl IntoIterator<Item = &'a str>,
+        origin: Origin,
+    ) -> (oneshot::Receiver<Response>, Request) {
+        let mut given = HashSet::new();
+        let mut duplicates = Vec::new();
+        let addresses = matches
+            .into_iter()
+            .filter(|m| {
+                if given.insert(*m) {
+                    return true;
+                }
+                if !duplicates.iter().any(|duplicate| duplicate == m) {
+                    duplicates.push(m.to_string());
+                }
+                false
+            })
+            .map(|m| {
+                use AddressOrAlmost::*;
+                match m.parse() {
+                    Ok(addr) => Address(Box::new(addr)),
+                    // Whether it's on a chain we can withdraw to is up to the responder
+                    Err(_) if !m.starts_with("penumbra") => External(m.to_string()),
+                    Err(e) => {
+                        tracing::trace!(error = ?e, "failed to parse address");
+                        Almost(m.to_string(), Diagnosis::of(m))
+                    }
+                }
+            })
+            .collect();
+
+        let (tx, rx) = oneshot::channel();
+        (
+            rx,
+            Request {
+                addresses,
+                duplicates,
+                response: tx,
+                origin,
+                max_addresses: None,
+                delegate: false,
+                assets: Vec::new(),
+                queued: Instant::now(),
+                locale: Locale::default(),
+                priority: false,
+                local: false,
+            },
+        )
+    }
+}
+
+/// Find every substring of the text which looks like a Penumbra address, whether or not it
+/// actually parses as one.
+///
+/// This takes in characters addresses never contain too, so that a typo like a `b` or a capital
+/// letter is reported as such, rather than as an address cut off where the typo was.
+pub fn address_matches(content: &str) -> Vec<&str> {
+    static ADDRESS: Lazy<Regex> = Lazy::new(|| Regex::new(r"penumbrav\dt1[0-9A-Za-z]*").unwrap());
+    ADDRESS.find_iter(content).map(|m| m.as_str()).collect()
+}
+
+/// Returns `true` if the text asks for delegation tokens, by saying "delegate".
+fn mentions_delegation(content: &str) -> bool {
+    static DELEGATE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bdelegate\b").unwrap());
+    DELEGATE.is_match(content)
+}
+
+/// The names of the assets asked for in the text, written after the last address on the same line
+/// (like `penumbrav2t1... gm gn`).
+fn asset_names(content: &str, matches: &[&str]) -> Vec<String> {
+    let start = matches
+        .iter()
+        .filter_map(|m| content.rfind(m).map(|index| index + m.len()))
+        .max()
+        .unwrap_or(content.len());
+    static NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z][a-z0-9_/.-]*$").unwrap());
+    content[start..]
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| word != "delegate" && NAME.is_match(word))
+        .collect()
+}
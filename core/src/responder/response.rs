@@ -0,0 +1,308 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use penumbra_asset::{asset, Value};
+use penumbra_keys::Address;
+use penumbra_transaction::Id;
+
+use super::{failure, Diagnosis};
+use crate::locale::{self, Locale};
+
+/// The details of tokens successfully dispensed to an address.
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    /// The hash of the transaction.
+    pub id: Id,
+    /// The block height at which the transaction was detected.
+    pub height: u64,
+    /// The values which were sent.
+    pub values: Vec<Value>,
+    /// Whether this was a dry run, in which the transaction was built but never broadcast.
+    pub simulated: bool,
+}
+
+impl Receipt {
+    /// Construct a message for the recipient describing what they were sent, and how to find it in
+    /// their wallet, in the given language.
+    pub fn message(&self, address: &Address, locale: Locale) -> String {
+        let values = format_values(&self.values);
+        let address = address.display_short_form();
+
+        if self.simulated {
+            return locale::fill(
+                locale.text().receipt_simulated,
+                &[("values", &values), ("address", &address)],
+            );
+        }
+
+        locale::fill(
+            locale.text().receipt,
+            &[
+                ("values", &values),
+                ("address", &address),
+                ("id", &self.id),
+                ("height", &self.height),
+            ],
+        )
+    }
+}
+
+/// Write out values for people to read, like `100penumbra, 10gm`.
+fn format_values(values: &[Value]) -> String {
+    let cache = asset::Cache::with_known_assets();
+    values
+        .iter()
+        .map(|value| value.format(&cache))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The response from a request to dispense tokens to a set of addresses.
+#[derive(Debug)]
+pub struct Response {
+    /// The addresses that were successfully dispensed tokens.
+    pub(super) succeeded: Vec<(Address, Receipt)>,
+    /// The addresses that failed to be dispensed tokens, accompanied by a string describing the
+    /// error.
+    pub(super) failed: Vec<(Address, String)>,
+    /// The full errors behind failures described to users only by their cause, by address, for
+    /// the ledger and the audit trail.
+    pub(super) errors: Vec<(String, String)>,
+    /// The addresses that couldn't be parsed, accompanied by what's wrong with each.
+    pub(super) unparsed: Vec<(String, Diagnosis)>,
+    /// The addresses that were limited from being dispensed tokens because only a certain number
+    /// are permitted to be given tokens per message.
+    pub(super) remaining: Vec<Address>,
+    /// The addresses skipped because they were given twice in the message, or were already being
+    /// sent tokens for another request.
+    pub(super) duplicates: Vec<String>,
+    /// The addresses on other chains to which tokens were withdrawn over IBC.
+    pub(super) withdrawn: Vec<(String, Receipt)>,
+    /// The addresses on other chains to which tokens couldn't be withdrawn, accompanied by a string
+    /// describing why.
+    pub(super) failed_withdrawals: Vec<(String, String)>,
+    /// Notes on what was asked for, like assets which aren't handed out.
+    pub(super) notes: Vec<String>,
+    /// The language to describe the response in.
+    pub(super) locale: Locale,
+}
+
+impl Response {
+    /// Returns the addresses that were successfully dispensed tokens.
+    pub fn succeeded(&self) -> &[(Address, Receipt)] {
+        &self.succeeded
+    }
+
+    /// Returns the addresses that failed to be dispensed tokens, accompanied by a string describing
+    /// the error.
+    pub fn failed(&self) -> &[(Address, String)] {
+        &self.failed
+    }
+
+    /// Returns the full errors behind failures which are described to users only by their cause,
+    /// by address.
+    pub fn errors(&self) -> &[(String, String)] {
+        &self.errors
+    }
+
+    /// Returns the full error behind the failure to send to the address, if it was an error from
+    /// sending (rather than, say, the budget running out).
+    pub fn error(&self, address: &str) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|(addr, _)| addr == address)
+            .map(|(_, error)| error.as_str())
+    }
+
+    /// Record that sending to the address failed with the error, describing it to the user by its
+    /// cause and keeping the full error for administrators.
+    pub(super) fn fail(&mut self, address: Address, error: &anyhow::Error) {
+        let (reason, detail) = failure::explain(&address.to_string(), error, self.locale);
+        self.failed.push((address, reason));
+        self.errors.push((address.to_string(), detail));
+    }
+
+    /// Returns the addresses that couldn't be parsed, accompanied by what's wrong with each.
+    pub fn unparsed(&self) -> &[(String, Diagnosis)] {
+        &self.unparsed
+    }
+
+    /// Returns the addresses that were limited from being dispensed tokens because only a certain
+    /// number are permitted to be given tokens per message.
+    pub fn remaining(&self) -> &[Address] {
+        &self.remaining
+    }
+
+    /// Returns the addresses skipped because they were given twice in the message, or were already
+    /// being sent tokens for another request.
+    pub fn duplicates(&self) -> &[String] {
+        &self.duplicates
+    }
+
+    /// Returns the addresses on other chains to which tokens were withdrawn over IBC.
+    pub fn withdrawn(&self) -> &[(String, Receipt)] {
+        &self.withdrawn
+    }
+
+    /// Returns the addresses on other chains to which tokens couldn't be withdrawn, accompanied by
+    /// a string describing why.
+    pub fn failed_withdrawals(&self) -> &[(String, String)] {
+        &self.failed_withdrawals
+    }
+
+    /// Returns the language the response is described in.
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Returns `true` only if all addresses were successfully dispensed tokens.
+    pub fn complete_success(&self) -> bool {
+        self.failed.is_empty()
+            && self.unparsed.is_empty()
+            && self.remaining.is_empty()
+            && self.failed_withdrawals.is_empty()
+    }
+
+    /// Returns `false` only if no addresses were successfully dispensed tokens.
+    pub fn complete_failure(&self) -> bool {
+        self.succeeded.is_empty() && self.withdrawn.is_empty() && !self.complete_success()
+    }
+
+    /// Construct a string summarizing the response, without mentioning anyone.
+    pub fn plain_summary(&self) -> String {
+        self.summary(None)
+    }
+
+    /// Construct a string summarizing the response, mentioning the given administrators (e.g. the
+    /// administrator roles of a Discord server) if an error occurred, so that they can look into
+    /// it.
+    pub fn summary(&self, mention_admins: Option<String>) -> String {
+        self.summary_in(self.locale, mention_admins)
+    }
+
+    /// Construct a string summarizing the response like [`Response::summary`], but in the given
+    /// language rather than the one it was asked for in (e.g. English, for the audit trail).
+    pub fn summary_in(&self, locale: Locale, mention_admins: Option<String>) -> String {
+        let text = locale.text();
+        let mut response = String::new();
+
+        let (simulated, succeeded): (Vec<_>, Vec<_>) = self
+            .succeeded
+            .iter()
+            .partition(|(_, receipt)| receipt.simulated);
+
+        if !simulated.is_empty() {
+            response.push_str(text.simulated);
+            for (addr, Receipt { values, .. }) in simulated {
+                let (addr, values) = (addr.display_short_form(), format_values(values));
+                write!(response, "\n`{}`: {}", addr, values).unwrap();
+            }
+        }
+
+        if !succeeded.is_empty() {
+            response.push_str(text.succeeded);
+            for (addr, Receipt { id, values, .. }) in succeeded.iter() {
+                let entry = locale::fill(
+                    text.succeeded_entry,
+                    &[
+                        ("address", &addr.display_short_form()),
+                        ("values", &format_values(values)),
+                        ("id", id),
+                    ],
+                );
+                write!(response, "\n{}", entry).unwrap();
+            }
+        }
+
+        if !self.withdrawn.is_empty() {
+            write!(response, "\n{}", text.withdrawn).unwrap();
+            for (addr, receipt) in self.withdrawn.iter() {
+                let values = format_values(&receipt.values);
+                let entry = if receipt.simulated {
+                    locale::fill(
+                        text.withdrawn_simulated,
+                        &[("address", addr), ("values", &values)],
+                    )
+                } else {
+                    locale::fill(
+                        text.withdrawn_entry,
+                        &[("address", addr), ("values", &values), ("id", &receipt.id)],
+                    )
+                };
+                write!(response, "\n{}", entry).unwrap();
+            }
+        }
+
+        // Total what was actually sent, when it went to more than one place
+        let sent = succeeded
+            .iter()
+            .map(|(_, receipt)| receipt)
+            .chain(self.withdrawn.iter().map(|(_, receipt)| receipt))
+            .filter(|receipt| !receipt.simulated)
+            .collect::<Vec<_>>();
+        if sent.len() > 1 {
+            let mut totals = BTreeMap::<asset::Id, u128>::new();
+            for value in sent.iter().flat_map(|receipt| receipt.values.iter()) {
+                *totals.entry(value.asset_id).or_default() += value.amount.value();
+            }
+            let totals = totals
+                .into_iter()
+                .map(|(asset_id, amount)| Value {
+                    amount: amount.into(),
+                    asset_id,
+                })
+                .collect::<Vec<_>>();
+            let total = locale::fill(text.total, &[("values", &format_values(&totals))]);
+            write!(response, "\n{}", total).unwrap();
+        }
+
+        if !self.failed.is_empty() || !self.failed_withdrawals.is_empty() {
+            response.push_str(text.failed);
+            let failed = self
+                .failed
+                .iter()
+                .map(|(addr, error)| (addr.to_string(), error));
+            let failed_withdrawals = self
+                .failed_withdrawals
+                .iter()
+                .map(|(addr, error)| (addr.clone(), error));
+            for (addr, error) in failed.chain(failed_withdrawals) {
+                let entry =
+                    locale::fill(text.failed_entry, &[("address", &addr), ("error", error)]);
+                write!(response, "\n{}", entry).unwrap();
+            }
+
+            if let Some(mention_admins) = mention_admins {
+                let investigate = locale::fill(text.investigate, &[("admins", &mention_admins)]);
+                write!(response, "\n{}", investigate).unwrap();
+            }
+        }
+
+        if !self.unparsed.is_empty() {
+            write!(response, "\n{}", text.unparsed).unwrap();
+            for (addr, diagnosis) in self.unparsed.iter() {
+                write!(response, "\n`{}`: {}", addr, diagnosis.describe(locale)).unwrap();
+            }
+        }
+
+        if !self.remaining.is_empty() {
+            let remaining = locale::fill(text.remaining, &[("count", &self.succeeded.len())]);
+            write!(response, "\n{}", remaining).unwrap();
+            for addr in self.remaining.iter() {
+                write!(response, "\n`{}`", addr).unwrap();
+            }
+        }
+
+        if !self.duplicates.is_empty() {
+            write!(response, "\n{}", text.duplicates).unwrap();
+            for addr in self.duplicates.iter() {
+                write!(response, "\n`{}`", addr).unwrap();
+            }
+        }
+
+        for note in self.notes.iter() {
+            write!(response, "\n{}", note).unwrap();
+        }
+
+        response
+    }
+}
@@ -0,0 +1,13 @@
+use penumbra_keys::Address;
+use tokio::sync::oneshot;
+
+/// A switch to a new dispenser, with a new spend key, made between requests so that nothing is
+/// in flight on the old key: whatever is left in the old wallet is swept to the new one first.
+pub struct Rotation<D> {
+    /// The dispenser to use from now on.
+    pub dispenser: D,
+    /// The new wallet's address, to which the old wallet's funds are swept.
+    pub address: Address,
+    /// Where to say how it went: the hash of the sweep transaction, if there was anything to sweep.
+    pub result: oneshot::Sender<anyhow::Result<Option<penumbra_transaction::Id>>>,
+}
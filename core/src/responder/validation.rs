@@ -0,0 +1,154 @@
+use std::fmt::{self, Write};
+
+use penumbra_keys::Address;
+
+use crate::locale::{self, Locale};
+
+/// The characters bech32 allows after the separator.
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The prefix of addresses on the current testnet. Keep this in step with the Penumbra crates, or
+/// current addresses which fail to parse will be blamed on their prefix.
+const CURRENT_PREFIX: &str = "penumbrav2t";
+
+/// How many characters follow the separator in an address: 80 bytes of address, plus a 6 character
+/// checksum.
+const ADDRESS_DATA_LEN: usize = 80 * 8 / 5 + 6;
+
+/// Why something which was meant as an address didn't parse as one, so that the person who sent it
+/// can be told how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnosis {
+    /// A validator's identity key, rather than an address.
+    ValidatorKey,
+    /// An address on a chain the faucet doesn't send to.
+    OtherChain,
+    /// A Penumbra address with another prefix than the current one, like an old testnet's.
+    WrongPrefix(String),
+    /// Characters addresses never contain, like capital letters, spaces, or `b`.
+    InvalidCharacters(String),
+    /// Too short to be an address, as if cut off while copying.
+    Truncated,
+    /// Too long to be an address, as if something else was pasted onto the end.
+    TooLong,
+    /// The right length and characters, but the checksum doesn't match, as if there's a typo.
+    Checksum,
+    /// Nothing like an address at all.
+    NotAnAddress,
+}
+
+impl Diagnosis {
+    /// Work out what's wrong with text which doesn't parse as an address.
+    pub fn of(text: &str) -> Diagnosis {
+        if text.starts_with("penumbravalid") {
+            return Diagnosis::ValidatorKey;
+        }
+        // The separator is the last `1`, since the characters after it never include one
+        let (prefix, data) = match text.rsplit_once('1') {
+            Some(split) => split,
+            None if text.starts_with("penumbra") => return Diagnosis::Truncated,
+            None => return Diagnosis::NotAnAddress,
+        };
+        if !prefix.to_lowercase().starts_with("penumbra") {
+            return Diagnosis::OtherChain;
+        }
+        let well_formed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+        if prefix != CURRENT_PREFIX && prefix.chars().all(well_formed) {
+            return Diagnosis::WrongPrefix(prefix.to_string());
+        }
+
+        let mut invalid = String::new();
+        for c in prefix
+            .chars()
+            .filter(|c| !well_formed(*c))
+            .chain(data.chars().filter(|c| !BECH32_CHARSET.contains(*c)))
+        {
+            if !invalid.contains(c) {
+                invalid.push(c);
+            }
+        }
+        if !invalid.is_empty() {
+            return Diagnosis::InvalidCharacters(invalid);
+        }
+
+        match data.len() {
+            len if len < ADDRESS_DATA_LEN => Diagnosis::Truncated,
+            len if len > ADDRESS_DATA_LEN => Diagnosis::TooLong,
+            _ => Diagnosis::Checksum,
+        }
+    }
+
+    /// Explain what's wrong, and how to fix it, in the given language.
+    pub fn describe(&self, locale: Locale) -> String {
+        let text = locale.text();
+        match self {
+            Diagnosis::ValidatorKey => text.validator_key.to_string(),
+            Diagnosis::OtherChain => text.other_chain.to_string(),
+            Diagnosis::WrongPrefix(prefix) => locale::fill(
+                text.wrong_prefix,
+                &[("prefix", prefix), ("current", &CURRENT_PREFIX)],
+            ),
+            Diagnosis::InvalidCharacters(invalid) => {
+                let invalid = invalid
+                    .chars()
+                    .map(|c| match c {
+                        ' ' => text.spaces.to_string(),
+                        c if c.is_whitespace() => text.line_breaks.to_string(),
+                        c => format!("`{}`", c),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                locale::fill(text.invalid_characters, &[("invalid", &invalid)])
+            }
+            Diagnosis::Truncated => text.truncated.to_string(),
+            Diagnosis::TooLong => text.too_long.to_string(),
+            Diagnosis::Checksum => text.checksum.to_string(),
+            Diagnosis::NotAnAddress => text.not_an_address.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(Locale::En))
+    }
+}
+
+/// Check whether the text is a valid Penumbra address, without dispensing anything, and explain
+/// the result (which address version it is, or what looks wrong with it) for a person to read.
+pub fn validate(text: &str) -> String {
+    let text = text.trim().trim_matches('`').trim();
+    if text.is_empty() {
+        return "Paste an address to check.".to_string();
+    }
+
+    match text.parse::<Address>() {
+        Ok(address) => {
+            let mut response = format!("`{}` is a valid Penumbra address", text);
+            if let Some(version) = version(text) {
+                write!(response, " (address version {})", version).unwrap();
+            }
+            // Addresses only have one canonical encoding, so anything else was tidied up on the way
+            if address.to_string() != text {
+                write!(response, ", written as `{}`", address).unwrap();
+            }
+            response.push('.');
+            response
+        }
+        Err(_) => format!(
+            "`{}` is not a valid Penumbra address: {}.",
+            text,
+            Diagnosis::of(text)
+        ),
+    }
+}
+
+/// The version in an address's prefix (the `2` in `penumbrav2t1...`), if it has one.
+fn version(text: &str) -> Option<u32> {
+    let (prefix, _) = text.rsplit_once('1')?;
+    prefix
+        .strip_prefix("penumbrav")?
+        .strip_suffix('t')?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,684 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{future::BoxFuture, Future, FutureExt};
+use ibc_types::core::{channel::ChannelId, client::Height};
+use penumbra_asset::{asset, Value};
+use penumbra_custody::{AuthorizeRequest, CustodyClient};
+use penumbra_ibc::Ics20Withdrawal;
+use penumbra_keys::{Address, FullViewingKey};
+use penumbra_tct::StateCommitment;
+use penumbra_transaction::{
+    memo::MemoPlaintext, plan::TransactionPlan, AuthorizationData, Transaction,
+};
+use penumbra_view::{SpendableNoteRecord, ViewClient};
+use penumbra_wallet::plan::Planner;
+use rand::rngs::OsRng;
+use tokio::{sync::Semaphore, time::Instant};
+use tower::{limit::ConcurrencyLimit, Service, ServiceExt};
+
+use crate::{
+    metrics,
+    responder::{Counterparty, Origin},
+};
+
+mod memo;
+pub use memo::Memo;
+
+mod failover;
+pub use failover::{Backup, Failover, FailoverPolicy};
+
+/// A transaction which has been built, being broadcast: resolves to its hash and the block height
+/// at which it was detected, once it's confirmed.
+pub type Broadcast = BoxFuture<'static, anyhow::Result<(penumbra_transaction::Id, u64)>>;
+
+/// Something which can dispense tokens: normally a [`Sender`] behind its concurrency limit, but
+/// this lets the rest of the faucet be driven without a chain (see `galileo simulate`).
+#[async_trait]
+pub trait Dispenser: Send + 'static {
+    /// Send the values to the address in answer to the request from the given origin, returning
+    /// the transaction hash and the block height at which it was detected.
+    async fn send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)>;
+
+    /// Build a transaction sending the values to the address like [`Dispenser::send`], but return
+    /// as soon as it's ready to broadcast, with the broadcast to wait on. The next transaction can
+    /// be built in the meantime. By default, this waits for the broadcast too.
+    async fn prepare_send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<Broadcast> {
+        let result = self.send(address, values, origin).await;
+        Ok(futures::future::ready(result).boxed())
+    }
+
+    /// Withdraw the values over IBC to an address on another chain, in answer to the request from
+    /// the given origin, returning the transaction hash and the block height at which it was
+    /// detected.
+    async fn withdraw(
+        &mut self,
+        address: String,
+        counterparty: Counterparty,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)>;
+
+    /// Send each address its values in a single transaction, returning the transaction hash and
+    /// the block height at which it was detected.
+    async fn send_batch(
+        &mut self,
+        outputs: Vec<(Address, Vec<Value>)>,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)>;
+
+    /// Send a zero-value transaction of the given asset to the faucet's own address, timing each
+    /// stage of the dispense path along the way.
+    async fn self_test(&mut self, asset_id: asset::Id) -> SelfTest;
+
+    /// The wallet's balance of each asset, as far as it has synchronized.
+    async fn balance(&mut self) -> anyhow::Result<Vec<Value>>;
+
+    /// Send everything in the wallet to the given address in one transaction, returning the
+    /// transaction hash and what was sent, or `None` if the wallet was empty.
+    async fn sweep(
+        &mut self,
+        address: Address,
+    ) -> anyhow::Result<Option<(penumbra_transaction::Id, Vec<Value>)>>;
+
+    /// Returns `true` if transactions are built but never broadcast.
+    fn is_dry_run(&self) -> bool;
+}
+
+/// How long a withdrawal over IBC has to be relayed to the other chain before it times out, and the
+/// tokens are returned to the faucet.
+const WITHDRAWAL_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How many transactions can be in flight at once: while one is broadcast and waits for
+/// confirmation, the next is built and proven, spending other notes.
+const PIPELINE_DEPTH: u32 = 2;
+
+/// How often to check whether the view has detected a broadcast transaction.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for the view to detect a broadcast transaction before giving up on it, so that
+/// one which never lands doesn't hold its notes and its place in the pipeline forever.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The histogram of how long planning each transaction takes.
+pub const PLAN_SECONDS: &str = "galileo_dispense_plan_seconds";
+
+/// The histogram of how long custody takes to authorize each transaction.
+pub const AUTHORIZE_SECONDS: &str = "galileo_dispense_authorize_seconds";
+
+/// The histogram of how long witnessing and proving each transaction takes.
+pub const PROVE_SECONDS: &str = "galileo_dispense_prove_seconds";
+
+/// The histogram of how long the node takes to accept each transaction.
+pub const BROADCAST_SECONDS: &str = "galileo_dispense_broadcast_seconds";
+
+/// The histogram of how long each transaction takes to be included in a block and detected by the
+/// view, once the node has accepted it.
+pub const CONFIRMATION_SECONDS: &str = "galileo_dispense_confirmation_seconds";
+
+/// Where to send tokens.
+#[derive(Debug, Clone)]
+pub enum Destination {
+    /// A Penumbra address, which is sent an output.
+    Address(Address),
+    /// An address on another chain, to which the tokens are withdrawn over IBC.
+    Withdrawal {
+        address: String,
+        counterparty: Counterparty,
+    },
+    /// Many Penumbra addresses, each sent its own values in one transaction (the values of the
+    /// request are ignored).
+    Batch(Vec<(Address, Vec<Value>)>),
+}
+
+/// The `Sender` maps `(Destination, Vec<Value>, String)` send requests (the last being the memo
+/// text) to [`Broadcast`]s of the transactions sending the funds, which resolve to their `[u8; 32]`
+/// transaction hashes, along with the block height at which each was detected.
+///
+/// Transactions are built one at a time, but each is broadcast in the background, so that the next
+/// can be built and proven while the last waits for confirmation.
+#[derive(Clone)]
+pub struct Sender<V, C>
+where
+    V: ViewClient + Clone + 'static,
+    C: CustodyClient + Clone + 'static,
+{
+    view: V,
+    custody: C,
+    fvk: FullViewingKey,
+    account: u32,
+    /// What to write in the memo of each transaction.
+    memo: Memo,
+    /// Whether to skip broadcasting transactions, so that nothing is actually sent.
+    dry_run: bool,
+    /// The notes spent by transactions in flight, which the next transaction mustn't spend.
+    reserved: Arc<Mutex<HashSet<StateCommitment>>>,
+    /// A permit for each transaction which can be in flight at once.
+    in_flight: Arc<Semaphore>,
+}
+
+impl<V, C> Sender<V, C>
+where
+    V: ViewClient + Clone + Send + 'static,
+    C: CustodyClient + Clone + Send + 'static,
+{
+    pub fn new(
+        account: u32,
+        fvk: FullViewingKey,
+        view: V,
+        custody: C,
+        memo: Memo,
+        dry_run: bool,
+    ) -> ConcurrencyLimit<Self> {
+        tower::ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(Self {
+                view,
+                custody,
+                fvk,
+                account,
+                memo,
+                dry_run,
+                reserved: Default::default(),
+                in_flight: Arc::new(Semaphore::new(PIPELINE_DEPTH as usize)),
+            })
+    }
+
+    /// Returns `true` if transactions are built but never broadcast.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The wallet's balance of each asset, as far as the view has synchronized.
+    pub async fn balance(&mut self) -> anyhow::Result<Vec<Value>> {
+        let notes = self
+            .view
+            .unspent_notes_by_asset_and_address(self.fvk.account_group_id())
+            .await?;
+        Ok(notes
+            .into_iter()
+            .map(|(asset_id, by_address)| {
+                let amount: u128 = by_address
+                    .values()
+                    .flatten()
+                    .map(|record| record.note.amount().value())
+                    .sum();
+                Value {
+                    amount: amount.into(),
+                    asset_id,
+                }
+            })
+            .filter(|value| value.amount.value() > 0)
+            .collect())
+    }
+
+    /// Plan a transaction sending the given values to the destination, with the given memo text.
+    async fn plan(
+        &mut self,
+        destination: Destination,
+        values: Vec<Value>,
+        memo: String,
+    ) -> anyhow::Result<TransactionPlan> {
+        let required = match &destination {
+            Destination::Batch(outputs) => outputs
+                .iter()
+                .flat_map(|(_, values)| values.iter().cloned())
+                .collect(),
+            _ => values.clone(),
+        };
+        let mut planner = Planner::new(OsRng);
+        match destination {
+            Destination::Address(address) => {
+                for value in values {
+                    planner.output(value, address);
+                }
+            }
+            Destination::Batch(outputs) => {
+                for (address, values) in outputs {
+                    for value in values {
+                        planner.output(value, address);
+                    }
+                }
+            }
+            Destination::Withdrawal {
+                address,
+                counterparty,
+            } => {
+                let cache = asset::Cache::with_known_assets();
+                let source_channel: ChannelId = counterparty
+                    .channel
+                    .parse()
+                    .with_context(|| format!("invalid channel {}", counterparty.channel))?;
+                // Rely on the timestamp alone to time out, since we don't track the other chain's
+                // height
+                let timeout_time = (Utc::now() + chrono::Duration::from_std(WITHDRAWAL_TIMEOUT)?)
+                    .timestamp_nanos() as u64;
+                for value in values {
+                    let denom = cache
+                        .get(&value.asset_id)
+                        .cloned()
+                        .with_context(|| format!("unknown asset {}", value.asset_id))?;
+                    planner.ics20_withdrawal(Ics20Withdrawal {
+                        amount: value.amount,
+                        denom,
+                        destination_chain_address: address.clone(),
+                        // If it times out, the tokens come back to the faucet
+                        return_address: self.fvk.payment_address(0.into()).0,
+                        timeout_height: Height::new(0, u64::MAX)?,
+                        timeout_time,
+                        source_channel: source_channel.clone(),
+                    });
+                }
+            }
+        }
+        planner
+            .memo(MemoPlaintext {
+                text: memo,
+                sender: self.fvk.payment_address(0.into()).0,
+            })
+            .context("invalid memo (is the memo template too long?)")?;
+        for record in self.unreserved_notes(&required).await? {
+            planner.spend(record.note, record.position);
+        }
+        let plan = planner.plan(
+            &mut self.view,
+            self.fvk.account_group_id(),
+            self.account.into(),
+        );
+        Ok(plan.await?)
+    }
+
+    /// While other transactions are in flight, choose notes to cover the values which none of them
+    /// spend, so that this one doesn't conflict with them. If there aren't enough, wait for them to
+    /// be confirmed instead, and leave the choice to the planner.
+    async fn unreserved_notes(
+        &mut self,
+        required: &[Value],
+    ) -> anyhow::Result<Vec<SpendableNoteRecord>> {
+        let reserved = self.reserved.lock().unwrap().clone();
+        if reserved.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut totals = BTreeMap::<asset::Id, u128>::new();
+        for value in required {
+            *totals.entry(value.asset_id).or_default() += value.amount.value();
+        }
+        let mut notes = self
+            .view
+            .unspent_notes_by_asset_and_address(self.fvk.account_group_id())
+            .await?;
+        let mut chosen = Vec::new();
+        for (asset_id, total) in totals {
+            let mut candidates = notes
+                .remove(&asset_id)
+                .into_iter()
+                .flat_map(|by_address| by_address.into_values().flatten())
+                .filter(|record| !reserved.contains(&record.note_commitment))
+                .collect::<Vec<_>>();
+            // Largest first, to spend as few notes as possible
+            candidates.sort_by_key(|record| std::cmp::Reverse(record.note.amount().value()));
+            let mut covered = 0;
+            for record in candidates {
+                if covered >= total {
+                    break;
+                }
+                covered += record.note.amount().value();
+                chosen.push(record);
+            }
+            if covered < total {
+                tracing::info!("not enough notes free, waiting for transactions in flight");
+                let _all = self.in_flight.acquire_many(PIPELINE_DEPTH - 1).await?;
+                return Ok(Vec::new());
+            }
+        }
+        Ok(chosen)
+    }
+
+    /// Get authorization from custody to spend the funds in the plan.
+    async fn authorize(&mut self, plan: &TransactionPlan) -> anyhow::Result<AuthorizationData> {
+        Ok(self
+            .custody
+            .authorize(AuthorizeRequest {
+                plan: plan.clone(),
+                account_group_id: Some(self.fvk.account_group_id()),
+                pre_authorizations: Vec::new(),
+            })
+            .await?
+            .data
+            .ok_or_else(|| anyhow::anyhow!("no auth data"))?
+            .try_into()?)
+    }
+
+    /// Witness, prove, and authorize the transaction described by the plan.
+    async fn build(
+        &mut self,
+        plan: TransactionPlan,
+        auth_data: AuthorizationData,
+    ) -> anyhow::Result<Transaction> {
+        let witness_data = self
+            .view
+            .witness(self.fvk.account_group_id(), &plan)
+            .await?;
+        let unauth_tx = plan
+            .build_concurrent(OsRng, &self.fvk, witness_data)
+            .await?;
+
+        Ok(unauth_tx.authorize(&mut OsRng, &auth_data)?)
+    }
+
+    /// Wait for the view to detect a broadcast transaction, returning the height at which it was
+    /// included, or failing if it isn't within [`CONFIRMATION_TIMEOUT`].
+    async fn confirm(&mut self, tx_id: penumbra_transaction::Id) -> anyhow::Result<u64> {
+        let detected = async {
+            loop {
+                match self.view.transaction_info_by_hash(tx_id).await {
+                    Ok(info) => return info.height,
+                    // Not detected yet
+                    Err(_) => tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await,
+                }
+            }
+        };
+        tokio::time::timeout(CONFIRMATION_TIMEOUT, detected)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "transaction {} was broadcast but not confirmed within {}, and may or may not land",
+                    tx_id,
+                    humantime::format_duration(CONFIRMATION_TIMEOUT)
+                )
+            })
+    }
+
+    /// Send a zero-value transaction of the given asset to the faucet's own address, timing each
+    /// stage of the dispense path along the way.
+    pub async fn self_test(&mut self, asset_id: asset::Id) -> SelfTest {
+        let address = self.fvk.payment_address(0.into()).0;
+        let mut stages = Vec::new();
+        let result = self.self_test_stages(address, asset_id, &mut stages).await;
+        SelfTest {
+            address,
+            stages,
+            result,
+            simulated: self.dry_run,
+        }
+    }
+
+    async fn self_test_stages(
+        &mut self,
+        address: Address,
+        asset_id: asset::Id,
+        stages: &mut Vec<(&'static str, Duration)>,
+    ) -> anyhow::Result<penumbra_transaction::Id> {
+        let value = Value {
+            amount: 0u64.into(),
+            asset_id,
+        };
+
+        let start = Instant::now();
+        let plan = self
+            .plan(
+                Destination::Address(address),
+                vec![value],
+                self.memo.render(None),
+            )
+            .await?;
+        stages.push(("plan", start.elapsed()));
+
+        let start = Instant::now();
+        let auth_data = self.authorize(&plan).await?;
+        stages.push(("authorize", start.elapsed()));
+
+        let start = Instant::now();
+        let tx = self.build(plan, auth_data).await?;
+        stages.push(("prove", start.elapsed()));
+
+        if self.dry_run {
+            return Ok(tx.id());
+        }
+
+        let start = Instant::now();
+        let (tx_id, _detection_height) = self.view.broadcast_transaction(tx, true).await?;
+        stages.push(("broadcast", start.elapsed()));
+
+        Ok(tx_id)
+    }
+}
+
+/// The outcome of a self-test, with the time taken by each stage which completed.
+#[derive(Debug)]
+pub struct SelfTest {
+    /// The faucet's own address, to which the self-test sends.
+    pub address: Address,
+    /// The name and duration of each stage which completed.
+    pub stages: Vec<(&'static str, Duration)>,
+    /// The transaction hash of the self-send, or the error which stopped it.
+    pub result: anyhow::Result<penumbra_transaction::Id>,
+    /// Whether this was a dry run, in which the transaction was built but never broadcast.
+    pub simulated: bool,
+}
+
+impl SelfTest {
+    /// Construct a string summarizing the self-test.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (stage, duration) in self.stages.iter() {
+            writeln!(
+                summary,
+                "`{}`: ok in {}",
+                stage,
+                humantime::Duration::from(*duration)
+            )
+            .unwrap();
+        }
+        match &self.result {
+            Ok(id) if self.simulated => write!(
+                summary,
+                "[Simulated] Self-test succeeded, but `{}` was not broadcast (dry run)",
+                id
+            )
+            .unwrap(),
+            Ok(id) => write!(summary, "Self-test succeeded: `{}`", id).unwrap(),
+            // Print the entire chain of causes, since this is for administrators
+            Err(e) => write!(summary, "Self-test failed: {:#}", e).unwrap(),
+        }
+        summary
+    }
+}
+
+#[async_trait]
+impl<V, C> Dispenser for ConcurrencyLimit<Sender<V, C>>
+where
+    V: ViewClient + Clone + Send + 'static,
+    C: CustodyClient + Clone + Send + 'static,
+{
+    async fn send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        self.prepare_send(address, values, origin).await?.await
+    }
+
+    async fn prepare_send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<Broadcast> {
+        let memo = self.get_ref().memo.render(Some(origin));
+        self.ready()
+            .await?
+            .call((Destination::Address(address), values, memo))
+            .await
+    }
+
+    async fn withdraw(
+        &mut self,
+        address: String,
+        counterparty: Counterparty,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        let memo = self.get_ref().memo.render(Some(origin));
+        let destination = Destination::Withdrawal {
+            address,
+            counterparty,
+        };
+        self.ready()
+            .await?
+            .call((destination, values, memo))
+            .await?
+            .await
+    }
+
+    async fn send_batch(
+        &mut self,
+        outputs: Vec<(Address, Vec<Value>)>,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        // A memo can't say which of the requests each output answers
+        let memo = self.get_ref().memo.render(None);
+        self.ready()
+            .await?
+            .call((Destination::Batch(outputs), Vec::new(), memo))
+            .await?
+            .await
+    }
+
+    async fn self_test(&mut self, asset_id: asset::Id) -> SelfTest {
+        self.get_mut().self_test(asset_id).await
+    }
+
+    async fn balance(&mut self) -> anyhow::Result<Vec<Value>> {
+        self.get_mut().balance().await
+    }
+
+    async fn sweep(
+        &mut self,
+        address: Address,
+    ) -> anyhow::Result<Option<(penumbra_transaction::Id, Vec<Value>)>> {
+        let values = self.get_mut().balance().await?;
+        if values.is_empty() {
+            return Ok(None);
+        }
+        let memo = self.get_ref().memo.render(None);
+        let outputs = vec![(address, values.clone())];
+        let (tx_id, _) = self
+            .ready()
+            .await?
+            .call((Destination::Batch(outputs), Vec::new(), memo))
+            .await?
+            .await?;
+        Ok(Some((tx_id, values)))
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.get_ref().is_dry_run()
+    }
+}
+
+impl<V, C> Service<(Destination, Vec<Value>, String)> for Sender<V, C>
+where
+    V: ViewClient + Clone + Send + 'static,
+    C: CustodyClient + Clone + Send + 'static,
+{
+    type Response = Broadcast;
+    type Error = anyhow::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn call(&mut self, req: (Destination, Vec<Value>, String)) -> Self::Future {
+        let mut self2 = self.clone();
+        async move {
+            let (destination, values, memo) = req;
+            let empty = match &destination {
+                Destination::Batch(outputs) => outputs.iter().all(|(_, values)| values.is_empty()),
+                _ => values.is_empty(),
+            };
+            if empty {
+                return Err(anyhow::anyhow!(
+                    "tried to send empty list of values to address"
+                ));
+            }
+
+            // Wait for room in the pipeline, if it's full
+            let permit = self2.in_flight.clone().acquire_owned().await?;
+
+            // 1. plan the transaction, spending notes no transaction in flight spends.
+            let start = Instant::now();
+            let plan = self2.plan(destination, values, memo).await?;
+            metrics::observe(PLAN_SECONDS, &[], start.elapsed());
+
+            // 2. Authorize and build the transaction.
+            let start = Instant::now();
+            let auth_data = self2.authorize(&plan).await?;
+            metrics::observe(AUTHORIZE_SECONDS, &[], start.elapsed());
+            let start = Instant::now();
+            let tx = self2.build(plan.clone(), auth_data).await?;
+            metrics::observe(PROVE_SECONDS, &[], start.elapsed());
+            let spent = plan
+                .spend_plans()
+                .map(|spend| spend.note.commit())
+                .collect::<Vec<_>>();
+            self2.reserved.lock().unwrap().extend(spent.iter().copied());
+
+            // 3. Broadcast the transaction and wait for confirmation, in the background, so that
+            // the next one can be built in the meantime.
+            let broadcast = tokio::spawn(async move {
+                let result: anyhow::Result<_> = async {
+                    // In a dry run, stop short of actually sending anything
+                    if self2.dry_run {
+                        tracing::info!("dry run: not broadcasting transaction");
+                        return Ok((tx.id(), 0));
+                    }
+                    let start = Instant::now();
+                    let (tx_id, _) = self2.view.broadcast_transaction(tx, false).await?;
+                    metrics::observe(BROADCAST_SECONDS, &[], start.elapsed());
+                    let start = Instant::now();
+                    let height = self2.confirm(tx_id).await?;
+                    metrics::observe(CONFIRMATION_SECONDS, &[], start.elapsed());
+                    Ok((tx_id, height))
+                }
+                .await;
+                // Whether or not it was confirmed, the notes are free for the next transaction
+                let mut reserved = self2.reserved.lock().unwrap();
+                for commitment in &spent {
+                    reserved.remove(commitment);
+                }
+                drop(permit);
+                result
+            });
+            let broadcast: Broadcast = async move { broadcast.await? }.boxed();
+            Ok(broadcast)
+        }
+        .boxed()
+    }
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        // We always return "ready" because we handle backpressure by making the
+        // constructor return a concurrency limit wrapper.
+        Poll::Ready(Ok(()))
+    }
+}
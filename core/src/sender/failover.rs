@@ -0,0 +1,449 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use penumbra_asset::{asset, Value};
+use penumbra_keys::Address;
+use tokio::{sync::Mutex, time::Instant};
+
+use super::{Broadcast, Dispenser, SelfTest};
+use crate::responder::{Counterparty, Origin};
+
+/// How long to stay on the backup wallet before checking whether the primary is usable again.
+const RETRY_PRIMARY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// How long to go on the last check of the primary's balance, rather than asking its view for the
+/// balance before every send.
+const BALANCE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A backup wallet to dispense from, shared so that it survives rotating the primary's key.
+pub type Backup = Arc<Mutex<Box<dyn Dispenser>>>;
+
+/// When to switch from the primary wallet to the backup.
+#[derive(Debug, Clone)]
+pub struct FailoverPolicy {
+    /// Switch when the primary's balance of any of these assets drops below the given amount.
+    pub min_balance: Vec<Value>,
+    /// Switch after this many sends from the primary fail in a row, or never for failures if 0.
+    pub max_failures: usize,
+}
+
+/// A [`Dispenser`] which dispenses from a primary wallet, switching to a backup wallet (if there
+/// is one) when the primary runs low or keeps failing, and back once the primary is usable again.
+pub struct Failover<P> {
+    primary: P,
+    backup: Option<Backup>,
+    policy: FailoverPolicy,
+    /// When we switched to the backup, if we're dispensing from it.
+    on_backup_since: Option<Instant>,
+    /// How many sends from the primary have failed in a row, shared with the broadcasts in flight.
+    failures: Arc<AtomicUsize>,
+    /// When the primary's balance was last checked, and why it was too low then, if it was.
+    balance_checked: Option<(Instant, Option<String>)>,
+    /// Tell administrators about a switch between wallets.
+    alert: Arc<dyn Fn(String) + Send + Sync>,
+}
+
+impl<P: Dispenser> Failover<P> {
+    pub fn new(
+        primary: P,
+        backup: Option<Backup>,
+        policy: FailoverPolicy,
+        alert: Arc<dyn Fn(String) + Send + Sync>,
+    ) -> Self {
+        Failover {
+            primary,
+            backup,
+            policy,
+            on_backup_since: None,
+            failures: Default::default(),
+            balance_checked: None,
+            alert,
+        }
+    }
+
+    /// Why the primary shouldn't be dispensed from, if it shouldn't (as in "the primary ..."). The
+    /// balance is only checked again once the last check is old, unless `fresh` is set.
+    async fn primary_unusable(&mut self, fresh: bool) -> Option<String> {
+        let failures = self.failures.load(Ordering::Relaxed);
+        if self.policy.max_failures > 0 && failures >= self.policy.max_failures {
+            return Some(format!("had {} sends fail in a row", failures));
+        }
+        if self.policy.min_balance.is_empty() {
+            return None;
+        }
+        match &self.balance_checked {
+            Some((at, reason)) if !fresh && at.elapsed() < BALANCE_CHECK_INTERVAL => reason.clone(),
+            _ => {
+                let reason = self.primary_low().await;
+                self.balance_checked = Some((Instant::now(), reason.clone()));
+                reason
+            }
+        }
+    }
+
+    /// Why the primary's balance is too low to dispense from, if it is.
+    async fn primary_low(&mut self) -> Option<String> {
+        let balance = match self.primary.balance().await {
+            Ok(balance) => balance,
+            Err(e) => return Some(format!("couldn't have its balance checked: {:#}", e)),
+        };
+        let cache = asset::Cache::with_known_assets();
+        self.policy.min_balance.iter().find_map(|min| {
+            let held = balance
+                .iter()
+                .filter(|value| value.asset_id == min.asset_id)
+                .map(|value| value.amount.value())
+                .sum::<u128>();
+            (held < min.amount.value())
+                .then(|| format!("has less than {} left", min.format(&cache)))
+        })
+    }
+
+    /// Pick which wallet to dispense from next, switching if need be, and returning the backup if
+    /// it's the one.
+    async fn choose(&mut self) -> Option<Backup> {
+        let backup = self.backup.clone()?;
+        match self.on_backup_since {
+            None => {
+                let reason = self.primary_unusable(false).await?;
+                tracing::warn!(%reason, "switching to the backup wallet");
+                (self.alert)(format!(
+                    "Switched to the backup wallet, because the primary {}. Top up or fix the \
+                    primary, and the faucet switches back to it within {}.",
+                    reason,
+                    humantime::format_duration(RETRY_PRIMARY_AFTER)
+                ));
+                self.on_backup_since = Some(Instant::now());
+                Some(backup)
+            }
+            Some(since) if since.elapsed() >= RETRY_PRIMARY_AFTER => {
+                // Give the primary another chance, as long as it has the funds
+                self.failures.store(0, Ordering::Relaxed);
+                if self.primary_unusable(true).await.is_some() {
+                    self.on_backup_since = Some(Instant::now());
+                    return Some(backup);
+                }
+                tracing::info!("switching back to the primary wallet");
+                (self.alert)("Switched back to the primary wallet.".to_string());
+                self.on_backup_since = None;
+                None
+            }
+            Some(_) => Some(backup),
+        }
+    }
+
+    /// Count a send from the primary towards switching to the backup, if it failed.
+    fn count<T>(&mut self, result: &anyhow::Result<T>) {
+        count(&self.failures, result)
+    }
+}
+
+/// Count a send towards the failures in a row, resetting them if it succeeded.
+fn count<T>(failures: &AtomicUsize, result: &anyhow::Result<T>) {
+    match result {
+        Ok(_) => failures.store(0, Ordering::Relaxed),
+        Err(_) => {
+            failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Dispenser> Dispenser for Failover<P> {
+    async fn send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if let Some(backup) = self.choose().await {
+            return backup.lock().await.send(address, values, origin).await;
+        }
+        let result = self.primary.send(address, values, origin).await;
+        self.count(&result);
+        result
+    }
+
+    async fn prepare_send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<Broadcast> {
+        if let Some(backup) = self.choose().await {
+            return backup
+                .lock()
+                .await
+                .prepare_send(address, values, origin)
+                .await;
+        }
+        // Only count the broadcast once it's done, whether or not it's built
+        let prepared = self.primary.prepare_send(address, values, origin).await;
+        if prepared.is_err() {
+            self.count(&prepared);
+        }
+        let failures = self.failures.clone();
+        Ok(prepared?
+            .inspect(move |result| count(&failures, result))
+            .boxed())
+    }
+
+    async fn withdraw(
+        &mut self,
+        address: String,
+        counterparty: Counterparty,
+        values: Vec<Value>,
+        origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if let Some(backup) = self.choose().await {
+            return backup
+                .lock()
+                .await
+                .withdraw(address, counterparty, values, origin)
+                .await;
+        }
+        let result = self
+            .primary
+            .withdraw(address, counterparty, values, origin)
+            .await;
+        self.count(&result);
+        result
+    }
+
+    async fn send_batch(
+        &mut self,
+        outputs: Vec<(Address, Vec<Value>)>,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if let Some(backup) = self.choose().await {
+            return backup.lock().await.send_batch(outputs).await;
+        }
+        let result = self.primary.send_batch(outputs).await;
+        self.count(&result);
+        result
+    }
+
+    async fn self_test(&mut self, asset_id: asset::Id) -> SelfTest {
+        match self.choose().await {
+            Some(backup) => backup.lock().await.self_test(asset_id).await,
+            None => self.primary.self_test(asset_id).await,
+        }
+    }
+
+    /// The balance of whichever wallet is being dispensed from.
+    async fn balance(&mut self) -> anyhow::Result<Vec<Value>> {
+        match self
+            .backup
+            .as_ref()
+            .filter(|_| self.on_backup_since.is_some())
+        {
+            Some(backup) => backup.lock().await.balance().await,
+            None => self.primary.balance().await,
+        }
+    }
+
+    async fn sweep(
+        &mut self,
+        address: Address,
+    ) -> anyhow::Result<Option<(penumbra_transaction::Id, Vec<Value>)>> {
+        // Only the primary's funds move with its key: the backup stays the backup
+        self.primary.sweep(address).await
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.primary.is_dry_run()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Mutex as StdMutex};
+
+    use penumbra_keys::keys::{SeedPhrase, SpendKey};
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::id::{ChannelId, MessageId, UserId};
+
+    /// A stand-in wallet, counting what it's asked to do.
+    #[derive(Clone, Default)]
+    struct Wallet(Arc<Counts>);
+
+    #[derive(Default)]
+    struct Counts {
+        balance: StdMutex<Vec<Value>>,
+        fail: AtomicBool,
+        sends: AtomicUsize,
+        balance_checks: AtomicUsize,
+    }
+
+    impl Wallet {
+        fn holding(balance: &[&str]) -> Self {
+            let wallet = Wallet::default();
+            *wallet.0.balance.lock().unwrap() =
+                balance.iter().map(|value| value.parse().unwrap()).collect();
+            wallet
+        }
+
+        fn failing() -> Self {
+            let wallet = Wallet::default();
+            wallet.0.fail.store(true, Ordering::Relaxed);
+            wallet
+        }
+
+        fn sends(&self) -> usize {
+            self.0.sends.load(Ordering::Relaxed)
+        }
+
+        fn result(&self) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+            self.0.sends.fetch_add(1, Ordering::Relaxed);
+            if self.0.fail.load(Ordering::Relaxed) {
+                anyhow::bail!("simulated failure");
+            }
+            Ok((penumbra_transaction::Id([0; 32]), 1))
+        }
+    }
+
+    #[async_trait]
+    impl Dispenser for Wallet {
+        async fn send(
+            &mut self,
+            _address: Address,
+            _values: Vec<Value>,
+            _origin: Origin,
+        ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+            self.result()
+        }
+
+        async fn withdraw(
+            &mut self,
+            _address: String,
+            _counterparty: Counterparty,
+            _values: Vec<Value>,
+            _origin: Origin,
+        ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+            self.result()
+        }
+
+        async fn send_batch(
+            &mut self,
+            _outputs: Vec<(Address, Vec<Value>)>,
+        ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+            self.result()
+        }
+
+        async fn self_test(&mut self, _asset_id: asset::Id) -> SelfTest {
+            unreachable!("not used in these tests")
+        }
+
+        async fn balance(&mut self) -> anyhow::Result<Vec<Value>> {
+            self.0.balance_checks.fetch_add(1, Ordering::Relaxed);
+            Ok(self.0.balance.lock().unwrap().clone())
+        }
+
+        async fn sweep(
+            &mut self,
+            _address: Address,
+        ) -> anyhow::Result<Option<(penumbra_transaction::Id, Vec<Value>)>> {
+            Ok(None)
+        }
+
+        fn is_dry_run(&self) -> bool {
+            false
+        }
+    }
+
+    /// A failover between the wallets, and how many switches it's alerted about.
+    fn failover(
+        primary: &Wallet,
+        backup: &Wallet,
+        min_balance: &[&str],
+        max_failures: usize,
+    ) -> (Failover<Wallet>, Arc<AtomicUsize>) {
+        let alerts = Arc::new(AtomicUsize::new(0));
+        let counted = alerts.clone();
+        let failover = Failover::new(
+            primary.clone(),
+            Some(Arc::new(Mutex::new(
+                Box::new(backup.clone()) as Box<dyn Dispenser>
+            ))),
+            FailoverPolicy {
+                min_balance: min_balance.iter().map(|v| v.parse().unwrap()).collect(),
+                max_failures,
+            },
+            Arc::new(move |_| {
+                counted.fetch_add(1, Ordering::Relaxed);
+            }),
+        );
+        (failover, alerts)
+    }
+
+    async fn send(
+        failover: &mut Failover<Wallet>,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        let spend_key = SpendKey::from_seed_phrase(SeedPhrase::generate(OsRng), 0);
+        let address = spend_key.full_viewing_key().payment_address(0.into()).0;
+        let origin = Origin {
+            user_id: UserId(1),
+            channel_id: ChannelId(1),
+            message_id: MessageId(1),
+        };
+        failover
+            .send(address, vec!["1penumbra".parse().unwrap()], origin)
+            .await
+    }
+
+    #[tokio::test]
+    async fn switches_after_failures_in_a_row() {
+        let (primary, backup) = (Wallet::failing(), Wallet::default());
+        let (mut failover, alerts) = failover(&primary, &backup, &[], 2);
+        assert!(send(&mut failover).await.is_err());
+        assert!(send(&mut failover).await.is_err());
+        assert!(send(&mut failover).await.is_ok());
+        assert_eq!((primary.sends(), backup.sends()), (2, 1));
+        assert_eq!(alerts.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn zero_failures_never_switches() {
+        let (primary, backup) = (Wallet::failing(), Wallet::default());
+        let (mut failover, alerts) = failover(&primary, &backup, &[], 0);
+        for _ in 0..3 {
+            assert!(send(&mut failover).await.is_err());
+        }
+        assert_eq!((primary.sends(), backup.sends()), (3, 0));
+        assert_eq!(alerts.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn switches_when_low_and_reports_the_backup_balance() {
+        let primary = Wallet::holding(&["50penumbra"]);
+        let backup = Wallet::holding(&["1000penumbra"]);
+        let (mut failover, alerts) = failover(&primary, &backup, &["100penumbra"], 3);
+        send(&mut failover).await.unwrap();
+        assert_eq!((primary.sends(), backup.sends()), (0, 1));
+        assert_eq!(alerts.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            failover.balance().await.unwrap(),
+            vec!["1000penumbra".parse::<Value>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn balance_is_checked_once_a_while() {
+        let primary = Wallet::holding(&["500penumbra"]);
+        let backup = Wallet::default();
+        let (mut failover, _) = failover(&primary, &backup, &["100penumbra"], 3);
+        for _ in 0..3 {
+            send(&mut failover).await.unwrap();
+        }
+        assert_eq!(primary.sends(), 3);
+        assert_eq!(primary.0.balance_checks.load(Ordering::Relaxed), 1);
+    }
+}
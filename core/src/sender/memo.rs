@@ -0,0 +1,50 @@
+use crate::responder::Origin;
+
+/// The memo attached to transactions which don't answer a request (like self-tests), and to every
+/// transaction when the memo is kept private.
+const GENERIC: &str = "Hello from Galileo, the Penumbra faucet bot";
+
+/// The default template for the memo of each dispense.
+const DEFAULT_TEMPLATE: &str = "galileo faucet — request {message} in channel {channel}";
+
+/// What to write in the memo of each transaction, so that recipients and auditors can tell which
+/// request it answered.
+///
+/// The template may contain `{user}`, `{channel}`, and `{message}`, which are replaced by the ids
+/// of the user who made the request, the channel they made it in, and their message.
+#[derive(Debug, Clone)]
+pub struct Memo {
+    /// The template to render, or `None` to say nothing about the request.
+    template: Option<String>,
+}
+
+impl Default for Memo {
+    fn default() -> Self {
+        Memo::new(DEFAULT_TEMPLATE.to_string())
+    }
+}
+
+impl Memo {
+    pub fn new(template: String) -> Self {
+        Memo {
+            template: Some(template),
+        }
+    }
+
+    /// A memo which says nothing about the request, for when that shouldn't be public.
+    pub fn private() -> Self {
+        Memo { template: None }
+    }
+
+    /// The memo text for a transaction answering a request from the given origin, or for one
+    /// which answers no request.
+    pub fn render(&self, origin: Option<Origin>) -> String {
+        match (&self.template, origin) {
+            (Some(template), Some(origin)) => template
+                .replace("{user}", &origin.user_id.0.to_string())
+                .replace("{channel}", &origin.channel_id.0.to_string())
+                .replace("{message}", &origin.message_id.0.to_string()),
+            _ => GENERIC.to_string(),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! Stand-ins for the chain and for a chat platform, so that scripted conversations can be run
+//! through the real intake and responder (see `galileo simulate`).
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use penumbra_asset::{asset, Value};
+use penumbra_keys::{
+    keys::{SeedPhrase, SpendKey},
+    Address,
+};
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{
+    intake::{ChatPlatform, Reaction},
+    responder::{Counterparty, Origin, Response},
+    sender::{Dispenser, SelfTest},
+    transport::Transport,
+};
+
+/// A [`Dispenser`] which never touches a chain: every send "succeeds" with a random transaction
+/// hash at an increasing block height, except to addresses it was told to fail.
+pub struct MockSender {
+    /// The faucet's own address, for self-tests.
+    address: Address,
+    /// The addresses to which sending should fail.
+    fail: HashSet<String>,
+    /// The block height at which the last transaction was "detected".
+    height: u64,
+}
+
+impl MockSender {
+    pub fn new(fail: HashSet<String>) -> Self {
+        let spend_key = SpendKey::from_seed_phrase(SeedPhrase::generate(OsRng), 0);
+        MockSender {
+            address: spend_key.full_viewing_key().payment_address(0.into()).0,
+            fail,
+            height: 0,
+        }
+    }
+
+    /// A random transaction hash.
+    fn id() -> penumbra_transaction::Id {
+        let mut id = [0u8; 32];
+        OsRng.fill_bytes(&mut id);
+        penumbra_transaction::Id(id)
+    }
+}
+
+#[async_trait]
+impl Dispenser for MockSender {
+    async fn send(
+        &mut self,
+        address: Address,
+        values: Vec<Value>,
+        _origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if values.is_empty() {
+            anyhow::bail!("tried to send empty list of values to address");
+        }
+        if self.fail.contains(&address.to_string()) {
+            anyhow::bail!("simulated failure");
+        }
+        self.height += 1;
+        Ok((Self::id(), self.height))
+    }
+
+    async fn send_batch(
+        &mut self,
+        outputs: Vec<(Address, Vec<Value>)>,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if outputs.iter().all(|(_, values)| values.is_empty()) {
+            anyhow::bail!("tried to send empty list of values to address");
+        }
+        if let Some((address, _)) = outputs
+            .iter()
+            .find(|(address, _)| self.fail.contains(&address.to_string()))
+        {
+            anyhow::bail!("simulated failure sending to {}", address);
+        }
+        self.height += 1;
+        Ok((Self::id(), self.height))
+    }
+
+    async fn withdraw(
+        &mut self,
+        address: String,
+        _counterparty: Counterparty,
+        values: Vec<Value>,
+        _origin: Origin,
+    ) -> anyhow::Result<(penumbra_transaction::Id, u64)> {
+        if values.is_empty() {
+            anyhow::bail!("tried to send empty list of values to address");
+        }
+        if self.fail.contains(&address) {
+            anyhow::bail!("simulated failure");
+        }
+        self.height += 1;
+        Ok((Self::id(), self.height))
+    }
+
+    async fn self_test(&mut self, _asset_id: asset::Id) -> SelfTest {
+        SelfTest {
+            address: self.address,
+            stages: vec![("simulate", Duration::ZERO)],
+            result: Ok(Self::id()),
+            simulated: false,
+        }
+    }
+
+    async fn balance(&mut self) -> anyhow::Result<Vec<Value>> {
+        Ok(Vec::new())
+    }
+
+    async fn sweep(
+        &mut self,
+        _address: Address,
+    ) -> anyhow::Result<Option<(penumbra_transaction::Id, Vec<Value>)>> {
+        // There's nothing in a simulated wallet to sweep
+        Ok(None)
+    }
+
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// Everything the faucet said in answer to a message.
+pub type Transcript = Arc<Mutex<Vec<String>>>;
+
+/// A [`ChatPlatform`] which records everything said in it, instead of sending it anywhere.
+#[derive(Default)]
+pub struct MockChat {
+    transcript: Transcript,
+    reactions: Mutex<Vec<Reaction>>,
+    cooldowns: Mutex<Vec<DateTime<Utc>>>,
+}
+
+impl MockChat {
+    /// Everything said in this chat so far.
+    pub fn transcript(&self) -> Vec<String> {
+        self.transcript.lock().unwrap().clone()
+    }
+
+    /// Every reaction to the message so far.
+    pub fn reactions(&self) -> Vec<Reaction> {
+        self.reactions.lock().unwrap().clone()
+    }
+
+    /// Every time the author was cooled down so far, by when the cooldown ends.
+    pub fn cooldowns(&self) -> Vec<DateTime<Utc>> {
+        self.cooldowns.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for MockChat {
+    async fn reply(&self, text: String) -> anyhow::Result<()> {
+        self.transcript.lock().unwrap().push(text);
+        Ok(())
+    }
+
+    async fn typing(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn react(&self, reaction: Reaction) -> anyhow::Result<()> {
+        self.reactions.lock().unwrap().push(reaction);
+        Ok(())
+    }
+
+    async fn progress(&self, text: String) -> anyhow::Result<()> {
+        self.transcript.lock().unwrap().push(text);
+        Ok(())
+    }
+
+    async fn cool_down(&self, until: DateTime<Utc>) -> anyhow::Result<()> {
+        self.cooldowns.lock().unwrap().push(until);
+        Ok(())
+    }
+
+    fn transports(&self, dm_receipts: bool) -> Vec<Box<dyn Transport>> {
+        let mut transports: Vec<Box<dyn Transport>> = vec![Box::new(Recorder {
+            transcript: self.transcript.clone(),
+            receipts: false,
+        })];
+        if dm_receipts {
+            transports.push(Box::new(Recorder {
+                transcript: self.transcript.clone(),
+                receipts: true,
+            }));
+        }
+        transports
+    }
+}
+
+/// Records results in a [`Transcript`], as a reply would show them or as receipts would.
+struct Recorder {
+    transcript: Transcript,
+    receipts: bool,
+}
+
+#[async_trait]
+impl Transport for Recorder {
+    fn name(&self) -> &'static str {
+        if self.receipts {
+            "simulated-receipts"
+        } else {
+            "simulated-reply"
+        }
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        let mut transcript = self.transcript.lock().unwrap();
+        if self.receipts {
+            for (address, receipt) in response.succeeded() {
+                transcript.push(receipt.message(address, response.locale()));
+            }
+        } else {
+            transcript.push(response.plain_summary());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        id::{ChannelId, MessageId, UserId},
+        intake::{Incoming, Intake, Overrides, RateLimit, ReplyLimits},
+        responder::{Counterparties, Menu, Routes},
+        Lifecycle, Locale, Responder, Store,
+    };
+
+    /// A fresh, empty store for one test.
+    fn store(name: &str) -> Store {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("galileo-simulate-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Store::load(dir).unwrap()
+    }
+
+    /// The intake of a faucet sending 100penumbra per request from a [`MockSender`] which fails to
+    /// send to the given addresses, with a day's rate limit.
+    fn faucet(store: &Store, fail: &[&str]) -> Intake {
+        faucet_with_overrides(store, fail, Overrides::default())
+    }
+
+    /// The same faucet, with limits overridden in some servers or channels.
+    fn faucet_with_overrides(store: &Store, fail: &[&str], overrides: Overrides) -> Intake {
+        let lifecycle = Lifecycle::default();
+        let (requests, _control, responder) = Responder::new(
+            MockSender::new(fail.iter().map(|address| address.to_string()).collect()),
+            1,
+            vec!["100penumbra".parse().unwrap()],
+            None,
+            Menu::default(),
+            Vec::new(),
+            Vec::new(),
+            None,
+            Counterparties::default(),
+            store.clone(),
+            lifecycle.clone(),
+        );
+        tokio::spawn(responder.run());
+        Intake::new(
+            RateLimit::new(Duration::from_secs(24 * 60 * 60)),
+            ReplyLimits::new(5, None, Vec::new()),
+            overrides,
+            Locale::En,
+            Counterparties::default(),
+            store.clone(),
+            false,
+            Vec::new(),
+            lifecycle,
+            Routes::new(requests),
+            None,
+            None,
+            None,
+            Vec::new(),
+            None,
+        )
+    }
+
+    fn address() -> String {
+        let spend_key = SpendKey::from_seed_phrase(SeedPhrase::generate(OsRng), 0);
+        spend_key
+            .full_viewing_key()
+            .payment_address(0.into())
+            .0
+            .to_string()
+    }
+
+    fn message(id: u64, user: u64, content: String) -> Incoming {
+        Incoming {
+            id: MessageId(id),
+            channel_id: ChannelId(1),
+            server_id: None,
+            author_id: UserId(user),
+            author_name: format!("user-{}", user),
+            author_roles: Vec::new(),
+            content,
+        }
+    }
+
+    #[tokio::test]
+    async fn dispenses_to_the_address_in_a_message() {
+        let store = store("dispense");
+        let intake = faucet(&store, &[]);
+        let address = address();
+
+        let chat = MockChat::default();
+        intake
+            .handle(
+                &chat,
+                message(1, 7, format!("please send to {}", address)),
+                false,
+            )
+            .await;
+
+        let dispenses = store.dispenses();
+        assert_eq!(dispenses.len(), 1);
+        assert_eq!(dispenses[0].address, address);
+        assert_eq!(dispenses[0].user_id, Some(7));
+        assert!(!chat.transcript().is_empty());
+        assert!(store.is_processed(MessageId(1)));
+    }
+
+    #[tokio::test]
+    async fn rate_limits_a_second_request() {
+        let store = store("rate-limit");
+        let intake = faucet(&store, &[]);
+
+        intake
+            .handle(&MockChat::default(), message(1, 7, address()), false)
+            .await;
+        let chat = MockChat::default();
+        intake.handle(&chat, message(2, 7, address()), false).await;
+
+        assert_eq!(store.dispenses().len(), 1);
+        assert!(!chat.transcript().is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_failures_without_dispensing() {
+        let store = store("failure");
+        let address = address();
+        let intake = faucet(&store, &[&address]);
+
+        let chat = MockChat::default();
+        intake
+            .handle(&chat, message(1, 7, address.clone()), false)
+            .await;
+
+        assert!(store.dispenses().is_empty());
+        let failures = store.failures();
+        assert_eq!(failures.len(), 1);
+        assert!(!chat.transcript().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failure_releases_only_the_latest_request() {
+        let store = store("release-latest");
+        let failing = address();
+        // Channel 2 barely rate-limits at all, while the default is a day
+        let overrides = Overrides::new(Vec::new(), vec!["2:rate-limit=1ms".parse().unwrap()]);
+        let intake = faucet_with_overrides(&store, &[&failing], overrides);
+        let in_channel = |id, channel, content| Incoming {
+            channel_id: ChannelId(channel),
+            ..message(id, 7, content)
+        };
+
+        intake
+            .handle(&MockChat::default(), in_channel(1, 2, address()), false)
+            .await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        intake
+            .handle(&MockChat::default(), in_channel(2, 2, failing), false)
+            .await;
+        // The failure gives back its own allowance, not that of the dispense before it, which
+        // still counts where the rate limit is a day
+        let chat = MockChat::default();
+        intake
+            .handle(&chat, in_channel(3, 1, address()), false)
+            .await;
+
+        assert_eq!(store.dispenses().len(), 1);
+        assert!(!chat.transcript().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ignores_messages_without_addresses() {
+        let store = store("no-address");
+        let intake = faucet(&store, &[]);
+
+        let chat = MockChat::default();
+        intake
+            .handle(&chat, message(1, 7, "hello there".to_string()), false)
+            .await;
+
+        assert!(store.dispenses().is_empty());
+        assert!(chat.transcript().is_empty());
+    }
+}
@@ -0,0 +1,1500 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use penumbra_asset::{asset, Value};
+use penumbra_transaction::Id;
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::oneshot;
+
+use crate::{
+    id::{ChannelId, MessageId, ServerId, UserId},
+    intake::Reaction,
+    responder::{Origin, Request, Response},
+};
+
+/// Operational state of the bot which must survive restarts, persisted as JSON in the data
+/// directory. Changes made for every message (claims and checkpoints) are appended to a journal
+/// instead of rewriting the whole state, which the journal is folded back into now and then.
+#[derive(Debug, Clone)]
+pub struct Store {
+    /// The directory in which the state is stored.
+    dir: PathBuf,
+    /// The in-memory copy of the state, written back to disk (or to the journal) on every change.
+    state: Arc<Mutex<State>>,
+    /// Every dispense made by the faucet, appended to the ledger file as it happens.
+    dispenses: Arc<Mutex<Vec<Dispense>>>,
+    /// Every donation received by the faucet, appended to the donation ledger as it's noticed.
+    donations: Arc<Mutex<Vec<Donation>>>,
+    /// Every failure to dispense to an address, appended to the failure ledger as it happens.
+    failures: Arc<Mutex<Vec<Failure>>>,
+    /// The channels being caught up on, with the newest message handled live in each meanwhile,
+    /// whose checkpoint waits for catch-up to finish.
+    catching_up: Arc<Mutex<BTreeMap<u64, Option<u64>>>>,
+    /// Replies being delivered right now, keyed by message id, to be saved to the outbox if we stop
+    /// before they're done.
+    delivering: Arc<Mutex<BTreeMap<u64, Undelivered>>>,
+    /// The lease on the lock under which we hold the store, past which the state is no longer ours
+    /// to write.
+    lease: Lease,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    /// The last message handled in each channel, keyed by channel id.
+    checkpoints: BTreeMap<u64, u64>,
+    /// The ids of messages whose requests have been (or are being) processed.
+    processed: BTreeSet<u64>,
+    /// Requests left unprocessed by an instance which handed off to another.
+    pending: Vec<Pending>,
+    /// Replies to requests which were answered, but not yet delivered, when an instance handed off
+    /// to another.
+    outbox: Vec<Undelivered>,
+    /// The chain the faucet was last running on, so that we can tell when it was reset.
+    chain_id: Option<String>,
+    /// Settings chosen by each server's administrators, keyed by server id.
+    servers: BTreeMap<u64, ServerSettings>,
+    /// Cooldown roles given to users, to be taken away again when they expire.
+    cooldowns: Vec<Cooldown>,
+    /// Users banned by a moderator or administrator, whose requests are ignored.
+    bans: Vec<Ban>,
+    /// How many changes have been appended to the journal since the state was last written.
+    #[serde(skip)]
+    journaled: usize,
+}
+
+/// A change made for every message, appended to the journal rather than rewriting the whole state,
+/// and replayed on top of the state when it's loaded.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+enum Change {
+    /// A message was claimed for processing.
+    Claim { message_id: u64 },
+    /// The claim on a message was released.
+    Unclaim { message_id: u64 },
+    /// The checkpoint for a channel moved up to a message.
+    Checkpoint { channel_id: u64, message_id: u64 },
+}
+
+impl Change {
+    /// Apply the change to the state, returning `false` if it made no difference. Applying a change
+    /// again is harmless, so replaying a journal which was already folded into the state is too.
+    fn apply(self, state: &mut State) -> bool {
+        match self {
+            Change::Claim { message_id } => {
+                // Forget about messages too old to be caught up on any more
+                let retention = chrono::Duration::days(PROCESSED_RETENTION_DAYS);
+                let oldest = MessageId::at(Utc::now() - retention);
+                state.processed = state.processed.split_off(&oldest.0);
+                state.processed.insert(message_id)
+            }
+            Change::Unclaim { message_id } => state.processed.remove(&message_id),
+            Change::Checkpoint {
+                channel_id,
+                message_id,
+            } => {
+                let checkpoint = state.checkpoints.entry(channel_id).or_default();
+                let advanced = message_id > *checkpoint;
+                *checkpoint = (*checkpoint).max(message_id);
+                advanced
+            }
+        }
+    }
+}
+
+/// Settings for one server, chosen by its administrators with `/faucet-admin`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// The emoji to react with for each kind of reaction which doesn't use the default, or `None`
+    /// if that kind of reaction is turned off. Custom emoji are written as `<:name:id>`.
+    pub reactions: BTreeMap<Reaction, Option<String>>,
+}
+
+/// A request which was queued but not yet processed when the bot handed off to another instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pending {
+    /// The user who made the request.
+    pub user_id: u64,
+    /// The channel in which the request was made.
+    pub channel_id: u64,
+    /// The message containing the request.
+    pub message_id: u64,
+    /// The addresses (or things that look like them) in the request, as written in the message.
+    pub addresses: Vec<String>,
+    /// Whether the request asked for delegation tokens.
+    #[serde(default)]
+    pub delegate: bool,
+    /// The assets asked for by name.
+    #[serde(default)]
+    pub assets: Vec<String>,
+    /// The maximum number of addresses to dispense to, if overridden where the request was made.
+    #[serde(default)]
+    pub max_addresses: Option<usize>,
+    /// The code of the language to answer the request in (English if absent).
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Whether the request jumps ahead of others waiting in the queue.
+    #[serde(default)]
+    pub priority: bool,
+}
+
+impl Pending {
+    /// Record a request for the next instance to process.
+    pub fn of(request: &Request) -> Self {
+        let origin = request.origin();
+        Pending {
+            user_id: origin.user_id.0,
+            channel_id: origin.channel_id.0,
+            message_id: origin.message_id.0,
+            addresses: request
+                .addresses()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            delegate: request.wants_delegation(),
+            assets: request.assets().to_vec(),
+            max_addresses: request.max_addresses(),
+            locale: Some(request.locale().code().to_string()),
+            priority: request.has_priority(),
+        }
+    }
+
+    /// The user and message the request came from.
+    pub fn origin(&self) -> Origin {
+        Origin {
+            user_id: UserId(self.user_id),
+            channel_id: ChannelId(self.channel_id),
+            message_id: MessageId(self.message_id),
+        }
+    }
+
+    /// Make the request again, as it was before it was handed off.
+    ///
+    /// Returns a receiver for the response to the request, as well as the request itself.
+    pub fn request(&self) -> (oneshot::Receiver<Response>, Request) {
+        let (response, mut request) =
+            Request::new(self.addresses.iter().map(String::as_str), self.origin());
+        if self.delegate {
+            request.request_delegation();
+        }
+        request.request_assets(self.assets.clone());
+        if let Some(max_addresses) = self.max_addresses {
+            request.limit_addresses(max_addresses);
+        }
+        if let Some(locale) = self.locale.as_ref().and_then(|code| code.parse().ok()) {
+            request.set_locale(locale);
+        }
+        if self.priority {
+            request.prioritize();
+        }
+        (response, request)
+    }
+}
+
+/// A reply to a request which was answered but not yet delivered, saved when the bot handed off to
+/// another instance so that the next one can deliver it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Undelivered {
+    /// The user who made the request.
+    pub user_id: u64,
+    /// The channel in which the request was made.
+    pub channel_id: u64,
+    /// The message containing the request, to reply to.
+    pub message_id: u64,
+    /// The text of the reply.
+    pub reply: String,
+}
+
+impl Undelivered {
+    pub fn new(origin: Origin, reply: String) -> Self {
+        Undelivered {
+            user_id: origin.user_id.0,
+            channel_id: origin.channel_id.0,
+            message_id: origin.message_id.0,
+            reply,
+        }
+    }
+
+    /// The user and message the request came from.
+    pub fn origin(&self) -> Origin {
+        Origin {
+            user_id: UserId(self.user_id),
+            channel_id: ChannelId(self.channel_id),
+            message_id: MessageId(self.message_id),
+        }
+    }
+}
+
+/// A cooldown role given to a user who kept requesting tokens past their reply limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cooldown {
+    /// The server the user was given the role in.
+    pub server_id: u64,
+    /// The user who was given the role.
+    pub user_id: u64,
+    /// The role they were given.
+    pub role_id: u64,
+    /// When to take the role away again.
+    pub until: DateTime<Utc>,
+}
+
+/// A user banned from requesting tokens, by a moderator or through the admin API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    /// The banned user.
+    pub user_id: u64,
+    /// Why they were banned, if the moderator said.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// When the ban expires, or `None` if it lasts until lifted.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// The moderator who issued the ban, or `None` if it was issued through the admin API.
+    #[serde(default)]
+    pub moderator_id: Option<u64>,
+    /// When the ban was issued.
+    pub time: DateTime<Utc>,
+}
+
+impl Ban {
+    /// Whether the ban is still in effect at the given time.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.map_or(true, |until| now < until)
+    }
+}
+
+/// Exclusive ownership of a store by one running instance of the bot, released when dropped.
+///
+/// The lock is a series of generations, each a file created atomically by the instance which holds
+/// it: taking over a stale lock means creating the next generation, which only one instance can do,
+/// and no instance ever writes to or removes a generation which isn't its own. Whoever holds the
+/// newest generation holds the lock.
+#[derive(Debug)]
+pub struct InstanceLock {
+    dir: PathBuf,
+    /// The generation of the lock we hold.
+    generation: u64,
+    /// How long the lock lasts without being renewed, if it has to be renewed at all.
+    lease: Option<Duration>,
+    /// Until when we can be sure no other instance has taken the lock over.
+    held: Lease,
+}
+
+/// Until when an instance can be sure it still holds the lock on its store, shared with the
+/// [`Store`] so that nothing is dispensed or written once it might not.
+#[derive(Debug, Clone, Default)]
+pub struct Lease {
+    /// The current term of the lease, if it runs out at all.
+    term: Arc<Mutex<Option<Term>>>,
+}
+
+/// One renewal of a [`Lease`].
+#[derive(Debug, Clone, Copy)]
+struct Term {
+    /// When the lease is overdue for renewal, after which what's left of it is only for handing
+    /// off.
+    overdue: Instant,
+    /// When the lease runs out.
+    expires: Instant,
+}
+
+impl Lease {
+    /// Returns `true` if the lease hasn't run out (or been lost).
+    pub fn is_valid(&self) -> bool {
+        self.term
+            .lock()
+            .unwrap()
+            .map_or(true, |term| Instant::now() < term.expires)
+    }
+
+    /// Returns `true` once the lease is overdue for renewal, and might run out soon: there's still
+    /// time to save what's in progress for the next instance, but nothing new should be started.
+    pub fn is_ending(&self) -> bool {
+        self.term
+            .lock()
+            .unwrap()
+            .map_or(false, |term| Instant::now() >= term.overdue)
+    }
+
+    /// Start a new term of the given length, as of when it was renewed.
+    fn extend(&self, renewed: Instant, lease: Duration) {
+        *self.term.lock().unwrap() = Some(Term {
+            // Renewals are due every third of the lease, so this leaves room for one to be late
+            overdue: renewed + lease * 2 / 3,
+            // As other instances see it, from the lock file
+            expires: renewed + Duration::from_secs(lease.as_secs()),
+        });
+    }
+
+    fn revoke(&self) {
+        let now = Instant::now();
+        *self.term.lock().unwrap() = Some(Term {
+            overdue: now,
+            expires: now,
+        });
+    }
+}
+
+/// Who holds an instance lock, as written to the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub host: String,
+    /// When the holder last renewed its lease on the lock, if it holds it on a lease.
+    #[serde(default)]
+    pub renewed: Option<DateTime<Utc>>,
+    /// How long after the last renewal the lease runs out, in seconds.
+    #[serde(default)]
+    pub lease_secs: Option<u64>,
+}
+
+impl LockHolder {
+    fn current(lease: Option<Duration>) -> Self {
+        LockHolder {
+            pid: std::process::id(),
+            host: hostname(),
+            renewed: lease.map(|_| Utc::now()),
+            lease_secs: lease.map(|lease| lease.as_secs()),
+        }
+    }
+
+    /// Returns `true` if the holder is this process.
+    fn is_current(&self) -> bool {
+        self.pid == std::process::id() && self.host == hostname()
+    }
+
+    /// Returns `true` if the holder is known to have exited without releasing the lock, or let its
+    /// lease run out.
+    pub fn is_stale(&self) -> bool {
+        if let (Some(renewed), Some(lease_secs)) = (self.renewed, self.lease_secs) {
+            if Utc::now() - renewed > chrono::Duration::seconds(lease_secs as i64) {
+                return true;
+            }
+        }
+        // We can only tell whether a process is alive if it's on the same host as us
+        self.host == hostname() && !Path::new(&format!("/proc/{}", self.pid)).exists()
+    }
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .unwrap_or_default()
+}
+
+impl InstanceLock {
+    /// The file holding the given generation of the lock: the first generation keeps the name
+    /// older versions of the bot use, so that they see it too.
+    fn path(dir: &Path, generation: u64) -> PathBuf {
+        match generation {
+            0 => dir.join("instance.lock"),
+            generation => dir.join(format!("instance.lock.{generation}")),
+        }
+    }
+
+    /// The generations of the lock present in the given directory.
+    fn generations(dir: &Path) -> anyhow::Result<Vec<u64>> {
+        let mut generations = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            match name.to_string_lossy().strip_prefix("instance.lock") {
+                Some("") => generations.push(0),
+                Some(rest) => {
+                    if let Some(Ok(generation)) = rest.strip_prefix('.').map(str::parse) {
+                        generations.push(generation);
+                    }
+                }
+                None => {}
+            }
+        }
+        Ok(generations)
+    }
+
+    /// The newest generation of the lock in the given directory, if any.
+    fn newest(dir: &Path) -> anyhow::Result<Option<u64>> {
+        Ok(Self::generations(dir)?.into_iter().max())
+    }
+
+    fn read(dir: &Path, generation: u64) -> Option<LockHolder> {
+        std::fs::read(Self::path(dir, generation))
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+    }
+
+    /// Who holds the lock on the store in the given directory, if anyone.
+    pub fn holder(dir: &Path) -> Option<LockHolder> {
+        let generation = Self::newest(dir).ok()??;
+        Self::read(dir, generation)
+    }
+
+    /// Try to take exclusive ownership of the store in the given directory for this instance,
+    /// returning `None` if another live instance holds it (or beat us to taking it over). With a
+    /// lease, the lock must be renewed (see [`InstanceLock::renew`]), or other instances will
+    /// consider it stale once the lease runs out, even from other hosts.
+    pub fn try_acquire(dir: &Path, lease: Option<Duration>) -> anyhow::Result<Option<Self>> {
+        let acquired = Instant::now();
+        let generation = match Self::newest(dir)? {
+            None => 0,
+            Some(newest) => match Self::read(dir, newest) {
+                Some(holder) if holder.is_stale() => {
+                    tracing::warn!(?holder, "taking over stale instance lock");
+                    newest + 1
+                }
+                Some(holder) => {
+                    tracing::debug!(?holder, "store is locked by another instance");
+                    return Ok(None);
+                }
+                // The holder might still be writing the file
+                None => return Ok(None),
+            },
+        };
+
+        // Only one instance can create each generation, so if two try to take over at once, one
+        // of them loses here
+        let path = Self::path(dir, generation);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                serde_json::to_writer(&mut file, &LockHolder::current(lease))?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                tracing::debug!("another instance took over the store first");
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("could not create {}", path.display()))
+            }
+        }
+
+        // Clear away the generations we superseded: whoever held them has lost the lock, and will
+        // notice when they next try to renew it
+        for older in Self::generations(dir)? {
+            if older < generation {
+                let _ = std::fs::remove_file(Self::path(dir, older));
+            }
+        }
+
+        let held = Lease::default();
+        if let Some(lease) = lease {
+            held.extend(acquired, lease);
+        }
+        Ok(Some(InstanceLock {
+            dir: dir.to_path_buf(),
+            generation,
+            lease,
+            held,
+        }))
+    }
+
+    /// Take exclusive ownership of the store in the given directory for this instance, optionally
+    /// waiting until any other instance releases it (or lets its lease run out).
+    pub async fn acquire(dir: &Path, wait: bool, lease: Option<Duration>) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir).context("can create store directory")?;
+        loop {
+            if let Some(lock) = Self::try_acquire(dir, lease)? {
+                return Ok(lock);
+            }
+            if !wait {
+                anyhow::bail!(
+                    "the store in {} is in use by another instance (hand it off with SIGUSR1, or pass --wait-for-lock)",
+                    dir.display()
+                );
+            }
+            tracing::info!("waiting for another instance to release the store");
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+    }
+
+    /// The lease on the lock, to check before doing anything only the holder may do.
+    pub fn lease(&self) -> Lease {
+        self.held.clone()
+    }
+
+    /// Keep renewing the lease on the lock, returning only if the lock was lost to another instance
+    /// (or can't be renewed in time), after which this instance must stop using the store. Without
+    /// a lease, never returns.
+    pub async fn renew(&self) -> anyhow::Result<()> {
+        let lease = match self.lease {
+            Some(lease) => lease,
+            None => return futures::future::pending().await,
+        };
+        let result = self.keep_renewing(lease).await;
+        self.held.revoke();
+        result
+    }
+
+    async fn keep_renewing(&self, lease: Duration) -> anyhow::Result<()> {
+        let path = Self::path(&self.dir, self.generation);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        loop {
+            tokio::time::sleep(lease / 3).await;
+            // If we were held up for so long that others may already consider the lock stale,
+            // renewing it now could clobber an instance which took it over
+            if !self.held.is_valid() {
+                anyhow::bail!("the lease on the store ran out before it could be renewed");
+            }
+            let renewing = Instant::now();
+            self.check()?;
+            std::fs::write(&tmp, serde_json::to_vec(&LockHolder::current(self.lease))?)?;
+            std::fs::rename(&tmp, &path)?;
+            // Another instance may have taken over while we were writing
+            self.check()?;
+            self.held.extend(renewing, lease);
+            tracing::trace!("renewed lease on instance lock");
+        }
+    }
+
+    /// Fail unless our generation of the lock is still the newest, and still ours.
+    fn check(&self) -> anyhow::Result<()> {
+        match Self::newest(&self.dir)? {
+            Some(newest) if newest == self.generation => {}
+            _ => anyhow::bail!(
+                "lost the lock on the store to {:?}",
+                Self::holder(&self.dir)
+            ),
+        }
+        match Self::read(&self.dir, self.generation) {
+            Some(holder) if holder.is_current() => Ok(()),
+            holder => anyhow::bail!("lost the lock on the store to {:?}", holder),
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Leave the lock alone if another instance might have taken it over after our lease ran
+        // out: it's no longer ours to release
+        if !self.held.is_valid() || self.check().is_err() {
+            tracing::warn!("instance lock is no longer ours, leaving it be");
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(Self::path(&self.dir, self.generation)) {
+            tracing::error!(error = ?e, "failed to release instance lock");
+        } else {
+            tracing::info!("released instance lock");
+        }
+    }
+}
+
+/// How many changes to append to the journal before folding it into the state file, so that it
+/// doesn't grow without bound when nothing else changes.
+const JOURNAL_LIMIT: usize = 1000;
+
+/// How many days to remember that a message was processed: catching up further back than this could
+/// process a message twice.
+const PROCESSED_RETENTION_DAYS: i64 = 30;
+
+/// A record of tokens dispensed by the faucet, as written to the dispense ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dispense {
+    /// When the transaction was confirmed.
+    pub time: DateTime<Utc>,
+    /// The hash of the transaction.
+    pub tx_id: String,
+    /// The address to which tokens were sent.
+    pub address: String,
+    /// The values which were sent.
+    pub values: Vec<String>,
+    /// The user who requested the tokens (absent for transactions not made on behalf of a user,
+    /// such as self-tests).
+    pub user_id: Option<u64>,
+    /// The channel in which the request was made.
+    pub channel_id: Option<u64>,
+    /// The message containing the request.
+    pub message_id: Option<u64>,
+}
+
+impl Dispense {
+    /// A record of a dispense confirmed just now.
+    pub fn new(
+        origin: Option<Origin>,
+        address: &impl fmt::Display,
+        tx_id: &Id,
+        values: &[Value],
+    ) -> Self {
+        let cache = asset::Cache::with_known_assets();
+        Dispense {
+            time: Utc::now(),
+            tx_id: tx_id.to_string(),
+            address: address.to_string(),
+            values: values.iter().map(|value| value.format(&cache)).collect(),
+            user_id: origin.map(|origin| origin.user_id.0),
+            channel_id: origin.map(|origin| origin.channel_id.0),
+            message_id: origin.map(|origin| origin.message_id.0),
+        }
+    }
+
+    /// Returns `true` if this was a transfer the faucet made for itself, such as a sweep or a
+    /// self-test, rather than tokens given out on request.
+    pub fn is_internal(&self) -> bool {
+        self.user_id.is_none()
+    }
+}
+
+/// A record of tokens donated to the faucet, as written to the donation ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Donation {
+    /// When the donation was noticed.
+    pub time: DateTime<Utc>,
+    /// The commitment to the note donated, which identifies it.
+    pub note_commitment: String,
+    /// The value donated.
+    pub value: String,
+    /// The block height at which the note was created.
+    pub height: u64,
+}
+
+/// A record of a failure to dispense to an address, as written to the failure ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    /// When the failure happened.
+    pub time: DateTime<Utc>,
+    /// The address which couldn't be sent tokens.
+    pub address: String,
+    /// Why not.
+    pub error: String,
+    /// The user who requested the tokens.
+    pub user_id: Option<u64>,
+    /// The channel in which the request was made.
+    pub channel_id: Option<u64>,
+    /// The message containing the request.
+    pub message_id: Option<u64>,
+}
+
+impl Failure {
+    /// A record of a failure just now.
+    pub fn new(origin: Option<Origin>, address: &impl fmt::Display, error: &str) -> Self {
+        Failure {
+            time: Utc::now(),
+            address: address.to_string(),
+            error: error.to_string(),
+            user_id: origin.map(|origin| origin.user_id.0),
+            channel_id: origin.map(|origin| origin.channel_id.0),
+            message_id: origin.map(|origin| origin.message_id.0),
+        }
+    }
+
+    /// The origin of the request which failed, if it was recorded.
+    pub fn origin(&self) -> Option<Origin> {
+        Some(Origin {
+            user_id: UserId(self.user_id?),
+            channel_id: ChannelId(self.channel_id?),
+            message_id: MessageId(self.message_id?),
+        })
+    }
+
+    /// What kind of failure this was, for counting alike failures together: the outermost part of
+    /// the error, without the details after it.
+    pub fn category(&self) -> String {
+        let category = self.error.split(':').next().unwrap_or_default().trim();
+        // Budget errors say when to try again, which differs every time
+        match category.split_once(';') {
+            Some((category, _)) => category.trim().to_string(),
+            None => category.to_string(),
+        }
+    }
+}
+
+impl Store {
+    /// Load the store from the given directory, creating it if it doesn't exist yet.
+    pub fn load(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).context("can create store directory")?;
+
+        let path = dir.join("state.json");
+        let mut state = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("could not parse state file {}", path.display()))?
+        } else {
+            State::default()
+        };
+        Self::replay(&dir.join("journal.jsonl"), &mut state)?;
+
+        let dispenses = Self::load_dispenses(&dir)?;
+        let donations = Self::load_ledger(&dir.join("donations.jsonl"))?;
+        let failures = Self::load_ledger(&dir.join("failures.jsonl"))?;
+
+        Ok(Store {
+            dir,
+            state: Arc::new(Mutex::new(state)),
+            dispenses: Arc::new(Mutex::new(dispenses)),
+            donations: Arc::new(Mutex::new(donations)),
+            failures: Arc::new(Mutex::new(failures)),
+            catching_up: Arc::new(Mutex::new(BTreeMap::new())),
+            delivering: Arc::new(Mutex::new(BTreeMap::new())),
+            lease: Lease::default(),
+        })
+    }
+
+    /// Hold the store under the lease on an [`InstanceLock`], refusing to change the state once it
+    /// runs out.
+    pub fn with_lease(mut self, lease: Lease) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Returns `true` unless the lease under which we hold the store ran out, in which case another
+    /// instance may have taken it over, and nothing more must be dispensed.
+    pub fn holds_lease(&self) -> bool {
+        self.lease.is_valid()
+    }
+
+    /// Returns `true` once the lease under which we hold the store is overdue for renewal, so that
+    /// nothing new should be dispensed, but there's still time to hand off what's in progress.
+    pub fn lease_ending(&self) -> bool {
+        self.lease.is_ending()
+    }
+
+    /// Read the dispense ledger in the given store directory, without otherwise loading the store.
+    pub fn load_dispenses(dir: &Path) -> anyhow::Result<Vec<Dispense>> {
+        Self::load_ledger(&dir.join("dispenses.jsonl"))
+    }
+
+    /// Read a ledger of JSON records, one per line.
+    fn load_ledger<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<Vec<T>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("could not parse ledger entry: {}", line))
+            })
+            .collect()
+    }
+
+    /// Replay the changes appended to the journal since the state was last written. If a crash cut
+    /// the last one short, it's left out, and the journal is folded into the state at the next
+    /// change so that nothing is appended after it.
+    fn replay(path: &Path, state: &mut State) -> anyhow::Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+        let journal = std::fs::read_to_string(path)?;
+        let mut lines = journal
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .peekable();
+        while let Some(line) = lines.next() {
+            match serde_json::from_str::<Change>(line) {
+                Ok(change) => {
+                    change.apply(state);
+                    state.journaled += 1;
+                }
+                Err(e) if lines.peek().is_none() => {
+                    tracing::warn!(error = ?e, line, "ignoring incomplete last journal entry");
+                    state.journaled = JOURNAL_LIMIT;
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("could not parse journal entry: {}", line))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Modify the state and write it back to disk.
+    fn update<T>(&self, f: impl FnOnce(&mut State) -> T) -> anyhow::Result<T> {
+        if !self.holds_lease() {
+            anyhow::bail!("the lease on the store ran out, so another instance may own it now");
+        }
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state);
+        self.save(&mut state)?;
+        Ok(result)
+    }
+
+    /// Make a change made for every message, appending it to the journal (or, once the journal is
+    /// long enough, folding it into the state), and returning `false` if it made no difference.
+    fn record(&self, change: Change) -> anyhow::Result<bool> {
+        if !self.holds_lease() {
+            anyhow::bail!("the lease on the store ran out, so another instance may own it now");
+        }
+        let mut state = self.state.lock().unwrap();
+        if !change.apply(&mut state) {
+            return Ok(false);
+        }
+        if state.journaled >= JOURNAL_LIMIT {
+            self.save(&mut state)?;
+        } else {
+            self.append("journal.jsonl", &change)?;
+            state.journaled += 1;
+        }
+        Ok(true)
+    }
+
+    /// Write the whole state to disk, after which the journal can be emptied.
+    fn save(&self, state: &mut State) -> anyhow::Result<()> {
+        // Write to a temporary file and then move it into place, so that crashing halfway through
+        // a write can't corrupt the state
+        let path = self.dir.join("state.json");
+        let tmp = self.dir.join("state.json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&*state)?)?;
+        std::fs::rename(&tmp, &path)?;
+
+        // Crashing before this is harmless, since replaying the journal changes nothing
+        if state.journaled > 0 {
+            std::fs::write(self.dir.join("journal.jsonl"), "")?;
+            state.journaled = 0;
+        }
+        Ok(())
+    }
+
+    /// The last message handled in each channel.
+    pub fn checkpoints(&self) -> Vec<(ChannelId, MessageId)> {
+        self.state
+            .lock()
+            .unwrap()
+            .checkpoints
+            .iter()
+            .map(|(&channel_id, &message_id)| (ChannelId(channel_id), MessageId(message_id)))
+            .collect()
+    }
+
+    /// Record that a message was handled live, advancing the checkpoint for its channel if the
+    /// message is newer than the last one recorded. While the channel is being caught up on, the
+    /// checkpoint is left where catch-up got to until it finishes (see
+    /// [`Store::finish_catch_up`]), so that a crash in the meantime doesn't skip the rest of the
+    /// backlog.
+    pub fn checkpoint(&self, channel_id: ChannelId, message_id: MessageId) -> anyhow::Result<()> {
+        if let Some(newest) = self.catching_up.lock().unwrap().get_mut(&channel_id.0) {
+            *newest = (*newest).max(Some(message_id.0));
+            return Ok(());
+        }
+        self.advance_checkpoint(channel_id, message_id)
+    }
+
+    /// Record that catching up on a channel got as far as the message.
+    pub fn catch_up_checkpoint(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> anyhow::Result<()> {
+        self.advance_checkpoint(channel_id, message_id)
+    }
+
+    /// Hold back the checkpoint for a channel from messages handled live, until catching up on it
+    /// finishes.
+    pub fn start_catch_up(&self, channel_id: ChannelId) {
+        self.catching_up
+            .lock()
+            .unwrap()
+            .entry(channel_id.0)
+            .or_default();
+    }
+
+    /// Record that catching up on a channel finished, moving its checkpoint up to the newest message
+    /// handled live in the meantime.
+    pub fn finish_catch_up(&self, channel_id: ChannelId) -> anyhow::Result<()> {
+        let newest = self.catching_up.lock().unwrap().remove(&channel_id.0);
+        match newest.flatten() {
+            Some(message_id) => self.advance_checkpoint(channel_id, MessageId(message_id)),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop holding back the checkpoint for a channel whose catch-up ended without finishing,
+    /// leaving it where catching up got to, so that asking to catch up again resumes from there.
+    pub fn abandon_catch_up(&self, channel_id: ChannelId) {
+        self.catching_up.lock().unwrap().remove(&channel_id.0);
+    }
+
+    /// Move the checkpoint for the channel up to the message, if it's newer than the last one
+    /// recorded.
+    fn advance_checkpoint(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> anyhow::Result<()> {
+        self.record(Change::Checkpoint {
+            channel_id: channel_id.0,
+            message_id: message_id.0,
+        })?;
+        Ok(())
+    }
+
+    /// Every dispense made by the faucet, oldest first.
+    pub fn dispenses(&self) -> Vec<Dispense> {
+        self.dispenses.lock().unwrap().clone()
+    }
+
+    /// The dispenses made since the given time, oldest first, without copying the rest of the
+    /// ledger.
+    pub fn dispenses_since(&self, since: DateTime<Utc>) -> Vec<Dispense> {
+        let dispenses = self.dispenses.lock().unwrap();
+        // The ledger is in the order the dispenses were made, so the window is at its end
+        let start = dispenses
+            .iter()
+            .rposition(|dispense| dispense.time < since)
+            .map_or(0, |older| older + 1);
+        dispenses[start..].to_vec()
+    }
+
+    /// Returns `true` if the user has ever been sent tokens, according to the ledger.
+    pub fn has_received(&self, user_id: UserId) -> bool {
+        self.dispenses
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|dispense| dispense.user_id == Some(user_id.0))
+    }
+
+    /// Append a dispense to the ledger.
+    pub fn record_dispense(&self, dispense: Dispense) -> anyhow::Result<()> {
+        let mut dispenses = self.dispenses.lock().unwrap();
+
+        self.append("dispenses.jsonl", &dispense)?;
+        dispenses.push(dispense);
+        Ok(())
+    }
+
+    /// Every failure to dispense, oldest first.
+    pub fn failures(&self) -> Vec<Failure> {
+        self.failures.lock().unwrap().clone()
+    }
+
+    /// The failures since the given time which haven't been made up for since, by sending tokens
+    /// to the same address: the latest for each address, oldest first.
+    pub fn dead_letters(&self, since: DateTime<Utc>) -> Vec<Failure> {
+        let dispenses = self.dispenses.lock().unwrap();
+        let mut latest = BTreeMap::<String, Failure>::new();
+        for failure in self.failures.lock().unwrap().iter() {
+            if failure.time >= since {
+                latest.insert(failure.address.clone(), failure.clone());
+            }
+        }
+        let mut dead = latest
+            .into_values()
+            .filter(|failure| {
+                !dispenses.iter().any(|dispense| {
+                    dispense.address == failure.address && dispense.time > failure.time
+                })
+            })
+            .collect::<Vec<_>>();
+        dead.sort_by_key(|failure| failure.time);
+        dead
+    }
+
+    /// Append a failure to dispense to the failure ledger.
+    pub fn record_failure(&self, failure: Failure) -> anyhow::Result<()> {
+        let mut failures = self.failures.lock().unwrap();
+        self.append("failures.jsonl", &failure)?;
+        failures.push(failure);
+        Ok(())
+    }
+
+    /// Every donation received by the faucet, oldest first.
+    pub fn donations(&self) -> Vec<Donation> {
+        self.donations.lock().unwrap().clone()
+    }
+
+    /// Append a donation to the donation ledger, unless it's already there, returning whether it
+    /// was new.
+    pub fn record_donation(&self, donation: Donation) -> anyhow::Result<bool> {
+        let mut donations = self.donations.lock().unwrap();
+        if donations
+            .iter()
+            .any(|known| known.note_commitment == donation.note_commitment)
+        {
+            return Ok(false);
+        }
+        self.append("donations.jsonl", &donation)?;
+        donations.push(donation);
+        Ok(true)
+    }
+
+    /// Append a record to the ledger file with the given name.
+    fn append(&self, name: &str, record: &impl Serialize) -> anyhow::Result<()> {
+        let mut ledger = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(name))?;
+        writeln!(ledger, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// Returns `true` if the message has already been claimed for processing.
+    pub fn is_processed(&self, message_id: MessageId) -> bool {
+        self.state.lock().unwrap().processed.contains(&message_id.0)
+    }
+
+    /// Claim a message for processing, returning `false` if it was already claimed (in which case
+    /// it must not be processed again).
+    pub fn claim(&self, message_id: MessageId) -> anyhow::Result<bool> {
+        self.record(Change::Claim {
+            message_id: message_id.0,
+        })
+    }
+
+    /// Release the claim on a message which could not be processed, so that it can be tried again.
+    pub fn unclaim(&self, message_id: MessageId) -> anyhow::Result<()> {
+        self.record(Change::Unclaim {
+            message_id: message_id.0,
+        })?;
+        Ok(())
+    }
+
+    /// The chain the faucet was last running on, if known.
+    pub fn chain_id(&self) -> Option<String> {
+        self.state.lock().unwrap().chain_id.clone()
+    }
+
+    /// The number of requests left unprocessed by a previous instance.
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Record the chain the faucet is running on, returning the one it was previously running on.
+    pub fn set_chain_id(&self, chain_id: String) -> anyhow::Result<Option<String>> {
+        self.update(|state| state.chain_id.replace(chain_id))
+    }
+
+    /// Save requests which this instance won't process, for the next instance to pick up,
+    /// replacing any already saved for the same messages.
+    pub fn save_pending(&self, pending: Vec<Pending>) -> anyhow::Result<()> {
+        self.update(|state| {
+            for request in pending {
+                state
+                    .pending
+                    .retain(|existing| existing.message_id != request.message_id);
+                state.pending.push(request);
+            }
+        })
+    }
+
+    /// The requests left unprocessed by a previous instance, which stay saved until they're
+    /// answered (see [`Store::answer_pending`]).
+    pub fn pending_requests(&self) -> Vec<Pending> {
+        self.state.lock().unwrap().pending.clone()
+    }
+
+    /// Replace a request left unprocessed by a previous instance with the reply to it, in the
+    /// outbox until it's delivered (see [`Store::remove_undelivered`]), so that it's neither
+    /// dispensed to again nor left unanswered if we stop in between.
+    pub fn answer_pending(&self, reply: Undelivered) -> anyhow::Result<()> {
+        self.update(|state| {
+            state
+                .pending
+                .retain(|request| request.message_id != reply.message_id);
+            state.outbox.push(reply);
+        })
+    }
+
+    /// Note that a reply is being delivered, so that it's saved to the outbox (see
+    /// [`Store::save_outbox`]) if we stop before it's done.
+    pub fn delivering(&self, reply: Undelivered) {
+        self.delivering
+            .lock()
+            .unwrap()
+            .insert(reply.message_id, reply);
+    }
+
+    /// Note that a reply noted with [`Store::delivering`] was delivered.
+    pub fn delivered(&self, message_id: MessageId) {
+        self.delivering.lock().unwrap().remove(&message_id.0);
+    }
+
+    /// Save the replies still being delivered to the outbox, for the next instance to deliver,
+    /// returning how many there were.
+    pub fn save_outbox(&self) -> anyhow::Result<usize> {
+        let delivering = std::mem::take(&mut *self.delivering.lock().unwrap());
+        if delivering.is_empty() {
+            return Ok(0);
+        }
+        let count = delivering.len();
+        self.update(|state| {
+            state
+                .outbox
+                .retain(|reply| !delivering.contains_key(&reply.message_id));
+            state.outbox.extend(delivering.into_values());
+        })?;
+        Ok(count)
+    }
+
+    /// The replies left undelivered by a previous instance.
+    pub fn outbox(&self) -> Vec<Undelivered> {
+        self.state.lock().unwrap().outbox.clone()
+    }
+
+    /// Forget a reply in the outbox, once it's been delivered (or can't be).
+    pub fn remove_undelivered(&self, message_id: MessageId) -> anyhow::Result<()> {
+        self.update(|state| {
+            state
+                .outbox
+                .retain(|reply| reply.message_id != message_id.0);
+        })
+    }
+
+    /// The emoji with which to react to messages in a server, or `None` if the server's
+    /// administrators turned that kind of reaction off.
+    pub fn reaction(&self, server_id: ServerId, reaction: Reaction) -> Option<String> {
+        match self
+            .state
+            .lock()
+            .unwrap()
+            .servers
+            .get(&server_id.0)
+            .and_then(|server| server.reactions.get(&reaction))
+        {
+            Some(emoji) => emoji.clone(),
+            None => Some(reaction.default_emoji().to_string()),
+        }
+    }
+
+    /// Choose the emoji with which to react to messages in a server, or turn that kind of reaction
+    /// off with `None`.
+    pub fn set_reaction(
+        &self,
+        server_id: ServerId,
+        reaction: Reaction,
+        emoji: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.update(|state| {
+            state
+                .servers
+                .entry(server_id.0)
+                .or_default()
+                .reactions
+                .insert(reaction, emoji);
+        })
+    }
+
+    /// Go back to reacting to messages in a server with the default emoji.
+    pub fn reset_reaction(&self, server_id: ServerId, reaction: Reaction) -> anyhow::Result<()> {
+        self.update(|state| {
+            if let Some(server) = state.servers.get_mut(&server_id.0) {
+                server.reactions.remove(&reaction);
+            }
+        })
+    }
+
+    /// Remember that a user was given a cooldown role, so that it's taken away again when it
+    /// expires (even if we restart in the meantime).
+    pub fn add_cooldown(&self, cooldown: Cooldown) -> anyhow::Result<()> {
+        self.update(|state| state.cooldowns.push(cooldown))
+    }
+
+    /// The cooldown roles which should have been taken away by now.
+    pub fn expired_cooldowns(&self, now: DateTime<Utc>) -> Vec<Cooldown> {
+        self.state
+            .lock()
+            .unwrap()
+            .cooldowns
+            .iter()
+            .filter(|cooldown| cooldown.until <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget a cooldown role, once it's been taken away.
+    pub fn remove_cooldown(&self, cooldown: &Cooldown) -> anyhow::Result<()> {
+        self.update(|state| state.cooldowns.retain(|existing| existing != cooldown))
+    }
+
+    /// Ban a user from requesting tokens, replacing any ban they already have, and forgetting bans
+    /// which have expired.
+    pub fn ban(&self, ban: Ban) -> anyhow::Result<()> {
+        self.update(|state| {
+            let now = Utc::now();
+            state
+                .bans
+                .retain(|existing| existing.user_id != ban.user_id && existing.is_active(now));
+            state.bans.push(ban);
+        })
+    }
+
+    /// Lift a user's ban, returning `false` if they weren't banned.
+    pub fn unban(&self, user_id: UserId) -> anyhow::Result<bool> {
+        self.update(|state| {
+            let now = Utc::now();
+            let banned = state
+                .bans
+                .iter()
+                .any(|ban| ban.user_id == user_id.0 && ban.is_active(now));
+            state
+                .bans
+                .retain(|ban| ban.user_id != user_id.0 && ban.is_active(now));
+            banned
+        })
+    }
+
+    /// The user's ban, if they're banned at the given time.
+    pub fn ban_of(&self, user_id: UserId, now: DateTime<Utc>) -> Option<Ban> {
+        self.state
+            .lock()
+            .unwrap()
+            .bans
+            .iter()
+            .find(|ban| ban.user_id == user_id.0 && ban.is_active(now))
+            .cloned()
+    }
+
+    /// The bans in effect at the given time.
+    pub fn bans(&self, now: DateTime<Utc>) -> Vec<Ban> {
+        self.state
+            .lock()
+            .unwrap()
+            .bans
+            .iter()
+            .filter(|ban| ban.is_active(now))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory for one test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("galileo-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn holder(pid: u32, host: &str, renewed_ago: Option<i64>, lease_secs: u64) -> LockHolder {
+        LockHolder {
+            pid,
+            host: host.to_string(),
+            renewed: renewed_ago.map(|secs| Utc::now() - chrono::Duration::seconds(secs)),
+            lease_secs: renewed_ago.map(|_| lease_secs),
+        }
+    }
+
+    #[test]
+    fn holder_is_stale_once_its_lease_runs_out() {
+        let pid = std::process::id();
+        assert!(!holder(pid, "elsewhere", Some(10), 30).is_stale());
+        assert!(holder(pid, "elsewhere", Some(31), 30).is_stale());
+    }
+
+    #[test]
+    fn holder_on_another_host_without_a_lease_is_never_stale() {
+        assert!(!holder(u32::MAX, "elsewhere", None, 0).is_stale());
+    }
+
+    #[test]
+    fn holder_on_this_host_is_stale_once_it_exits() {
+        assert!(!holder(std::process::id(), &hostname(), None, 0).is_stale());
+        assert!(holder(u32::MAX, &hostname(), None, 0).is_stale());
+    }
+
+    #[test]
+    fn lock_is_exclusive_until_released() {
+        let dir = scratch("lock-exclusive");
+        let lock = InstanceLock::try_acquire(&dir, None).unwrap().unwrap();
+        assert!(InstanceLock::try_acquire(&dir, None).unwrap().is_none());
+        drop(lock);
+        assert!(InstanceLock::holder(&dir).is_none());
+        assert!(InstanceLock::try_acquire(&dir, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over_by_the_next_generation() {
+        let dir = scratch("lock-takeover");
+        let stale = holder(u32::MAX, &hostname(), None, 0);
+        std::fs::write(
+            dir.join("instance.lock"),
+            serde_json::to_vec(&stale).unwrap(),
+        )
+        .unwrap();
+
+        let lock = InstanceLock::try_acquire(&dir, None).unwrap().unwrap();
+        assert_eq!(lock.generation, 1);
+        assert_eq!(InstanceLock::generations(&dir).unwrap(), vec![1]);
+        assert!(InstanceLock::holder(&dir).unwrap().is_current());
+        // The winner holds the lock, so nobody else can take it over
+        assert!(InstanceLock::try_acquire(&dir, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn superseded_lock_is_not_released() {
+        let dir = scratch("lock-superseded");
+        let lock = InstanceLock::try_acquire(&dir, None).unwrap().unwrap();
+        let newer = holder(u32::MAX - 1, "elsewhere", None, 0);
+        std::fs::write(
+            dir.join("instance.lock.1"),
+            serde_json::to_vec(&newer).unwrap(),
+        )
+        .unwrap();
+
+        assert!(lock.check().is_err());
+        drop(lock);
+        assert_eq!(InstanceLock::holder(&dir).unwrap().pid, u32::MAX - 1);
+    }
+
+    #[test]
+    fn store_refuses_changes_once_the_lease_runs_out() {
+        let dir = scratch("lease");
+        let lease = Lease::default();
+        let store = Store::load(&dir).unwrap().with_lease(lease.clone());
+        assert!(store.claim(MessageId(1)).unwrap());
+
+        lease.revoke();
+        assert!(!store.holds_lease());
+        assert!(store.claim(MessageId(2)).is_err());
+    }
+
+    #[test]
+    fn lease_ends_before_it_runs_out() {
+        let lease = Lease::default();
+        assert!(!lease.is_ending());
+        let lease_length = Duration::from_secs(30);
+        lease.extend(Instant::now() - lease_length * 3 / 4, lease_length);
+        assert!(lease.is_ending());
+        assert!(lease.is_valid());
+    }
+
+    #[test]
+    fn claim_is_taken_once_until_released() {
+        let dir = scratch("claim");
+        let store = Store::load(&dir).unwrap();
+        let message_id = MessageId::at(Utc::now());
+        assert!(store.claim(message_id).unwrap());
+        assert!(!store.claim(message_id).unwrap());
+        assert!(Store::load(&dir).unwrap().is_processed(message_id));
+
+        store.unclaim(message_id).unwrap();
+        assert!(!store.is_processed(message_id));
+        assert!(store.claim(message_id).unwrap());
+    }
+
+    #[test]
+    fn journal_is_folded_into_the_state_once_long_enough() {
+        let dir = scratch("journal");
+        let store = Store::load(&dir).unwrap();
+        let now = MessageId::at(Utc::now()).0;
+        for offset in 0..JOURNAL_LIMIT as u64 {
+            store.claim(MessageId(now + offset)).unwrap();
+        }
+        assert!(!std::fs::read_to_string(dir.join("journal.jsonl"))
+            .unwrap()
+            .is_empty());
+        store.checkpoint(ChannelId(1), MessageId(now)).unwrap();
+        assert!(std::fs::read_to_string(dir.join("journal.jsonl"))
+            .unwrap()
+            .is_empty());
+
+        let loaded = Store::load(&dir).unwrap();
+        assert!(loaded.is_processed(MessageId(now + JOURNAL_LIMIT as u64 - 1)));
+        assert_eq!(loaded.checkpoints(), vec![(ChannelId(1), MessageId(now))]);
+    }
+
+    #[test]
+    fn journal_entry_cut_short_is_ignored() {
+        let dir = scratch("journal-torn");
+        let message_id = MessageId::at(Utc::now());
+        Store::load(&dir).unwrap().claim(message_id).unwrap();
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join("journal.jsonl"))
+            .unwrap();
+        write!(journal, "{{\"change\":\"cla").unwrap();
+
+        let store = Store::load(&dir).unwrap();
+        assert!(store.is_processed(message_id));
+        // The next change folds the journal into the state, rather than appending after the rest
+        store.unclaim(message_id).unwrap();
+        assert!(std::fs::read_to_string(dir.join("journal.jsonl"))
+            .unwrap()
+            .is_empty());
+        assert!(!Store::load(&dir).unwrap().is_processed(message_id));
+    }
+
+    #[test]
+    fn claim_forgets_messages_too_old_to_catch_up_on() {
+        let store = Store::load(scratch("claim-retention")).unwrap();
+        let old = MessageId::at(Utc::now() - chrono::Duration::days(PROCESSED_RETENTION_DAYS + 1));
+        store.claim(old).unwrap();
+        store.claim(MessageId::at(Utc::now())).unwrap();
+        assert!(!store.is_processed(old));
+    }
+
+    #[test]
+    fn checkpoint_only_moves_forward() {
+        let dir = scratch("checkpoint");
+        let store = Store::load(&dir).unwrap();
+        let channel_id = ChannelId(1);
+        store.checkpoint(channel_id, MessageId(5)).unwrap();
+        store.checkpoint(channel_id, MessageId(3)).unwrap();
+        assert_eq!(store.checkpoints(), vec![(channel_id, MessageId(5))]);
+        assert_eq!(
+            Store::load(&dir).unwrap().checkpoints(),
+            vec![(channel_id, MessageId(5))]
+        );
+    }
+
+    #[test]
+    fn checkpoint_waits_for_catch_up_to_finish() {
+        let store = Store::load(scratch("checkpoint-catch-up")).unwrap();
+        let channel_id = ChannelId(1);
+        store.start_catch_up(channel_id);
+        store.checkpoint(channel_id, MessageId(10)).unwrap();
+        assert!(store.checkpoints().is_empty());
+
+        store.catch_up_checkpoint(channel_id, MessageId(4)).unwrap();
+        assert_eq!(store.checkpoints(), vec![(channel_id, MessageId(4))]);
+        store.finish_catch_up(channel_id).unwrap();
+        assert_eq!(store.checkpoints(), vec![(channel_id, MessageId(10))]);
+    }
+
+    #[test]
+    fn dispenses_since_only_copies_the_window() {
+        let store = Store::load(scratch("dispenses-since")).unwrap();
+        let now = Utc::now();
+        for minutes_ago in [90, 30, 10] {
+            store
+                .record_dispense(Dispense {
+                    time: now - chrono::Duration::minutes(minutes_ago),
+                    tx_id: String::new(),
+                    address: String::new(),
+                    values: vec!["1penumbra".to_string()],
+                    user_id: Some(minutes_ago as u64),
+                    channel_id: None,
+                    message_id: None,
+                })
+                .unwrap();
+        }
+        let recent = store.dispenses_since(now - chrono::Duration::hours(1));
+        let users = recent.iter().map(|dispense| dispense.user_id);
+        assert_eq!(users.collect::<Vec<_>>(), vec![Some(30), Some(10)]);
+        assert!(store.dispenses_since(now).is_empty());
+        assert_eq!(
+            store.dispenses_since(now - chrono::Duration::days(1)).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn abandoned_catch_up_stops_holding_back_the_checkpoint() {
+        let store = Store::load(scratch("checkpoint-abandon")).unwrap();
+        let channel_id = ChannelId(1);
+        store.start_catch_up(channel_id);
+        store.catch_up_checkpoint(channel_id, MessageId(4)).unwrap();
+        store.checkpoint(channel_id, MessageId(10)).unwrap();
+        store.abandon_catch_up(channel_id);
+        assert_eq!(store.checkpoints(), vec![(channel_id, MessageId(4))]);
+
+        store.checkpoint(channel_id, MessageId(12)).unwrap();
+        assert_eq!(store.checkpoints(), vec![(channel_id, MessageId(12))]);
+    }
+}
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+
+use crate::responder::Response;
+
+mod webhook;
+pub use webhook::Webhook;
+
+mod email;
+pub use email::{Email, Smtp};
+
+/// A way of delivering the result of a request to whoever should hear about it.
+///
+/// The responder only produces [`Response`]s; everything about how they reach people lives behind
+/// this trait, so new transports don't touch the responder.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// A short name for the transport, for logging.
+    fn name(&self) -> &'static str;
+
+    /// Deliver the result of a request.
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()>;
+}
+
+/// Deliver the result of a request over each of the given transports in turn, logging rather than
+/// failing if any of them can't deliver it.
+pub async fn deliver_all(transports: &[&dyn Transport], response: &Response) {
+    for transport in transports {
+        if let Err(e) = transport.deliver(response).await {
+            tracing::error!(transport = transport.name(), error = ?e, "failed to deliver result");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+use url::Url;
+
+use super::Transport;
+use crate::responder::Response;
+
+/// Post a summary of every result to a webhook, as JSON with a `content` field (the format used by
+/// Discord and Slack-compatible webhooks).
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl Webhook {
+    pub fn new(url: Url) -> Self {
+        Webhook {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        self.client
+            .post(self.url.clone())
+            .header("Content-Type", "application/json")
+            .body(serde_json::json!({ "content": response.plain_summary() }).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
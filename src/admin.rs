@@ -0,0 +1,267 @@
+use std::{
+    fmt::Write as _,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use penumbra_asset::{asset, Value};
+use penumbra_keys::FullViewingKey;
+use penumbra_view::ViewClient;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    sync::watch,
+    time::Instant,
+};
+
+use crate::{
+    intake::{self, RateLimit},
+    metrics,
+    store::Dispense,
+    Lifecycle, Store,
+};
+
+mod api;
+pub use api::AdminApi;
+
+/// The name of the admin socket, in the directory where the bot keeps its state.
+pub const SOCKET_FILE: &str = "admin.sock";
+
+/// How long to wait for the view to say how far it's synchronized before answering without it.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A snapshot of how a running instance is doing, as reported over its admin socket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    /// How long the instance has been up, in seconds.
+    pub uptime_secs: u64,
+    /// Whether it's still accepting requests (rather than shutting down or handing off).
+    pub accepting: bool,
+    /// How many requests are queued or being dispensed.
+    pub queue_depth: usize,
+    /// The wallet's balance of each asset, or `None` if it hasn't been checked yet.
+    pub balances: Option<Vec<String>>,
+    /// The per-user rate limit, like `1day`.
+    pub rate_limit: String,
+    /// How many requests were turned away by the rate limit since the instance started.
+    pub rate_limited: u64,
+    /// How many requests were turned away as part of a suspected sybil cluster since then.
+    pub sybil_throttled: u64,
+    /// The most recent dispense in the ledger, if any.
+    pub last_dispense: Option<Dispense>,
+    /// The height the view has synchronized to, or `None` if it couldn't be reached.
+    pub sync_height: Option<u64>,
+    /// Whether the view is still catching up with the chain.
+    pub catching_up: bool,
+}
+
+impl Status {
+    /// Describe the status for a person to read.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        let _ = writeln!(
+            summary,
+            "Up for {}, {}.",
+            humantime::format_duration(Duration::from_secs(self.uptime_secs)),
+            if self.accepting {
+                "accepting requests"
+            } else {
+                "no longer accepting requests"
+            }
+        );
+        let _ = writeln!(summary, "Queue depth: {}", self.queue_depth);
+        match &self.balances {
+            Some(balances) if !balances.is_empty() => {
+                let _ = writeln!(summary, "Balance: {}", balances.join(", "));
+            }
+            Some(_) => summary.push_str("Balance: empty\n"),
+            None => summary.push_str("Balance: not checked yet\n"),
+        }
+        let _ = writeln!(
+            summary,
+            "Rate limit: {} per user; {} requests rate limited and {} throttled as sybils since start",
+            self.rate_limit, self.rate_limited, self.sybil_throttled
+        );
+        match &self.last_dispense {
+            Some(dispense) => {
+                let ago = (Utc::now() - dispense.time).to_std().unwrap_or_default();
+                let _ = writeln!(
+                    summary,
+                    "Last dispense: {} ago, {} to {} in {}",
+                    humantime::format_duration(Duration::from_secs(ago.as_secs())),
+                    dispense.values.join(", "),
+                    dispense.address,
+                    dispense.tx_id
+                );
+            }
+            None => summary.push_str("Last dispense: none yet\n"),
+        }
+        match self.sync_height {
+            Some(height) if self.catching_up => {
+                let _ = writeln!(summary, "Sync height: {} (catching up)", height);
+            }
+            Some(height) => {
+                let _ = writeln!(summary, "Sync height: {}", height);
+            }
+            None => summary.push_str("Sync height: view unreachable\n"),
+        }
+        summary
+    }
+}
+
+/// Takes snapshots of how a running instance is doing, for the admin socket and the admin API.
+pub struct Monitor<V> {
+    /// When the instance started.
+    started: Instant,
+    /// How many requests are in flight, and whether new ones are accepted.
+    lifecycle: Lifecycle,
+    /// Persistent state, whose dispense ledger holds the last dispense.
+    store: Store,
+    /// The latest balance of each asset in the wallet.
+    balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+    /// The view, to ask how far it's synchronized.
+    view: Mutex<V>,
+    /// The wallet's full viewing key.
+    fvk: FullViewingKey,
+    /// The per-user rate limit.
+    rate_limit: RateLimit,
+}
+
+impl<V> Monitor<V>
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    pub fn new(
+        lifecycle: Lifecycle,
+        store: Store,
+        balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+        view: V,
+        fvk: FullViewingKey,
+        rate_limit: RateLimit,
+    ) -> Self {
+        Monitor {
+            started: Instant::now(),
+            lifecycle,
+            store,
+            balances,
+            view: Mutex::new(view),
+            fvk,
+            rate_limit,
+        }
+    }
+
+    /// Take a snapshot of how the instance is doing.
+    pub async fn status(&self) -> Status {
+        let cache = asset::Cache::with_known_assets();
+        let balances = self.balances.borrow().as_ref().map(|balances| {
+            balances
+                .iter()
+                .map(|(asset_id, amount)| {
+                    Value {
+                        amount: (*amount).into(),
+                        asset_id: *asset_id,
+                    }
+                    .format(&cache)
+                })
+                .collect()
+        });
+
+        let filtered = |rule: &str| -> u64 {
+            metrics::counters(intake::FILTERED)
+                .into_iter()
+                .filter(|(labels, _)| {
+                    labels
+                        .iter()
+                        .any(|(name, value)| *name == "rule" && value == rule)
+                })
+                .map(|(_, count)| count)
+                .sum()
+        };
+
+        let mut view = self.view.lock().unwrap().clone();
+        let sync = tokio::time::timeout(
+            SYNC_TIMEOUT,
+            ViewClient::status(&mut view, self.fvk.account_group_id()),
+        )
+        .await;
+        let (sync_height, catching_up) = match sync {
+            Ok(Ok(status)) => (Some(status.sync_height), status.catching_up),
+            Ok(Err(e)) => {
+                tracing::debug!(error = ?e, "failed to get view status");
+                (None, false)
+            }
+            Err(_) => (None, false),
+        };
+
+        Status {
+            uptime_secs: self.started.elapsed().as_secs(),
+            accepting: self.lifecycle.is_accepting(),
+            queue_depth: self.lifecycle.in_flight(),
+            balances,
+            rate_limit: humantime::format_duration(self.rate_limit.get()).to_string(),
+            rate_limited: filtered("rate-limited"),
+            sybil_throttled: filtered("sybil"),
+            last_dispense: self
+                .store
+                .dispenses()
+                .into_iter()
+                .max_by_key(|dispense| dispense.time),
+            sync_height,
+            catching_up,
+        }
+    }
+}
+
+/// Listen on a Unix socket at the given path (replacing any left behind by an instance which didn't
+/// clean up) until it fails, answering every connection with the instance's [`Status`], so
+/// operators can check on it from the host (with `galileo status`) without reading logs. Only the
+/// user running the bot can connect to it.
+pub async fn serve<V>(monitor: Arc<Monitor<V>>, path: PathBuf) -> anyhow::Result<()>
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("could not remove {}", path.display())),
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("could not listen on {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    tracing::info!(path = %path.display(), "admin socket listening");
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let status = monitor.status().await;
+        tokio::spawn(async move {
+            let result = async {
+                let mut json = serde_json::to_vec(&status)?;
+                json.push(b'\n');
+                stream.write_all(&json).await?;
+                stream.shutdown().await?;
+                anyhow::Ok(())
+            };
+            if let Err(e) = result.await {
+                tracing::debug!(error = ?e, "failed to answer on admin socket");
+            }
+        });
+    }
+}
+
+/// Ask the instance listening on the admin socket at the given path how it's doing.
+pub async fn query(path: &Path) -> anyhow::Result<Status> {
+    let mut stream = UnixStream::connect(path).await.with_context(|| {
+        format!(
+            "could not connect to {} (is the bot running, with this data directory?)",
+            path.display()
+        )
+    })?;
+    let mut json = Vec::new();
+    stream.read_to_end(&mut json).await?;
+    serde_json::from_slice(&json).context("could not parse status from admin socket")
+}
@@ -0,0 +1,592 @@
+use std::{collections::HashSet, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{Request as HttpRequest, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response as HttpResponse},
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use num_traits::identities::Zero;
+use penumbra_asset::Value;
+use penumbra_keys::Address;
+use penumbra_view::ViewClient;
+use serde::Deserialize;
+use serde_json::json;
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, MessageId},
+};
+use tokio::sync::{mpsc, oneshot};
+
+use super::Monitor;
+use crate::{
+    audit,
+    handler::Trigger,
+    http, id,
+    intake::RateLimit,
+    responder::{Control, Routes},
+    store::Ban,
+    Catchup, Lifecycle, Pacing, Store,
+};
+
+/// The environment variable holding the token which callers of the admin API must present.
+pub const TOKEN_VAR: &str = "GALILEO_ADMIN_TOKEN";
+
+/// An HTTP API through which operators' tooling can control the faucet while it runs: pausing and
+/// resuming, changing what it sends and how often, banning users, sweeping the wallet, and
+/// catching up on a channel. Every call must present the admin token as
+/// `Authorization: Bearer <token>`.
+pub struct AdminApi<V> {
+    /// The token callers must present.
+    token: String,
+    /// Takes snapshots of how the faucet is doing.
+    monitor: Arc<Monitor<V>>,
+    /// The queue of administrative requests to the responder.
+    control: mpsc::Sender<Control>,
+    /// The default per-user rate limit.
+    rate_limit: RateLimit,
+    /// Persistent state, where bans are kept.
+    store: Store,
+    /// Whether we're still accepting requests, for catch-up workers.
+    lifecycle: Lifecycle,
+    /// The queues of requests to the responders, for catch-up workers.
+    requests: Routes,
+    /// The Discord HTTP client, for catch-up workers to read channel history with.
+    http: Arc<Http>,
+    /// What a message has to do to be treated as a request.
+    trigger: Trigger,
+    /// How many results catch-up workers report per message.
+    catch_up_batch_size: usize,
+    /// How hard catch-up workers may push, and which channels are being caught up on, shared with
+    /// those started at startup.
+    catch_up_pacing: Pacing,
+    /// The channels the faucet was configured to serve, which may be caught up on along with any
+    /// it has handled requests in.
+    channels: HashSet<ChannelId>,
+}
+
+type Reply = (StatusCode, Json<serde_json::Value>);
+
+/// An error reply.
+fn error(status: StatusCode, message: impl Into<String>) -> Reply {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+/// Record an administrative action in the audit trail.
+fn record(action: String) {
+    audit::record("admin", None, None, None, format!("admin API: {}", action));
+}
+
+/// The body of a request to change the values sent.
+#[derive(Debug, Clone, Deserialize)]
+struct ValuesRequest {
+    /// The values to send to each address, written like `10penumbra`.
+    values: Vec<String>,
+}
+
+/// The body of a request to change the rate limit.
+#[derive(Debug, Clone, Deserialize)]
+struct RateLimitRequest {
+    /// The minimum time between sending tokens to a user, written like `1h`.
+    rate_limit: String,
+}
+
+/// The body of a request to ban a user.
+#[derive(Debug, Clone, Deserialize)]
+struct BanRequest {
+    /// Why the user is banned.
+    #[serde(default)]
+    reason: Option<String>,
+    /// How long the ban lasts, written like `7d`, or `None` until it's lifted.
+    #[serde(default, rename = "for")]
+    duration: Option<String>,
+}
+
+/// The body of a request to sweep the wallet.
+#[derive(Debug, Clone, Deserialize)]
+struct SweepRequest {
+    /// The address to send everything in the wallet to.
+    address: String,
+}
+
+/// The body of a request to catch up on a channel.
+#[derive(Debug, Clone, Deserialize)]
+struct CatchUpRequest {
+    /// The channel, as an id or a URL as generated by Discord.
+    channel: String,
+    /// Catch up on requests posted within this long ago, written like `6h`.
+    #[serde(default)]
+    since: Option<String>,
+    /// Catch up on requests from this message on, as an id.
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl<V> AdminApi<V>
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        token: String,
+        monitor: Arc<Monitor<V>>,
+        control: mpsc::Sender<Control>,
+        rate_limit: RateLimit,
+        store: Store,
+        lifecycle: Lifecycle,
+        requests: Routes,
+        http: Arc<Http>,
+        trigger: Trigger,
+        catch_up_batch_size: usize,
+        catch_up_pacing: Pacing,
+        channels: HashSet<ChannelId>,
+    ) -> Self {
+        AdminApi {
+            token,
+            monitor,
+            control,
+            rate_limit,
+            store,
+            lifecycle,
+            requests,
+            http,
+            trigger,
+            catch_up_batch_size,
+            catch_up_pacing,
+            channels,
+        }
+    }
+
+    /// Serve the API on the given address until it fails.
+    pub async fn serve(self, bind: SocketAddr) -> anyhow::Result<()> {
+        let api = Arc::new(self);
+        let app = Router::new()
+            .route("/status", get(status::<V>))
+            .route("/pause", post(pause::<V>))
+            .route("/resume", post(resume::<V>))
+            .route("/values", put(values::<V>))
+            .route("/rate-limit", put(rate_limit::<V>))
+            .route("/bans", get(bans::<V>))
+            .route("/bans/:user_id", put(ban::<V>).delete(unban::<V>))
+            .route("/sweep", post(sweep::<V>))
+            .route("/catch-up", post(catch_up::<V>))
+            .route_layer(middleware::from_fn_with_state(
+                api.clone(),
+                authorize::<V, _>,
+            ))
+            .with_state(api);
+        tracing::info!(%bind, "serving admin API");
+        axum::Server::try_bind(&bind)?
+            .serve(app.into_make_service())
+            .await?;
+        Ok(())
+    }
+
+    /// Send a control message to the responder.
+    async fn control(&self, control: Control) -> Result<(), Reply> {
+        self.control.send(control).await.map_err(|_| {
+            error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "the responder is not running",
+            )
+        })
+    }
+}
+
+/// Parse a period of time written like `6h`.
+fn parse_period(period: &str) -> anyhow::Result<chrono::Duration> {
+    let period = humantime::parse_duration(period.trim())?;
+    Ok(chrono::Duration::from_std(period)?)
+}
+
+/// Parse the values to dispense, of which there must be at least one, all non-zero.
+fn parse_values(values: &[String]) -> Result<Vec<Value>, String> {
+    let values = values
+        .iter()
+        .map(|value| value.parse::<Value>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid value: {}", e))?;
+    if values.is_empty() {
+        Err("at least one value must be provided".to_string())
+    } else if values.iter().any(|value| value.amount.value().is_zero()) {
+        Err("all values must be non-zero".to_string())
+    } else {
+        Ok(values)
+    }
+}
+
+/// Parse a channel written as an id or as a URL as generated by Discord.
+fn parse_channel(channel: &str) -> Option<ChannelId> {
+    channel
+        .trim()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .map(ChannelId)
+}
+
+/// Find the message to catch up from, given exactly one of a period before `now` or a message id.
+fn catch_up_start(
+    since: Option<&str>,
+    message: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<MessageId, String> {
+    match (since, message) {
+        (Some(since), None) => match parse_period(since) {
+            Ok(since) => Ok(MessageId(id::MessageId::at(now - since).0)),
+            Err(e) => Err(format!("invalid period: {}", e)),
+        },
+        (None, Some(message)) => match message.trim().parse() {
+            Ok(message_id) => Ok(MessageId(message_id)),
+            Err(_) => Err("invalid message id".to_string()),
+        },
+        _ => Err("give exactly one of `since` and `message`".to_string()),
+    }
+}
+
+/// Turn away any call which doesn't present the admin token.
+async fn authorize<V, B>(
+    State(api): State<Arc<AdminApi<V>>>,
+    request: HttpRequest<B>,
+    next: Next<B>,
+) -> HttpResponse {
+    if !http::presents_token(request.headers(), &api.token) {
+        return error(StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Handle `GET /status`, with the same snapshot as `galileo status`.
+async fn status<V>(State(api): State<Arc<AdminApi<V>>>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    (StatusCode::OK, Json(json!(api.monitor.status().await)))
+}
+
+/// Handle `POST /pause`, leaving requests waiting in the queue until resumed.
+async fn pause<V>(State(api): State<Arc<AdminApi<V>>>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    record("pause".to_string());
+    match api.control(Control::Pause).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "paused": true }))),
+        Err(reply) => reply,
+    }
+}
+
+/// Handle `POST /resume`, dispensing again after pausing.
+async fn resume<V>(State(api): State<Arc<AdminApi<V>>>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    record("resume".to_string());
+    match api.control(Control::Resume).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "paused": false }))),
+        Err(reply) => reply,
+    }
+}
+
+/// Handle `PUT /values`, changing what's sent to each address from the next request on.
+async fn values<V>(State(api): State<Arc<AdminApi<V>>>, Json(body): Json<ValuesRequest>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let values = match parse_values(&body.values) {
+        Ok(values) => values,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    record(format!("set values to {}", body.values.join(", ")));
+    match api.control(Control::SetValues(values)).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "values": body.values }))),
+        Err(reply) => reply,
+    }
+}
+
+/// Handle `PUT /rate-limit`, changing the default per-user rate limit from the next request on.
+async fn rate_limit<V>(
+    State(api): State<Arc<AdminApi<V>>>,
+    Json(body): Json<RateLimitRequest>,
+) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let rate_limit = match humantime::parse_duration(body.rate_limit.trim()) {
+        Ok(rate_limit) => rate_limit,
+        Err(e) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                format!("invalid rate limit: {}", e),
+            )
+        }
+    };
+    record(format!(
+        "set rate limit to {}",
+        humantime::format_duration(rate_limit)
+    ));
+    api.rate_limit.set(rate_limit);
+    (
+        StatusCode::OK,
+        Json(json!({ "rate_limit": humantime::format_duration(rate_limit).to_string() })),
+    )
+}
+
+/// Describe a ban as JSON, with ids as strings since they don't all fit in a JavaScript number.
+fn ban_json(ban: &Ban) -> serde_json::Value {
+    json!({
+        "user_id": ban.user_id.to_string(),
+        "reason": ban.reason,
+        "until": ban.until,
+        "moderator_id": ban.moderator_id.map(|moderator_id| moderator_id.to_string()),
+        "time": ban.time,
+    })
+}
+
+/// Handle `GET /bans`, listing the bans in effect.
+async fn bans<V>(State(api): State<Arc<AdminApi<V>>>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let bans = api
+        .store
+        .bans(Utc::now())
+        .iter()
+        .map(ban_json)
+        .collect::<Vec<_>>();
+    (StatusCode::OK, Json(json!({ "bans": bans })))
+}
+
+/// Handle `PUT /bans/:user_id`, ignoring the user's requests until the ban expires or is lifted.
+async fn ban<V>(
+    State(api): State<Arc<AdminApi<V>>>,
+    Path(user_id): Path<u64>,
+    Json(body): Json<BanRequest>,
+) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let now = Utc::now();
+    let until = match body.duration.as_deref().map(parse_period).transpose() {
+        Ok(duration) => duration.map(|duration| now + duration),
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid duration: {}", e)),
+    };
+    let ban = Ban {
+        user_id,
+        reason: body.reason.filter(|reason| !reason.trim().is_empty()),
+        until,
+        moderator_id: None,
+        time: now,
+    };
+    record(format!("ban user {}", user_id));
+    match api.store.ban(ban.clone()) {
+        Ok(()) => (StatusCode::OK, Json(ban_json(&ban))),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+    }
+}
+
+/// Handle `DELETE /bans/:user_id`, lifting the user's ban.
+async fn unban<V>(State(api): State<Arc<AdminApi<V>>>, Path(user_id): Path<u64>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    record(format!("unban user {}", user_id));
+    match api.store.unban(id::UserId(user_id)) {
+        Ok(true) => (StatusCode::OK, Json(json!({ "banned": false }))),
+        Ok(false) => error(StatusCode::NOT_FOUND, "the user isn't banned"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+    }
+}
+
+/// Handle `POST /sweep`, sending everything in the wallet to an address once the transactions in
+/// flight are confirmed, and replying with the hash of the sweep transaction.
+async fn sweep<V>(State(api): State<Arc<AdminApi<V>>>, Json(body): Json<SweepRequest>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let address = match body.address.trim().parse::<Address>() {
+        Ok(address) => address,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "invalid address"),
+    };
+    record(format!("sweep wallet to {}", body.address.trim()));
+    let (result, swept) = oneshot::channel();
+    if let Err(reply) = api.control(Control::Sweep { address, result }).await {
+        return reply;
+    }
+    match swept.await {
+        Ok(Ok(Some(tx_id))) => (
+            StatusCode::OK,
+            Json(json!({ "transaction": tx_id.to_string() })),
+        ),
+        Ok(Ok(None)) => (StatusCode::OK, Json(json!({ "transaction": null }))),
+        Ok(Err(e)) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to sweep: {:#}", e),
+        ),
+        Err(_) => error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the responder stopped before sweeping",
+        ),
+    }
+}
+
+/// Handle `POST /catch-up`, catching up on the requests in a channel since a message (including
+/// it) or a time, in the background. Only channels the faucet serves may be caught up on, and only
+/// once at a time.
+async fn catch_up<V>(State(api): State<Arc<AdminApi<V>>>, Json(body): Json<CatchUpRequest>) -> Reply
+where
+    V: ViewClient + Clone + Send + 'static,
+{
+    let channel_id = match parse_channel(&body.channel) {
+        Some(channel_id) => channel_id,
+        None => return error(StatusCode::BAD_REQUEST, "invalid channel"),
+    };
+    let served = api.channels.contains(&channel_id)
+        || api
+            .store
+            .checkpoints()
+            .iter()
+            .any(|(served, _)| served.0 == channel_id.0);
+    if !served {
+        return error(
+            StatusCode::FORBIDDEN,
+            "the faucet does not serve that channel",
+        );
+    }
+    let message_id =
+        match catch_up_start(body.since.as_deref(), body.message.as_deref(), Utc::now()) {
+            Ok(message_id) => message_id,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        };
+    if !api.lifecycle.is_accepting() {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the faucet is shutting down",
+        );
+    }
+    let claim = match api.catch_up_pacing.claim(channel_id) {
+        Some(claim) => claim,
+        None => {
+            return error(
+                StatusCode::CONFLICT,
+                "that channel is already being caught up on",
+            )
+        }
+    };
+
+    record(format!(
+        "catch up on channel {} from message {}",
+        channel_id, message_id
+    ));
+    let catch_up = Catchup::new(
+        channel_id,
+        api.catch_up_batch_size,
+        api.http.clone(),
+        api.requests.queue(id::ChannelId(channel_id.0)).clone(),
+        api.store.clone(),
+        api.lifecycle.clone(),
+        api.trigger.clone(),
+        api.catch_up_pacing.clone(),
+    );
+    tokio::spawn(async move {
+        if let Err(e) = catch_up.run(claim, message_id, true).await {
+            tracing::error!(error = ?e, ?channel_id, "catch-up requested over admin API failed");
+        }
+    });
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "channel": channel_id.0.to_string(),
+            "from": message_id.0.to_string(),
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn values_must_parse() {
+        let values = parse_values(&strings(&["100penumbra", "10gm"])).unwrap();
+        assert_eq!(values.len(), 2);
+        assert!(parse_values(&strings(&["100penumbra", "lots"]))
+            .unwrap_err()
+            .starts_with("invalid value"));
+    }
+
+    #[test]
+    fn values_must_be_given_and_non_zero() {
+        assert_eq!(
+            parse_values(&[]).unwrap_err(),
+            "at least one value must be provided"
+        );
+        assert_eq!(
+            parse_values(&strings(&["100penumbra", "0gm"])).unwrap_err(),
+            "all values must be non-zero"
+        );
+    }
+
+    #[test]
+    fn channel_is_an_id_or_a_url() {
+        let channel = Some(ChannelId(915_710_851_917_439_060));
+        assert_eq!(parse_channel(" 915710851917439060 "), channel);
+        assert_eq!(
+            parse_channel("https://discord.com/channels/824484045370818580/915710851917439060/"),
+            channel
+        );
+        assert_eq!(parse_channel("https://discord.com/channels/"), None);
+        assert_eq!(parse_channel("general"), None);
+        assert_eq!(parse_channel(""), None);
+    }
+
+    #[test]
+    fn period_is_a_human_duration() {
+        assert_eq!(parse_period(" 6h ").unwrap(), chrono::Duration::hours(6));
+        assert_eq!(
+            parse_period("1day 30m").unwrap(),
+            chrono::Duration::minutes(24 * 60 + 30)
+        );
+        assert!(parse_period("6").is_err());
+        assert!(parse_period("soon").is_err());
+    }
+
+    #[test]
+    fn catch_up_starts_at_a_period_or_a_message() {
+        let now = Utc::now();
+        assert_eq!(
+            catch_up_start(Some("6h"), None, now).unwrap(),
+            MessageId(id::MessageId::at(now - chrono::Duration::hours(6)).0)
+        );
+        assert_eq!(
+            catch_up_start(None, Some(" 915710851917439060 "), now).unwrap(),
+            MessageId(915_710_851_917_439_060)
+        );
+        assert!(catch_up_start(Some("whenever"), None, now)
+            .unwrap_err()
+            .starts_with("invalid period"));
+        assert_eq!(
+            catch_up_start(None, Some("latest"), now).unwrap_err(),
+            "invalid message id"
+        );
+    }
+
+    #[test]
+    fn catch_up_needs_exactly_one_start() {
+        let now = Utc::now();
+        let both = catch_up_start(Some("6h"), Some("915710851917439060"), now);
+        assert_eq!(
+            both.unwrap_err(),
+            "give exactly one of `since` and `message`"
+        );
+        assert!(catch_up_start(None, None, now).is_err());
+    }
+}
@@ -1,18 +1,35 @@
-use std::fmt::Write;
-use std::{collections::HashSet, sync::Arc};
+use std::fmt::{self, Display, Write};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use async_stream::try_stream;
-use futures::{Stream, StreamExt};
+use chrono::Utc;
+use futures::{future, FutureExt, Stream, StreamExt, TryStreamExt};
 use serenity::{
     http::Http,
-    model::id::{ChannelId, MessageId, UserId},
+    model::{
+        channel::Message,
+        id::{ChannelId, MessageId, UserId},
+    },
+};
+use tokio::{
+    sync::{mpsc::error::SendError, oneshot, OwnedSemaphorePermit, Semaphore},
+    time::Instant,
 };
-use tokio::sync::mpsc;
 use tracing::instrument;
 
 use crate::{
     gather_history,
-    responder::{Request, Response},
+    handler::Trigger,
+    id,
+    opt::HISTORY_PAGE_SIZE,
+    responder::{AddressOrAlmost, Origin, Request, RequestQueue, Response},
+    rest,
+    store::Pending,
+    Lifecycle, Store,
 };
 
 pub struct Catchup {
@@ -23,7 +40,153 @@ pub struct Catchup {
     /// The Discord http context.
     http: Arc<Http>,
     /// The queue of requests to process.
-    requests: mpsc::Sender<Request>,
+    requests: RequestQueue,
+    /// Persistent state, where we record our progress through the backlog.
+    store: Store,
+    /// Whether we're still accepting requests.
+    lifecycle: Lifecycle,
+    /// What a message has to do to be treated as a request, as when handling messages live.
+    trigger: Trigger,
+    /// How hard catching up may push, shared with the other channels being caught up on.
+    pacing: Pacing,
+}
+
+/// How hard catching up may push, shared by every channel being caught up on, so that a large
+/// backlog neither starves live requests nor runs into Discord's rate limits.
+#[derive(Debug, Clone, Default)]
+pub struct Pacing {
+    /// Limits how many channels are caught up on at once, if set.
+    concurrency: Option<Arc<Semaphore>>,
+    /// Spaces out reading messages from channel history, if set.
+    messages: Option<Pace>,
+    /// Spaces out submitting requests to be dispensed, if set.
+    dispenses: Option<Pace>,
+    /// The channels being caught up on, so that none is caught up on twice at once.
+    running: Arc<Mutex<HashSet<ChannelId>>>,
+}
+
+impl Pacing {
+    pub fn new(
+        concurrency: Option<usize>,
+        messages_per_second: Option<u32>,
+        dispenses_per_minute: Option<u32>,
+    ) -> Self {
+        Pacing {
+            concurrency: concurrency.map(|permits| Arc::new(Semaphore::new(permits))),
+            messages: messages_per_second.map(|count| Pace::new(count, Duration::from_secs(1))),
+            dispenses: dispenses_per_minute.map(|count| Pace::new(count, Duration::from_secs(60))),
+            running: Arc::default(),
+        }
+    }
+
+    /// Claim the channel to catch up on, unless it's being caught up on already.
+    pub fn claim(&self, channel_id: ChannelId) -> Option<Claim> {
+        if !self.running.lock().unwrap().insert(channel_id) {
+            return None;
+        }
+        Some(Claim {
+            channel_id,
+            running: self.running.clone(),
+        })
+    }
+
+    /// Wait for a turn to catch up on a channel, which lasts until the permit is dropped.
+    async fn start(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.concurrency {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Wait until another page of messages may be read from channel history.
+    async fn page(&self) {
+        if let Some(pace) = &self.messages {
+            pace.wait_for(HISTORY_PAGE_SIZE as u32).await;
+        }
+    }
+
+    /// Wait until another request may be submitted to be dispensed.
+    async fn dispense(&self) {
+        if let Some(pace) = &self.dispenses {
+            pace.wait().await;
+        }
+    }
+}
+
+/// The right to catch up on a channel, given up when dropped.
+#[derive(Debug)]
+pub struct Claim {
+    /// The channel being caught up on.
+    channel_id: ChannelId,
+    /// The channels being caught up on, to take this one out of.
+    running: Arc<Mutex<HashSet<ChannelId>>>,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(&self.channel_id);
+    }
+}
+
+/// A channel's checkpoint held back from messages handled live while it's caught up on, let go
+/// when dropped however catching up ended, so that a failure doesn't hold it back for good.
+struct HeldCheckpoint<'a> {
+    store: &'a Store,
+    channel_id: id::ChannelId,
+}
+
+impl<'a> HeldCheckpoint<'a> {
+    fn hold(store: &'a Store, channel_id: id::ChannelId) -> Self {
+        store.start_catch_up(channel_id);
+        HeldCheckpoint { store, channel_id }
+    }
+
+    /// Move the checkpoint up to the newest message handled live in the meantime.
+    fn finish(self) -> anyhow::Result<()> {
+        self.store.finish_catch_up(self.channel_id)
+    }
+}
+
+impl Drop for HeldCheckpoint<'_> {
+    fn drop(&mut self) {
+        // Does nothing once finished
+        self.store.abandon_catch_up(self.channel_id);
+    }
+}
+
+/// Spaces out events evenly, however many tasks share it.
+#[derive(Debug, Clone)]
+struct Pace {
+    /// The time between one event and the next.
+    period: Duration,
+    /// When the next event may happen.
+    next: Arc<Mutex<Instant>>,
+}
+
+impl Pace {
+    /// Allow this many events in each window of time (at least one).
+    fn new(count: u32, window: Duration) -> Self {
+        Pace {
+            period: window / count.max(1),
+            next: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Wait for the next turn.
+    async fn wait(&self) {
+        self.wait_for(1).await;
+    }
+
+    /// Wait for the next turn, taking this many turns at once (say, for a batch of events).
+    async fn wait_for(&self, turns: u32) {
+        let turn = {
+            let mut next = self.next.lock().unwrap();
+            let turn = (*next).max(Instant::now());
+            *next = turn + self.period * turns;
+            turn
+        };
+        tokio::time::sleep_until(turn).await;
+    }
 }
 
 impl Catchup {
@@ -31,19 +194,98 @@ impl Catchup {
         channel_id: ChannelId,
         response_batch_size: usize,
         http: Arc<Http>,
-        requests: mpsc::Sender<Request>,
+        requests: RequestQueue,
+        store: Store,
+        lifecycle: Lifecycle,
+        trigger: Trigger,
+        pacing: Pacing,
     ) -> Self {
         Catchup {
             channel_id,
             response_batch_size,
             http,
             requests,
+            store,
+            lifecycle,
+            trigger,
+            pacing,
         }
     }
 
-    pub async fn run(self, start_message_id: MessageId) -> anyhow::Result<()> {
-        let results = self.gather(start_message_id).await?;
-        self.summarize(results).await
+    /// Catch up on all requests since the given message, including that message itself only if
+    /// `inclusive` is set (it isn't when resuming from a checkpoint, since that message was
+    /// already handled). The channel must have been claimed with [`Pacing::claim`], and is given up
+    /// once done.
+    pub async fn run(
+        self,
+        claim: Claim,
+        start_message_id: MessageId,
+        inclusive: bool,
+    ) -> anyhow::Result<()> {
+        debug_assert_eq!(claim.channel_id, self.channel_id);
+        // Until the whole backlog is done, messages handled live mustn't move the checkpoint past it
+        let held = HeldCheckpoint::hold(&self.store, id::ChannelId(self.channel_id.0));
+        // Wait for a turn if too many channels are being caught up on already
+        let _turn = self.pacing.start().await;
+        let results = self.gather(start_message_id, inclusive).await?;
+        self.summarize(results).await?;
+        // If we stopped partway, the rest of the backlog is left for the next instance
+        if self.lifecycle.is_accepting() {
+            held.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Scan the backlog as [`Catchup::run`] would, but instead of dispensing tokens, report what
+    /// would be dispensed, checking against the dispense ledger for users who were recently sent
+    /// tokens (within the rate limit) and addresses which were already funded.
+    pub async fn report(
+        &self,
+        start_message_id: MessageId,
+        inclusive: bool,
+        rate_limit: Duration,
+    ) -> anyhow::Result<Report> {
+        let history = self.history(start_message_id, inclusive).await?;
+        report(self.channel_id, history, &self.store, rate_limit).await
+    }
+
+    /// The requests in the channel since the given message, in reverse chronological order.
+    async fn history(
+        &self,
+        start: MessageId,
+        inclusive: bool,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<Backlogged>> + Send + 'static> {
+        let trigger = self.trigger.clone();
+        // Only look up who we are if messages have to mention us
+        let self_id = if trigger.requires_mention() {
+            rest::call("current-user", || self.http.get_current_user())
+                .await?
+                .id
+        } else {
+            UserId(0)
+        };
+        let admit = move |message: &Message| trigger.admits(message, self_id);
+        // Pace reading history by the page, since that's when Discord is called
+        let pacing = self.pacing.clone();
+        let pace = move || {
+            let pacing = pacing.clone();
+            async move { pacing.page().await }.boxed()
+        };
+        Ok(gather_history(
+            self.http.clone(),
+            self.channel_id,
+            None,
+            Some(start),
+            admit,
+            pace,
+        )
+        .try_filter_map(move |(_, _, message_id, response, request)| {
+            if !inclusive && message_id == start {
+                tracing::debug!(?message_id, "skipping already-handled checkpoint message");
+                return future::ready(Ok(None));
+            }
+            future::ready(Ok(Some((response, request))))
+        }))
     }
 
     async fn summarize(
@@ -73,16 +315,20 @@ impl Catchup {
             response_batch.push((user_id, response));
             if response_batch.len() >= self.response_batch_size {
                 let notification = notification(&mut response_batch);
-                self.channel_id
-                    .send_message(self.http.as_ref(), |m| m.content(notification))
-                    .await?;
+                rest::call("catch-up-notification", || {
+                    self.channel_id
+                        .send_message(self.http.as_ref(), |m| m.content(&notification))
+                })
+                .await?;
             }
         }
         if !response_batch.is_empty() {
             let notification = notification(&mut response_batch);
-            self.channel_id
-                .send_message(self.http.as_ref(), |m| m.content(notification))
-                .await?;
+            rest::call("catch-up-notification", || {
+                self.channel_id
+                    .send_message(self.http.as_ref(), |m| m.content(&notification))
+            })
+            .await?;
         }
 
         Ok(())
@@ -92,34 +338,294 @@ impl Catchup {
     async fn gather(
         &self,
         start: MessageId,
+        inclusive: bool,
     ) -> anyhow::Result<
         impl Stream<Item = anyhow::Result<(UserId, Response)>> + Send + Unpin + 'static,
     > {
-        let requests = self.requests.clone();
-        let mut users: HashSet<UserId> = HashSet::new();
-        let mut history = gather_history(self.http.clone(), self.channel_id, None, Some(start));
-
         tracing::info!("gathering history to catch up on...");
-        let mut stack = Vec::new();
-        while let Some(result) = history.next().await {
-            let (_, user, _, response, request) = result?;
-            if !users.contains(&user.id) {
-                tracing::debug!(user_name = ?user.name, user_id = ?user.id, "adding request to backlog stack");
-                users.insert(user.id);
-                stack.push((user.id, response, request));
-            } else {
-                tracing::debug!(user_name = ?user.name, user_id = ?user.id, "duplicate request in backlog");
+        let backlog = backlog(self.history(start, inclusive).await?, &self.store).await?;
+        Ok(submit(
+            backlog,
+            self.requests.clone(),
+            self.store.clone(),
+            self.lifecycle.clone(),
+            self.pacing.clone(),
+            true,
+        ))
+    }
+}
+
+/// A request found in the history of a channel, with the receiver for its response.
+pub type Backlogged = (oneshot::Receiver<Response>, Request);
+
+/// Pick out the requests in a channel's history (in reverse chronological order) which should be
+/// honored: those which weren't already processed, and of those only the latest from each user.
+/// They're returned in the order they should be submitted, latest last.
+pub async fn backlog(
+    history: impl Stream<Item = anyhow::Result<Backlogged>>,
+    store: &Store,
+) -> anyhow::Result<Vec<Backlogged>> {
+    futures::pin_mut!(history);
+    let mut users = HashSet::new();
+    let mut stack = Vec::new();
+    while let Some(result) = history.next().await {
+        let (response, request) = result?;
+        let Origin {
+            user_id,
+            message_id,
+            ..
+        } = request.origin();
+        if store.is_processed(message_id) {
+            tracing::debug!(?message_id, "skipping already-processed message");
+            continue;
+        }
+        if users.insert(user_id) {
+            tracing::debug!(?user_id, ?message_id, "adding request to backlog stack");
+            stack.push((response, request));
+        } else {
+            tracing::debug!(?user_id, ?message_id, "duplicate request in backlog");
+        }
+    }
+    // Submit the oldest requests first
+    stack.reverse();
+    Ok(stack)
+}
+
+/// Submit a backlog of requests to be processed one at a time, as fast as the pacing allows,
+/// streaming the responses as they come. Each message is claimed first, in case it was processed
+/// live in the meantime, and if `checkpoint` is set, its channel's checkpoint is moved up to it
+/// once it's done.
+pub fn submit(
+    backlog: Vec<Backlogged>,
+    requests: RequestQueue,
+    store: Store,
+    lifecycle: Lifecycle,
+    pacing: Pacing,
+    checkpoint: bool,
+) -> impl Stream<Item = anyhow::Result<(UserId, Response)>> + Send + Unpin + 'static {
+    Box::pin(try_stream! {
+        tracing::info!("submitting backlog to be processed");
+        let total = backlog.len();
+        for (submitted, (response, request)) in backlog.into_iter().enumerate() {
+            pacing.dispense().await;
+            // Leave the rest of the backlog for the next instance to catch up on
+            if !lifecycle.is_accepting() {
+                tracing::info!(remaining = total - submitted, "shutting down, stopping catch-up");
+                break;
+            }
+            // The live handler may have processed this message since we gathered the backlog
+            let origin = request.origin();
+            if !store.claim(origin.message_id)? {
+                tracing::debug!(message_id = ?origin.message_id, "message processed while catching up");
+                continue;
             }
+            let user_id = UserId(origin.user_id.0);
+            tracing::debug!(?user_id, "requesting tokens for backlog");
+            let in_flight = lifecycle.begin();
+            if let Err(SendError(request)) = requests.send(request).await {
+                // The responder stopped taking requests, so hand this one off with its queue, or
+                // failing that, leave it to be caught up on again
+                let remaining = total - submitted;
+                tracing::info!(remaining, "responder stopped, stopping catch-up");
+                store
+                    .save_pending(vec![Pending::of(&request)])
+                    .or_else(|e| store.unclaim(origin.message_id).and(Err(e)))?;
+                break;
+            }
+            let response = response.await?;
+            if checkpoint {
+                store.catch_up_checkpoint(origin.channel_id, origin.message_id)?;
+            }
+            drop(in_flight);
+            yield (user_id, response);
+        }
+    })
+}
+
+/// Report on what dispensing to the requests in a channel's history (in reverse chronological
+/// order) would do, without dispensing anything.
+pub async fn report(
+    channel_id: ChannelId,
+    history: impl Stream<Item = anyhow::Result<Backlogged>>,
+    store: &Store,
+    rate_limit: Duration,
+) -> anyhow::Result<Report> {
+    let dispenses = store.dispenses();
+    let cutoff = Utc::now() - chrono::Duration::from_std(rate_limit)?;
+    let funded_addresses: HashSet<&str> = dispenses
+        .iter()
+        .map(|dispense| dispense.address.as_str())
+        .collect();
+    let recent_users: HashSet<u64> = dispenses
+        .iter()
+        .filter(|dispense| dispense.time >= cutoff)
+        .filter_map(|dispense| dispense.user_id)
+        .collect();
+
+    let mut report = Report {
+        channel_id,
+        already_processed: 0,
+        requests: 0,
+        duplicates: 0,
+        addresses: 0,
+        unparsed: 0,
+        rate_limited: Vec::new(),
+        already_funded: Vec::new(),
+    };
+    let mut users = HashSet::new();
+    futures::pin_mut!(history);
+    while let Some(result) = history.next().await {
+        let (_, request) = result?;
+        let Origin {
+            user_id,
+            message_id,
+            ..
+        } = request.origin();
+        if store.is_processed(message_id) {
+            report.already_processed += 1;
+            continue;
         }
 
-        Ok(Box::pin(try_stream! {
-            tracing::info!("submitting backlog to be processed");
-            while let Some((user_id, response, request)) = stack.pop() {
-                tracing::debug!(?user_id, "requesting tokens for backlog");
-                requests.send(request).await?;
-                let response = response.await?;
-                yield (user_id, response);
+        report.requests += 1;
+        // Like catch-up itself, only the latest request from each user counts
+        if !users.insert(user_id) {
+            report.duplicates += 1;
+            continue;
+        }
+        if recent_users.contains(&user_id.0) {
+            report.rate_limited.push(UserId(user_id.0));
+        }
+        for address in request.addresses() {
+            match address {
+                AddressOrAlmost::Address(address) => {
+                    let address = address.to_string();
+                    if funded_addresses.contains(address.as_str()) {
+                        report.already_funded.push(address);
+                    }
+                    report.addresses += 1;
+                }
+                AddressOrAlmost::Almost(..) => report.unparsed += 1,
+                // Only matched when withdrawing to other chains, which a backlog isn't scanned for
+                AddressOrAlmost::External(_) => {}
             }
-        }))
+        }
+    }
+
+    Ok(report)
+}
+
+/// A report on the backlog in a channel, describing what catching up would do.
+#[derive(Debug)]
+pub struct Report {
+    /// The channel which was scanned.
+    pub channel_id: ChannelId,
+    /// The number of messages which were skipped because they were already processed.
+    pub already_processed: usize,
+    /// The number of messages which contained addresses and weren't already processed.
+    pub requests: usize,
+    /// The number of those messages which would be skipped, because the same user made a later
+    /// request.
+    pub duplicates: usize,
+    /// The number of valid addresses in the requests which would be honored.
+    pub addresses: usize,
+    /// The number of things which looked like addresses but didn't parse.
+    pub unparsed: usize,
+    /// Users who would be sent tokens, but were already sent some within the rate limit.
+    pub rate_limited: Vec<UserId>,
+    /// Addresses which would be sent tokens, but were already sent some at some point.
+    pub already_funded: Vec<String>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Channel {}:", self.channel_id)?;
+        writeln!(
+            f,
+            "  {} requests already processed (skipped)",
+            self.already_processed
+        )?;
+        writeln!(
+            f,
+            "  {} requests ({} superseded by a later request from the same user)",
+            self.requests, self.duplicates
+        )?;
+        writeln!(
+            f,
+            "  {} addresses to dispense to ({} more look like addresses but are invalid)",
+            self.addresses, self.unparsed
+        )?;
+        writeln!(
+            f,
+            "  {} users already sent tokens within the rate limit",
+            self.rate_limited.len()
+        )?;
+        for user_id in self.rate_limited.iter() {
+            writeln!(f, "    {}", user_id)?;
+        }
+        writeln!(
+            f,
+            "  {} addresses already sent tokens before",
+            self.already_funded.len()
+        )?;
+        for address in self.already_funded.iter() {
+            writeln!(f, "    {}", address)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pace_spaces_out_turns() {
+        let pace = Pace::new(10, Duration::from_millis(200));
+        let start = Instant::now();
+        for _ in 0..5 {
+            pace.wait().await;
+        }
+        // The first turn is right away, and each after it 20ms later
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[tokio::test]
+    async fn pace_is_shared_between_clones() {
+        let pace = Pace::new(10, Duration::from_millis(200));
+        let other = pace.clone();
+        let start = Instant::now();
+        pace.wait().await;
+        other.wait().await;
+        pace.wait().await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn pace_takes_several_turns_at_once() {
+        let pace = Pace::new(10, Duration::from_millis(200));
+        let start = Instant::now();
+        pace.wait_for(5).await;
+        pace.wait().await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn pace_allows_at_least_one_turn_per_window() {
+        let pace = Pace::new(0, Duration::from_millis(50));
+        let start = Instant::now();
+        pace.wait().await;
+        pace.wait().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn a_channel_is_claimed_once_at_a_time() {
+        let pacing = Pacing::default();
+        let other = pacing.clone();
+        let claim = pacing.claim(ChannelId(1)).unwrap();
+        assert!(other.claim(ChannelId(1)).is_none());
+        assert!(other.claim(ChannelId(2)).is_some());
+        drop(claim);
+        assert!(other.claim(ChannelId(1)).is_some());
     }
 }
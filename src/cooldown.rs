@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use serenity::{
+    client::Context,
+    http::Http,
+    model::id::{GuildId, RoleId, UserId},
+};
+
+use crate::{metrics, rest, store, Store};
+
+/// How often to look for cooldown roles to take away.
+pub const INTERVAL: Duration = Duration::from_secs(60);
+
+/// The longest Discord lets a timeout last, in days.
+const MAX_TIMEOUT_DAYS: i64 = 28;
+
+/// The counter of users cooled down, by how.
+pub const COOLDOWNS: &str = "galileo_cooldowns_total";
+
+/// What to do to users who keep posting addresses after we've stopped replying to them about their
+/// rate limit, until they can ask for tokens again.
+#[derive(Debug, Clone)]
+pub enum Cooldown {
+    /// Time them out, so they can't post anything at all.
+    Timeout,
+    /// Give them whichever of these roles belongs to the server (say, one which can't post in the
+    /// faucet's channels), and take it away again afterwards.
+    Role(Vec<RoleId>),
+}
+
+impl Cooldown {
+    /// Cool down a user in a server until the given time.
+    pub async fn apply(
+        &self,
+        ctx: &Context,
+        store: &Store,
+        guild_id: GuildId,
+        user_id: UserId,
+        until: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Cooldown::Timeout => {
+                let latest = Utc::now() + chrono::Duration::days(MAX_TIMEOUT_DAYS);
+                let until = until.min(latest).to_rfc3339();
+                rest::call("cooldown", || {
+                    guild_id.edit_member(&ctx.http, user_id, |member| {
+                        member.disable_communication_until(until.clone())
+                    })
+                })
+                .await
+                .context("failed to time out user")?;
+                metrics::increment(COOLDOWNS, &[("kind", "timeout".to_string())]);
+            }
+            Cooldown::Role(roles) => {
+                let role_id = roles
+                    .iter()
+                    .find(|role_id| ctx.cache.role(guild_id, **role_id).is_some())
+                    .with_context(|| format!("no cooldown role in server {}", guild_id))?;
+                rest::call("cooldown", || {
+                    ctx.http.add_member_role(
+                        guild_id.0,
+                        user_id.0,
+                        role_id.0,
+                        Some("kept requesting tokens while rate limited"),
+                    )
+                })
+                .await
+                .context("failed to give user the cooldown role")?;
+                store.add_cooldown(store::Cooldown {
+                    server_id: guild_id.0,
+                    user_id: user_id.0,
+                    role_id: role_id.0,
+                    until,
+                })?;
+                metrics::increment(COOLDOWNS, &[("kind", "role".to_string())]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Periodically take away the cooldown roles which have expired, including those given before a
+/// restart.
+pub async fn lift_expired(http: Arc<Http>, store: Store, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        for cooldown in store.expired_cooldowns(Utc::now()) {
+            let result = rest::call("cooldown", || {
+                http.remove_member_role(
+                    cooldown.server_id,
+                    cooldown.user_id,
+                    cooldown.role_id,
+                    Some("cooldown expired"),
+                )
+            })
+            .await;
+            match result {
+                Ok(()) => {
+                    tracing::info!(user_id = cooldown.user_id, "lifted cooldown");
+                }
+                // The user left, or the role was deleted: there's nothing left to take away
+                Err(serenity::Error::Http(e))
+                    if e.status_code().map(|status| status.as_u16()) == Some(404) =>
+                {
+                    tracing::debug!(user_id = cooldown.user_id, "cooldown already gone");
+                }
+                // Try again next time
+                Err(e) => {
+                    tracing::warn!(error = ?e, user_id = cooldown.user_id, "failed to lift cooldown");
+                    continue;
+                }
+            }
+            if let Err(e) = store.remove_cooldown(&cooldown) {
+                tracing::error!(error = ?e, "failed to forget lifted cooldown");
+            }
+        }
+    }
+}
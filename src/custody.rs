@@ -0,0 +1,480 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use penumbra_asset::{asset, Value};
+use penumbra_custody::{soft_kms::SoftKms, CustodyClient};
+use penumbra_keys::FullViewingKey;
+use penumbra_proto::custody::v1alpha1::{
+    self as pb,
+    custody_protocol_service_client::CustodyProtocolServiceClient,
+    custody_protocol_service_server::{CustodyProtocolService, CustodyProtocolServiceServer},
+};
+use penumbra_transaction::plan::{ActionPlan, TransactionPlan};
+use subtle::ConstantTimeEq;
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    service::Interceptor,
+    transport::{Channel, ClientTlsConfig},
+    Status,
+};
+use url::{Host, Url};
+
+use crate::Wallet;
+
+/// The environment variable holding the token shared between the bot and a remote signer.
+pub const TOKEN_VAR: &str = "GALILEO_SIGNER_TOKEN";
+
+/// Custody using the spend key in a (possibly encrypted) local custody file, doing gRPC with
+/// ourselves.
+pub fn local(wallet: &Wallet) -> impl CustodyClient + Clone + Send + 'static {
+    let soft_kms = SoftKms::new(wallet.spend_key.clone().into());
+    CustodyProtocolServiceClient::new(CustodyProtocolServiceServer::new(soft_kms))
+}
+
+/// The value of the `authorization` header carrying the shared token.
+fn bearer(token: &str) -> anyhow::Result<MetadataValue<Ascii>> {
+    format!("Bearer {}", token)
+        .parse()
+        .context("signer token must be printable ASCII")
+}
+
+/// Returns `true` if the URL points at this host, where a connection can't be listened in on.
+pub fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip).is_loopback(),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip).is_loopback(),
+        None => false,
+    }
+}
+
+/// Connect to a remote signer (as run by `galileo sign`), authenticating with the shared token.
+/// Since the token and every transaction plan are sent to it, the connection must use TLS unless
+/// the signer is on this host.
+pub async fn remote(
+    url: Url,
+    token: &str,
+) -> anyhow::Result<impl CustodyClient + Clone + Send + 'static> {
+    let authorization = bearer(token)?;
+    let mut endpoint = Channel::from_shared(url.to_string())?;
+    match url.scheme() {
+        "https" => {
+            let domain = url.host_str().context("signer URL has no host")?;
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().domain_name(domain))
+                .context("could not configure TLS for the signer")?;
+        }
+        "http" if is_loopback(&url) => {}
+        _ => anyhow::bail!(
+            "the signer at {} must be reached over https, unless it's on this host",
+            url
+        ),
+    }
+    let channel = endpoint
+        .connect()
+        .await
+        .with_context(|| format!("could not connect to signer at {}", url))?;
+
+    Ok(CustodyProtocolServiceClient::with_interceptor(
+        channel,
+        move |mut request: tonic::Request<()>| {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+            Ok(request)
+        },
+    ))
+}
+
+/// An interceptor rejecting any request which doesn't carry the shared token.
+pub fn require_token(token: &str) -> anyhow::Result<impl Interceptor + Clone> {
+    let expected = bearer(token)?;
+    Ok(
+        move |request: tonic::Request<()>| match request.metadata().get("authorization") {
+            Some(provided) if bool::from(provided.as_bytes().ct_eq(expected.as_bytes())) => {
+                Ok(request)
+            }
+            _ => Err(Status::unauthenticated("missing or invalid signer token")),
+        },
+    )
+}
+
+/// Custody which only authorizes transactions sending at most the configured amounts of each asset
+/// to addresses outside the wallet, both in each transaction and over a rolling window, so that
+/// whoever can submit plans can't drain the wallet, whether in one go or bit by bit, to Penumbra
+/// addresses or over IBC. Plans containing any action the faucet never makes, or paying more than
+/// the maximum fee, are refused outright.
+pub struct Limited<T> {
+    /// The custody service which actually authorizes transactions.
+    inner: T,
+    /// The full viewing key of the wallet, to tell which outputs are change.
+    fvk: FullViewingKey,
+    /// The maximum amount of each asset which may leave the wallet in a single transaction.
+    limits: BTreeMap<asset::Id, u128>,
+    /// The maximum fee a transaction may pay.
+    max_fee: u128,
+    /// The maximum amount of each asset which may leave the wallet within the window, fees
+    /// included.
+    window_limits: BTreeMap<asset::Id, u128>,
+    /// What left the wallet in the transactions authorized within the window.
+    window: Mutex<Window>,
+}
+
+impl<T> Limited<T> {
+    pub fn new(
+        inner: T,
+        fvk: FullViewingKey,
+        limits: Vec<Value>,
+        max_fee: u64,
+        window_limits: Vec<Value>,
+        window: Duration,
+    ) -> Self {
+        Limited {
+            inner,
+            fvk,
+            limits: totals(limits),
+            max_fee: max_fee.into(),
+            window_limits: totals(window_limits),
+            window: Mutex::new(Window::new(window)),
+        }
+    }
+
+    /// Check that the plan stays within the limits for a single transaction, returning what it
+    /// sends out of the wallet, fee included.
+    fn check(&self, plan: &TransactionPlan) -> Result<BTreeMap<asset::Id, u128>, Status> {
+        let mut outgoing = self.outgoing(plan)?;
+        within_limits(&self.limits, outgoing.clone())?;
+
+        let fee = plan.fee.amount().value();
+        if fee > self.max_fee {
+            return Err(Status::permission_denied(format!(
+                "plan pays a fee of {}, more than the maximum of {}",
+                fee, self.max_fee
+            )));
+        }
+        if fee > 0 {
+            *outgoing.entry(plan.fee.asset_id()).or_default() += fee;
+        }
+        Ok(outgoing)
+    }
+
+    /// Add up how much of each asset the plan sends out of the wallet, refusing any action which
+    /// isn't on the allowlist, since other actions could move value in ways not counted here.
+    fn outgoing(&self, plan: &TransactionPlan) -> Result<BTreeMap<asset::Id, u128>, Status> {
+        let mut outgoing = BTreeMap::<asset::Id, u128>::new();
+        for action in &plan.actions {
+            match action {
+                ActionPlan::Spend(_) => {}
+                ActionPlan::Output(output) => {
+                    if self.fvk.address_index(&output.dest_address).is_none() {
+                        *outgoing.entry(output.value.asset_id).or_default() +=
+                            output.value.amount.value();
+                    }
+                }
+                // Withdrawals always leave the wallet, even though any that time out come back
+                ActionPlan::Withdrawal(withdrawal) => {
+                    *outgoing.entry(withdrawal.denom.id()).or_default() +=
+                        withdrawal.amount.value();
+                }
+                _ => {
+                    return Err(Status::permission_denied(
+                        "plan contains an action other than spends, outputs, and IBC withdrawals, \
+                        which is not permitted",
+                    ))
+                }
+            }
+        }
+        Ok(outgoing)
+    }
+}
+
+/// Add up values by asset.
+fn totals(values: Vec<Value>) -> BTreeMap<asset::Id, u128> {
+    let mut totals = BTreeMap::<asset::Id, u128>::new();
+    for value in values {
+        *totals.entry(value.asset_id).or_default() += value.amount.value();
+    }
+    totals
+}
+
+/// What left the wallet in each transaction authorized within a rolling window of time.
+#[derive(Debug)]
+struct Window {
+    /// How long the window is.
+    length: Duration,
+    /// When each transaction was authorized, and what it sent out of the wallet, oldest first.
+    sent: VecDeque<(Instant, BTreeMap<asset::Id, u128>)>,
+}
+
+impl Window {
+    fn new(length: Duration) -> Self {
+        Window {
+            length,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Count a transaction sending the given amounts out of the wallet against the window, unless
+    /// that would take the total of any asset over its limit.
+    fn reserve(
+        &mut self,
+        limits: &BTreeMap<asset::Id, u128>,
+        outgoing: BTreeMap<asset::Id, u128>,
+        now: Instant,
+    ) -> Result<(), Status> {
+        while let Some((time, _)) = self.sent.front() {
+            if now.saturating_duration_since(*time) < self.length {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        let mut total = outgoing.clone();
+        for (_, sent) in &self.sent {
+            for (asset_id, amount) in sent {
+                *total.entry(*asset_id).or_default() += amount;
+            }
+        }
+        within_limits(limits, total).map_err(|status| {
+            Status::permission_denied(format!(
+                "{} (counting everything sent in the last {})",
+                status.message(),
+                humantime::format_duration(self.length)
+            ))
+        })?;
+        self.sent.push_back((now, outgoing));
+        Ok(())
+    }
+
+    /// Stop counting a transaction reserved at the given time, which wasn't authorized after all.
+    fn release(&mut self, at: Instant) {
+        if let Some(index) = self.sent.iter().position(|(time, _)| *time == at) {
+            self.sent.remove(index);
+        }
+    }
+}
+
+/// Check that the amounts sent out of the wallet are each within the limit for their asset, and
+/// that no asset without a limit is sent at all.
+fn within_limits(
+    limits: &BTreeMap<asset::Id, u128>,
+    outgoing: BTreeMap<asset::Id, u128>,
+) -> Result<(), Status> {
+    for (asset_id, amount) in outgoing {
+        match limits.get(&asset_id) {
+            Some(&limit) if amount <= limit => {}
+            Some(&limit) => {
+                return Err(Status::permission_denied(format!(
+                    "plan sends {} of asset {} out of the wallet, more than the limit of {}",
+                    amount, asset_id, limit
+                )))
+            }
+            None => {
+                return Err(Status::permission_denied(format!(
+                    "plan sends asset {} out of the wallet, which is not permitted",
+                    asset_id
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl<T: CustodyProtocolService> CustodyProtocolService for Limited<T> {
+    async fn authorize(
+        &self,
+        request: tonic::Request<pb::AuthorizeRequest>,
+    ) -> Result<tonic::Response<pb::AuthorizeResponse>, Status> {
+        let plan: TransactionPlan = request
+            .get_ref()
+            .plan
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("missing transaction plan"))?
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("invalid transaction plan: {:#}", e)))?;
+
+        // Count the transaction against the window before authorizing it, so that plans submitted
+        // at once can't each squeeze in under the limits
+        let now = Instant::now();
+        let checked = self.check(&plan).and_then(|outgoing| {
+            self.window
+                .lock()
+                .unwrap()
+                .reserve(&self.window_limits, outgoing, now)
+        });
+        if let Err(status) = checked {
+            tracing::warn!(?status, "refusing to authorize transaction");
+            return Err(status);
+        }
+
+        tracing::info!("authorizing transaction");
+        let authorized = self.inner.authorize(request).await;
+        if authorized.is_err() {
+            self.window.lock().unwrap().release(now);
+        }
+        authorized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use penumbra_keys::keys::{SeedPhrase, SpendKey};
+    use penumbra_transaction::plan::OutputPlan;
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    fn fvk() -> FullViewingKey {
+        SpendKey::from_seed_phrase(SeedPhrase::generate(OsRng), 0)
+            .full_viewing_key()
+            .clone()
+    }
+
+    /// A plan with an output of each value, to the given wallet.
+    fn plan(outputs: &[(&str, &FullViewingKey)]) -> TransactionPlan {
+        TransactionPlan {
+            actions: outputs
+                .iter()
+                .map(|(v, fvk)| {
+                    let address = fvk.payment_address(0.into()).0;
+                    ActionPlan::Output(OutputPlan::new(&mut OsRng, value(v), address))
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn value(s: &str) -> Value {
+        s.parse().unwrap()
+    }
+
+    fn amounts(values: &[&str]) -> BTreeMap<asset::Id, u128> {
+        let mut amounts = BTreeMap::new();
+        for v in values.iter().map(|v| value(v)) {
+            *amounts.entry(v.asset_id).or_default() += v.amount.value();
+        }
+        amounts
+    }
+
+    #[test]
+    fn within_limits_allows_up_to_the_limit() {
+        let limits = amounts(&["100penumbra", "10gm"]);
+        assert!(within_limits(&limits, amounts(&[])).is_ok());
+        assert!(within_limits(&limits, amounts(&["100penumbra"])).is_ok());
+        assert!(within_limits(&limits, amounts(&["60penumbra", "40penumbra", "10gm"])).is_ok());
+    }
+
+    #[test]
+    fn within_limits_refuses_more_than_the_limit() {
+        let limits = amounts(&["100penumbra"]);
+        let status = within_limits(&limits, amounts(&["60penumbra", "41penumbra"])).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn within_limits_refuses_assets_without_a_limit() {
+        let limits = amounts(&["100penumbra"]);
+        let status = within_limits(&limits, amounts(&["1gm"])).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    /// Custody limited to 100penumbra per transaction and 250penumbra per hour, without fees.
+    fn custody(wallet: &FullViewingKey) -> Limited<()> {
+        Limited::new(
+            (),
+            wallet.clone(),
+            vec![value("100penumbra")],
+            0,
+            vec![value("250penumbra")],
+            Duration::from_secs(3600),
+        )
+    }
+
+    #[test]
+    fn window_refuses_once_the_total_is_over_the_limit() {
+        let limits = amounts(&["250penumbra"]);
+        let mut window = Window::new(Duration::from_secs(3600));
+        let start = Instant::now();
+        for _ in 0..2 {
+            window
+                .reserve(&limits, amounts(&["100penumbra"]), start)
+                .unwrap();
+        }
+        let status = window
+            .reserve(&limits, amounts(&["100penumbra"]), start)
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(window
+            .reserve(&limits, amounts(&["50penumbra"]), start)
+            .is_ok());
+    }
+
+    #[test]
+    fn window_forgets_what_was_sent_before_it() {
+        let limits = amounts(&["250penumbra"]);
+        let mut window = Window::new(Duration::from_secs(3600));
+        let start = Instant::now();
+        window
+            .reserve(&limits, amounts(&["200penumbra"]), start)
+            .unwrap();
+        assert!(window
+            .reserve(&limits, amounts(&["100penumbra"]), start)
+            .is_err());
+        let later = start + Duration::from_secs(3600);
+        assert!(window
+            .reserve(&limits, amounts(&["100penumbra"]), later)
+            .is_ok());
+    }
+
+    #[test]
+    fn window_stops_counting_what_was_released() {
+        let limits = amounts(&["250penumbra"]);
+        let mut window = Window::new(Duration::from_secs(3600));
+        let start = Instant::now();
+        window
+            .reserve(&limits, amounts(&["200penumbra"]), start)
+            .unwrap();
+        window.release(start);
+        assert!(window
+            .reserve(&limits, amounts(&["200penumbra"]), start)
+            .is_ok());
+    }
+
+    #[test]
+    fn only_local_signers_may_be_reached_without_tls() {
+        for url in [
+            "http://127.0.0.1:8081",
+            "http://localhost:8081",
+            "http://[::1]:8081",
+        ] {
+            assert!(is_loopback(&url.parse().unwrap()), "{}", url);
+        }
+        for url in ["http://signer.example:8081", "http://10.0.0.2:8081"] {
+            assert!(!is_loopback(&url.parse().unwrap()), "{}", url);
+        }
+    }
+
+    #[test]
+    fn check_counts_outputs_leaving_the_wallet() {
+        let (wallet, user) = (fvk(), fvk());
+        let limited = custody(&wallet);
+        assert!(limited.check(&plan(&[("100penumbra", &user)])).is_ok());
+        let status = limited
+            .check(&plan(&[("60penumbra", &user), ("41penumbra", &user)]))
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn check_ignores_change() {
+        let (wallet, user) = (fvk(), fvk());
+        let limited = custody(&wallet);
+        let change = plan(&[("100penumbra", &user), ("5000penumbra", &wallet)]);
+        assert!(limited.check(&change).is_ok());
+    }
+}
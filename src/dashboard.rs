@@ -0,0 +1,132 @@
+use std::{fmt::Write, time::Duration};
+
+use axum::{extract::State, response::Html, routing::get, Router};
+use chrono::Utc;
+use penumbra_asset::{asset, Value};
+use tokio::{sync::watch, time::Instant};
+
+use crate::{Lifecycle, Store};
+
+/// How often to check the wallet's balances for the dashboard.
+pub const BALANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The number of recent dispenses to show.
+const RECENT: usize = 10;
+
+/// A public web page showing whether the faucet is healthy, so the community can check without
+/// asking the administrators: its balance, how many requests are waiting, what it sent recently,
+/// and how long it's been up.
+#[derive(Clone)]
+pub struct Dashboard {
+    /// When the faucet started.
+    started: Instant,
+    /// How many requests are waiting.
+    lifecycle: Lifecycle,
+    /// Persistent state, whose dispense ledger holds the recent dispenses.
+    store: Store,
+    /// The latest balance of each asset in the wallet.
+    balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+}
+
+impl Dashboard {
+    pub fn new(
+        lifecycle: Lifecycle,
+        store: Store,
+        balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+    ) -> Self {
+        Dashboard {
+            started: Instant::now(),
+            lifecycle,
+            store,
+            balances,
+        }
+    }
+
+    /// The routes serving the dashboard, to merge into the HTTP API's.
+    pub fn router<S>(self) -> Router<S> {
+        Router::new().route("/", get(page)).with_state(self)
+    }
+
+    /// Render the dashboard.
+    fn render(&self) -> String {
+        let cache = asset::Cache::with_known_assets();
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+            <meta http-equiv=\"refresh\" content=\"30\">\n<title>Galileo</title>\n</head>\n<body>\n\
+            <h1>Galileo faucet status</h1>\n",
+        );
+
+        let uptime = Duration::from_secs(self.started.elapsed().as_secs());
+        let _ = writeln!(
+            html,
+            "<p>Up for {}. {} requests waiting.</p>",
+            humantime::format_duration(uptime),
+            self.lifecycle.in_flight(),
+        );
+
+        html.push_str("<h2>Balance</h2>\n");
+        match &*self.balances.borrow() {
+            Some(balances) if !balances.is_empty() => {
+                html.push_str("<ul>\n");
+                for (asset_id, amount) in balances {
+                    let value = Value {
+                        amount: (*amount).into(),
+                        asset_id: *asset_id,
+                    };
+                    let _ = writeln!(html, "<li>{}</li>", escape(&value.format(&cache)));
+                }
+                html.push_str("</ul>\n");
+            }
+            Some(_) => html.push_str("<p>Empty.</p>\n"),
+            None => html.push_str("<p>Not checked yet.</p>\n"),
+        }
+
+        // Only what's on chain anyway is shown: not who asked, or where
+        html.push_str("<h2>Recent dispenses</h2>\n");
+        let mut dispenses = self.store.dispenses();
+        dispenses.sort_by_key(|dispense| std::cmp::Reverse(dispense.time));
+        if dispenses.is_empty() {
+            html.push_str("<p>None yet.</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>When</th><th>Address</th><th>Sent</th><th>Transaction</th></tr>\n",
+            );
+            for dispense in dispenses.iter().take(RECENT) {
+                let ago = (Utc::now() - dispense.time).to_std().unwrap_or_default();
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{} ago</td><td><code>{}</code></td><td>{}</td><td><code>{}</code></td></tr>",
+                    humantime::format_duration(Duration::from_secs(ago.as_secs())),
+                    escape(&shorten(&dispense.address, 24)),
+                    escape(&dispense.values.join(", ")),
+                    escape(&shorten(&dispense.tx_id, 16)),
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// Handle `GET /`.
+async fn page(State(dashboard): State<Dashboard>) -> Html<String> {
+    Html(dashboard.render())
+}
+
+/// Shorten a long identifier to its first characters.
+fn shorten(text: &str, length: usize) -> String {
+    match text.char_indices().nth(length) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Escape text for inclusion in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use penumbra_asset::{asset, Value};
+use penumbra_keys::{keys::AddressIndex, Address, FullViewingKey};
+use penumbra_view::ViewClient;
+use rand::rngs::OsRng;
+
+use crate::{store::Donation, Store};
+
+/// How often to look for new donations.
+pub const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the community can send unused testnet tokens back to the faucet.
+#[derive(Debug, Clone)]
+pub struct Donations {
+    /// The full viewing key of the faucet's wallet, from which donation addresses are derived.
+    fvk: FullViewingKey,
+    /// The amounts sent for each request, so donors know which assets are most useful.
+    values: Vec<Value>,
+}
+
+impl Donations {
+    pub fn new(fvk: FullViewingKey, values: Vec<Value>) -> Self {
+        Donations { fvk, values }
+    }
+
+    /// A fresh address for the faucet's wallet, which can't be linked to its others on chain.
+    ///
+    /// The faucet never sends anything to these (its change goes to its default address), so any
+    /// note received at one is a donation.
+    pub fn address(&self) -> Address {
+        self.fvk.ephemeral_address(OsRng, AddressIndex::new(0)).0
+    }
+
+    /// The reply to `/faucet-donate`, with a fresh address.
+    pub fn message(&self, store: &Store) -> String {
+        let cache = asset::Cache::with_known_assets();
+        let values = self
+            .values
+            .iter()
+            .map(|value| value.format(&cache))
+            .collect::<Vec<_>>()
+            .join(" and ");
+        format!(
+            "Thanks for giving back! Send unused testnet tokens to:\n`{}`\n\
+            (This address is fresh each time you ask, and can't be linked to the faucet's others.) \
+            The faucet hands out {} per request, so those assets are the most useful, but any \
+            asset is welcome. Donations received so far: {}.",
+            self.address(),
+            values,
+            store.donations().len()
+        )
+    }
+}
+
+/// Record every note received at a donation address in the donation ledger, checking the view at
+/// the given interval.
+pub async fn watch<V: ViewClient>(
+    mut view: V,
+    fvk: FullViewingKey,
+    store: Store,
+    interval: Duration,
+) {
+    let cache = asset::Cache::with_known_assets();
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let notes = match view
+            .unspent_notes_by_asset_and_address(fvk.account_group_id())
+            .await
+        {
+            Ok(notes) => notes,
+            Err(e) => {
+                tracing::warn!(error = ?e, "failed to check for donations");
+                continue;
+            }
+        };
+
+        let donated = notes
+            .into_values()
+            .flat_map(|by_address| by_address.into_iter())
+            // Only the addresses handed out by `/faucet-donate` are randomized
+            .filter(|(address_index, _)| address_index.randomizer != [0; 12])
+            .flat_map(|(_, records)| records);
+        for record in donated {
+            let donation = Donation {
+                time: Utc::now(),
+                note_commitment: record.note_commitment.to_string(),
+                value: record.note.value().format(&cache),
+                height: record.height_created,
+            };
+            let value = donation.value.clone();
+            match store.record_donation(donation) {
+                Ok(true) => tracing::info!(%value, "received donation"),
+                Ok(false) => {}
+                Err(e) => tracing::error!(error = ?e, "failed to record donation"),
+            }
+        }
+    }
+}
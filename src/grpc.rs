@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use penumbra_asset::{asset, Value};
+use penumbra_keys::Address;
+use tonic::{transport::Server, Code};
+
+use crate::{
+    audit,
+    http::{self, Limits},
+    metrics,
+    responder::{Request, RequestQueue, Response},
+    transport::{self, Transport},
+    Lifecycle, Store,
+};
+
+mod proto {
+    tonic::include_proto!("galileo.faucet.v1");
+}
+
+use proto::{
+    faucet_service_server::{FaucetService, FaucetServiceServer},
+    request_status_response::Status,
+    LimitsRequest, LimitsResponse, RequestFundsRequest, RequestFundsResponse, RequestStatusRequest,
+    RequestStatusResponse,
+};
+
+/// The counter of requests made to the gRPC service, by method and outcome.
+pub const REQUESTS: &str = "galileo_grpc_requests_total";
+
+/// How long to remember the status of a request after it was made. Requests which were sent can
+/// still be looked up in the dispense ledger after that.
+const STATUS_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A gRPC service for requesting tokens, which unlike the HTTP API replies as soon as the request
+/// is queued, with an id to poll for its result.
+pub struct Faucet {
+    /// The queue of requests for the responder.
+    requests: RequestQueue,
+    /// Whether we're still accepting requests, and which are in flight.
+    lifecycle: Lifecycle,
+    /// Per-IP and per-address rate limits, shared with the HTTP API.
+    limits: Arc<Limits>,
+    /// The amounts sent for each request.
+    values: Vec<Value>,
+    /// Where else to deliver the result of every request.
+    transports: Arc<Vec<Box<dyn Transport>>>,
+    /// Persistent state, whose dispense ledger records requests which were sent.
+    store: Store,
+    /// The status of each recent request, by id, and when it was made.
+    statuses: Arc<Mutex<HashMap<u64, (Instant, RequestStatusResponse)>>>,
+}
+
+impl Faucet {
+    pub fn new(
+        requests: RequestQueue,
+        lifecycle: Lifecycle,
+        limits: Arc<Limits>,
+        values: Vec<Value>,
+        transports: Vec<Box<dyn Transport>>,
+        store: Store,
+    ) -> Self {
+        Faucet {
+            requests,
+            lifecycle,
+            limits,
+            values,
+            transports: Arc::new(transports),
+            store,
+            statuses: Default::default(),
+        }
+    }
+
+    /// Serve the service on the given address until it fails. If `token` is given, callers must
+    /// present it as `authorization: Bearer <token>` metadata.
+    pub async fn serve(self, bind: SocketAddr, token: Option<String>) -> anyhow::Result<()> {
+        let service = FaucetServiceServer::with_interceptor(self, move |request| {
+            if let Some(token) = &token {
+                let authorization = request
+                    .metadata()
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok());
+                if authorization != Some(format!("Bearer {}", token).as_str()) {
+                    return Err(tonic::Status::unauthenticated("missing or invalid token"));
+                }
+            }
+            Ok(request)
+        });
+        tracing::info!(%bind, "serving gRPC API");
+        Server::builder().add_service(service).serve(bind).await?;
+        Ok(())
+    }
+}
+
+/// An error status, counted under the method and outcome.
+fn error(
+    method: &'static str,
+    code: Code,
+    outcome: &'static str,
+    message: impl Into<String>,
+) -> tonic::Status {
+    metrics::increment(
+        REQUESTS,
+        &[
+            ("method", method.to_string()),
+            ("outcome", outcome.to_string()),
+        ],
+    );
+    tonic::Status::new(code, message)
+}
+
+/// Reject a request for being rate-limited, recording it in the audit trail.
+fn rate_limited(method: &'static str, address: &str, message: String) -> tonic::Status {
+    audit::record(
+        "rejected",
+        None,
+        None,
+        None,
+        format!("gRPC request for {}: {}", address, message),
+    );
+    error(method, Code::ResourceExhausted, "rate-limited", message)
+}
+
+/// The status of a request, once its response is in.
+fn status(response: &Response) -> RequestStatusResponse {
+    let cache = asset::Cache::with_known_assets();
+    let receipt = response
+        .succeeded()
+        .first()
+        .map(|(_, receipt)| receipt)
+        .or_else(|| response.withdrawn().first().map(|(_, receipt)| receipt));
+    match receipt {
+        Some(receipt) => RequestStatusResponse {
+            status: Status::Sent as i32,
+            transaction_id: receipt.id.to_string(),
+            height: receipt.height,
+            values: receipt
+                .values
+                .iter()
+                .map(|value| value.format(&cache))
+                .collect(),
+            simulated: receipt.simulated,
+            error: String::new(),
+        },
+        None => RequestStatusResponse {
+            status: Status::Failed as i32,
+            error: response
+                .failed()
+                .first()
+                .map(|(_, error)| error)
+                .or_else(|| {
+                    response
+                        .failed_withdrawals()
+                        .first()
+                        .map(|(_, error)| error)
+                })
+                .cloned()
+                .unwrap_or_else(|| response.plain_summary()),
+            ..Default::default()
+        },
+    }
+}
+
+#[tonic::async_trait]
+impl FaucetService for Faucet {
+    async fn request_funds(
+        &self,
+        request: tonic::Request<RequestFundsRequest>,
+    ) -> Result<tonic::Response<RequestFundsResponse>, tonic::Status> {
+        const METHOD: &str = "RequestFunds";
+
+        if !self.lifecycle.is_accepting() {
+            return Err(error(
+                METHOD,
+                Code::Unavailable,
+                "stopping",
+                "the faucet is shutting down, try again shortly",
+            ));
+        }
+        let remote = request.remote_addr();
+        let address_text = request.into_inner().address.trim().to_string();
+        let address = address_text
+            .parse::<Address>()
+            .map_err(|_| error(METHOD, Code::InvalidArgument, "invalid", "invalid address"))?;
+
+        if let Some(wait) = self.limits.address_wait(&address) {
+            return Err(rate_limited(
+                METHOD,
+                &address_text,
+                format!(
+                    "this address was sent tokens recently, try again in {}",
+                    humantime::format_duration(Duration::from_secs(wait.as_secs()))
+                ),
+            ));
+        }
+        let ip = remote.map(|remote| remote.ip());
+        if let Some(wait) = ip.and_then(|ip| self.limits.ip_wait(ip)) {
+            return Err(rate_limited(
+                METHOD,
+                &address_text,
+                format!(
+                    "too many requests, try again in {}",
+                    humantime::format_duration(Duration::from_secs(wait.as_secs()))
+                ),
+            ));
+        }
+
+        let origin = http::origin();
+        let request_id = origin.message_id.0;
+        tracing::info!(?ip, address = %address_text, request_id, "request over gRPC");
+
+        let in_flight = self.lifecycle.begin();
+        let (response, request) = Request::new([address_text.as_str()], origin);
+        if self.requests.send(request).await.is_err() {
+            if let Some(ip) = ip {
+                self.limits.release(ip);
+            }
+            return Err(error(
+                METHOD,
+                Code::Unavailable,
+                "stopping",
+                "the faucet is shutting down, try again shortly",
+            ));
+        }
+        {
+            let mut statuses = self.statuses.lock().unwrap();
+            statuses.retain(|_, (made, _)| made.elapsed() < STATUS_RETENTION);
+            statuses.insert(
+                request_id,
+                (
+                    Instant::now(),
+                    RequestStatusResponse {
+                        status: Status::Pending as i32,
+                        ..Default::default()
+                    },
+                ),
+            );
+        }
+
+        // Wait for the result in the background, so the caller can poll for it
+        let statuses = self.statuses.clone();
+        let limits = self.limits.clone();
+        let transports = self.transports.clone();
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            let response = match response.await {
+                Ok(response) => response,
+                Err(_) => {
+                    // Stopped before sending: the request is saved and will be sent after a
+                    // restart, so leave it pending
+                    return;
+                }
+            };
+            // Only count the request against the caller if they actually received something
+            if response.succeeded().is_empty() && response.withdrawn().is_empty() {
+                if let Some(ip) = ip {
+                    limits.release(ip);
+                }
+            }
+            if let Some((_, status)) = statuses.lock().unwrap().get_mut(&request_id) {
+                *status = self::status(&response);
+            }
+            let transports: Vec<&dyn Transport> =
+                transports.iter().map(|transport| &**transport).collect();
+            transport::deliver_all(&transports, &response).await;
+        });
+
+        metrics::increment(
+            REQUESTS,
+            &[
+                ("method", METHOD.to_string()),
+                ("outcome", "queued".to_string()),
+            ],
+        );
+        Ok(tonic::Response::new(RequestFundsResponse {
+            request_id: request_id.to_string(),
+        }))
+    }
+
+    async fn request_status(
+        &self,
+        request: tonic::Request<RequestStatusRequest>,
+    ) -> Result<tonic::Response<RequestStatusResponse>, tonic::Status> {
+        const METHOD: &str = "RequestStatus";
+
+        let request_id: u64 = request
+            .into_inner()
+            .request_id
+            .trim()
+            .parse()
+            .map_err(|_| {
+                error(
+                    METHOD,
+                    Code::InvalidArgument,
+                    "invalid",
+                    "invalid request id",
+                )
+            })?;
+
+        let recent = self
+            .statuses
+            .lock()
+            .unwrap()
+            .get(&request_id)
+            .map(|(_, status)| status.clone());
+        // Requests we've forgotten about (e.g. since restarting) can still be found in the ledger,
+        // if they were sent
+        let status = recent.or_else(|| {
+            self.store
+                .dispenses()
+                .into_iter()
+                .find(|dispense| dispense.message_id == Some(request_id))
+                .map(|dispense| RequestStatusResponse {
+                    status: Status::Sent as i32,
+                    transaction_id: dispense.tx_id,
+                    values: dispense.values,
+                    ..Default::default()
+                })
+        });
+
+        match status {
+            Some(status) => {
+                metrics::increment(
+                    REQUESTS,
+                    &[
+                        ("method", METHOD.to_string()),
+                        ("outcome", "found".to_string()),
+                    ],
+                );
+                Ok(tonic::Response::new(status))
+            }
+            None => Err(error(
+                METHOD,
+                Code::NotFound,
+                "not-found",
+                "no such request",
+            )),
+        }
+    }
+
+    async fn limits(
+        &self,
+        _request: tonic::Request<LimitsRequest>,
+    ) -> Result<tonic::Response<LimitsResponse>, tonic::Status> {
+        metrics::increment(
+            REQUESTS,
+            &[
+                ("method", "Limits".to_string()),
+                ("outcome", "found".to_string()),
+            ],
+        );
+        let cache = asset::Cache::with_known_assets();
+        Ok(tonic::Response::new(LimitsResponse {
+            values: self
+                .values
+                .iter()
+                .map(|value| value.format(&cache))
+                .collect(),
+            ip_rate_limit_seconds: self.limits.ip_rate_limit.as_secs(),
+            address_rate_limit_seconds: self.limits.address_rate_limit.as_secs(),
+        }))
+    }
+}
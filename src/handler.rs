@@ -1,68 +1,339 @@
 use std::{
-    borrow::Borrow,
-    collections::VecDeque,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    time::Duration,
 };
 
+use chrono::{DateTime, Utc};
+
 use serenity::{
     async_trait,
-    client::{Context, EventHandler},
+    client::{bridge::gateway::event::ShardStageUpdateEvent, Context, EventHandler},
+    gateway::ConnectionStage,
     model::gateway::Ready,
     model::{
-        channel::Message,
-        id::{GuildId, UserId},
+        application::interaction::{modal::ModalSubmitInteraction, Interaction},
+        channel::{GuildChannel, Message, ReactionType},
+        event::MessageUpdateEvent,
+        id::{ChannelId, GuildId, MessageId},
     },
+    prelude::TypeMapKey,
 };
-use tokio::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
 use tracing::instrument;
 
-use super::responder::{Request, RequestQueue};
+use crate::{
+    cooldown::Cooldown,
+    donate::Donations,
+    id,
+    intake::{self, ChatPlatform, Incoming, Intake, Reaction},
+    metrics,
+    responder::{Control, Origin},
+    rest,
+    transport::{DirectMessage, Reply, Transport},
+    Store,
+};
+
+mod claim;
+
+mod commands;
+
+mod dm;
+pub use dm::Operators;
+
+mod moderation;
+
+mod trigger;
+pub use trigger::Trigger;
+
+mod extract;
+
+/// `TypeMap` key for the control queue (so that `serenity` worker can send to it).
+pub struct ControlQueue;
+
+/// Associate the `ControlQueue` key with an `mpsc::Sender` for [`Control`] messages in the `TypeMap`.
+impl TypeMapKey for ControlQueue {
+    type Value = mpsc::Sender<Control>;
+}
+
+/// `TypeMap` key for the control queues of the responders for other chains (see
+/// [`Profile`](crate::profile::Profile)), keyed by the channels they serve.
+pub struct ProfileControls;
+
+impl TypeMapKey for ProfileControls {
+    type Value = HashMap<id::ChannelId, mpsc::Sender<Control>>;
+}
+
+/// The origin of a request made in the given message.
+pub fn origin(message: &Message) -> Origin {
+    Origin {
+        user_id: id::UserId(message.author.id.0),
+        channel_id: id::ChannelId(message.channel_id.0),
+        message_id: id::MessageId(message.id.0),
+    }
+}
+
+/// The gauge which is 1 for each gateway shard while it's connected, and 0 otherwise.
+pub const SHARD_CONNECTED: &str = "galileo_discord_shard_connected";
 
+/// Count an event of the given kind in a channel.
+fn count_event(kind: &'static str, channel_id: ChannelId) {
+    intake::count_event(kind, id::ChannelId(channel_id.0));
+}
+
+/// Count an event in a channel being ignored because of the given rule.
+fn count_filtered(rule: &'static str, channel_id: ChannelId) {
+    intake::count_filtered(rule, id::ChannelId(channel_id.0));
+}
+
+/// Summarize the events received per channel since the bot started, and which rules caused them
+/// to be ignored, along with how much Discord REST calls have had to wait and how many gateway
+/// shards are connected.
+pub fn event_summary() -> String {
+    fn label<'a>(labels: &'a metrics::Labels, name: &str) -> &'a str {
+        labels
+            .iter()
+            .find(|(label, _)| *label == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+
+    let mut channels = BTreeMap::<String, (Vec<String>, Vec<String>)>::new();
+    for (labels, count) in metrics::counters(intake::EVENTS) {
+        channels
+            .entry(label(&labels, "channel").to_string())
+            .or_default()
+            .0
+            .push(format!("{} {}", count, label(&labels, "kind")));
+    }
+    for (labels, count) in metrics::counters(intake::FILTERED) {
+        channels
+            .entry(label(&labels, "channel").to_string())
+            .or_default()
+            .1
+            .push(format!("{} {}", count, label(&labels, "rule")));
+    }
+
+    let calls: u64 = metrics::counters(rest::CALLS)
+        .into_iter()
+        .map(|(_, count)| count)
+        .sum();
+    let waited: u64 = metrics::counters(rest::WAIT)
+        .into_iter()
+        .map(|(_, millis)| millis)
+        .sum();
+    let rate_limited: u64 = metrics::counters(rest::RATE_LIMITED)
+        .into_iter()
+        .map(|(_, count)| count)
+        .sum();
+    let mut rest_calls = format!(
+        "{} Discord REST calls, which waited {} in total for a turn ({} rate limited and retried)",
+        calls,
+        humantime::format_duration(Duration::from_millis(waited)),
+        rate_limited
+    );
+    let shards = metrics::gauges(SHARD_CONNECTED);
+    if !shards.is_empty() {
+        let connected = shards.iter().filter(|(_, value)| *value > 0).count();
+        rest_calls.push_str(&format!(
+            "\n{} of {} gateway shards connected",
+            connected,
+            shards.len()
+        ));
+    }
+
+    if channels.is_empty() {
+        return format!("No events received yet.\n{}", rest_calls);
+    }
+    let mut summary = channels
+        .into_iter()
+        .map(|(channel, (events, filtered))| {
+            format!(
+                "<#{}>: received {}; ignored {}",
+                channel,
+                events.join(", "),
+                if filtered.is_empty() {
+                    "none".to_string()
+                } else {
+                    filtered.join(", ")
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    summary.push('\n');
+    summary.push_str(&rest_calls);
+    summary
+}
+
+/// Receives events from Discord, and passes messages which might contain requests on to the
+/// [`Intake`].
 pub struct Handler {
-    /// The minimum duration between dispensing tokens to a user.
-    rate_limit: Duration,
-    /// Limit of the number of times, per user, we will inform that user of their rate limit.
-    reply_limit: usize,
-    /// History of requests we answered for token dispersal, with a timestamp and the number of
-    /// times we've told the user about the rate limit (so that eventually we can stop replying if
-    /// they keep asking).
-    send_history: Arc<Mutex<VecDeque<(UserId, Instant, usize)>>>,
+    intake: Intake,
+    /// Persistent state, where each server's settings are kept.
+    store: Store,
+    /// Where to tell people who ask to send donations.
+    donations: Donations,
+    /// The administrators who may control the bot by direct message, if any.
+    operators: Option<Operators>,
+    /// What a message has to do to be scanned for addresses.
+    trigger: Trigger,
+    /// The largest text file attached to a message to scan for addresses too, in bytes, if any.
+    max_attachment_size: Option<u64>,
+    /// What to do to users who keep asking past their reply limit, if anything.
+    cooldown: Option<Cooldown>,
+    /// The channels in which to keep a message with a button to request tokens through.
+    claim_channels: Vec<ChannelId>,
 }
 
 impl Handler {
-    pub fn new(rate_limit: Duration, reply_limit: usize) -> Self {
+    pub fn new(
+        intake: Intake,
+        store: Store,
+        donations: Donations,
+        operators: Option<Operators>,
+        trigger: Trigger,
+        max_attachment_size: Option<u64>,
+        cooldown: Option<Cooldown>,
+        claim_channels: Vec<ChannelId>,
+    ) -> Self {
         Handler {
-            rate_limit,
-            reply_limit,
-            send_history: Arc::new(Mutex::new(VecDeque::new())),
+            intake,
+            store,
+            donations,
+            operators,
+            trigger,
+            max_attachment_size,
+            cooldown,
+            claim_channels,
         }
     }
-}
 
-#[async_trait]
-impl EventHandler for Handler {
-    #[instrument(skip(self, ctx))]
-    async fn message(&self, ctx: Context, message: Message) {
+    /// Handle an address submitted through the claim button: check it, and if it's sound, handle
+    /// it like a message containing it, answering only the person who submitted it.
+    async fn claim(&self, ctx: Context, modal: ModalSubmitInteraction) {
+        let channel_id = modal.channel_id;
+        count_event("claim", channel_id);
+
+        let guild_id = if let Some(guild_id) = modal.guild_id {
+            guild_id
+        } else {
+            count_filtered("not-in-server", channel_id);
+            return;
+        };
+
+        // Catch typos right away, rather than queueing a request that can only fail
+        let address = claim::address(&modal);
+        if let Err(reason) = claim::check(&address) {
+            count_filtered("invalid-claim", channel_id);
+            if let Err(e) = claim::respond(&ctx, &modal, reason).await {
+                tracing::error!(error = ?e, "failed to answer claim");
+            }
+            return;
+        }
+        if let Err(e) = claim::defer(&ctx, &modal).await {
+            tracing::error!(error = ?e, "failed to acknowledge claim");
+            return;
+        }
+
+        let incoming = Incoming {
+            // Interaction ids are snowflakes like message ids, so they don't collide with them
+            id: id::MessageId(modal.id.0),
+            channel_id: id::ChannelId(channel_id.0),
+            server_id: Some(id::ServerId(guild_id.0)),
+            author_id: id::UserId(modal.user.id.0),
+            author_name: modal.user.name.clone(),
+            author_roles: modal
+                .member
+                .iter()
+                .flat_map(|member| member.roles.iter())
+                .map(|role_id| id::RoleId(role_id.0))
+                .collect(),
+            content: address,
+        };
+        let chat = claim::ClaimChat::new(
+            ctx,
+            modal,
+            guild_id,
+            self.store.clone(),
+            self.cooldown.clone(),
+        );
+        self.intake.handle(&chat, incoming, false).await;
+        chat.finish().await;
+    }
+
+    /// Check an answer, by direct message, to a challenge to prove ownership of addresses, and
+    /// once the proof holds handle the message whose request was waiting on it.
+    async fn prove(&self, ctx: Context, message: Message) {
+        let answer = Incoming {
+            id: id::MessageId(message.id.0),
+            channel_id: id::ChannelId(message.channel_id.0),
+            server_id: None,
+            author_id: id::UserId(message.author.id.0),
+            author_name: message.author.name.clone(),
+            author_roles: Vec::new(),
+            content: message.content.clone(),
+        };
+        let (reply, waiting) = match self.intake.prove(&answer) {
+            Some(answer) => answer,
+            None => {
+                count_filtered("not-in-server", message.channel_id);
+                return;
+            }
+        };
+        if let Err(e) = rest::call("reply", || message.reply(&ctx, reply.clone())).await {
+            tracing::error!(error = ?e, "failed to reply");
+        }
+        let waiting = match waiting {
+            Some(waiting) => waiting,
+            None => return,
+        };
+
+        // Messages fetched from the API don't say which server they're in
+        let channel_id = ChannelId(waiting.channel_id.0);
+        let message_id = MessageId(waiting.id.0);
+        let fetched = rest::call("fetch", || channel_id.message(&ctx.http, message_id)).await;
+        match fetched {
+            Ok(mut waiting_message) => {
+                waiting_message.guild_id = waiting.server_id.map(|server_id| GuildId(server_id.0));
+                self.handle(ctx, waiting_message, false).await
+            }
+            Err(e) => tracing::error!(error = ?e, "failed to fetch the message waiting on a proof"),
+        }
+    }
+
+    /// Handle a new or edited message, if it was posted somewhere we can respond to it.
+    async fn handle(&self, ctx: Context, message: Message, edited: bool) {
         tracing::trace!("parsing message: {:#?}", message);
+        let channel_id = message.channel_id;
+        if !edited {
+            count_event("message", channel_id);
+        }
+
         // Get the guild id of this message
         let guild_id = if let Some(guild_id) = message.guild_id {
             guild_id
+        } else if let Some(operators) = self
+            .operators
+            .as_ref()
+            .filter(|operators| operators.authorized(&message))
+        {
+            dm::handle(&ctx, &message, operators, &self.store).await;
+            return;
         } else {
+            count_filtered("not-in-server", channel_id);
             return;
         };
 
         // Get the channel of this message
-        let guild_channel = if let Some(guild_channel) = ctx.cache.guild_channel(message.channel_id)
-        {
+        let guild_channel = if let Some(guild_channel) = ctx.cache.guild_channel(channel_id) {
             guild_channel
         } else {
             tracing::trace!("could not find server");
+            count_filtered("channel-not-cached", channel_id);
             return;
         };
 
         let self_id = ctx.cache.current_user().id;
-        let user_id = message.author.id;
-        let user_name = message.author.name.clone();
 
         // Stop if we're not allowed to respond in this channel
         if let Ok(self_permissions) = guild_channel.permissions_for_user(&ctx, self_id) {
@@ -71,123 +342,200 @@ impl EventHandler for Handler {
                     ?guild_channel,
                     "not allowed to send messages in this channel"
                 );
+                count_filtered("no-send-permission", channel_id);
                 return;
             }
         } else {
+            count_filtered("unknown-permissions", channel_id);
             return;
         };
 
         // Don't trigger on messages we ourselves send
-        if user_id == self_id {
+        if message.author.id == self_id {
             tracing::trace!("detected message from ourselves");
+            count_filtered("own-message", channel_id);
             return;
         }
 
-        // Prune the send history of all expired rate limit timeouts
-        {
-            tracing::trace!("pruning send history");
-            // scoped to prevent deadlock on send_history
-            let mut send_history = self.send_history.lock().unwrap();
-            while let Some((user, last_fulfilled, _)) = send_history.front() {
-                if last_fulfilled.elapsed() >= self.rate_limit {
-                    tracing::debug!(?user, ?last_fulfilled, "rate limit expired");
-                    send_history.pop_front();
-                } else {
-                    break;
-                }
-            }
-            tracing::trace!("finished pruning send history");
+        // Moderators can ban users from the faucet with commands in the channel
+        if moderation::handle(&ctx, &message, &guild_channel, &self.store).await {
+            count_filtered("moderation-command", channel_id);
+            return;
         }
 
-        // Check if the message contains a penumbra address and create a request for it if so
-        let (response, request) = if let Some(parsed) = { Request::try_new(&message) } {
-            parsed
-        } else {
-            tracing::trace!("no addresses found in message");
+        // Where messages have to ask the bot explicitly, leave the rest of the conversation alone
+        if !self.trigger.admits(&message, self_id) {
+            count_filtered("no-trigger", channel_id);
             return;
+        }
+
+        let incoming = Incoming {
+            id: id::MessageId(message.id.0),
+            channel_id: id::ChannelId(channel_id.0),
+            server_id: Some(id::ServerId(guild_id.0)),
+            author_id: id::UserId(message.author.id.0),
+            author_name: message.author.name.clone(),
+            author_roles: message
+                .member
+                .iter()
+                .flat_map(|member| member.roles.iter())
+                .map(|role_id| id::RoleId(role_id.0))
+                .collect(),
+            content: extract::content(&message, self.max_attachment_size).await,
         };
+        let chat = DiscordChat {
+            ctx,
+            message,
+            guild_channel,
+            guild_id,
+            store: self.store.clone(),
+            progress: Mutex::new(None),
+            cooldown: self.cooldown.clone(),
+        };
+        self.intake.handle(&chat, incoming, edited).await
+    }
+}
 
-        // If the message author was in the send history, don't send them tokens
-        let rate_limited = self
-            .send_history
-            .lock()
-            .unwrap()
-            .iter_mut()
-            .find(|(user, _, _)| *user == user_id)
-            .map(|(_, last_fulfilled, notified)| {
-                // Increase the notification count by one and return the previous count:
-                let old_notified = *notified;
-                *notified += 1;
-                (*last_fulfilled, old_notified)
-            });
-
-        if let Some((last_fulfilled, notified)) = rate_limited {
-            tracing::info!(
-                ?user_name,
-                ?notified,
-                user_id = ?user_id.to_string(),
-                ?last_fulfilled,
-                "rate-limited user"
-            );
+/// A message posted in a Discord channel.
+struct DiscordChat {
+    ctx: Context,
+    message: Message,
+    guild_channel: GuildChannel,
+    guild_id: GuildId,
+    store: Store,
+    /// Our reply reporting the progress of a slow request, once we've posted one.
+    progress: Mutex<Option<Message>>,
+    /// What to do to the author if they keep asking past their reply limit, if anything.
+    cooldown: Option<Cooldown>,
+}
 
-            // If we already notified the user, don't reply again
-            if notified > self.reply_limit + 1 {
-                return;
-            }
+#[async_trait]
+impl ChatPlatform for DiscordChat {
+    async fn reply(&self, text: String) -> anyhow::Result<()> {
+        rest::call("reply", || {
+            self.message.reply_ping(self.ctx.http.clone(), text.clone())
+        })
+        .await?;
+        Ok(())
+    }
 
-            let response = format!(
-                "Please wait for another {} before requesting more tokens. Thanks!",
-                format_remaining_time(last_fulfilled, self.rate_limit)
-            );
-            reply(&ctx, &message, response).await;
+    async fn typing(&self) -> anyhow::Result<()> {
+        rest::call("typing", || self.guild_channel.broadcast_typing(&self.ctx)).await?;
+        Ok(())
+    }
 
-            // Setting the notified count to zero "un-rate-limits" an entry, which we do when a
-            // request fails, so we don't have to traverse the entire list:
-            if notified > 0 {
-                // So therefore we only prevent the request when the notification count is greater
-                // than zero
-                return;
+    async fn react(&self, reaction: Reaction) -> anyhow::Result<()> {
+        // The server's administrators may have turned this kind of reaction off
+        let emoji = match self.store.reaction(id::ServerId(self.guild_id.0), reaction) {
+            Some(emoji) => emoji,
+            None => return Ok(()),
+        };
+        let reaction_type = emoji.parse::<ReactionType>()?;
+        rest::call("react", || {
+            self.message.react(&self.ctx, reaction_type.clone())
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn progress(&self, text: String) -> anyhow::Result<()> {
+        let mut progress = self.progress.lock().await;
+        let posted = match &*progress {
+            // Edit a copy, so that each retry starts from the reply as it was
+            Some(reply) => {
+                rest::call("progress", || {
+                    let (mut reply, text) = (reply.clone(), text.clone());
+                    async move {
+                        reply.edit(&self.ctx, |edit| edit.content(text)).await?;
+                        Ok(reply)
+                    }
+                })
+                .await?
             }
+            None => rest::call("progress", || self.message.reply(&self.ctx, text.clone())).await?,
+        };
+        *progress = Some(posted);
+        Ok(())
+    }
+
+    async fn cool_down(&self, until: DateTime<Utc>) -> anyhow::Result<()> {
+        let cooldown = match &self.cooldown {
+            Some(cooldown) => cooldown,
+            None => return Ok(()),
+        };
+        cooldown
+            .apply(
+                &self.ctx,
+                &self.store,
+                self.guild_id,
+                self.message.author.id,
+                until,
+            )
+            .await
+    }
+
+    fn transports(&self, dm_receipts: bool) -> Vec<Box<dyn Transport>> {
+        let mut transports: Vec<Box<dyn Transport>> = vec![Box::new(Reply::from_context(
+            &self.ctx,
+            &self.message,
+            self.guild_id,
+        ))];
+        if dm_receipts {
+            transports.push(Box::new(DirectMessage::new(
+                self.ctx.http.clone(),
+                self.message.author.id,
+            )));
         }
+        transports
+    }
+}
 
-        // Push the user into the send history queue for rate-limiting in the future
-        tracing::trace!(?user_name, user_id = ?user_id.to_string(), "pushing user into send history");
-        self.send_history
-            .lock()
-            .unwrap()
-            .push_back((user_id, Instant::now(), 1));
+#[async_trait]
+impl EventHandler for Handler {
+    // The content isn't recorded, since it may be a proof of ownership holding a full viewing key
+    #[instrument(skip(self, ctx, message), fields(message_id = %message.id))]
+    async fn message(&self, ctx: Context, message: Message) {
+        if message.guild_id.is_none() && intake::is_answer(&message.content) {
+            return self.prove(ctx, message).await;
+        }
+        self.handle(ctx, message, false).await
+    }
 
-        // Send the message to the queue, to be processed asynchronously
-        tracing::trace!("sending message to worker queue");
-        ctx.data
-            .read()
-            .await
-            .get::<RequestQueue>()
-            .expect("address queue exists")
-            .send(request)
-            .await
-            .expect("send to queue always succeeds");
+    #[instrument(skip(self, ctx, _old_if_available, _new))]
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        count_event("update", event.channel_id);
 
-        // Broadcast to the channel that we are typing, so users know something is happening
-        if let Err(e) = guild_channel.broadcast_typing(&ctx).await {
-            tracing::error!(error = ?e, "failed to broadcast typing");
+        // Updates without content are things like embeds being attached, not edits by the user
+        if event.content.is_none() {
+            count_filtered("update-without-content", event.channel_id);
+            return;
         }
 
-        // Reply to the user with the response from the responder
-        if let Ok(response) = response.await {
-            reply(&ctx, message, response.summary(&ctx, guild_id).await).await;
-        } else {
-            self.send_history
-                .lock()
-                .unwrap()
-                .iter_mut()
-                .find(|(user, _, _)| *user == user_id)
-                .map(|(_, _, notified)| {
-                    // If the request failed, we set the notification count to zero, so that the rate
-                    // limit will not apply to future requests
-                    *notified = notified.saturating_sub(1);
-                });
+        // Only re-scan edits to messages we've seen since we started, so that edits to ancient
+        // messages don't trigger a request
+        if !self.intake.has_seen(id::MessageId(event.id.0)) {
+            tracing::trace!("ignoring edit to unknown message");
+            count_filtered("edit-to-unknown-message", event.channel_id);
+            return;
         }
+
+        // Fetch the full edited message, since the update event only contains the changed fields
+        let fetched = rest::call("fetch", || event.channel_id.message(&ctx.http, event.id)).await;
+        let message = match fetched {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!(error = ?e, "failed to fetch edited message");
+                return;
+            }
+        };
+
+        self.handle(ctx, message, true).await
     }
 
     async fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
@@ -200,28 +548,73 @@ impl EventHandler for Handler {
                 server_id = ?guild_id.to_string(),
                 "connected to server"
             );
+
+            if let Err(e) = commands::register(&ctx, guild_id).await {
+                tracing::error!(error = ?e, ?server_name, "failed to register commands");
+            }
+        }
+
+        for &channel_id in &self.claim_channels {
+            let guild_id = match ctx.cache.guild_channel(channel_id) {
+                Some(channel) if guilds.contains(&channel.guild_id) => channel.guild_id,
+                _ => continue,
+            };
+            let locale = self
+                .intake
+                .locale(Some(id::ServerId(guild_id.0)), id::ChannelId(channel_id.0));
+            if let Err(e) = claim::post(&ctx, channel_id, locale).await {
+                tracing::error!(error = ?e, ?channel_id, "failed to post claim message");
+            }
         }
     }
 
-    async fn ready(&self, _: Context, ready: Ready) {
-        tracing::info!("{} is connected!", ready.user.name);
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => {
+                count_event("command", command.channel_id);
+                commands::handle(&ctx, &command, &self.store, &self.donations).await;
+            }
+            Interaction::MessageComponent(component) if claim::is_button(&component) => {
+                let locale = self.intake.locale(
+                    component.guild_id.map(|guild_id| id::ServerId(guild_id.0)),
+                    id::ChannelId(component.channel_id.0),
+                );
+                if let Err(e) = claim::open(&ctx, &component, locale).await {
+                    tracing::error!(error = ?e, "failed to open claim form");
+                }
+            }
+            Interaction::ModalSubmit(modal) if claim::is_modal(&modal) => {
+                self.claim(ctx, modal).await
+            }
+            _ => {}
+        }
     }
-}
 
-async fn reply(ctx: &Context, message: impl Borrow<Message>, response: impl Borrow<str>) {
-    message
-        .borrow()
-        .reply_ping(ctx.http.clone(), response.borrow())
-        .await
-        .map(|_| ())
-        .unwrap_or_else(|e| tracing::error!(error = ?e, "failed to reply"));
-}
+    async fn ready(&self, _: Context, ready: Ready) {
+        match ready.shard {
+            Some([shard, shards]) => {
+                tracing::info!(shard, shards, "{} is connected!", ready.user.name)
+            }
+            None => tracing::info!("{} is connected!", ready.user.name),
+        }
+    }
 
-fn format_remaining_time(last_fulfilled: Instant, rate_limit: Duration) -> String {
-    humantime::Duration::from(rate_limit - last_fulfilled.elapsed())
-        .to_string()
-        .split(' ')
-        .take(2)
-        .collect::<Vec<_>>()
-        .join(" ")
+    async fn shard_stage_update(&self, _: Context, event: ShardStageUpdateEvent) {
+        let shard = event.shard_id.0;
+        if event.new == ConnectionStage::Connected {
+            tracing::info!(shard, "gateway shard connected");
+        } else {
+            tracing::warn!(
+                shard,
+                stage = ?event.new,
+                previously = ?event.old,
+                "gateway shard not connected"
+            );
+        }
+        metrics::set(
+            SHARD_CONNECTED,
+            &[("shard", shard.to_string())],
+            (event.new == ConnectionStage::Connected) as i64,
+        );
+    }
 }
@@ -0,0 +1,315 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use chrono::{DateTime, Utc};
+use penumbra_keys::Address;
+use serenity::{
+    async_trait,
+    client::Context,
+    model::{
+        application::{
+            component::{ActionRowComponent, ButtonStyle, InputTextStyle},
+            interaction::{
+                message_component::MessageComponentInteraction, modal::ModalSubmitInteraction,
+                InteractionResponseType,
+            },
+        },
+        id::{ChannelId, GuildId},
+    },
+};
+
+use crate::{
+    cooldown::Cooldown,
+    id,
+    intake::{ChatPlatform, Reaction},
+    responder::validate,
+    rest,
+    transport::{DirectMessage, InteractionReply, Transport},
+    Locale, Store,
+};
+
+/// The id of the button on the claim message.
+const BUTTON: &str = "galileo-claim";
+
+/// The id of the modal the button opens.
+const MODAL: &str = "galileo-claim-modal";
+
+/// The id of the modal's address field.
+const ADDRESS: &str = "address";
+
+/// How many of a channel's latest messages to look through for a claim message already posted.
+const SEARCH_DEPTH: u64 = 50;
+
+/// Make sure the channel has a claim message with a button to request tokens, posting one unless
+/// there's one among its latest messages already.
+pub(super) async fn post(
+    ctx: &Context,
+    channel_id: ChannelId,
+    locale: Locale,
+) -> anyhow::Result<()> {
+    let self_id = ctx.cache.current_user().id;
+    let messages = rest::call("claim", || {
+        channel_id.messages(&ctx.http, |retriever| retriever.limit(SEARCH_DEPTH))
+    })
+    .await?;
+    let posted = messages.iter().any(|message| {
+        message.author.id == self_id
+            && message
+                .components
+                .iter()
+                .flat_map(|row| row.components.iter())
+                .any(|component| match component {
+                    ActionRowComponent::Button(button) => {
+                        button.custom_id.as_deref() == Some(BUTTON)
+                    }
+                    _ => false,
+                })
+    });
+    if posted {
+        return Ok(());
+    }
+
+    rest::call("claim", || {
+        channel_id.send_message(&ctx.http, |message| {
+            message
+                .content(locale.text().claim_prompt)
+                .components(|components| {
+                    components.create_action_row(|row| {
+                        row.create_button(|button| {
+                            button
+                                .custom_id(BUTTON)
+                                .label(locale.text().claim_button)
+                                .style(ButtonStyle::Primary)
+                        })
+                    })
+                })
+        })
+    })
+    .await?;
+    tracing::info!(?channel_id, "posted claim message");
+    Ok(())
+}
+
+/// Whether the button pressed is the claim message's.
+pub(super) fn is_button(component: &MessageComponentInteraction) -> bool {
+    component.data.custom_id == BUTTON
+}
+
+/// Whether the modal submitted is the one the claim button opens.
+pub(super) fn is_modal(modal: &ModalSubmitInteraction) -> bool {
+    modal.data.custom_id == MODAL
+}
+
+/// Open the modal asking for an address, in answer to the button being pressed.
+pub(super) async fn open(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+    locale: Locale,
+) -> anyhow::Result<()> {
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::Modal)
+                .interaction_response_data(|data| {
+                    data.custom_id(MODAL)
+                        .title(locale.text().claim_button)
+                        .components(|components| {
+                            components.create_action_row(|row| {
+                                row.create_input_text(|input| {
+                                    input
+                                        .custom_id(ADDRESS)
+                                        .label(locale.text().claim_field)
+                                        .placeholder("penumbrav2t1...")
+                                        .style(InputTextStyle::Paragraph)
+                                        .required(true)
+                                })
+                            })
+                        })
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// The address submitted in the modal, trimmed.
+pub(super) fn address(modal: &ModalSubmitInteraction) -> String {
+    modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == ADDRESS => {
+                Some(input.value.trim().to_string())
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Answer the modal at once, with something only the person who submitted it can see.
+pub(super) async fn respond(
+    ctx: &Context,
+    modal: &ModalSubmitInteraction,
+    content: impl ToString,
+) -> anyhow::Result<()> {
+    modal
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await?;
+    Ok(())
+}
+
+/// Let the person who submitted the modal know we're on it, while the request is handled.
+pub(super) async fn defer(ctx: &Context, modal: &ModalSubmitInteraction) -> anyhow::Result<()> {
+    modal
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| data.ephemeral(true))
+        })
+        .await?;
+    Ok(())
+}
+
+/// An address submitted through the claim button, whose answers only the person who submitted it
+/// can see. There's no message to react to, so reactions become answers too.
+pub(super) struct ClaimChat {
+    ctx: Context,
+    modal: Arc<ModalSubmitInteraction>,
+    guild_id: GuildId,
+    store: Store,
+    /// What to do to the author if they keep asking past their reply limit, if anything.
+    cooldown: Option<Cooldown>,
+    /// Set once anything has been said in answer to the modal.
+    answered: Arc<AtomicBool>,
+}
+
+impl ClaimChat {
+    pub fn new(
+        ctx: Context,
+        modal: ModalSubmitInteraction,
+        guild_id: GuildId,
+        store: Store,
+        cooldown: Option<Cooldown>,
+    ) -> Self {
+        ClaimChat {
+            ctx,
+            modal: Arc::new(modal),
+            guild_id,
+            store,
+            cooldown,
+            answered: Default::default(),
+        }
+    }
+
+    /// Answer the modal with more text.
+    async fn follow_up(&self, text: String) -> anyhow::Result<()> {
+        rest::call("followup", || {
+            self.modal
+                .create_followup_message(&self.ctx.http, |followup| {
+                    followup.content(&text).ephemeral(true)
+                })
+        })
+        .await?;
+        self.answered.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// If nothing was said in answer to the modal (say, because its author is banned), take away
+    /// the placeholder showing we're working on it.
+    pub async fn finish(&self) {
+        if self.answered.load(Ordering::SeqCst) {
+            return;
+        }
+        let result = rest::call("followup", || {
+            self.modal
+                .delete_original_interaction_response(&self.ctx.http)
+        })
+        .await;
+        if let Err(e) = result {
+            tracing::debug!(error = ?e, "failed to remove claim placeholder");
+        }
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for ClaimChat {
+    async fn reply(&self, text: String) -> anyhow::Result<()> {
+        self.follow_up(text).await
+    }
+
+    async fn typing(&self) -> anyhow::Result<()> {
+        // The deferred answer already shows that we're working on it
+        Ok(())
+    }
+
+    async fn react(&self, reaction: Reaction) -> anyhow::Result<()> {
+        if reaction == Reaction::Received {
+            return Ok(());
+        }
+        match self.store.reaction(id::ServerId(self.guild_id.0), reaction) {
+            Some(emoji) => self.follow_up(emoji).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn progress(&self, text: String) -> anyhow::Result<()> {
+        rest::call("progress", || {
+            self.modal
+                .edit_original_interaction_response(&self.ctx.http, |edit| edit.content(&text))
+        })
+        .await?;
+        self.answered.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn cool_down(&self, until: DateTime<Utc>) -> anyhow::Result<()> {
+        let cooldown = match &self.cooldown {
+            Some(cooldown) => cooldown,
+            None => return Ok(()),
+        };
+        cooldown
+            .apply(
+                &self.ctx,
+                &self.store,
+                self.guild_id,
+                self.modal.user.id,
+                until,
+            )
+            .await
+    }
+
+    fn in_history(&self) -> bool {
+        // Only this instance can answer the interaction, and it's not a message to catch up on
+        false
+    }
+
+    fn transports(&self, dm_receipts: bool) -> Vec<Box<dyn Transport>> {
+        let mut transports: Vec<Box<dyn Transport>> = vec![Box::new(InteractionReply::new(
+            self.ctx.http.clone(),
+            self.modal.clone(),
+            self.answered.clone(),
+        ))];
+        if dm_receipts {
+            transports.push(Box::new(DirectMessage::new(
+                self.ctx.http.clone(),
+                self.modal.user.id,
+            )));
+        }
+        transports
+    }
+}
+
+/// Whether the text is an address we could send to, or else what's wrong with it.
+pub(super) fn check(address: &str) -> Result<(), String> {
+    match address.trim_matches('`').parse::<Address>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(validate(address)),
+    }
+}
@@ -0,0 +1,312 @@
+use serenity::{
+    client::Context,
+    model::{
+        application::{
+            command::CommandOptionType,
+            interaction::{
+                application_command::ApplicationCommandInteraction, InteractionResponseType,
+            },
+        },
+        channel::ReactionType,
+        id::GuildId,
+        permissions::Permissions,
+    },
+};
+use tokio::sync::oneshot;
+
+use super::{ControlQueue, ProfileControls};
+use crate::{
+    audit,
+    donate::Donations,
+    id::{ChannelId, ServerId},
+    intake::Reaction,
+    responder::{self, Control},
+    Store,
+};
+
+/// Register the bot's slash commands in the given server.
+pub(super) async fn register(ctx: &Context, guild_id: GuildId) -> anyhow::Result<()> {
+    guild_id
+        .set_application_commands(&ctx.http, |commands| {
+            commands.create_application_command(|command| {
+                command
+                    .name("faucet-admin")
+                    .description("Administer the faucet")
+                    .default_member_permissions(Permissions::ADMINISTRATOR)
+                    .dm_permission(false)
+                    .create_option(|option| {
+                        option
+                            .name("selftest")
+                            .description(
+                                "Send a zero-value transaction to the faucet itself, \
+                                timing each stage of the dispense path",
+                            )
+                            .kind(CommandOptionType::SubCommand)
+                    })
+                    .create_option(|option| {
+                        option
+                            .name("events")
+                            .description(
+                                "Show how many events arrived per channel, and why any were ignored",
+                            )
+                            .kind(CommandOptionType::SubCommand)
+                    })
+                    .create_option(|option| {
+                        option
+                            .name("reaction")
+                            .description(
+                                "Choose the emoji the faucet reacts with, or turn a reaction off",
+                            )
+                            .kind(CommandOptionType::SubCommand)
+                            .create_sub_option(|kind| {
+                                kind.name("kind")
+                                    .description("Which reaction to change")
+                                    .kind(CommandOptionType::String)
+                                    .required(true);
+                                for reaction in Reaction::ALL {
+                                    kind.add_string_choice(reaction.name(), reaction.name());
+                                }
+                                kind
+                            })
+                            .create_sub_option(|emoji| {
+                                emoji
+                                    .name("emoji")
+                                    .description(
+                                        "The emoji to use (including this server's own), \
+                                        \"none\" to turn it off, or leave out for the default",
+                                    )
+                                    .kind(CommandOptionType::String)
+                            })
+                    })
+            });
+            commands.create_application_command(|command| {
+                command
+                    .name("faucet-donate")
+                    .description("Get an address to send unused testnet tokens back to the faucet")
+                    .dm_permission(false)
+            });
+            commands.create_application_command(|command| {
+                command
+                    .name("faucet-validate")
+                    .description("Check whether an address is valid, without being sent tokens")
+                    .dm_permission(false)
+                    .create_option(|option| {
+                        option
+                            .name("address")
+                            .description("The address to check")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Handle an invocation of one of the bot's slash commands.
+pub(super) async fn handle(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    store: &Store,
+    donations: &Donations,
+) {
+    let result = match command.data.name.as_str() {
+        "faucet-admin" => admin(ctx, command, store).await,
+        "faucet-donate" => respond(ctx, command, donations.message(store)).await,
+        "faucet-validate" => validate(ctx, command).await,
+        name => {
+            tracing::warn!(?name, "unknown command");
+            return;
+        }
+    };
+
+    if let Err(e) = result {
+        tracing::error!(error = ?e, command = ?command.data.name, "failed to handle command");
+    }
+}
+
+async fn admin(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    store: &Store,
+) -> anyhow::Result<()> {
+    // Discord hides the command from non-administrators by default, but servers can override that,
+    // so check again here
+    let is_admin = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false);
+    let refused = if is_admin {
+        ""
+    } else {
+        " (refused: not an administrator)"
+    };
+    audit::record(
+        "admin",
+        Some(command.user.id.0),
+        Some(command.channel_id.0),
+        None,
+        format!("{}{}", describe(command), refused),
+    );
+    if !is_admin {
+        return respond(
+            ctx,
+            command,
+            "Only server administrators can use this command.",
+        )
+        .await;
+    }
+
+    match command
+        .data
+        .options
+        .first()
+        .map(|option| option.name.as_str())
+    {
+        Some("selftest") => selftest(ctx, command).await,
+        Some("events") => respond(ctx, command, super::event_summary()).await,
+        Some("reaction") => reaction(ctx, command, store).await,
+        _ => respond(ctx, command, "Unknown subcommand.").await,
+    }
+}
+
+/// Describe an invocation of a command with its subcommand and options, as typed.
+fn describe(command: &ApplicationCommandInteraction) -> String {
+    let mut description = format!("/{}", command.data.name);
+    for subcommand in &command.data.options {
+        description.push(' ');
+        description.push_str(&subcommand.name);
+        for option in &subcommand.options {
+            if let Some(value) = &option.value {
+                description.push_str(&format!(" {}:{}", option.name, value));
+            }
+        }
+    }
+    description
+}
+
+/// Check the address given to the command, without dispensing to it or counting it against the
+/// rate limit.
+async fn validate(ctx: &Context, command: &ApplicationCommandInteraction) -> anyhow::Result<()> {
+    let address = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "address")
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or_default();
+    respond(ctx, command, responder::validate(address)).await
+}
+
+async fn selftest(ctx: &Context, command: &ApplicationCommandInteraction) -> anyhow::Result<()> {
+    // Proving takes a while, so acknowledge the command right away and fill in the result later
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| data.ephemeral(true))
+        })
+        .await?;
+
+    // Test the wallet of whichever chain dispenses in this channel
+    let control = {
+        let data = ctx.data.read().await;
+        data.get::<ProfileControls>()
+            .and_then(|controls| controls.get(&ChannelId(command.channel_id.0)))
+            .or_else(|| data.get::<ControlQueue>())
+            .expect("control queue exists")
+            .clone()
+    };
+    let (tx, rx) = oneshot::channel();
+    control
+        .send(Control::SelfTest(tx))
+        .await
+        .map_err(|_| anyhow::anyhow!("responder is not running"))?;
+    let report = rx.await?;
+
+    command
+        .edit_original_interaction_response(&ctx.http, |response| {
+            response.content(report.summary())
+        })
+        .await?;
+    Ok(())
+}
+
+async fn reaction(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    store: &Store,
+) -> anyhow::Result<()> {
+    let server_id = command
+        .guild_id
+        .map(|guild_id| ServerId(guild_id.0))
+        .ok_or_else(|| anyhow::anyhow!("reaction command used outside a server"))?;
+
+    // Find the value of each of the subcommand's options
+    let options = command
+        .data
+        .options
+        .first()
+        .map(|subcommand| subcommand.options.as_slice())
+        .unwrap_or_default();
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_str())
+            .map(str::trim)
+    };
+
+    let reaction: Reaction = option("kind").unwrap_or_default().parse()?;
+    let content = match option("emoji") {
+        None => {
+            store.reset_reaction(server_id, reaction)?;
+            format!(
+                "The {} reaction is back to the default, {}.",
+                reaction.name(),
+                reaction.default_emoji()
+            )
+        }
+        Some("none") => {
+            store.set_reaction(server_id, reaction, None)?;
+            format!("The {} reaction is turned off.", reaction.name())
+        }
+        Some(emoji) => {
+            if emoji.parse::<ReactionType>().is_err() {
+                return respond(
+                    ctx,
+                    command,
+                    format!(
+                        "`{}` isn't an emoji I can react with: use a standard emoji, or one of \
+                        this server's own.",
+                        emoji
+                    ),
+                )
+                .await;
+            }
+            store.set_reaction(server_id, reaction, Some(emoji.to_string()))?;
+            format!("The {} reaction is now {}.", reaction.name(), emoji)
+        }
+    };
+    respond(ctx, command, content).await
+}
+
+/// Respond to a command with a message only visible to the person who invoked it.
+async fn respond(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: impl ToString,
+) -> anyhow::Result<()> {
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await?;
+    Ok(())
+}
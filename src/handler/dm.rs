@@ -0,0 +1,197 @@
+use std::{fmt::Write, time::Duration};
+
+use chrono::Utc;
+use penumbra_asset::{asset, Value};
+use serenity::{client::Context, model::channel::Message};
+use tokio::sync::watch;
+
+use super::ControlQueue;
+use crate::{
+    audit, id,
+    report::Report,
+    responder::{Control, Request, RequestQueue},
+    rest, Lifecycle, Store,
+};
+
+/// How far back `stats` reports on, and `retry` looks for failures, unless told otherwise.
+const DEFAULT_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What the commands administrators can send by direct message need: who may send them, and
+/// handles on the parts of the faucet they control.
+pub struct Operators {
+    /// The users allowed to send commands.
+    users: Vec<id::UserId>,
+    /// The queue of requests to the responder, for retrying failures.
+    requests: RequestQueue,
+    /// Whether we're accepting requests, and how many are waiting.
+    lifecycle: Lifecycle,
+    /// The latest balance of each asset in the wallet.
+    balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+}
+
+impl Operators {
+    pub fn new(
+        users: Vec<id::UserId>,
+        requests: RequestQueue,
+        lifecycle: Lifecycle,
+        balances: watch::Receiver<Option<Vec<(asset::Id, u128)>>>,
+    ) -> Self {
+        Operators {
+            users,
+            requests,
+            lifecycle,
+            balances,
+        }
+    }
+
+    /// Whether the author of the message may send commands.
+    pub fn authorized(&self, message: &Message) -> bool {
+        self.users.contains(&id::UserId(message.author.id.0))
+    }
+}
+
+/// Carry out a command sent by an administrator in a direct message, and reply with the result.
+pub async fn handle(ctx: &Context, message: &Message, operators: &Operators, store: &Store) {
+    let text = message.content.trim();
+    audit::record(
+        "admin",
+        Some(message.author.id.0),
+        Some(message.channel_id.0),
+        Some(message.id.0),
+        format!("direct message: {}", text),
+    );
+
+    let mut words = text.split_whitespace();
+    let command = words.next().unwrap_or_default().to_lowercase();
+    let period = match words.next().map(humantime::parse_duration).transpose() {
+        Ok(period) => period.unwrap_or(DEFAULT_PERIOD),
+        Err(e) => return reply(ctx, message, format!("Invalid period: {}", e)).await,
+    };
+    let result = match command.as_str() {
+        "pause" => control(ctx, Control::Pause).await.map(|()| {
+            "Paused: requests will wait in the queue until you send `resume`.".to_string()
+        }),
+        "resume" => control(ctx, Control::Resume)
+            .await
+            .map(|()| "Resumed dispensing.".to_string()),
+        "stats" => Ok(stats(operators, store, period)),
+        "balance" => Ok(balance(operators)),
+        "retry" => retry(operators, store, period).await,
+        _ => Ok(HELP.to_string()),
+    };
+    let text = result.unwrap_or_else(|e| format!("Failed: {:#}", e));
+    reply(ctx, message, text).await
+}
+
+/// The commands administrators can send.
+const HELP: &str = "Commands:\n\
+    `pause`: stop dispensing, leaving requests waiting in the queue\n\
+    `resume`: dispense again after pausing\n\
+    `stats [period]`: what the faucet did in the last day (or period, like `1h`)\n\
+    `balance`: the wallet's balance of each asset\n\
+    `retry [period]`: try again to send to every address which failed in the last day (or \
+    period) and hasn't been sent tokens since";
+
+/// Send a control message to the responder.
+async fn control(ctx: &Context, control: Control) -> anyhow::Result<()> {
+    ctx.data
+        .read()
+        .await
+        .get::<ControlQueue>()
+        .expect("control queue exists")
+        .send(control)
+        .await
+        .map_err(|_| anyhow::anyhow!("responder is not running"))
+}
+
+fn stats(operators: &Operators, store: &Store, period: Duration) -> String {
+    let report = Report::generate(store, period, Utc::now());
+    format!(
+        "{}\n{} requests waiting.",
+        report.summary(),
+        operators.lifecycle.in_flight()
+    )
+}
+
+fn balance(operators: &Operators) -> String {
+    let cache = asset::Cache::with_known_assets();
+    match &*operators.balances.borrow() {
+        Some(balances) if !balances.is_empty() => {
+            let mut text = String::from("Balance:");
+            for (asset_id, amount) in balances {
+                let value = Value {
+                    amount: (*amount).into(),
+                    asset_id: *asset_id,
+                };
+                let _ = write!(text, "\n- {}", value.format(&cache));
+            }
+            text
+        }
+        Some(_) => "The wallet is empty.".to_string(),
+        None => "The balance hasn't been checked yet.".to_string(),
+    }
+}
+
+/// Send every address which failed within the period, and hasn't been sent tokens since, to the
+/// responder again, waiting to hear how it went.
+async fn retry(operators: &Operators, store: &Store, period: Duration) -> anyhow::Result<String> {
+    let since = Utc::now() - chrono::Duration::from_std(period)?;
+    let dead_letters = store.dead_letters(since);
+    if dead_letters.is_empty() {
+        return Ok("Nothing to retry.".to_string());
+    }
+
+    let mut responses = Vec::new();
+    let mut skipped = 0;
+    for failure in dead_letters {
+        // Without the original request's origin, we couldn't tell anyone how it went
+        let origin = match failure.origin() {
+            Some(origin) => origin,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+        tracing::info!(address = %failure.address, ?origin, "retrying failed request");
+        let in_flight = operators.lifecycle.begin();
+        let (response, request) = Request::new([failure.address.as_str()], origin);
+        operators
+            .requests
+            .send(request)
+            .await
+            .map_err(|_| anyhow::anyhow!("responder is not running"))?;
+        responses.push(async move {
+            let response = response.await;
+            drop(in_flight);
+            response
+        });
+    }
+
+    let (mut succeeded, mut failed) = (0, 0);
+    for response in futures::future::join_all(responses).await {
+        match response {
+            Ok(response) if !response.complete_failure() => succeeded += 1,
+            _ => failed += 1,
+        }
+    }
+    let mut text = format!(
+        "Retried {} failed requests: {} succeeded, {} failed again.",
+        succeeded + failed,
+        succeeded,
+        failed
+    );
+    if skipped > 0 {
+        let _ = write!(
+            text,
+            " Skipped {} without a recorded origin to answer.",
+            skipped
+        );
+    }
+    Ok(text)
+}
+
+async fn reply(ctx: &Context, message: &Message, text: String) {
+    if let Err(e) = rest::call("reply", || message.channel_id.say(&ctx.http, &text)).await {
+        tracing::error!(error = ?e, "failed to reply to direct message");
+    }
+}
@@ -0,0 +1,93 @@
+use penumbra_keys::Address;
+use serenity::model::channel::{Attachment, Message};
+
+use crate::rest;
+
+/// The text of a message to scan for addresses: its content with markdown code formatting
+/// undone, followed by the contents of any small text files attached to it, if we're asked to
+/// read those (up to the given size in bytes).
+pub async fn content(message: &Message, max_attachment_size: Option<u64>) -> String {
+    let mut content = normalize(&message.content);
+    let max_size = match max_attachment_size {
+        Some(max_size) => max_size,
+        None => return content,
+    };
+    for attachment in message
+        .attachments
+        .iter()
+        .filter(|attachment| is_text(attachment) && attachment.size <= max_size)
+    {
+        match rest::call("attachment", || attachment.download()).await {
+            Ok(bytes) => {
+                content.push('\n');
+                content.push_str(&normalize(&String::from_utf8_lossy(&bytes)));
+            }
+            Err(e) => {
+                tracing::warn!(error = ?e, filename = %attachment.filename, "failed to download attachment")
+            }
+        }
+    }
+    content
+}
+
+/// Whether the attachment is a plain text file, going by its type or else its name.
+fn is_text(attachment: &Attachment) -> bool {
+    match &attachment.content_type {
+        Some(content_type) => content_type.starts_with("text/plain"),
+        None => attachment.filename.ends_with(".txt"),
+    }
+}
+
+/// Undo the markdown code formatting addresses are often pasted in: remove the fences (and their
+/// language tags) and backticks, and rejoin addresses which were broken over several lines inside
+/// a code block (as terminals and some editors wrap them).
+fn normalize(content: &str) -> String {
+    let mut normalized = String::with_capacity(content.len());
+    let mut in_block = false;
+    for (index, piece) in content.split("```").enumerate() {
+        if index > 0 {
+            in_block = !in_block;
+            normalized.push('\n');
+        }
+        if !in_block {
+            normalized.push_str(&piece.replace('`', " "));
+            continue;
+        }
+
+        // The first line of a block is its language tag, if there's anything on it
+        let (tag, body) = piece.split_once('\n').unwrap_or(("", piece));
+        let body = if tag.trim().starts_with("penumbra") {
+            piece
+        } else {
+            body
+        };
+        let mut lines = body.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next() {
+            let mut line = line.to_string();
+            // A wrapped address carries on over lines with nothing else on them, until it's whole
+            while ends_with_partial_address(&line) {
+                match lines.peek() {
+                    Some(next) if !next.is_empty() && next.chars().all(is_bech32) => {
+                        line.push_str(next);
+                        lines.next();
+                    }
+                    _ => break,
+                }
+            }
+            normalized.push_str(&line);
+            normalized.push('\n');
+        }
+    }
+    normalized
+}
+
+/// Whether the line ends with the start of a Penumbra address which isn't a whole address yet.
+fn ends_with_partial_address(line: &str) -> bool {
+    let word = line.rsplit(char::is_whitespace).next().unwrap_or_default();
+    word.starts_with("penumbra") && word.chars().all(is_bech32) && word.parse::<Address>().is_err()
+}
+
+/// Whether the character could be part of the data of a bech32 address.
+fn is_bech32(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit()
+}
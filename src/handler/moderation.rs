@@ -0,0 +1,158 @@
+use std::fmt::Write;
+
+use chrono::Utc;
+use serenity::{
+    client::Context,
+    model::channel::{GuildChannel, Message},
+};
+
+use crate::{audit, id, rest, store::Ban, Store};
+
+/// The prefix of the commands moderators can post in a channel.
+const PREFIX: &str = "!faucet";
+
+/// Carry out a moderation command posted in a channel (`!faucet ban`, `!faucet unban`, or
+/// `!faucet bans`), replying with the result. Returns `false` if the message isn't one, so that
+/// it's handled like any other.
+pub(super) async fn handle(
+    ctx: &Context,
+    message: &Message,
+    guild_channel: &GuildChannel,
+    store: &Store,
+) -> bool {
+    let mut words = message.content.split_whitespace();
+    if !words
+        .next()
+        .map_or(false, |word| word.eq_ignore_ascii_case(PREFIX))
+    {
+        return false;
+    }
+    let command = match words.next().map(str::to_lowercase) {
+        Some(command) if ["ban", "unban", "bans"].contains(&command.as_str()) => command,
+        _ => return false,
+    };
+
+    // Anyone who can time out or ban members may ban them from the faucet too
+    let is_moderator = guild_channel
+        .permissions_for_user(ctx, message.author.id)
+        .map(|permissions| permissions.moderate_members() || permissions.ban_members())
+        .unwrap_or(false);
+    if !is_moderator {
+        reply(ctx, message, "Only moderators can do that.".to_string()).await;
+        return true;
+    }
+    audit::record(
+        "admin",
+        Some(message.author.id.0),
+        Some(message.channel_id.0),
+        Some(message.id.0),
+        format!("moderation command: {}", message.content.trim()),
+    );
+
+    let words = words.collect::<Vec<_>>();
+    let result = match command.as_str() {
+        "ban" => ban(message, store, &words),
+        "unban" => unban(store, &words),
+        _ => Ok(bans(store)),
+    };
+    let text = result.unwrap_or_else(|e| format!("Failed: {:#}", e));
+    reply(ctx, message, text).await;
+    true
+}
+
+/// How to use the ban command.
+const BAN_USAGE: &str = "Usage: `!faucet ban @user [duration] [reason]`, like \
+    `!faucet ban @user 7d farming`; without a duration, the ban lasts until lifted with \
+    `!faucet unban @user`.";
+
+/// Ban the mentioned user, for a duration if one follows the mention.
+fn ban(message: &Message, store: &Store, words: &[&str]) -> anyhow::Result<String> {
+    let (user_id, rest) = match words.split_first() {
+        Some((user, rest)) => match parse_user(user) {
+            Some(user_id) => (user_id, rest),
+            None => return Ok(BAN_USAGE.to_string()),
+        },
+        None => return Ok(BAN_USAGE.to_string()),
+    };
+    let now = Utc::now();
+    let (until, rest) = match rest
+        .split_first()
+        .and_then(|(word, rest)| Some((humantime::parse_duration(word).ok()?, rest)))
+    {
+        Some((duration, rest)) => (Some(now + chrono::Duration::from_std(duration)?), rest),
+        None => (None, rest),
+    };
+    let reason = Some(rest.join(" ")).filter(|reason| !reason.is_empty());
+
+    store.ban(Ban {
+        user_id: user_id.0,
+        reason,
+        until,
+        moderator_id: Some(message.author.id.0),
+        time: now,
+    })?;
+    tracing::info!(user_id = user_id.0, ?until, "banned user");
+    Ok(match until {
+        Some(until) => format!(
+            "Banned <@{}> from the faucet until <t:{}:f>.",
+            user_id,
+            until.timestamp()
+        ),
+        None => format!("Banned <@{}> from the faucet until unbanned.", user_id),
+    })
+}
+
+/// Lift the mentioned user's ban.
+fn unban(store: &Store, words: &[&str]) -> anyhow::Result<String> {
+    let user_id = match words.first().and_then(|user| parse_user(user)) {
+        Some(user_id) => user_id,
+        None => return Ok("Usage: `!faucet unban @user`".to_string()),
+    };
+    Ok(if store.unban(user_id)? {
+        tracing::info!(user_id = user_id.0, "unbanned user");
+        format!("Lifted the ban on <@{}>.", user_id)
+    } else {
+        format!("<@{}> isn't banned.", user_id)
+    })
+}
+
+/// List the bans in effect.
+fn bans(store: &Store) -> String {
+    let bans = store.bans(Utc::now());
+    if bans.is_empty() {
+        return "No one is banned.".to_string();
+    }
+    let mut text = String::from("Banned:");
+    for ban in bans {
+        let _ = write!(text, "\n- <@{}>", ban.user_id);
+        match ban.until {
+            Some(until) => {
+                let _ = write!(text, " until <t:{}:f>", until.timestamp());
+            }
+            None => text.push_str(" until unbanned"),
+        }
+        if let Some(moderator_id) = ban.moderator_id {
+            let _ = write!(text, ", by <@{}>", moderator_id);
+        }
+        if let Some(reason) = &ban.reason {
+            let _ = write!(text, ": {}", reason);
+        }
+    }
+    text
+}
+
+/// Parse a user mentioned like `<@123>` (or `<@!123>`), or given by id.
+fn parse_user(word: &str) -> Option<id::UserId> {
+    let id = word
+        .strip_prefix("<@")
+        .and_then(|word| word.strip_suffix('>'))
+        .map(|word| word.trim_start_matches('!'))
+        .unwrap_or(word);
+    id.parse().ok().map(id::UserId)
+}
+
+async fn reply(ctx: &Context, message: &Message, text: String) {
+    if let Err(e) = rest::call("reply", || message.reply(&ctx.http, &text)).await {
+        tracing::error!(error = ?e, "failed to reply to moderation command");
+    }
+}
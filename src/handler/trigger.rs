@@ -0,0 +1,43 @@
+use serenity::model::{channel::Message, id::UserId};
+
+/// What a message has to do to be scanned for addresses, for channels where addresses come up in
+/// conversation: mention the bot, or start with one of the prefixes. Without either, every message
+/// is scanned.
+#[derive(Debug, Clone, Default)]
+pub struct Trigger {
+    /// Whether mentioning the bot triggers a request.
+    mention: bool,
+    /// The prefixes which trigger a request, like `!faucet`, compared ignoring case.
+    prefixes: Vec<String>,
+}
+
+impl Trigger {
+    pub fn new(mention: bool, prefixes: Vec<String>) -> Self {
+        Trigger {
+            mention,
+            prefixes: prefixes
+                .into_iter()
+                .map(|prefix| prefix.trim().to_lowercase())
+                .filter(|prefix| !prefix.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Whether messages have to mention the bot to be scanned.
+    pub fn requires_mention(&self) -> bool {
+        self.mention
+    }
+
+    /// Whether the message should be scanned for addresses, given the bot's own user id.
+    pub fn admits(&self, message: &Message, self_id: UserId) -> bool {
+        if !self.mention && self.prefixes.is_empty() {
+            return true;
+        }
+        let content = message.content.trim_start().to_lowercase();
+        (self.mention && message.mentions_user_id(self_id))
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| content.starts_with(prefix.as_str()))
+    }
+}
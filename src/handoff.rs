@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use serenity::{
+    cache::Cache,
+    http::Http,
+    model::{
+        channel::{Channel, Message},
+        id::{ChannelId, GuildId, MessageId},
+    },
+};
+
+use crate::{
+    responder::{Origin, Routes},
+    rest,
+    store::Undelivered,
+    transport::{self, Reply},
+    Store,
+};
+
+/// Process the requests left pending by the previous instance when it handed off to this one,
+/// replying to each original message as if it had been handled live, after delivering any replies
+/// it didn't get around to.
+///
+/// Each request stays saved until it's answered, so that if this instance stops too before getting
+/// to all of them, the rest are left for the next.
+pub async fn resume(
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    requests: Routes,
+    store: Store,
+) -> anyhow::Result<()> {
+    let outbox = store.outbox();
+    if !outbox.is_empty() {
+        tracing::info!(
+            count = outbox.len(),
+            "delivering replies left undelivered by previous instance"
+        );
+    }
+    for undelivered in outbox {
+        deliver(&http, &undelivered).await;
+        store.remove_undelivered(undelivered.origin().message_id)?;
+    }
+
+    let pending = store.pending_requests();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        count = pending.len(),
+        "resuming requests handed off by previous instance"
+    );
+
+    for entry in pending {
+        let origin = entry.origin();
+        let (response, request) = entry.request();
+        if requests
+            .queue(origin.channel_id)
+            .send(request)
+            .await
+            .is_err()
+        {
+            tracing::info!("responder stopped, leaving the rest of the handed-off requests");
+            return Ok(());
+        }
+        let response = match response.await {
+            Ok(response) => response,
+            // The responder handed it off again, saving it for the next instance
+            Err(_) => {
+                tracing::info!("handing off again, leaving the rest of the handed-off requests");
+                return Ok(());
+            }
+        };
+        store.answer_pending(Undelivered::new(origin, response.plain_summary()))?;
+        if let Err(e) = store.checkpoint(origin.channel_id, origin.message_id) {
+            tracing::error!(error = ?e, "failed to record checkpoint");
+        }
+
+        // Reply to the original message, if it's still there
+        if let Some((message, guild_id)) = original(&http, origin).await {
+            let reply = Reply::new(http.clone(), cache.clone(), message, guild_id);
+            transport::deliver_all(&[&reply], &response).await;
+        }
+        store.remove_undelivered(origin.message_id)?;
+    }
+
+    Ok(())
+}
+
+/// Deliver a reply saved in the outbox, logging rather than failing if we can't.
+async fn deliver(http: &Arc<Http>, undelivered: &Undelivered) {
+    let origin = undelivered.origin();
+    let message = match original(http, origin).await {
+        Some((message, _)) => message,
+        None => return,
+    };
+    if let Err(e) = rest::call("reply", || {
+        message.reply_ping(http.clone(), undelivered.reply.clone())
+    })
+    .await
+    {
+        tracing::warn!(error = ?e, ?origin, "failed to deliver handed-off reply");
+    }
+}
+
+/// The message a handed-off request was made in, and the server it's in, if they're still there.
+async fn original(http: &Arc<Http>, origin: Origin) -> Option<(Message, GuildId)> {
+    let channel_id = ChannelId(origin.channel_id.0);
+    let message_id = MessageId(origin.message_id.0);
+    let message = match rest::call("fetch", || channel_id.message(http.as_ref(), message_id)).await
+    {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::warn!(error = ?e, ?origin, "could not fetch handed-off message to reply to");
+            return None;
+        }
+    };
+    match rest::call("fetch", || channel_id.to_channel(http.as_ref())).await {
+        Ok(Channel::Guild(channel)) => Some((message, channel.guild_id)),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!(error = ?e, ?origin, "could not fetch channel of handed-off message");
+            None
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use penumbra_asset::asset;
+use penumbra_keys::Address;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinHandle,
+};
+
+use crate::{
+    audit,
+    dashboard::Dashboard,
+    id, metrics,
+    responder::{Origin, Request, RequestQueue},
+    transport::{self, Email, Smtp, Transport},
+    view::SyncProgress,
+    Lifecycle, Store,
+};
+
+mod pow;
+pub use pow::ProofOfWork;
+
+/// The environment variable holding the token which callers of the HTTP API must present, if any.
+pub const TOKEN_VAR: &str = "GALILEO_HTTP_TOKEN";
+
+/// The counter of requests made to the HTTP API, by outcome.
+pub const REQUESTS: &str = "galileo_http_requests_total";
+
+/// Rate limits on requests made through the APIs rather than in chat, shared by all of them.
+pub struct Limits {
+    /// Persistent state, whose dispense ledger is checked for addresses funded recently.
+    store: Store,
+    /// The minimum duration between requests from the same IP address.
+    pub ip_rate_limit: Duration,
+    /// The minimum duration between dispensing tokens to the same Penumbra address.
+    pub address_rate_limit: Duration,
+    /// When each IP address last made a request which was honored.
+    recent: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl Limits {
+    pub fn new(store: Store, ip_rate_limit: Duration, address_rate_limit: Duration) -> Self {
+        Limits {
+            store,
+            ip_rate_limit,
+            address_rate_limit,
+            recent: Default::default(),
+        }
+    }
+
+    /// How much longer until the address may be sent tokens again, according to the dispense
+    /// ledger.
+    pub fn address_wait(&self, address: &Address) -> Option<Duration> {
+        let address = address.to_string();
+        let last = self
+            .store
+            .dispenses()
+            .into_iter()
+            .filter(|dispense| dispense.address == address)
+            .map(|dispense| dispense.time)
+            .max()?;
+        let elapsed = (Utc::now() - last).to_std().unwrap_or_default();
+        self.address_rate_limit.checked_sub(elapsed)
+    }
+
+    /// Record a request from the IP address, returning how much longer it must wait if it made
+    /// one too recently.
+    pub fn ip_wait(&self, ip: IpAddr) -> Option<Duration> {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|_, last| last.elapsed() < self.ip_rate_limit);
+        if let Some(last) = recent.get(&ip) {
+            return self.ip_rate_limit.checked_sub(last.elapsed());
+        }
+        recent.insert(ip, Instant::now());
+        None
+    }
+
+    /// Don't count the last request from the IP address against it, because nothing was sent.
+    pub fn release(&self, ip: IpAddr) {
+        self.recent.lock().unwrap().remove(&ip);
+    }
+}
+
+/// The origin recorded for a request made through an API: it isn't made in any channel or by any
+/// chat user, and the message id only needs to be unique, and to tell when the request was made.
+/// It doubles as the id of the request.
+pub fn origin() -> Origin {
+    Origin {
+        user_id: id::UserId(0),
+        channel_id: id::ChannelId(0),
+        message_id: id::MessageId(
+            id::MessageId::at(Utc::now()).0 | rand::thread_rng().gen_range(0..1 << 22),
+        ),
+    }
+}
+
+/// An HTTP API for requesting tokens without going through Discord, feeding the same queue as the
+/// bot.
+pub struct Api {
+    /// The queue of requests for the responder.
+    requests: RequestQueue,
+    /// Whether we're still accepting requests, and which are in flight.
+    lifecycle: Lifecycle,
+    /// Per-IP and per-address rate limits.
+    limits: Arc<Limits>,
+    /// The token callers must present as `Authorization: Bearer <token>`, if any.
+    token: Option<String>,
+    /// Whether to take the caller's IP address from `X-Forwarded-For`, when behind a reverse
+    /// proxy.
+    trust_proxy: bool,
+    /// Where to send email receipts from, if callers may ask for them.
+    smtp: Option<Smtp>,
+    /// Where else to deliver the result of every request.
+    transports: Vec<Box<dyn Transport>>,
+    /// The proof of work callers must do for each request, if any.
+    pow: Option<ProofOfWork>,
+}
+
+/// The body of a request to dispense tokens.
+#[derive(Debug, Clone, Deserialize)]
+struct DispenseRequest {
+    /// The address to send tokens to.
+    address: String,
+    /// An email address to send the receipt to.
+    #[serde(default)]
+    email: Option<String>,
+    /// The challenge fetched from `GET /challenge`, when proof of work is required.
+    #[serde(default)]
+    challenge: Option<String>,
+    /// The solution to the challenge, when proof of work is required.
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+type Reply = (StatusCode, Json<serde_json::Value>);
+
+/// An error reply, counted under the given outcome.
+fn error(status: StatusCode, outcome: &'static str, message: impl Into<String>) -> Reply {
+    metrics::increment(REQUESTS, &[("outcome", outcome.to_string())]);
+    (status, Json(json!({ "error": message.into() })))
+}
+
+/// Whether a call presents the token as `Authorization: Bearer <token>`, compared in constant time
+/// so that how long a wrong token takes to be turned away gives nothing away about the right one.
+pub fn presents_token(headers: &HeaderMap, token: &str) -> bool {
+    let expected = format!("Bearer {}", token);
+    headers.get("authorization").map_or(false, |provided| {
+        bool::from(provided.as_bytes().ct_eq(expected.as_bytes()))
+    })
+}
+
+/// Reject a request for being rate-limited, recording it in the audit trail.
+fn rate_limited(address: &str, message: String) -> Reply {
+    audit::record(
+        "rejected",
+        None,
+        None,
+        None,
+        format!("HTTP request for {}: {}", address, message),
+    );
+    error(StatusCode::TOO_MANY_REQUESTS, "rate-limited", message)
+}
+
+impl Api {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        requests: RequestQueue,
+        lifecycle: Lifecycle,
+        limits: Arc<Limits>,
+        token: Option<String>,
+        trust_proxy: bool,
+        smtp: Option<Smtp>,
+        transports: Vec<Box<dyn Transport>>,
+        pow: Option<ProofOfWork>,
+    ) -> Self {
+        Api {
+            requests,
+            lifecycle,
+            limits,
+            token,
+            trust_proxy,
+            smtp,
+            transports,
+            pow,
+        }
+    }
+
+    /// Serve the API, and the status dashboard, on the given address until it fails.
+    pub async fn serve(self, bind: SocketAddr, dashboard: Dashboard) -> anyhow::Result<()> {
+        let app = Router::new()
+            .route("/dispense", post(dispense))
+            .route("/challenge", get(challenge))
+            .route("/ready", get(ready))
+            .route("/metrics", get(scrape))
+            .with_state(Arc::new(self))
+            .merge(dashboard.router());
+        tracing::info!(%bind, "serving HTTP API");
+        axum::Server::try_bind(&bind)?
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+        Ok(())
+    }
+
+    /// The IP address the request came from.
+    fn caller(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        if self.trust_proxy {
+            // The proxy appends the address it saw, so the last one is the one to trust
+            let forwarded = headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+        peer.ip()
+    }
+}
+
+/// Answers `GET /ready` on the HTTP API's address while the faucet synchronizes at startup (with
+/// how far along it is), until the API itself takes the address over.
+pub struct Startup {
+    stop: oneshot::Sender<()>,
+    server: JoinHandle<anyhow::Result<()>>,
+}
+
+impl Startup {
+    pub fn serve(bind: SocketAddr, progress: watch::Receiver<SyncProgress>) -> Self {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let app = Router::new()
+            .route("/ready", get(syncing))
+            .with_state(progress);
+        let server = tokio::spawn(async move {
+            tracing::info!(%bind, "serving readiness checks while syncing");
+            axum::Server::try_bind(&bind)?
+                .serve(app.into_make_service())
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await?;
+            Ok(())
+        });
+        Startup { stop, server }
+    }
+
+    /// Stop answering, releasing the address.
+    pub async fn stop(self) -> anyhow::Result<()> {
+        let _ = self.stop.send(());
+        self.server.await?
+    }
+}
+
+/// Handle `GET /ready` while synchronizing at startup: not ready yet, and how far along.
+async fn syncing(State(progress): State<watch::Receiver<SyncProgress>>) -> Reply {
+    let progress = *progress.borrow();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "ready": false,
+            "sync_height": progress.sync_height,
+            "latest_height": progress.latest_height,
+            "percent": progress.percent(),
+            "eta_secs": progress.eta.map(|eta| eta.as_secs()),
+        })),
+    )
+}
+
+/// Handle `GET /ready` once the faucet is up: ready, until it starts shutting down.
+async fn ready(State(api): State<Arc<Api>>) -> Reply {
+    if api.lifecycle.is_accepting() {
+        (StatusCode::OK, Json(json!({ "ready": true })))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "ready": false, "stopping": true })),
+        )
+    }
+}
+
+/// Handle `GET /metrics`, rendering every metric for Prometheus to scrape.
+async fn scrape() -> String {
+    metrics::render()
+}
+
+/// Handle `GET /challenge`, issuing a challenge to solve before requesting tokens.
+async fn challenge(State(api): State<Arc<Api>>) -> Reply {
+    let pow = match &api.pow {
+        Some(pow) => pow,
+        None => {
+            return error(
+                StatusCode::NOT_FOUND,
+                "invalid",
+                "this faucet doesn't require proof of work",
+            )
+        }
+    };
+    (
+        StatusCode::OK,
+        Json(json!({
+            "challenge": pow.issue(),
+            "difficulty": pow.difficulty(),
+            "expires_in_secs": pow::CHALLENGE_TTL.as_secs(),
+        })),
+    )
+}
+
+/// Handle `POST /dispense`, replying once the tokens are sent (or sending failed).
+async fn dispense(
+    State(api): State<Arc<Api>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(body): Json<DispenseRequest>,
+) -> Reply {
+    if let Some(token) = &api.token {
+        if !presents_token(&headers, token) {
+            return error(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "missing or invalid token",
+            );
+        }
+    }
+    if !api.lifecycle.is_accepting() {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "stopping",
+            "the faucet is shutting down, try again shortly",
+        );
+    }
+
+    let address_text = body.address.trim();
+    let address = match address_text.parse::<Address>() {
+        Ok(address) => address,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "invalid", "invalid address"),
+    };
+    if let Some(pow) = &api.pow {
+        let solved = match (&body.challenge, &body.nonce) {
+            (Some(challenge), Some(nonce)) => pow.verify(challenge, address_text, nonce),
+            _ => Err("proof of work required: fetch a challenge from GET /challenge"),
+        };
+        if let Err(message) = solved {
+            return error(StatusCode::FORBIDDEN, "unsolved", message);
+        }
+    }
+    let email = match (&body.email, &api.smtp) {
+        (None, _) => None,
+        (Some(_), None) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "invalid",
+                "email receipts aren't enabled on this faucet",
+            )
+        }
+        (Some(to), Some(smtp)) => match Email::new(smtp.clone(), to) {
+            Ok(email) => Some(email),
+            Err(e) => return error(StatusCode::BAD_REQUEST, "invalid", e.to_string()),
+        },
+    };
+
+    if let Some(wait) = api.limits.address_wait(&address) {
+        return rate_limited(
+            address_text,
+            format!(
+                "this address was sent tokens recently, try again in {}",
+                humantime::format_duration(Duration::from_secs(wait.as_secs()))
+            ),
+        );
+    }
+    let ip = api.caller(peer, &headers);
+    if let Some(wait) = api.limits.ip_wait(ip) {
+        return rate_limited(
+            address_text,
+            format!(
+                "too many requests, try again in {}",
+                humantime::format_duration(Duration::from_secs(wait.as_secs()))
+            ),
+        );
+    }
+
+    let origin = origin();
+    tracing::info!(%ip, address = %address_text, "request over HTTP");
+
+    let _in_flight = api.lifecycle.begin();
+    let (response, request) = Request::new([address_text], origin);
+    if api.requests.send(request).await.is_err() {
+        api.limits.release(ip);
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "stopping",
+            "the faucet is shutting down, try again shortly",
+        );
+    }
+    let response = match response.await {
+        Ok(response) => response,
+        Err(_) => {
+            return error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "stopping",
+                "the faucet is shutting down, and will send the tokens once it restarts",
+            )
+        }
+    };
+
+    // Only count the request against the caller if they actually received something
+    if response.succeeded().is_empty() && response.withdrawn().is_empty() {
+        api.limits.release(ip);
+    }
+    let mut transports: Vec<&dyn Transport> = api
+        .transports
+        .iter()
+        .map(|transport| &**transport)
+        .collect();
+    if let Some(email) = &email {
+        transports.push(email);
+    }
+    transport::deliver_all(&transports, &response).await;
+
+    let cache = asset::Cache::with_known_assets();
+    let sent = response
+        .succeeded()
+        .iter()
+        .map(|(address, receipt)| (address.to_string(), receipt))
+        .chain(
+            response
+                .withdrawn()
+                .iter()
+                .map(|(address, receipt)| (address.clone(), receipt)),
+        )
+        .map(|(address, receipt)| {
+            json!({
+                "address": address,
+                "transaction": receipt.id.to_string(),
+                "height": receipt.height,
+                "values": receipt
+                    .values
+                    .iter()
+                    .map(|value| value.format(&cache))
+                    .collect::<Vec<_>>(),
+                "simulated": receipt.simulated,
+            })
+        })
+        .collect::<Vec<_>>();
+    let failed = response
+        .failed()
+        .iter()
+        .map(|(address, error)| (address.to_string(), error.clone()))
+        .chain(response.failed_withdrawals().iter().cloned())
+        .map(|(address, error)| json!({ "address": address, "error": error }))
+        .collect::<Vec<_>>();
+    let (status, outcome) = if sent.is_empty() {
+        (StatusCode::INTERNAL_SERVER_ERROR, "failed")
+    } else {
+        (StatusCode::OK, "sent")
+    };
+    metrics::increment(REQUESTS, &[("outcome", outcome.to_string())]);
+    (
+        status,
+        Json(json!({
+            "sent": sent,
+            "failed": failed,
+            "message": response.plain_summary(),
+        })),
+    )
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeSet,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// How long a challenge can be solved for after it's issued.
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The reason a challenge which we didn't issue, or which expired, is refused.
+const UNKNOWN: &str = "unknown or expired challenge, fetch a new one";
+
+/// A hashcash-style proof of work required of callers of the HTTP API, so that scripting requests
+/// from many IP addresses costs CPU time for each one.
+///
+/// Callers fetch a challenge, then find a nonce for which the SHA-256 hash of
+/// `<challenge>:<address>:<nonce>` starts with at least `difficulty` zero bits. Each challenge can
+/// only be used once, and only for a short while.
+///
+/// Challenges are `<issued>.<salt>.<mac>`, signed rather than stored, so that issuing them keeps
+/// no state which asking for lots of them could fill up: only challenges already used are
+/// remembered, until they expire, and each of those took a solved proof of work.
+pub struct ProofOfWork {
+    /// How many leading zero bits the hash must have.
+    difficulty: u32,
+    /// The key challenges are signed with, which lasts as long as the process.
+    key: [u8; 32],
+    /// The challenges used and not yet expired, by when they were issued (in seconds since the
+    /// Unix epoch).
+    used: Mutex<BTreeSet<(u64, String)>>,
+}
+
+impl ProofOfWork {
+    pub fn new(difficulty: u32) -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        ProofOfWork {
+            difficulty,
+            key,
+            used: Default::default(),
+        }
+    }
+
+    /// How many leading zero bits a solution's hash must have.
+    pub fn difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    /// Issue a new challenge.
+    pub fn issue(&self) -> String {
+        self.issue_at(now())
+    }
+
+    fn issue_at(&self, issued: u64) -> String {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let salt = hex::encode(bytes);
+        format!("{}.{}.{}", issued, salt, self.sign(issued, &salt))
+    }
+
+    /// Check a solution to a challenge for a request to send to the address, using up the
+    /// challenge if it's solved.
+    pub fn verify(&self, challenge: &str, address: &str, nonce: &str) -> Result<(), &'static str> {
+        self.verify_at(challenge, address, nonce, now())
+    }
+
+    fn verify_at(
+        &self,
+        challenge: &str,
+        address: &str,
+        nonce: &str,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        let issued = match challenge.split('.').collect::<Vec<_>>().as_slice() {
+            [issued, salt, mac] => {
+                let issued: u64 = issued.parse().map_err(|_| UNKNOWN)?;
+                let expected = self.sign(issued, salt);
+                if !bool::from(expected.as_bytes().ct_eq(mac.as_bytes())) {
+                    return Err(UNKNOWN);
+                }
+                issued
+            }
+            _ => return Err(UNKNOWN),
+        };
+        if now.saturating_sub(issued) >= CHALLENGE_TTL.as_secs() {
+            return Err(UNKNOWN);
+        }
+        let hash = Sha256::digest(format!("{}:{}:{}", challenge, address, nonce));
+        if leading_zero_bits(&hash) < self.difficulty {
+            return Err("proof of work doesn't meet the difficulty");
+        }
+
+        let mut used = self.used.lock().unwrap();
+        // Challenges issued before the cutoff have expired, so needn't be remembered any more
+        let cutoff = now.saturating_sub(CHALLENGE_TTL.as_secs());
+        if used
+            .iter()
+            .next()
+            .map_or(false, |(issued, _)| *issued < cutoff)
+        {
+            *used = used.split_off(&(cutoff, String::new()));
+        }
+        if !used.insert((issued, challenge.to_string())) {
+            return Err("that challenge was already used, fetch a new one");
+        }
+        Ok(())
+    }
+
+    /// The signature over a challenge issued at the given time with the given salt, in hex.
+    fn sign(&self, issued: u64, salt: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(format!("{}.{}", issued, salt).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The number of zero bits at the start of the hash.
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A nonce solving the challenge for the address.
+    fn solve(pow: &ProofOfWork, challenge: &str, address: &str) -> String {
+        (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}:{}:{}", challenge, address, nonce));
+                leading_zero_bits(&hash) >= pow.difficulty
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff, 0x00]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0xff]), 7);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10, 0x00]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+        assert_eq!(leading_zero_bits(&[]), 0);
+    }
+
+    #[test]
+    fn accepts_a_solution_once() {
+        let pow = ProofOfWork::new(8);
+        let challenge = pow.issue();
+        let nonce = solve(&pow, &challenge, "address");
+        assert_eq!(pow.verify(&challenge, "address", &nonce), Ok(()));
+        assert!(pow.verify(&challenge, "address", &nonce).is_err());
+    }
+
+    #[test]
+    fn rejects_challenges_it_did_not_issue() {
+        let pow = ProofOfWork::new(0);
+        let challenge = pow.issue();
+        let (issued, rest) = challenge.split_once('.').unwrap();
+        let later = format!("{}.{}", issued.parse::<u64>().unwrap() + 60, rest);
+        assert_eq!(pow.verify(&later, "address", "0"), Err(UNKNOWN));
+        assert_eq!(pow.verify("garbage", "address", "0"), Err(UNKNOWN));
+        let other = ProofOfWork::new(0).issue();
+        assert_eq!(pow.verify(&other, "address", "0"), Err(UNKNOWN));
+    }
+
+    #[test]
+    fn rejects_expired_challenges() {
+        let pow = ProofOfWork::new(0);
+        let issued = now() - CHALLENGE_TTL.as_secs();
+        let challenge = pow.issue_at(issued);
+        assert_eq!(pow.verify(&challenge, "address", "0"), Err(UNKNOWN));
+    }
+
+    #[test]
+    fn forgets_used_challenges_once_they_expire() {
+        let pow = ProofOfWork::new(0);
+        let start = now();
+        let old = pow.issue_at(start);
+        assert_eq!(pow.verify_at(&old, "address", "0", start), Ok(()));
+        let later = start + CHALLENGE_TTL.as_secs() + 1;
+        let new = pow.issue_at(later);
+        assert_eq!(pow.verify_at(&new, "address", "0", later), Ok(()));
+        assert_eq!(pow.used.lock().unwrap().len(), 1);
+    }
+}
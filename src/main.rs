@@ -1,12 +1,14 @@
 #![recursion_limit = "256"]
+// The faucet engine lives in `galileo-core`; this crate has the chat frontends, and the CLI
+pub use galileo_core::{
+    audit, id, intake, lifecycle, locale, metrics, report, responder, sender, simulate, store,
+    Lifecycle, Locale, Responder, Sender, Store,
+};
+
 mod handler;
 pub use handler::Handler;
 
-mod responder;
-pub use responder::Responder;
-
-mod sender;
-pub use sender::Sender;
+mod matrix;
 
 mod opt;
 pub use opt::{gather_history, Opt};
@@ -15,7 +17,40 @@ mod wallet;
 pub use wallet::Wallet;
 
 mod catchup;
-pub use catchup::Catchup;
+pub use catchup::{Catchup, Pacing};
+
+mod handoff;
+
+mod http;
+
+mod dashboard;
+
+mod grpc;
+
+mod view;
+
+mod node;
+
+mod notice;
+
+mod rest;
+
+mod custody;
+
+mod cooldown;
+
+mod admin;
+
+mod donate;
+
+mod profile;
+pub use profile::Profile;
+
+mod registry;
+
+mod systemd;
+
+mod transport;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
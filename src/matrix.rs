@@ -0,0 +1,450 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use crate::{
+    id,
+    intake::{self, ChatPlatform, Incoming, Intake, Reaction},
+    transport::{MatrixReply, Transport},
+    Store,
+};
+
+/// How long the homeserver may hold a sync request open waiting for new events.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many recent messages to remember the ids of, so that edits to them can be handled.
+const SEEN_MESSAGES: usize = 1000;
+
+/// A minimal client for the Matrix client-server API, with just what the faucet needs.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    homeserver: Url,
+    access_token: String,
+}
+
+/// The part of a `/sync` response we care about: messages in rooms we've joined.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Sync {
+    next_batch: String,
+    #[serde(default)]
+    rooms: SyncRooms,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct SyncRooms {
+    #[serde(default)]
+    join: BTreeMap<String, JoinedRoom>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    event_id: String,
+    sender: String,
+    origin_server_ts: i64,
+    #[serde(default)]
+    content: serde_json::Value,
+}
+
+impl Client {
+    pub fn new(homeserver: Url, access_token: String) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            homeserver,
+            access_token,
+        }
+    }
+
+    /// The URL of an endpoint of the client-server API, given its path segments (which are
+    /// escaped, so room ids and aliases can be passed as they are).
+    fn url<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Url> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|()| anyhow::anyhow!("invalid homeserver URL: {}", self.homeserver))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// Make an authenticated request, returning the JSON response.
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+        body: Option<serde_json::Value>,
+    ) -> anyhow::Result<serde_json::Value> {
+        let mut request = request.bearer_auth(&self.access_token);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            anyhow::bail!("homeserver returned {}: {}", status, text);
+        }
+        serde_json::from_str(&text).context("could not parse homeserver response")
+    }
+
+    /// The user id the access token belongs to.
+    pub async fn whoami(&self) -> anyhow::Result<String> {
+        let response = self
+            .request(self.http.get(self.url(["account", "whoami"])?), None)
+            .await?;
+        response["user_id"]
+            .as_str()
+            .map(String::from)
+            .context("homeserver didn't say who we are")
+    }
+
+    /// Join a room by its id or alias, returning its id.
+    pub async fn join(&self, room: &str) -> anyhow::Result<String> {
+        let response = self
+            .request(self.http.post(self.url(["join", room])?), Some(json!({})))
+            .await?;
+        response["room_id"]
+            .as_str()
+            .map(String::from)
+            .context("homeserver didn't say which room we joined")
+    }
+
+    /// Wait for events since the given point in the stream (or get the current state, if none).
+    async fn sync(&self, since: Option<&str>) -> anyhow::Result<Sync> {
+        let mut url = self.url(["sync"])?;
+        url.query_pairs_mut()
+            .append_pair("timeout", &SYNC_TIMEOUT.as_millis().to_string());
+        if let Some(since) = since {
+            url.query_pairs_mut().append_pair("since", since);
+        }
+        let response = self.request(self.http.get(url), None).await?;
+        serde_json::from_value(response).context("could not parse sync response")
+    }
+
+    /// Send an event to a room, returning its id.
+    pub async fn send(
+        &self,
+        room_id: &str,
+        kind: &str,
+        content: serde_json::Value,
+    ) -> anyhow::Result<String> {
+        let txn_id = format!("galileo-{}", rand::thread_rng().gen::<u64>());
+        let url = self.url(["rooms", room_id, "send", kind, &txn_id])?;
+        let response = self.request(self.http.put(url), Some(content)).await?;
+        response["event_id"]
+            .as_str()
+            .map(String::from)
+            .context("homeserver didn't say which event we sent")
+    }
+
+    /// Reply to a message with some text, sent as a notice (the convention for bots), returning
+    /// the reply's event id.
+    pub async fn reply(
+        &self,
+        room_id: &str,
+        event_id: &str,
+        text: String,
+    ) -> anyhow::Result<String> {
+        self.send(
+            room_id,
+            "m.room.message",
+            json!({
+                "msgtype": "m.notice",
+                "body": text,
+                "m.relates_to": { "m.in_reply_to": { "event_id": event_id } },
+            }),
+        )
+        .await
+    }
+
+    /// Replace the text of a notice we sent.
+    pub async fn edit(&self, room_id: &str, event_id: &str, text: String) -> anyhow::Result<()> {
+        self.send(
+            room_id,
+            "m.room.message",
+            json!({
+                "msgtype": "m.notice",
+                "body": format!("* {}", text),
+                "m.new_content": { "msgtype": "m.notice", "body": text },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": event_id },
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Show that a user is typing in a room.
+    async fn typing(&self, room_id: &str, user_id: &str) -> anyhow::Result<()> {
+        let url = self.url(["rooms", room_id, "typing", user_id])?;
+        self.request(
+            self.http.put(url),
+            Some(json!({ "typing": true, "timeout": 30_000 })),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Map a Matrix identifier (for a user or room) onto a numeric id, stably across restarts, since
+/// the ids are persisted in the store and the dispense ledger.
+fn numeric_id(matrix_id: &str) -> u64 {
+    // 64-bit FNV-1a
+    matrix_id.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Map a Matrix event onto a message id which increases over time like a Discord snowflake, so
+/// that the store can tell how old it is.
+fn event_message_id(event_id: &str, origin_server_ts: i64) -> id::MessageId {
+    let time = Utc
+        .timestamp_millis_opt(origin_server_ts)
+        .single()
+        .unwrap_or_else(Utc::now);
+    // The low 22 bits of a snowflake distinguish messages sent in the same millisecond
+    id::MessageId(id::MessageId::at(time).0 | (numeric_id(event_id) & 0x3f_ffff))
+}
+
+/// Receives messages from Matrix rooms, and passes those which might contain requests on to the
+/// [`Intake`].
+pub struct Handler {
+    client: Client,
+    intake: Arc<Intake>,
+    /// Persistent state, where each room's settings are kept.
+    store: Store,
+    /// Our own user id, so we can ignore our own messages.
+    user_id: String,
+    /// The rooms to respond in, by room id.
+    rooms: Vec<String>,
+    /// The message ids of recent messages by event id, so that edits map onto the original.
+    seen: Mutex<VecDeque<(String, id::MessageId)>>,
+}
+
+impl Handler {
+    /// Join each of the given rooms (by id or alias), ready to handle messages posted in them.
+    pub async fn new(
+        client: Client,
+        intake: Intake,
+        store: Store,
+        rooms: &[String],
+    ) -> anyhow::Result<Self> {
+        let user_id = client.whoami().await?;
+        let mut room_ids = Vec::new();
+        for room in rooms {
+            let room_id = client
+                .join(room)
+                .await
+                .with_context(|| format!("could not join {}", room))?;
+            tracing::info!(%room, %room_id, "joined room");
+            room_ids.push(room_id);
+        }
+        Ok(Handler {
+            client,
+            intake: Arc::new(intake),
+            store,
+            user_id,
+            rooms: room_ids,
+            seen: Default::default(),
+        })
+    }
+
+    /// Handle messages as they arrive, forever. Messages sent before the bot started are skipped.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut since = self.client.sync(None).await?.next_batch;
+        tracing::info!(user_id = %self.user_id, "connected to matrix");
+        loop {
+            let sync = match self.client.sync(Some(&since)).await {
+                Ok(sync) => sync,
+                Err(e) => {
+                    tracing::warn!(error = ?e, "sync failed, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            since = sync.next_batch;
+            for (room_id, room) in sync.rooms.join {
+                if !self.rooms.contains(&room_id) {
+                    continue;
+                }
+                for event in room.timeline.events {
+                    self.handle(&room_id, event);
+                }
+            }
+        }
+    }
+
+    /// Handle a new or edited message, if it's one we should respond to.
+    fn handle(&self, room_id: &str, event: Event) {
+        if event.kind != "m.room.message" {
+            return;
+        }
+        let channel_id = id::ChannelId(numeric_id(room_id));
+        // An edit is sent as a new event replacing the original, whose id we need to recognize it
+        let replaces = event.content["m.relates_to"]["rel_type"] == "m.replace";
+        intake::count_event(if replaces { "update" } else { "message" }, channel_id);
+
+        // Don't trigger on messages we ourselves send
+        if event.sender == self.user_id {
+            intake::count_filtered("own-message", channel_id);
+            return;
+        }
+
+        let (message_id, reply_to, content) = if replaces {
+            let original = event.content["m.relates_to"]["event_id"]
+                .as_str()
+                .unwrap_or_default();
+            let message_id = self
+                .seen
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(event_id, _)| event_id == original)
+                .map(|(_, message_id)| *message_id);
+            match message_id {
+                Some(message_id) if self.intake.has_seen(message_id) => (
+                    message_id,
+                    original.to_string(),
+                    &event.content["m.new_content"]["body"],
+                ),
+                _ => {
+                    intake::count_filtered("edit-to-unknown-message", channel_id);
+                    return;
+                }
+            }
+        } else {
+            let message_id = event_message_id(&event.event_id, event.origin_server_ts);
+            let mut seen = self.seen.lock().unwrap();
+            seen.push_back((event.event_id.clone(), message_id));
+            if seen.len() > SEEN_MESSAGES {
+                seen.pop_front();
+            }
+            (message_id, event.event_id.clone(), &event.content["body"])
+        };
+        let content = content.as_str().unwrap_or_default().to_string();
+
+        let incoming = Incoming {
+            id: message_id,
+            channel_id,
+            // Rooms aren't grouped into servers
+            server_id: None,
+            author_id: id::UserId(numeric_id(&event.sender)),
+            author_name: event.sender.clone(),
+            // Matrix has power levels rather than roles, which don't bear on rate limits
+            author_roles: Vec::new(),
+            content,
+        };
+        let chat = MatrixChat {
+            client: self.client.clone(),
+            store: self.store.clone(),
+            user_id: self.user_id.clone(),
+            room_id: room_id.to_string(),
+            event_id: reply_to,
+            progress: Default::default(),
+        };
+        let intake = self.intake.clone();
+        tokio::spawn(async move { intake.handle(&chat, incoming, replaces).await });
+    }
+}
+
+/// A message posted in a Matrix room.
+struct MatrixChat {
+    client: Client,
+    store: Store,
+    /// Our own user id.
+    user_id: String,
+    room_id: String,
+    event_id: String,
+    /// The id of our reply reporting the progress of a slow request, once we've posted one.
+    progress: tokio::sync::Mutex<Option<String>>,
+}
+
+#[async_trait]
+impl ChatPlatform for MatrixChat {
+    async fn reply(&self, text: String) -> anyhow::Result<()> {
+        self.client
+            .reply(&self.room_id, &self.event_id, text)
+            .await?;
+        Ok(())
+    }
+
+    async fn typing(&self) -> anyhow::Result<()> {
+        self.client.typing(&self.room_id, &self.user_id).await
+    }
+
+    async fn react(&self, reaction: Reaction) -> anyhow::Result<()> {
+        // Rooms stand in for servers when it comes to settings
+        let server_id = id::ServerId(numeric_id(&self.room_id));
+        let emoji = match self.store.reaction(server_id, reaction) {
+            Some(emoji) => emoji,
+            None => return Ok(()),
+        };
+        self.client
+            .send(
+                &self.room_id,
+                "m.reaction",
+                json!({
+                    "m.relates_to": {
+                        "rel_type": "m.annotation",
+                        "event_id": self.event_id,
+                        "key": emoji,
+                    },
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn progress(&self, text: String) -> anyhow::Result<()> {
+        let mut progress = self.progress.lock().await;
+        match &*progress {
+            Some(reply_id) => self.client.edit(&self.room_id, reply_id, text).await?,
+            None => {
+                *progress = Some(
+                    self.client
+                        .reply(&self.room_id, &self.event_id, text)
+                        .await?,
+                )
+            }
+        }
+        Ok(())
+    }
+
+    async fn cool_down(&self, _until: DateTime<Utc>) -> anyhow::Result<()> {
+        // Matrix has no timeouts, and rooms have no roles to give, so we just stay silent
+        Ok(())
+    }
+
+    fn transports(&self, _dm_receipts: bool) -> Vec<Box<dyn Transport>> {
+        // Receipts by direct message aren't supported on Matrix, since they'd need a room each
+        vec![Box::new(MatrixReply::new(
+            self.client.clone(),
+            self.room_id.clone(),
+            self.event_id.clone(),
+        ))]
+    }
+}
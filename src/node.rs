@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use penumbra_proto::client::v1alpha1::{
+    tendermint_proxy_service_client::TendermintProxyServiceClient, GetStatusRequest,
+};
+use url::Url;
+
+use crate::metrics;
+
+/// The gauge which is 1 for the node currently in use, and 0 for the others.
+const ACTIVE: &str = "galileo_node_active";
+
+/// How long to wait for a node to answer a health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a node reports about itself.
+#[derive(Debug, Clone)]
+pub struct Status {
+    /// The chain the node is on.
+    pub chain_id: String,
+    /// Whether the node is still catching up with the chain.
+    pub catching_up: bool,
+}
+
+/// Ask a node for its status.
+pub async fn status(node: &Url) -> anyhow::Result<Status> {
+    let check = async {
+        let mut client = TendermintProxyServiceClient::connect(node.to_string()).await?;
+        let status = client.get_status(GetStatusRequest {}).await?.into_inner();
+        anyhow::Ok(Status {
+            chain_id: status
+                .node_info
+                .map(|info| info.network)
+                .unwrap_or_default(),
+            catching_up: status
+                .sync_info
+                .map(|info| info.catching_up)
+                .unwrap_or(true),
+        })
+    };
+    tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out asking {} for its status", node))?
+}
+
+/// Check whether a node is reachable and caught up with the chain.
+pub async fn is_healthy(node: &Url) -> bool {
+    match status(node).await {
+        Ok(status) => !status.catching_up,
+        Err(e) => {
+            tracing::debug!(%node, error = ?e, "node health check failed");
+            false
+        }
+    }
+}
+
+/// Find the first healthy node of those given, in order of preference.
+pub async fn first_healthy(nodes: &[Url]) -> Option<Url> {
+    for node in nodes {
+        if is_healthy(node).await {
+            return Some(node.clone());
+        }
+        tracing::warn!(%node, "node is unhealthy");
+    }
+    None
+}
+
+/// Record which node is in use, in the logs and in metrics.
+pub fn set_active(nodes: &[Url], active: &Url) {
+    tracing::info!(node = %active, "using node");
+    for node in nodes {
+        metrics::set(
+            ACTIVE,
+            &[("node", node.to_string())],
+            (node == active) as i64,
+        );
+    }
+}
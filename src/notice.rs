@@ -0,0 +1,127 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use serenity::{
+    http::Http,
+    model::{channel::Message, id::ChannelId},
+};
+use tokio::sync::{mpsc, watch};
+
+use crate::{audit::Entry, report::Report, view::SyncProgress, Store};
+
+/// Where to forward notices for administrators, once the Discord client is running.
+static NOTICES: Mutex<Option<mpsc::UnboundedSender<String>>> = Mutex::new(None);
+
+/// Tell administrators about something which needs their attention: it's logged, and posted to
+/// the admin channel if there is one.
+pub fn send(notice: impl Into<String>) {
+    let notice = notice.into();
+    tracing::warn!("{}", notice);
+    if let Some(notices) = NOTICES.lock().unwrap().as_ref() {
+        let _ = notices.send(notice);
+    }
+}
+
+/// Post every notice from now on to the given channel.
+pub fn forward(http: Arc<Http>, channel_id: ChannelId) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    *NOTICES.lock().unwrap() = Some(tx);
+    tokio::spawn(async move {
+        while let Some(notice) = rx.recv().await {
+            let result = crate::rest::call("notice", || {
+                channel_id.send_message(http.as_ref(), |m| m.content(&notice))
+            })
+            .await;
+            if let Err(e) = result {
+                tracing::error!(error = ?e, "failed to post notice to admin channel");
+            }
+        }
+    });
+}
+
+/// Post a report of what the faucet did to the given channel at the end of every period.
+pub fn report(http: Arc<Http>, channel_id: ChannelId, store: Store, period: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            let report = Report::generate(&store, period, Utc::now());
+            tracing::info!(?report, "posting report");
+            let result = crate::rest::call("report", || {
+                channel_id.send_message(http.as_ref(), |m| m.content(report.summary()))
+            })
+            .await;
+            if let Err(e) = result {
+                tracing::error!(error = ?e, "failed to post report to admin channel");
+            }
+        }
+    });
+}
+
+/// Post every entry received from the audit trail to the given channel.
+pub fn audit(http: Arc<Http>, channel_id: ChannelId, mut entries: mpsc::UnboundedReceiver<Entry>) {
+    tokio::spawn(async move {
+        while let Some(entry) = entries.recv().await {
+            let result = crate::rest::call("audit", || {
+                channel_id.send_message(http.as_ref(), |m| m.content(entry.message()))
+            })
+            .await;
+            if let Err(e) = result {
+                tracing::error!(error = ?e, seq = entry.seq, "failed to post audit entry");
+            }
+        }
+    });
+}
+
+/// How often to update the sync status message, at most, to stay well within Discord's limits.
+const SYNC_STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Post the progress of synchronizing to the given channel, editing the same message as it goes,
+/// until it's done.
+pub fn sync_status(
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    mut progress: watch::Receiver<SyncProgress>,
+) {
+    tokio::spawn(async move {
+        let mut message: Option<Message> = None;
+        loop {
+            let current = *progress.borrow_and_update();
+            let summary = current.summary();
+            let result = match &message {
+                // Edit a copy, so that each retry starts from the message as it was
+                Some(posted) => {
+                    crate::rest::call("sync-status", || {
+                        let (mut posted, http, summary) =
+                            (posted.clone(), http.clone(), summary.clone());
+                        async move {
+                            posted.edit(http.as_ref(), |m| m.content(summary)).await?;
+                            Ok(posted)
+                        }
+                    })
+                    .await
+                }
+                None => {
+                    crate::rest::call("sync-status", || {
+                        channel_id.send_message(http.as_ref(), |m| m.content(&summary))
+                    })
+                    .await
+                }
+            };
+            match result {
+                Ok(posted) => message = Some(posted),
+                Err(e) => tracing::warn!(error = ?e, "failed to post sync status"),
+            }
+            if current.done {
+                return;
+            }
+            tokio::time::sleep(SYNC_STATUS_INTERVAL).await;
+            if progress.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+}
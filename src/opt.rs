@@ -1,12 +1,28 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
+use directories::ProjectDirs;
 use serenity::model::id::{ChannelId, MessageId};
 
+mod backend;
+mod backup;
+mod doctor;
+mod encrypt_custody;
 mod history;
+mod import_backlog;
+mod mirror;
+mod preset;
+mod prove_address;
+mod restore;
 mod serve;
+mod serve_matrix;
+mod sign;
+mod simulate;
+mod status;
+mod view;
 
-pub use history::gather as gather_history;
+pub use history::{gather as gather_history, PAGE_SIZE as HISTORY_PAGE_SIZE};
 
 #[derive(Debug, Clone, Parser)]
 #[clap(author, version, about)]
@@ -19,7 +35,19 @@ impl Opt {
     pub async fn exec(self) -> anyhow::Result<()> {
         match self.command {
             Command::Serve(serve) => serve.exec().await,
+            Command::ServeMatrix(serve) => serve.exec().await,
             Command::History(history) => history.exec().await,
+            Command::Mirror(mirror) => mirror.exec().await,
+            Command::Sign(sign) => sign.exec().await,
+            Command::EncryptCustody(encrypt) => encrypt.exec().await,
+            Command::Doctor(doctor) => doctor.exec().await,
+            Command::Simulate(simulate) => simulate.exec().await,
+            Command::ImportBacklog(import) => import.exec().await,
+            Command::Backup(backup) => backup.exec().await,
+            Command::Restore(restore) => restore.exec().await,
+            Command::View(view) => view.exec().await,
+            Command::Status(status) => status.exec().await,
+            Command::ProveAddress(prove) => prove.exec().await,
         }
     }
 }
@@ -28,8 +56,49 @@ impl Opt {
 pub enum Command {
     /// Run the bot.
     Serve(serve::Serve),
+    /// Run the bot on Matrix, in the given rooms.
+    ServeMatrix(serve_matrix::ServeMatrix),
     /// Export the history of requests from the channel as CSV to stdout.
     History(history::History),
+    /// Follow the faucet's wallet with only its full viewing key, alerting on any spends which
+    /// aren't in the primary instance's dispense ledger.
+    Mirror(mirror::Mirror),
+    /// Hold the faucet's spend key and sign transactions for a bot running elsewhere with only
+    /// the full viewing key.
+    Sign(sign::Sign),
+    /// Encrypt a custody file with a passphrase, so that the spend key isn't stored in the clear.
+    EncryptCustody(encrypt_custody::EncryptCustody),
+    /// Inspect a deployment (configuration, state, nodes, Discord permissions, and balances) and
+    /// print a prioritized list of problems, with suggested fixes.
+    Doctor(doctor::Doctor),
+    /// Run a scripted conversation through the faucet, with a stand-in for Discord and for the
+    /// chain, checking its replies against what the script expects.
+    Simulate(simulate::Simulate),
+    /// Dispense to the requests in a channel exported from Discord, for when its history can't be
+    /// read through the API any more (such as when the channel was deleted), skipping those which
+    /// were already handled.
+    ImportBacklog(import_backlog::ImportBacklog),
+    /// Save the bot's state (rate limits, ledgers, queued requests, and catch-up checkpoints) to a
+    /// tarball, for moving the faucet to another host.
+    Backup(backup::Backup),
+    /// Restore the bot's state from a tarball written by `backup`.
+    Restore(restore::Restore),
+    /// Manage the faucet wallet's view database.
+    View(view::View),
+    /// Show how a running bot is doing (queue depth, balances, rate limiting, last dispense, and
+    /// sync height), by asking it over its admin socket.
+    Status(status::Status),
+    /// Answer the faucet's challenge to prove that addresses are yours, by signing it with your
+    /// wallet's spend key, and print the reply to post.
+    ProveAddress(prove_address::ProveAddress),
+}
+
+/// The platform appdata directory shared with `pcli`, where we look for data by default.
+pub fn default_data_dir() -> PathBuf {
+    ProjectDirs::from("zone", "penumbra", "pcli")
+        .expect("can access penumbra project dir")
+        .data_dir()
+        .to_owned()
 }
 
 /// A pair of channel id and message id that uniquely identifies a message.
@@ -53,3 +122,33 @@ impl FromStr for ChannelIdAndMessageId {
         }
     }
 }
+
+/// The smallest possible id of a message sent at the given time, which can be used as a bound when
+/// walking back through the history of a channel.
+pub fn message_id_at(time: DateTime<Utc>) -> MessageId {
+    MessageId(galileo_core::id::MessageId::at(time).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn message_id_at_bounds_messages_sent_then() {
+        // A snowflake from Discord's documentation, and when it was made
+        let snowflake = 175928847299117063;
+        let time = Utc.timestamp_millis_opt(1462015105796).unwrap();
+        let bound = message_id_at(time);
+        assert!(bound.0 <= snowflake);
+        assert_eq!(bound.0, snowflake >> 22 << 22);
+        assert!(message_id_at(time + chrono::Duration::milliseconds(1)).0 > snowflake);
+    }
+
+    #[test]
+    fn message_id_at_is_zero_before_discord() {
+        let time = Utc.timestamp_millis_opt(0).unwrap();
+        assert_eq!(message_id_at(time).0, 0);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+use penumbra_keys::FullViewingKey;
+use url::Url;
+
+/// Where the faucet keeps its data, and how it reaches the chain and its wallet: the options shared
+/// by every command which works with the faucet's wallet.
+#[derive(Debug, Clone, Args)]
+pub struct Backend {
+    /// Path to the directory to use to store data [default: platform appdata directory].
+    #[clap(long, short)]
+    pub data_dir: Option<PathBuf>,
+    /// The URL of the pd gRPC endpoint on the remote node. May be given more than once, in order of
+    /// preference: the first healthy node is used, and if it becomes unhealthy, the bot fails over
+    /// to another.
+    #[clap(
+        short,
+        long = "node",
+        default_value = "http://testnet.penumbra.zone:8080",
+        multiple_occurrences = true
+    )]
+    pub nodes: Vec<Url>,
+    /// How often to check the health of the node in use, and the view's connection to it.
+    #[clap(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+    pub node_check_interval: Duration,
+    /// How many checks in a row can fail to reach a node, or to reconnect the view to one, before
+    /// giving up and exiting.
+    #[clap(long, default_value = "10")]
+    pub max_node_failures: usize,
+    /// The URL of an external view service (such as `pclientd`) to use, instead of running one in
+    /// memory which has to sync from scratch every time. It must be configured with the faucet's
+    /// full viewing key.
+    #[clap(long)]
+    pub view_url: Option<Url>,
+    /// Path to the custody file holding the faucet's spend key [default: `custody.json` in the data
+    /// directory]. If it is encrypted (see `galileo encrypt-custody`), the passphrase must be given
+    /// in the `GALILEO_CUSTODY_PASSPHRASE` environment variable.
+    #[clap(long, conflicts_with = "custody_url")]
+    pub custody_file: Option<PathBuf>,
+    /// The URL of a remote signer (run with `galileo sign`) to authorize transactions, instead of
+    /// using the spend key in the local custody file. The token shared with the signer must be
+    /// given in the `GALILEO_SIGNER_TOKEN` environment variable.
+    #[clap(long, requires = "fvk")]
+    pub custody_url: Option<Url>,
+    /// The full viewing key of the faucet's wallet, for use with a remote signer.
+    #[clap(long)]
+    pub fvk: Option<FullViewingKey>,
+}
+
+impl Backend {
+    /// The directory to store data in.
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir
+            .clone()
+            .unwrap_or_else(super::default_data_dir)
+    }
+
+    /// The custody file holding the faucet's spend key, given the data directory.
+    pub fn custody_file(&self, data_dir: &Path) -> PathBuf {
+        self.custody_file
+            .clone()
+            .unwrap_or_else(|| data_dir.join("custody.json"))
+    }
+
+    /// The remote signer to authorize transactions, and the full viewing key of the wallet it signs
+    /// for, if there is one. A full viewing key without a signer would be ignored, so it's refused.
+    pub fn remote_signer(&self) -> anyhow::Result<Option<(Url, FullViewingKey)>> {
+        match (&self.custody_url, &self.fvk) {
+            (Some(custody_url), Some(fvk)) => Ok(Some((custody_url.clone(), fvk.clone()))),
+            (Some(_), None) => anyhow::bail!("--fvk is required when using a remote signer"),
+            (None, Some(_)) => {
+                anyhow::bail!("--fvk is only used with a remote signer (--custody-url)")
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
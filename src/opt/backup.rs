@@ -0,0 +1,91 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use flate2::{write::GzEncoder, Compression};
+
+/// The directories in the data directory holding the bots' own state (the Discord bot's, and the
+/// Matrix bot's): rate limits, ledgers, queued requests, and catch-up checkpoints.
+pub(super) const STORE_DIRS: &[&str] = &["galileo", "galileo-matrix"];
+
+/// The view database `pcli` keeps in the same data directory, which can be large, and can be
+/// rebuilt by syncing from scratch.
+pub(super) const VIEW_FILE: &str = "pcli-view.sqlite";
+
+#[derive(Debug, Clone, Parser)]
+pub struct Backup {
+    /// The data directory to back up [default: the platform appdata directory].
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+    /// Also back up the view database, which is large, but saves syncing from scratch after
+    /// restoring.
+    #[clap(long)]
+    include_view: bool,
+    /// Path to which to write the backup, as a gzipped tarball.
+    #[clap(long, short)]
+    output: PathBuf,
+}
+
+impl Backup {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        if self.output.exists() {
+            anyhow::bail!("{} already exists", self.output.display());
+        }
+        let data_dir = self.data_dir.unwrap_or_else(super::default_data_dir);
+
+        let mut files = Vec::new();
+        for name in STORE_DIRS {
+            let dir = data_dir.join(name);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                // The lock belongs to whichever instance is running here, not to the state, and
+                // temporary files are only half-written
+                let lossy = file_name.to_string_lossy();
+                if lossy.starts_with("instance.lock") || lossy.ends_with(".tmp") {
+                    continue;
+                }
+                if entry.file_type()?.is_file() {
+                    files.push(Path::new(name).join(file_name));
+                }
+            }
+        }
+        if files.is_empty() {
+            anyhow::bail!("no galileo state found in {}", data_dir.display());
+        }
+        // A running bot empties the journal once it's folded into the state, so take the journal
+        // first: replaying it on top of a newer state is harmless, but the other way round loses
+        // whatever it held
+        files.sort_by_key(|file| file.file_name() != Some("journal.jsonl".as_ref()));
+        if self.include_view {
+            if data_dir.join(VIEW_FILE).exists() {
+                files.push(PathBuf::from(VIEW_FILE));
+            } else {
+                tracing::warn!(data_dir = %data_dir.display(), "no view database to back up");
+            }
+        }
+
+        let output = File::create(&self.output)
+            .with_context(|| format!("could not create {}", self.output.display()))?;
+        let mut archive = tar::Builder::new(GzEncoder::new(output, Compression::default()));
+        for file in &files {
+            archive
+                .append_path_with_name(data_dir.join(file), file)
+                .with_context(|| format!("could not back up {}", file.display()))?;
+        }
+        archive.into_inner()?.finish()?;
+
+        tracing::info!(
+            output = %self.output.display(),
+            files = files.len(),
+            "wrote backup: the custody file is not included, so copy it separately if needed"
+        );
+        Ok(())
+    }
+}
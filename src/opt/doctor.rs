@@ -0,0 +1,473 @@
+use std::{env, fmt, sync::Arc};
+
+use super::backend::Backend;
+use crate::{custody, node, store::InstanceLock, view, wallet, Store, Wallet};
+use clap::Parser;
+use penumbra_asset::{asset, Value};
+use penumbra_keys::FullViewingKey;
+use penumbra_view::ViewClient;
+use serenity::{
+    http::Http,
+    model::{
+        id::{ChannelId, RoleId},
+        permissions::Permissions,
+    },
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct Doctor {
+    #[clap(flatten)]
+    backend: Backend,
+    /// Without an external view service, sync an in-memory view to check balances (which can take
+    /// a long time).
+    #[clap(long)]
+    sync: bool,
+    /// Warn when the balance is enough for fewer than this many more dispenses.
+    #[clap(long, default_value = "100")]
+    min_dispenses: u128,
+    /// The amounts the bot sends for each response, to check the balance against.
+    values: Vec<Value>,
+}
+
+/// How urgently a problem needs fixing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    /// The faucet can't work until this is fixed.
+    Critical,
+    /// The faucet works, but something is wrong or about to go wrong.
+    Warning,
+    /// Worth knowing, but not necessarily a problem.
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Critical => write!(f, "CRITICAL"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Info => write!(f, "INFO"),
+        }
+    }
+}
+
+/// A problem found by the doctor, and what to do about it.
+#[derive(Debug)]
+struct Problem {
+    severity: Severity,
+    problem: String,
+    fix: String,
+}
+
+/// The findings of the doctor, as it goes through its checklist.
+#[derive(Debug, Default)]
+struct Findings {
+    problems: Vec<Problem>,
+    passed: Vec<String>,
+}
+
+impl Findings {
+    fn problem(&mut self, severity: Severity, problem: impl Into<String>, fix: impl Into<String>) {
+        self.problems.push(Problem {
+            severity,
+            problem: problem.into(),
+            fix: fix.into(),
+        });
+    }
+
+    fn ok(&mut self, check: impl Into<String>) {
+        self.passed.push(check.into());
+    }
+}
+
+impl Doctor {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let mut findings = Findings::default();
+
+        let data_dir = self.backend.data_dir();
+        let fvk = self.check_custody(&data_dir, &mut findings);
+        let store = self.check_store(&data_dir, &mut findings);
+        let chain_id = self.check_nodes(&mut findings).await;
+        if let (Some(store), Some(chain_id)) = (&store, &chain_id) {
+            match store.chain_id() {
+                Some(previous) if previous != *chain_id => findings.problem(
+                    Severity::Warning,
+                    format!(
+                        "the chain was reset since the bot last ran (now `{}`, was `{}`)",
+                        chain_id, previous
+                    ),
+                    "make sure the faucet wallet is funded on the new chain; if using an external \
+                    view service, reset its storage",
+                ),
+                _ => findings.ok(format!("running on chain `{}`", chain_id)),
+            }
+        }
+        self.check_discord(store.as_ref(), &mut findings).await;
+        if let Some(fvk) = fvk {
+            self.check_balance(&fvk, &mut findings).await;
+        }
+
+        for check in &findings.passed {
+            println!("ok: {}", check);
+        }
+        findings.problems.sort_by_key(|problem| problem.severity);
+        for Problem {
+            severity,
+            problem,
+            fix,
+        } in &findings.problems
+        {
+            println!("{}: {}\n  fix: {}", severity, problem, fix);
+        }
+
+        let critical = findings
+            .problems
+            .iter()
+            .filter(|problem| problem.severity == Severity::Critical)
+            .count();
+        if critical > 0 {
+            anyhow::bail!("found {} critical problem(s)", critical);
+        }
+        if findings.problems.is_empty() {
+            println!("no problems found");
+        }
+        Ok(())
+    }
+
+    /// Check that the spend key (or full viewing key, with a remote signer) is available.
+    fn check_custody(
+        &self,
+        data_dir: &std::path::Path,
+        findings: &mut Findings,
+    ) -> Option<FullViewingKey> {
+        if let Some(fvk) = &self.backend.fvk {
+            if env::var(custody::TOKEN_VAR).is_err() {
+                findings.problem(
+                    Severity::Critical,
+                    format!("{} is not set", custody::TOKEN_VAR),
+                    "set it to the token shared with the remote signer (`galileo sign`)",
+                );
+            } else {
+                findings.ok("remote signer token is set");
+            }
+            return Some(fvk.clone());
+        }
+
+        let custody_file = self.backend.custody_file(data_dir);
+        if !custody_file.exists() {
+            findings.problem(
+                Severity::Critical,
+                format!("custody file {} does not exist", custody_file.display()),
+                "create a wallet with `pcli init`, pass `--custody-file`, or use a remote signer \
+                with `--fvk`",
+            );
+            return None;
+        }
+        match Wallet::load(&custody_file) {
+            Ok(wallet) => {
+                findings.ok(format!("custody file {} loads", custody_file.display()));
+                Some(wallet.spend_key.full_viewing_key().clone())
+            }
+            Err(e) => {
+                let fix = if env::var(wallet::PASSPHRASE_VAR).is_err() {
+                    format!(
+                        "if the custody file is encrypted, set {} to its passphrase",
+                        wallet::PASSPHRASE_VAR
+                    )
+                } else {
+                    "check that the file is a valid custody file and the passphrase is right"
+                        .to_string()
+                };
+                findings.problem(
+                    Severity::Critical,
+                    format!("could not load custody file: {:#}", e),
+                    fix,
+                );
+                None
+            }
+        }
+    }
+
+    /// Check the bot's own persistent state.
+    fn check_store(&self, data_dir: &std::path::Path, findings: &mut Findings) -> Option<Store> {
+        let store_dir = data_dir.join("galileo");
+        let store = match Store::load(&store_dir) {
+            Ok(store) => store,
+            Err(e) => {
+                findings.problem(
+                    Severity::Critical,
+                    format!("could not load the bot's state: {:#}", e),
+                    format!(
+                        "check the permissions of {}, or move the corrupted file aside",
+                        store_dir.display()
+                    ),
+                );
+                return None;
+            }
+        };
+        findings.ok(format!(
+            "state in {} loads ({} dispenses in the ledger, {} channels checkpointed)",
+            store_dir.display(),
+            store.dispenses().len(),
+            store.checkpoints().len()
+        ));
+
+        let running = match InstanceLock::holder(&store_dir) {
+            Some(holder) if holder.is_stale() => {
+                findings.problem(
+                    Severity::Info,
+                    format!(
+                        "stale instance lock left by process {} (no longer running)",
+                        holder.pid
+                    ),
+                    "nothing: the bot takes it over when it next starts",
+                );
+                false
+            }
+            Some(holder) => {
+                findings.ok(format!(
+                    "an instance is running (process {} on {})",
+                    holder.pid, holder.host
+                ));
+                true
+            }
+            None => {
+                findings.problem(
+                    Severity::Warning,
+                    "no instance of the bot is running against this data directory",
+                    "start it with `galileo serve`",
+                );
+                false
+            }
+        };
+
+        let pending = store.pending();
+        if pending > 0 && !running {
+            findings.problem(
+                Severity::Warning,
+                format!(
+                    "{} requests handed off by a previous instance are waiting",
+                    pending
+                ),
+                "start the bot, which picks them up and replies to them",
+            );
+        }
+
+        Some(store)
+    }
+
+    /// Check that the nodes are reachable and caught up, returning the chain they're on.
+    async fn check_nodes(&self, findings: &mut Findings) -> Option<String> {
+        let mut chain_id = None;
+        for node in &self.backend.nodes {
+            match node::status(node).await {
+                Ok(status) if status.catching_up => findings.problem(
+                    Severity::Warning,
+                    format!("node {} is still catching up", node),
+                    "wait for it to catch up, or configure another with `--node`",
+                ),
+                Ok(status) => {
+                    findings.ok(format!("node {} is healthy", node));
+                    chain_id.get_or_insert(status.chain_id);
+                }
+                Err(e) => findings.problem(
+                    Severity::Warning,
+                    format!("node {} is unreachable: {:#}", node, e),
+                    "check the URL and the node, or configure another with `--node`",
+                ),
+            }
+        }
+        if chain_id.is_none() {
+            findings.problem(
+                Severity::Critical,
+                "no node is healthy",
+                "configure a working node with `--node`",
+            );
+        }
+        chain_id
+    }
+
+    /// Check that the bot can log into Discord, and has the permissions it needs in each server.
+    async fn check_discord(&self, store: Option<&Store>, findings: &mut Findings) {
+        let token = if let Ok(token) = env::var("DISCORD_TOKEN") {
+            token
+        } else {
+            findings.problem(
+                Severity::Critical,
+                "DISCORD_TOKEN is not set",
+                "set it to the bot's token from the Discord developer portal",
+            );
+            return;
+        };
+        let http = Arc::new(Http::new(&token));
+
+        let user = match http.get_current_user().await {
+            Ok(user) => user,
+            Err(e) => {
+                findings.problem(
+                    Severity::Critical,
+                    format!("could not log into Discord: {}", e),
+                    "check DISCORD_TOKEN, and reset the token in the developer portal if needed",
+                );
+                return;
+            }
+        };
+        findings.ok(format!("logged into Discord as {}", user.tag()));
+
+        let guilds = match user.guilds(http.as_ref()).await {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                findings.problem(
+                    Severity::Critical,
+                    format!("could not list the bot's servers: {}", e),
+                    "check the bot's connectivity to Discord",
+                );
+                return;
+            }
+        };
+        if guilds.is_empty() {
+            findings.problem(
+                Severity::Critical,
+                "the bot isn't in any server",
+                "invite it to the faucet's server from the developer portal",
+            );
+        }
+
+        let required = Permissions::VIEW_CHANNEL
+            | Permissions::SEND_MESSAGES
+            | Permissions::READ_MESSAGE_HISTORY;
+        for guild in guilds {
+            let permissions = async {
+                let partial = guild.id.to_partial_guild(http.as_ref()).await?;
+                let member = guild.id.member(http.clone(), user.id).await?;
+                // Server-wide permissions are those of @everyone (whose id is the server's) and
+                // every role the bot has
+                let everyone = RoleId(guild.id.0);
+                let mut permissions = Permissions::empty();
+                for role_id in member.roles.iter().chain([&everyone]) {
+                    if let Some(role) = partial.roles.get(role_id) {
+                        permissions |= role.permissions;
+                    }
+                }
+                anyhow::Ok(permissions)
+            }
+            .await;
+            match permissions {
+                Ok(permissions)
+                    if permissions.administrator() || permissions.contains(required) =>
+                {
+                    findings.ok(format!("server-wide permissions in {}", guild.name))
+                }
+                Ok(permissions) => findings.problem(
+                    Severity::Warning,
+                    format!(
+                        "missing server-wide permissions in {}: {}",
+                        guild.name,
+                        required - permissions
+                    ),
+                    "grant them to the bot's role, or make sure channel overrides grant them in \
+                    the faucet channels",
+                ),
+                Err(e) => findings.problem(
+                    Severity::Warning,
+                    format!("could not check permissions in {}: {:#}", guild.name, e),
+                    "check that the bot is still a member of the server",
+                ),
+            }
+        }
+
+        // Catch-up resumes in every checkpointed channel, so each must still be readable
+        for (channel_id, _) in store.map(Store::checkpoints).unwrap_or_default() {
+            let channel_id = ChannelId(channel_id.0);
+            match channel_id
+                .messages(http.as_ref(), |retriever| retriever.limit(1))
+                .await
+            {
+                Ok(_) => findings.ok(format!("can read history in <#{}>", channel_id)),
+                Err(e) => findings.problem(
+                    Severity::Warning,
+                    format!(
+                        "can't read history in checkpointed channel {}: {}",
+                        channel_id, e
+                    ),
+                    "grant the bot View Channel and Read Message History there, or it can't \
+                    catch up after a restart",
+                ),
+            }
+        }
+    }
+
+    /// Check that the faucet has enough funds for the configured amounts.
+    async fn check_balance(&self, fvk: &FullViewingKey, findings: &mut Findings) {
+        let balances = if let Some(view_url) = self.backend.view_url.clone() {
+            match view::remote(view_url).await {
+                Ok(mut view) => balances(&mut view, fvk).await,
+                Err(e) => Err(e),
+            }
+        } else if self.sync {
+            match self.backend.nodes.first() {
+                Some(node) => match view::in_memory(fvk, node.clone()).await {
+                    Ok(mut view) => balances(&mut view, fvk).await,
+                    Err(e) => Err(e),
+                },
+                None => return,
+            }
+        } else {
+            findings.problem(
+                Severity::Info,
+                "balances were not checked",
+                "pass `--view-url` (or `--sync`, which can take a long time) to check them",
+            );
+            return;
+        };
+        let balances = match balances {
+            Ok(balances) => balances,
+            Err(e) => {
+                findings.problem(
+                    Severity::Warning,
+                    format!("could not check balances: {:#}", e),
+                    "check the view service",
+                );
+                return;
+            }
+        };
+
+        let cache = asset::Cache::with_known_assets();
+        for value in &self.values {
+            let per_dispense = value.amount.value();
+            let available = balances
+                .iter()
+                .find(|(asset_id, _)| *asset_id == value.asset_id)
+                .map_or(0, |(_, amount)| *amount);
+            let remaining = available / per_dispense.max(1);
+            let description = format!(
+                "enough for {} more dispenses of {}",
+                remaining,
+                value.format(&cache)
+            );
+            if remaining == 0 {
+                findings.problem(
+                    Severity::Critical,
+                    format!("out of funds: {}", description),
+                    "send funds to the faucet's wallet",
+                );
+            } else if remaining < self.min_dispenses {
+                findings.problem(
+                    Severity::Warning,
+                    format!("low on funds: {}", description),
+                    "send funds to the faucet's wallet",
+                );
+            } else {
+                findings.ok(description);
+            }
+        }
+    }
+}
+
+/// The total unspent balance of each asset in the wallet, after syncing the view.
+async fn balances<V: ViewClient>(
+    view: &mut V,
+    fvk: &FullViewingKey,
+) -> anyhow::Result<Vec<(asset::Id, u128)>> {
+    view::sync(view, fvk).await?;
+    view::balances(view, fvk).await
+}
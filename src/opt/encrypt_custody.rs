@@ -0,0 +1,39 @@
+use std::{env, path::PathBuf};
+
+use anyhow::Context;
+use clap::Parser;
+
+use crate::{wallet::PASSPHRASE_VAR, Wallet};
+
+#[derive(Debug, Clone, Parser)]
+pub struct EncryptCustody {
+    /// Path to the plaintext custody file to encrypt [default: `custody.json` in the platform
+    /// appdata directory].
+    #[clap(long)]
+    custody_file: Option<PathBuf>,
+    /// Path to which to write the encrypted custody file.
+    #[clap(long, short)]
+    output: PathBuf,
+}
+
+impl EncryptCustody {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let passphrase = env::var(PASSPHRASE_VAR)
+            .with_context(|| format!("missing environment variable {}", PASSPHRASE_VAR))?;
+        if self.output.exists() {
+            anyhow::bail!("{} already exists", self.output.display());
+        }
+
+        let custody_file = self
+            .custody_file
+            .unwrap_or_else(|| super::default_data_dir().join("custody.json"));
+        let encrypted = Wallet::encrypt(&custody_file, &passphrase)?;
+        std::fs::write(&self.output, encrypted)?;
+
+        tracing::info!(
+            output = %self.output.display(),
+            "wrote encrypted custody file: check that the bot can load it, then delete the plaintext one"
+        );
+        Ok(())
+    }
+}
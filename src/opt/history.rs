@@ -3,13 +3,13 @@ use std::{env, sync::Arc};
 use anyhow::Context;
 use async_stream::stream;
 use clap::Parser;
-use futures::{Stream, StreamExt};
+use futures::{future, future::BoxFuture, FutureExt, Stream, StreamExt};
 use serde::Serialize;
 use serenity::{
     http::Http,
     model::{
         id::{ChannelId, MessageId, UserId},
-        prelude::User,
+        prelude::{Message, User},
         Timestamp,
     },
     prelude::GatewayIntents,
@@ -17,7 +17,11 @@ use serenity::{
 };
 use tokio::sync::oneshot;
 
-use crate::responder::{AddressOrAlmost, Request, Response};
+use crate::{
+    handler,
+    responder::{AddressOrAlmost, Request, Response},
+    rest,
+};
 
 #[derive(Debug, Clone, Parser)]
 pub struct History {
@@ -32,7 +36,7 @@ pub struct History {
     after: Option<MessageId>,
 }
 
-fn parse_message_id(s: &str) -> Result<MessageId, anyhow::Error> {
+pub(super) fn parse_message_id(s: &str) -> Result<MessageId, anyhow::Error> {
     let parts: Vec<&str> = s.split('/').collect();
     match parts.as_slice() {
         [.., message_id] => Ok(MessageId(message_id.parse().context("invalid message id")?)),
@@ -40,7 +44,7 @@ fn parse_message_id(s: &str) -> Result<MessageId, anyhow::Error> {
     }
 }
 
-fn parse_channel_id(s: &str) -> Result<ChannelId, anyhow::Error> {
+pub(super) fn parse_channel_id(s: &str) -> Result<ChannelId, anyhow::Error> {
     let parts: Vec<&str> = s.split('/').collect();
     match parts.as_slice() {
         [.., channel_id] => Ok(ChannelId(channel_id.parse().context("invalid channel id")?)),
@@ -72,6 +76,8 @@ impl History {
             self.channel,
             self.before,
             self.after,
+            |_| true,
+            || future::ready(()).boxed(),
         );
 
         #[derive(Serialize, Debug)]
@@ -119,13 +125,19 @@ impl History {
     }
 }
 
-// Gather and parse into requests messages in a given channel, streaming the results in reverse
-// chronological order.
+/// How many messages to read from channel history at a time.
+pub const PAGE_SIZE: u64 = 50;
+
+// Gather and parse into requests messages in a given channel (those which `admit` accepts),
+// streaming the results in reverse chronological order. Each page of history is only fetched once
+// `pace` lets it be.
 pub fn gather(
     http: Arc<Http>,
     channel_id: ChannelId,
     mut before: Option<MessageId>,
     after: Option<MessageId>,
+    admit: impl Fn(&Message) -> bool + Send + 'static,
+    pace: impl Fn() -> BoxFuture<'static, ()> + Send + 'static,
 ) -> impl Stream<
     Item = anyhow::Result<(
         Timestamp,
@@ -138,38 +150,36 @@ pub fn gather(
        + Unpin
        + 'static {
     Box::pin(stream! {
-        let after_message = if let Some(after) = after {
-            let message = channel_id.message(http.as_ref(), after).await?;
-            if message.channel_id != channel_id {
-                yield Err(anyhow::anyhow!("after message is not in the channel"));
-                return;
-            }
-            Some(message)
-        } else {
-            None
-        };
-
         loop {
-            let messages = channel_id.messages(http.as_ref(), |retriever| if let Some(before) = before {
-                retriever.before(before)
-            } else {
-                retriever
-            }).await?;
+            pace().await;
+            let messages = rest::call("history", || channel_id.messages(http.as_ref(), |retriever| {
+                let retriever = retriever.limit(PAGE_SIZE);
+                if let Some(before) = before {
+                    retriever.before(before)
+                } else {
+                    retriever
+                }
+            })).await?;
             if messages.is_empty() {
                 break;
             }
 
             for message in messages {
-                if let Some((response, request)) = Request::try_new(&message) {
-                    yield Ok((message.timestamp, message.author, message.id, response, request));
-
-                    // Terminate after we see the after-message
-                    if let Some(ref after_message) = after_message {
-                        if message.id == after_message.id {
-                            return;
-                        }
+                // Terminate once we're past the after-message: message ids increase over time, so
+                // this works even if there's no message with exactly that id
+                if let Some(after) = after {
+                    if message.id < after {
+                        return;
                     }
                 }
+
+                if !admit(&message) {
+                    before = Some(message.id);
+                    continue;
+                }
+                if let Some((response, request)) = Request::try_new(&message.content, handler::origin(&message)) {
+                    yield Ok((message.timestamp, message.author, message.id, response, request));
+                }
                 before = Some(message.id);
             }
         }
@@ -0,0 +1,279 @@
+use std::{collections::HashSet, env, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::Parser;
+use futures::StreamExt;
+use num_traits::identities::Zero;
+use penumbra_asset::Value;
+use penumbra_custody::CustodyClient;
+use penumbra_keys::FullViewingKey;
+use penumbra_view::ViewClient;
+use serde::Deserialize;
+use serenity::model::id::ChannelId;
+
+use super::backend::Backend;
+use crate::{
+    catchup::{self, Backlogged, Pacing},
+    custody, id,
+    responder::{Counterparties, Menu, Origin, Request},
+    sender::Memo,
+    store::InstanceLock,
+    view, Lifecycle, Responder, Sender, Store, Wallet,
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct ImportBacklog {
+    /// Instead of dispensing tokens, print a report of what importing would do (how many requests
+    /// and addresses there are, and which were already sent tokens according to the dispense
+    /// ledger) and exit, without connecting to the chain or changing any state.
+    #[clap(long)]
+    dry_run: bool,
+    /// Per-user rate limit (e.g. "10m" or "1day"), used by `--dry-run` to flag users who were sent
+    /// tokens recently.
+    #[clap(short, long, default_value = "1day", parse(try_from_str = humantime::parse_duration))]
+    rate_limit: Duration,
+    /// Maximum number of addresses per message to which to dispense tokens.
+    #[clap(long, default_value = "1")]
+    max_addresses: usize,
+    #[clap(flatten)]
+    backend: Backend,
+    /// If the bot is running on the same data directory, wait for it to hand off (see `SIGUSR1`)
+    /// instead of exiting.
+    #[clap(long)]
+    wait_for_lock: bool,
+    /// The exported channel, in the JSON format written by DiscordChatExporter.
+    file: PathBuf,
+    /// The amounts to send for each response, written as typed values 1.87penumbra, 12cubes, etc.
+    values: Vec<Value>,
+}
+
+/// A channel exported from Discord, as written by DiscordChatExporter.
+#[derive(Debug, Clone, Deserialize)]
+struct Export {
+    channel: ExportedChannel,
+    messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExportedChannel {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExportedMessage {
+    id: String,
+    #[serde(default)]
+    content: String,
+    author: ExportedAuthor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedAuthor {
+    id: String,
+    #[serde(default)]
+    is_bot: bool,
+}
+
+/// Parse one of the ids in an export, which are written as strings.
+fn parse_id(id: &str, what: &str) -> anyhow::Result<u64> {
+    id.parse()
+        .with_context(|| format!("invalid {} id in export: {:?}", what, id))
+}
+
+impl ImportBacklog {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let export: Export = serde_json::from_slice(
+            &std::fs::read(&self.file)
+                .with_context(|| format!("could not read {}", self.file.display()))?,
+        )
+        .with_context(|| format!("could not parse export {}", self.file.display()))?;
+        let channel_id = parse_id(&export.channel.id, "channel")?;
+        tracing::info!(
+            channel_id,
+            channel_name = ?export.channel.name,
+            messages = export.messages.len(),
+            "loaded export"
+        );
+
+        let data_dir = self.backend.data_dir();
+        let store_dir = data_dir.join("galileo");
+        if self.dry_run {
+            let store = Store::load(&store_dir).context("can load galileo state")?;
+            let history = futures::stream::iter(self.requests(&export, channel_id, &store)?);
+            let report =
+                catchup::report(ChannelId(channel_id), history, &store, self.rate_limit).await?;
+            println!("{}", report);
+            return Ok(());
+        }
+
+        if self.values.is_empty() {
+            anyhow::bail!("at least one value must be provided");
+        } else if self.values.iter().any(|v| v.amount.value().is_zero()) {
+            anyhow::bail!("all values must be non-zero");
+        }
+
+        if let Some((custody_url, fvk)) = self.backend.remote_signer()? {
+            let token = env::var(custody::TOKEN_VAR)
+                .with_context(|| format!("missing environment variable {}", custody::TOKEN_VAR))?;
+            let custody = custody::remote(custody_url, &token).await?;
+            self.import(export, channel_id, store_dir, fvk, custody)
+                .await
+        } else {
+            let custody_file = self.backend.custody_file(&data_dir);
+            let wallet = Wallet::load(custody_file)
+                .context("Failed to load wallet from local custody file")?;
+            let custody = custody::local(&wallet);
+            let fvk = wallet.spend_key.full_viewing_key().clone();
+            self.import(export, channel_id, store_dir, fvk, custody)
+                .await
+        }
+    }
+
+    /// Dispense to the requests in the export, using the given custody service to authorize
+    /// transactions.
+    async fn import<C>(
+        self,
+        export: Export,
+        channel_id: u64,
+        store_dir: PathBuf,
+        fvk: FullViewingKey,
+        custody: C,
+    ) -> anyhow::Result<()>
+    where
+        C: CustodyClient + Clone + Send + 'static,
+    {
+        if let Some(view_url) = self.backend.view_url.clone() {
+            let view = view::remote(view_url).await?;
+            self.run(export, channel_id, store_dir, fvk, view, custody)
+                .await
+        } else {
+            let view = view::failover(
+                &fvk,
+                self.backend.nodes.clone(),
+                self.backend.node_check_interval,
+                self.backend.max_node_failures,
+            )
+            .await?;
+            self.run(export, channel_id, store_dir, fvk, view, custody)
+                .await
+        }
+    }
+
+    /// Dispense to the requests in the export, using the given view and custody services.
+    async fn run<V, C>(
+        self,
+        export: Export,
+        channel_id: u64,
+        store_dir: PathBuf,
+        fvk: FullViewingKey,
+        mut view: V,
+        custody: C,
+    ) -> anyhow::Result<()>
+    where
+        V: ViewClient + Clone + Send + 'static,
+        C: CustodyClient + Clone + Send + 'static,
+    {
+        tracing::info!("starting initial sync");
+        view::sync(&mut view, &fvk).await?;
+        tracing::info!("initial sync complete");
+
+        // The bot must not be processing requests from the same store at the same time
+        let _lock = InstanceLock::acquire(&store_dir, self.wait_for_lock, None).await?;
+        let store = Store::load(&store_dir).context("can load galileo state")?;
+        let history = futures::stream::iter(self.requests(&export, channel_id, &store)?);
+        let backlog = catchup::backlog(history, &store).await?;
+        tracing::info!(count = backlog.len(), "importing backlog");
+
+        let lifecycle = Lifecycle::default();
+        let sender = Sender::new(0, fvk, view, custody, Memo::default(), false);
+        let (requests, _control, responder) = Responder::new(
+            sender,
+            self.max_addresses,
+            self.values.clone(),
+            None,
+            Menu::default(),
+            Vec::new(),
+            Vec::new(),
+            None,
+            Counterparties::default(),
+            store.clone(),
+            lifecycle.clone(),
+        );
+        let responder = tokio::spawn(responder.run());
+
+        // The channel may be gone, so don't leave a checkpoint for the bot to catch up from there
+        let mut results = catchup::submit(
+            backlog,
+            requests,
+            store,
+            lifecycle,
+            Pacing::default(),
+            false,
+        );
+        let (mut succeeded, mut failed) = (0, 0);
+        while let Some(result) = results.next().await {
+            let (user_id, response) = result?;
+            if response.complete_failure() {
+                failed += 1;
+            } else {
+                succeeded += 1;
+            }
+            println!("{}: {}", user_id, response.plain_summary());
+        }
+        // Closing the request queue lets the responder finish
+        drop(results);
+        responder.await??;
+
+        println!(
+            "Imported backlog: {} requests dispensed to, {} failed",
+            succeeded, failed
+        );
+        Ok(())
+    }
+
+    /// The requests in the export which haven't already been dispensed to, in reverse chronological
+    /// order like the history of a channel.
+    fn requests(
+        &self,
+        export: &Export,
+        channel_id: u64,
+        store: &Store,
+    ) -> anyhow::Result<Vec<anyhow::Result<Backlogged>>> {
+        // Messages too old to be remembered as processed can still be found in the dispense ledger
+        let dispensed: HashSet<u64> = store
+            .dispenses()
+            .iter()
+            .filter_map(|dispense| dispense.message_id)
+            .collect();
+
+        let mut requests = Vec::new();
+        for message in export.messages.iter() {
+            if message.author.is_bot {
+                continue;
+            }
+            let message_id = parse_id(&message.id, "message")?;
+            if dispensed.contains(&message_id) {
+                tracing::debug!(message_id, "skipping message in dispense ledger");
+                continue;
+            }
+            let origin = Origin {
+                user_id: id::UserId(parse_id(&message.author.id, "user")?),
+                channel_id: id::ChannelId(channel_id),
+                message_id: id::MessageId(message_id),
+            };
+            if let Some((response, request)) = Request::try_new(&message.content, origin) {
+                requests.push((message_id, (response, request)));
+            }
+        }
+
+        // Exports are usually oldest first, but don't rely on it
+        requests.sort_by_key(|(message_id, _)| std::cmp::Reverse(*message_id));
+        Ok(requests
+            .into_iter()
+            .map(|(_, backlogged)| Ok(backlogged))
+            .collect())
+    }
+}
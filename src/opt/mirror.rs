@@ -0,0 +1,164 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use penumbra_keys::FullViewingKey;
+use penumbra_transaction::Action;
+use penumbra_view::ViewClient;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{view, Store};
+
+#[derive(Debug, Clone, Parser)]
+pub struct Mirror {
+    /// The full viewing key of the faucet's wallet.
+    #[clap(long)]
+    fvk: FullViewingKey,
+    /// The URL of the pd gRPC endpoint on the remote node.
+    #[clap(short, long, default_value = "http://testnet.penumbra.zone:8080")]
+    node: Url,
+    /// The state directory of the primary instance (the `galileo` directory inside its data
+    /// directory), from which its dispense ledger is read. This must be shared with, or regularly
+    /// copied from, the primary.
+    #[clap(long)]
+    primary_state: PathBuf,
+    /// Path to the file in which to keep the mirror's own ledger of spends from the faucet wallet.
+    #[clap(long, default_value = "mirror.jsonl")]
+    ledger: PathBuf,
+    /// How often to check for new transactions.
+    #[clap(long, default_value = "1m", parse(try_from_str = humantime::parse_duration))]
+    poll_interval: Duration,
+    /// A Discord webhook URL to which to post alerts about unexpected spends.
+    #[clap(long)]
+    alert_webhook: Option<Url>,
+}
+
+/// A spend from the faucet wallet, as recorded in the mirror's own ledger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Spend {
+    /// When the mirror decided whether the spend was expected.
+    time: DateTime<Utc>,
+    /// The height at which the transaction was included.
+    height: u64,
+    /// The hash of the transaction.
+    tx_id: String,
+    /// Whether the transaction was found in the primary's dispense ledger.
+    expected: bool,
+}
+
+impl Mirror {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        // Resume after the last spend we recorded, if any
+        let mut next_height = self
+            .read_ledger()?
+            .last()
+            .map_or(0, |spend| spend.height + 1);
+
+        let mut view = view::in_memory(&self.fvk, self.node.clone()).await?;
+        tracing::info!("starting initial sync");
+        view::sync(&mut view, &self.fvk).await?;
+        tracing::info!(?next_height, "initial sync complete, watching for spends");
+
+        // Spends which weren't in the primary's ledger the last time we looked, by transaction
+        // hash: we give the primary one more poll interval to record them before alerting, since
+        // the primary only writes to its ledger after the transaction is confirmed
+        let mut suspects = BTreeMap::<String, u64>::new();
+
+        let mut interval = tokio::time::interval(self.poll_interval);
+        loop {
+            interval.tick().await;
+
+            let expected: HashSet<String> = Store::load_dispenses(&self.primary_state)
+                .context("could not read primary's dispense ledger")?
+                .into_iter()
+                .map(|dispense| dispense.tx_id)
+                .collect();
+
+            // Spends by the faucet are the transactions visible to its viewing key which spend
+            // any of its notes
+            let previous_suspects = std::mem::take(&mut suspects);
+            let mut spends = previous_suspects.clone();
+            for info in view.transaction_info(Some(next_height), None).await? {
+                next_height = next_height.max(info.height + 1);
+                if info
+                    .transaction
+                    .actions()
+                    .any(|action| matches!(action, Action::Spend(_)))
+                {
+                    spends.insert(info.id.to_string(), info.height);
+                }
+            }
+
+            for (tx_id, height) in spends {
+                if expected.contains(&tx_id) {
+                    tracing::debug!(?tx_id, ?height, "spend matches primary's ledger");
+                    self.record(height, tx_id, true)?;
+                } else if previous_suspects.contains_key(&tx_id) {
+                    self.alert(format!(
+                        "Spend from the faucet wallet not found in the primary's ledger: \
+                        transaction `{}` at height {}",
+                        tx_id, height,
+                    ))
+                    .await;
+                    self.record(height, tx_id, false)?;
+                } else {
+                    tracing::debug!(?tx_id, ?height, "spend not yet in primary's ledger");
+                    suspects.insert(tx_id, height);
+                }
+            }
+        }
+    }
+
+    /// Read the mirror's own ledger of spends.
+    fn read_ledger(&self) -> anyhow::Result<Vec<Spend>> {
+        if !self.ledger.exists() {
+            return Ok(Vec::new());
+        }
+        std::fs::read_to_string(&self.ledger)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Append a spend to the mirror's own ledger.
+    fn record(&self, height: u64, tx_id: String, expected: bool) -> anyhow::Result<()> {
+        let spend = Spend {
+            time: Utc::now(),
+            height,
+            tx_id,
+            expected,
+        };
+        let mut ledger = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.ledger)?;
+        writeln!(ledger, "{}", serde_json::to_string(&spend)?)?;
+        Ok(())
+    }
+
+    /// Log an alert, and post it to the alert webhook if one is configured.
+    async fn alert(&self, message: String) {
+        tracing::warn!("{}", message);
+        if let Some(webhook) = &self.alert_webhook {
+            let result = reqwest::Client::new()
+                .post(webhook.clone())
+                .header("Content-Type", "application/json")
+                .body(serde_json::json!({ "content": message }).to_string())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                tracing::error!(error = ?e, "failed to post alert");
+            }
+        }
+    }
+}
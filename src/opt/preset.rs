@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use clap::ArgEnum;
+
+/// A named bundle of settings for a common kind of deployment. Options given explicitly on the
+/// command line take precedence over the preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Preset {
+    /// An ordinary testnet: a day between requests, and one address per message.
+    Testnet,
+    /// A testnet phase with a lot of attention (e.g. a launch or incentivized phase): smaller
+    /// amounts so the faucet lasts, and little patience for users who keep asking.
+    TestnetLarge,
+    /// A devnet used by a handful of developers: large amounts, often, with several addresses per
+    /// message.
+    Devnet,
+}
+
+/// The settings which a preset stands for.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The amounts to send for each response.
+    pub values: &'static [&'static str],
+    /// Per-user rate limit.
+    pub rate_limit: Duration,
+    /// Maximum number of times to reply to a user informing them of the rate limit.
+    pub reply_limit: usize,
+    /// The same, for users who have never been sent tokens.
+    pub first_time_reply_limit: Option<usize>,
+    /// Maximum number of addresses per message to which to dispense tokens.
+    pub max_addresses: usize,
+}
+
+/// The settings used without a preset, which don't include any amounts to send.
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            values: &[],
+            rate_limit: Duration::from_secs(24 * 60 * 60),
+            reply_limit: 5,
+            first_time_reply_limit: None,
+            max_addresses: 1,
+        }
+    }
+}
+
+impl Preset {
+    pub fn settings(self) -> Settings {
+        match self {
+            Preset::Testnet => Settings {
+                values: &["100penumbra", "1000test_usd"],
+                ..Settings::default()
+            },
+            Preset::TestnetLarge => Settings {
+                values: &["10penumbra", "100test_usd"],
+                reply_limit: 1,
+                // Newcomers are the ones most likely to be confused by the rate limit
+                first_time_reply_limit: Some(5),
+                ..Settings::default()
+            },
+            Preset::Devnet => Settings {
+                values: &["1000penumbra", "10000test_usd"],
+                rate_limit: Duration::from_secs(10 * 60),
+                max_addresses: 5,
+                ..Settings::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use rand::rngs::OsRng;
+
+use crate::{
+    intake::{signed_message, PROVE_COMMAND},
+    Wallet,
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct ProveAddress {
+    /// Path to the custody file of the wallet whose addresses to prove ownership of [default:
+    /// `custody.json` in the platform appdata directory, where `pcli` keeps it]. If it is
+    /// encrypted, the passphrase must be given in the `GALILEO_CUSTODY_PASSPHRASE` environment
+    /// variable.
+    #[clap(long)]
+    custody_file: Option<PathBuf>,
+    /// The challenge the faucet gave.
+    challenge: String,
+}
+
+impl ProveAddress {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let custody_file = self
+            .custody_file
+            .unwrap_or_else(|| super::default_data_dir().join("custody.json"));
+        let wallet = Wallet::load(&custody_file).with_context(|| {
+            format!(
+                "Failed to load wallet from custody file {}",
+                custody_file.display()
+            )
+        })?;
+
+        let fvk = wallet.spend_key.full_viewing_key();
+        let signature = wallet
+            .spend_key
+            .spend_auth_key()
+            .sign(OsRng, &signed_message(self.challenge.trim()));
+
+        // This reveals the full viewing key to the faucet, which is what proves the addresses
+        // belong to the wallet, so it's to be sent by direct message rather than posted
+        println!(
+            "{} {} {}",
+            PROVE_COMMAND,
+            fvk,
+            hex::encode(signature.to_bytes())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    path::{Component, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use flate2::read::GzDecoder;
+
+use super::backup::{STORE_DIRS, VIEW_FILE};
+use crate::{store::InstanceLock, Store};
+
+#[derive(Debug, Clone, Parser)]
+pub struct Restore {
+    /// The data directory to restore into [default: the platform appdata directory].
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+    /// Replace any state already in the data directory, which is moved aside (with the suffix
+    /// `.before-restore`) rather than deleted.
+    #[clap(long)]
+    force: bool,
+    /// Path to the backup to restore, as written by `galileo backup`.
+    #[clap(long, short)]
+    input: PathBuf,
+}
+
+impl Restore {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let data_dir = self
+            .data_dir
+            .clone()
+            .unwrap_or_else(super::default_data_dir);
+        std::fs::create_dir_all(&data_dir).context("can create data dir")?;
+
+        // Check everything in the backup is something we'd have put there, before touching anything
+        let mut restoring = BTreeSet::new();
+        for entry in self.open()?.entries()? {
+            let entry = entry?;
+            let path = entry.path()?;
+            let top = match path.components().next() {
+                Some(Component::Normal(top)) => top.to_string_lossy().into_owned(),
+                _ => anyhow::bail!("unexpected path in backup: {}", path.display()),
+            };
+            if !STORE_DIRS.contains(&top.as_str()) && top != VIEW_FILE {
+                anyhow::bail!("unexpected path in backup: {}", path.display());
+            }
+            restoring.insert(top);
+        }
+
+        // Never pull the state out from under a running bot
+        for name in STORE_DIRS.iter().filter(|name| restoring.contains(**name)) {
+            match InstanceLock::holder(&data_dir.join(name)) {
+                Some(holder) if !holder.is_stale() => anyhow::bail!(
+                    "{} is in use by an instance (pid {} on {}): stop it before restoring",
+                    name,
+                    holder.pid,
+                    holder.host
+                ),
+                _ => {}
+            }
+        }
+
+        for name in &restoring {
+            let path = data_dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+            if !self.force {
+                anyhow::bail!(
+                    "{} already exists: pass --force to move it aside and restore anyway",
+                    path.display()
+                );
+            }
+            let aside = data_dir.join(format!("{}.before-restore", name));
+            if aside.exists() {
+                anyhow::bail!(
+                    "{} already exists from an earlier restore: remove it first",
+                    aside.display()
+                );
+            }
+            std::fs::rename(&path, &aside)
+                .with_context(|| format!("could not move {} aside", path.display()))?;
+            tracing::info!(from = %path.display(), to = %aside.display(), "moved aside");
+        }
+
+        for entry in self.open()?.entries()? {
+            let mut entry = entry?;
+            if !entry.unpack_in(&data_dir)? {
+                anyhow::bail!("backup tried to write outside the data directory");
+            }
+        }
+
+        // Make sure what we restored is something the bot can load
+        for name in STORE_DIRS.iter().filter(|name| restoring.contains(**name)) {
+            Store::load(data_dir.join(name))
+                .with_context(|| format!("could not load restored {}", name))?;
+        }
+
+        tracing::info!(
+            data_dir = %data_dir.display(),
+            restored = ?restoring,
+            "restored backup"
+        );
+        Ok(())
+    }
+
+    /// Open the backup for reading from the start.
+    fn open(&self) -> anyhow::Result<tar::Archive<GzDecoder<File>>> {
+        let file = File::open(&self.input)
+            .with_context(|| format!("could not open {}", self.input.display()))?;
+        Ok(tar::Archive::new(GzDecoder::new(file)))
+    }
+}
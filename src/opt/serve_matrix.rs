@@ -0,0 +1,287 @@
+use std::{env, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::Parser;
+use num_traits::identities::Zero;
+use penumbra_asset::Value;
+use penumbra_custody::CustodyClient;
+use penumbra_keys::FullViewingKey;
+use penumbra_view::ViewClient;
+use tokio::signal::unix::{signal, SignalKind};
+use url::Url;
+
+use super::backend::Backend;
+use crate::{
+    custody,
+    intake::{Intake, Overrides, RateLimit, ReplyLimits},
+    matrix,
+    responder::{BatchSchedule, Budget, Counterparties, Delegation, Menu, MenuItem, Routes},
+    sender::Memo,
+    store::InstanceLock,
+    transport::{Transport, Webhook},
+    view, Lifecycle, Locale, Responder, Sender, Store, Wallet,
+};
+
+/// The environment variable holding the bot account's Matrix access token.
+const ACCESS_TOKEN_VAR: &str = "MATRIX_ACCESS_TOKEN";
+
+#[derive(Debug, Clone, Parser)]
+pub struct ServeMatrix {
+    /// The URL of the homeserver of the bot's Matrix account.
+    #[clap(long)]
+    homeserver: Url,
+    /// A room to dispense in, by id or alias (e.g. `#faucet:penumbra.zone`), which the bot joins
+    /// when it starts. May be given more than once.
+    #[clap(long = "room", required = true, multiple_occurrences = true)]
+    rooms: Vec<String>,
+    /// Per-user rate limit (e.g. "10m" or "1day").
+    #[clap(short, long, default_value = "1day", parse(try_from_str = humantime::parse_duration))]
+    rate_limit: Duration,
+    /// Maximum number of times to reply to a user informing them of the rate limit.
+    #[clap(long, default_value = "5")]
+    reply_limit: usize,
+    /// Maximum number of times to reply to a user who has never been sent tokens informing them of
+    /// the rate limit, if different from `--reply-limit`.
+    #[clap(long)]
+    first_time_reply_limit: Option<usize>,
+    /// Maximum number of addresses per message to which to dispense tokens.
+    #[clap(long, default_value = "1")]
+    max_addresses: usize,
+    /// The language to reply to requests in (`en`, `es`, `pt`, or `fr`).
+    #[clap(long, default_value = "en")]
+    locale: Locale,
+    /// A cap on the total amount of an asset sent in any window of time across all users, written
+    /// like `500penumbra/1h`, to bound how fast the faucet can be drained. Once it's spent,
+    /// requests are turned away (saying when to try again) until the window rolls over. May be
+    /// given more than once.
+    #[clap(long = "budget", multiple_occurrences = true)]
+    budgets: Vec<Budget>,
+    /// A cap on the total amount of an asset sent to any one user in any window of time, written
+    /// like `200penumbra/1day`, so that giving many addresses doesn't multiply what they get. Once
+    /// it's reached, further addresses are turned away (saying when to try again). May be given
+    /// more than once.
+    #[clap(long = "user-cap", multiple_occurrences = true)]
+    user_caps: Vec<Budget>,
+    /// Instead of dispensing each request as it comes in, collect them and dispense them all in a
+    /// single transaction this often (e.g. `10m`), trading latency for far fewer transactions and
+    /// fees. Users are told when the next batch goes out.
+    #[clap(long, parse(try_from_str = humantime::parse_duration))]
+    batch_interval: Option<Duration>,
+    /// What to write in the memo of each transaction, so recipients and auditors can tell which
+    /// request it answered: `{user}`, `{channel}`, and `{message}` are replaced by the ids of the
+    /// requester, their channel, and their message [default: `galileo faucet — request {message}
+    /// in channel {channel}`].
+    #[clap(long, conflicts_with = "private_memo")]
+    memo_template: Option<String>,
+    /// Keep the memo of each transaction generic, saying nothing about the request it answered,
+    /// since memos are visible to the recipient.
+    #[clap(long)]
+    private_memo: bool,
+    /// Also send delegation tokens of a validator to requests which say "delegate", so stakers can
+    /// try undelegating without delegating first, written as `<amount>@<validator identity key>`
+    /// (e.g. `10@penumbravalid1...`). The faucet's wallet must hold the tokens.
+    #[clap(long)]
+    delegate: Option<Delegation>,
+    /// Send only the delegation tokens to requests which say "delegate", instead of the usual
+    /// values as well.
+    #[clap(long, requires = "delegate")]
+    delegate_instead: bool,
+    /// An asset users can ask for by name after their address (like `penumbrav2t1... gm gn`),
+    /// instead of receiving the usual values, written as the value to send and optionally how
+    /// often each user may ask for it (e.g. `10gm/1h`). May be given more than once.
+    #[clap(long = "menu", multiple_occurrences = true)]
+    menu: Vec<MenuItem>,
+    #[clap(flatten)]
+    backend: Backend,
+    /// Go through the whole dispense pipeline, but never broadcast anything, marking replies as
+    /// simulated.
+    #[clap(long)]
+    dry_run: bool,
+    /// If a request takes longer than this (e.g. proving on slow hardware), reply saying we're
+    /// still working on it, and keep that reply updated with the time taken until it's done. Set to
+    /// 0 to never do so.
+    #[clap(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+    progress_after: Duration,
+    /// A webhook to which to post a summary of the result of every request, as JSON with a
+    /// `content` field.
+    #[clap(long)]
+    result_webhook: Option<Url>,
+    /// On SIGTERM (or Ctrl-C), how long to keep processing requests already queued before exiting.
+    #[clap(long, default_value = "1m", parse(try_from_str = humantime::parse_duration))]
+    drain_timeout: Duration,
+    /// The amounts to send for each response, written as typed values 1.87penumbra, 12cubes, etc.
+    values: Vec<Value>,
+}
+
+impl ServeMatrix {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        if self.values.is_empty() {
+            anyhow::bail!("at least one value must be provided");
+        } else if self.values.iter().any(|v| v.amount.value().is_zero()) {
+            anyhow::bail!("all values must be non-zero");
+        }
+
+        let access_token = env::var(ACCESS_TOKEN_VAR)
+            .with_context(|| format!("missing environment variable {}", ACCESS_TOKEN_VAR))?;
+        let client = matrix::Client::new(self.homeserver.clone(), access_token);
+
+        let data_dir = self.backend.data_dir();
+        std::fs::create_dir_all(&data_dir).context("can create data dir")?;
+        // Kept apart from the Discord bot's state, whose checkpoints are for Discord channels
+        let store_dir = data_dir.join("galileo-matrix");
+
+        if let Some((custody_url, fvk)) = self.backend.remote_signer()? {
+            let token = env::var(custody::TOKEN_VAR)
+                .with_context(|| format!("missing environment variable {}", custody::TOKEN_VAR))?;
+            let custody = custody::remote(custody_url, &token).await?;
+            self.serve(client, store_dir, fvk, custody).await
+        } else {
+            let custody_file = self.backend.custody_file(&data_dir);
+            let wallet = Wallet::load(custody_file)
+                .context("Failed to load wallet from local custody file")?;
+            let custody = custody::local(&wallet);
+            let fvk = wallet.spend_key.full_viewing_key().clone();
+            self.serve(client, store_dir, fvk, custody).await
+        }
+    }
+
+    /// Run the bot, using the given custody service to authorize transactions.
+    async fn serve<C>(
+        self,
+        client: matrix::Client,
+        store_dir: PathBuf,
+        fvk: FullViewingKey,
+        custody: C,
+    ) -> anyhow::Result<()>
+    where
+        C: CustodyClient + Clone + Send + 'static,
+    {
+        if let Some(view_url) = self.backend.view_url.clone() {
+            let view = view::remote(view_url).await?;
+            self.run(client, store_dir, fvk, view, custody).await
+        } else {
+            let view = view::failover(
+                &fvk,
+                self.backend.nodes.clone(),
+                self.backend.node_check_interval,
+                self.backend.max_node_failures,
+            )
+            .await?;
+            self.run(client, store_dir, fvk, view, custody).await
+        }
+    }
+
+    /// Run the bot, using the given view and custody services.
+    async fn run<V, C>(
+        self,
+        client: matrix::Client,
+        store_dir: PathBuf,
+        fvk: FullViewingKey,
+        mut view: V,
+        custody: C,
+    ) -> anyhow::Result<()>
+    where
+        V: ViewClient + Clone + Send + 'static,
+        C: CustodyClient + Clone + Send + 'static,
+    {
+        tracing::info!(
+            "starting initial sync: please wait for sync to complete before requesting tokens"
+        );
+        view::sync(&mut view, &fvk).await?;
+        tracing::info!("initial sync complete");
+
+        let _lock = InstanceLock::acquire(&store_dir, false, None).await?;
+        let store = Store::load(&store_dir).context("can load galileo state")?;
+
+        // Shut down gracefully on SIGTERM or Ctrl-C: stop accepting requests, and give those already
+        // queued until the drain timeout to be processed
+        let lifecycle = Lifecycle::default();
+        let mut terminate_signal = signal(SignalKind::terminate())?;
+        let shutdown = {
+            let lifecycle = lifecycle.clone();
+            let drain_timeout = self.drain_timeout;
+            async move {
+                tokio::select! {
+                    _ = terminate_signal.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                tracing::info!("shutting down: no longer accepting requests, draining queue");
+                lifecycle.drain();
+                if tokio::time::timeout(drain_timeout, lifecycle.idle())
+                    .await
+                    .is_err()
+                {
+                    tracing::warn!("timed out draining queue");
+                }
+            }
+        };
+
+        if self.dry_run {
+            tracing::warn!("dry run: transactions will be built but never broadcast");
+        }
+        let memo = if self.private_memo {
+            Memo::private()
+        } else {
+            self.memo_template
+                .clone()
+                .map(Memo::new)
+                .unwrap_or_default()
+        };
+        let batch = self.batch_interval.map(BatchSchedule::new);
+        let delegation = self.delegate.clone().map(|delegation| Delegation {
+            instead: self.delegate_instead,
+            ..delegation
+        });
+        let sender = Sender::new(0, fvk, view, custody, memo, self.dry_run);
+        let (send_requests, _send_control, responder) = Responder::new(
+            sender,
+            self.max_addresses,
+            self.values,
+            delegation,
+            Menu::new(self.menu),
+            self.budgets,
+            self.user_caps,
+            batch,
+            Counterparties::default(),
+            store.clone(),
+            lifecycle.clone(),
+        );
+
+        let intake = Intake::new(
+            RateLimit::new(self.rate_limit),
+            // Matrix has no roles, so there are no per-role reply limits
+            ReplyLimits::new(self.reply_limit, self.first_time_reply_limit, Vec::new()),
+            Overrides::default(),
+            self.locale,
+            Counterparties::default(),
+            store.clone(),
+            false,
+            self.result_webhook
+                .iter()
+                .cloned()
+                .map(|url| Box::new(Webhook::new(url)) as Box<dyn Transport>)
+                .collect(),
+            lifecycle,
+            Routes::new(send_requests),
+            Some(self.progress_after).filter(|after| !after.is_zero()),
+            batch,
+            None,
+            // Nor priority roles
+            Vec::new(),
+            None,
+        );
+        let handler = matrix::Handler::new(client, intake, store, &self.rooms).await?;
+
+        tokio::select! {
+            result = tokio::spawn(handler.run()) =>
+                result.unwrap().context("error in matrix client"),
+            result = tokio::spawn(responder.run()) =>
+                result.unwrap().context("error in responder service"),
+            reason = view::gave_up() => Err(anyhow::anyhow!(reason)).context("error in view service"),
+            () = shutdown => {
+                tracing::info!("stopped");
+                Ok(())
+            }
+        }
+    }
+}
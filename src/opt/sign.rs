@@ -0,0 +1,103 @@
+use std::{env, net::SocketAddr, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::Parser;
+use penumbra_asset::Value;
+use penumbra_custody::soft_kms::SoftKms;
+use penumbra_proto::custody::v1alpha1::custody_protocol_service_server::CustodyProtocolServiceServer;
+use tonic::transport::{Identity, ServerTlsConfig};
+
+use crate::{custody, Wallet};
+
+#[derive(Debug, Clone, Parser)]
+pub struct Sign {
+    /// Path to the directory containing the custody file [default: platform appdata directory].
+    #[clap(long, short)]
+    data_dir: Option<PathBuf>,
+    /// Path to the custody file [default: `custody.json` in the data directory]. If it is
+    /// encrypted, the passphrase must be given in the `GALILEO_CUSTODY_PASSPHRASE` environment
+    /// variable.
+    #[clap(long)]
+    custody_file: Option<PathBuf>,
+    /// The address on which to listen for requests from the bot. Unless it's a loopback address,
+    /// `--tls-cert` and `--tls-key` must be given too, since the shared token and every transaction
+    /// plan are sent to it.
+    #[clap(long, default_value = "127.0.0.1:8081")]
+    bind: SocketAddr,
+    /// Path to the PEM-encoded certificate (chain) to serve TLS with.
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Path to the PEM-encoded private key for the TLS certificate.
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// The most of each asset which a single transaction may send out of the wallet, written as
+    /// typed values 100penumbra, 12cubes, etc. Transactions sending any other asset are refused,
+    /// so delegation tokens handed out with `--delegate` need a limit of their own.
+    #[clap(long = "limit", required = true)]
+    limits: Vec<Value>,
+    /// The most of each asset which may leave the wallet over `--window` altogether, fees included,
+    /// written like `--limit`, so that limit-sized transactions one after another can't drain it.
+    #[clap(long = "window-limit", required = true)]
+    window_limits: Vec<Value>,
+    /// The rolling window over which `--window-limit` applies.
+    #[clap(long, default_value = "24h", parse(try_from_str = humantime::parse_duration))]
+    window: Duration,
+    /// The largest fee a transaction may pay (in upenumbra).
+    #[clap(long, default_value = "0")]
+    max_fee: u64,
+}
+
+impl Sign {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let token = env::var(custody::TOKEN_VAR)
+            .with_context(|| format!("missing environment variable {}", custody::TOKEN_VAR))?;
+        let tls = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                let cert = std::fs::read(cert)
+                    .with_context(|| format!("could not read {}", cert.display()))?;
+                let key = std::fs::read(key)
+                    .with_context(|| format!("could not read {}", key.display()))?;
+                Some(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+            }
+            _ if !self.bind.ip().is_loopback() => anyhow::bail!(
+                "--tls-cert and --tls-key are required to listen on {}, which isn't a loopback address",
+                self.bind
+            ),
+            _ => None,
+        };
+
+        let custody_file = self.custody_file.unwrap_or_else(|| {
+            self.data_dir
+                .unwrap_or_else(super::default_data_dir)
+                .join("custody.json")
+        });
+        let wallet =
+            Wallet::load(custody_file).context("Failed to load wallet from local custody file")?;
+        let fvk = wallet.spend_key.full_viewing_key().clone();
+        let soft_kms = SoftKms::new(wallet.spend_key.clone().into());
+
+        // The bot needs this to plan transactions, so print it for the operator's convenience
+        tracing::info!(%fvk, bind = %self.bind, "serving signing requests");
+
+        let mut server = tonic::transport::Server::builder();
+        if let Some(tls) = tls {
+            server = server.tls_config(tls).context("could not configure TLS")?;
+        }
+        server
+            .add_service(CustodyProtocolServiceServer::with_interceptor(
+                custody::Limited::new(
+                    soft_kms,
+                    fvk,
+                    self.limits,
+                    self.max_fee,
+                    self.window_limits,
+                    self.window,
+                ),
+                custody::require_token(&token)?,
+            ))
+            .serve(self.bind)
+            .await?;
+
+        Ok(())
+    }
+}
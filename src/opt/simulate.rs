@@ -0,0 +1,277 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::Parser;
+use penumbra_asset::Value;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::{
+    id::{ChannelId, MessageId, RoleId, UserId},
+    intake::{Incoming, Intake, Overrides, RateLimit, ReplyLimits, RoleReplyLimit},
+    responder::{Counterparties, Counterparty, Menu, Routes},
+    simulate::{MockChat, MockSender},
+    Lifecycle, Locale, Responder, Store,
+};
+
+#[derive(Debug, Clone, Parser)]
+pub struct Simulate {
+    /// Per-user rate limit (e.g. "10m" or "1day").
+    #[clap(short, long, default_value = "1day", parse(try_from_str = humantime::parse_duration))]
+    rate_limit: Duration,
+    /// Maximum number of times to reply to a user informing them of the rate limit.
+    #[clap(long, default_value = "5")]
+    reply_limit: usize,
+    /// Maximum number of times to reply to a user who has never been sent tokens informing them of
+    /// the rate limit, if different from `--reply-limit`.
+    #[clap(long)]
+    first_time_reply_limit: Option<usize>,
+    /// Maximum number of times to reply to users with a particular role informing them of the rate
+    /// limit, written as `<role_id>=<limit>`.
+    #[clap(long)]
+    role_reply_limit: Vec<RoleReplyLimit>,
+    /// Maximum number of addresses per message to which to dispense tokens.
+    #[clap(long, default_value = "1")]
+    max_addresses: usize,
+    /// Include the receipts which would be sent by direct message in the transcript.
+    #[clap(long)]
+    dm_receipts: bool,
+    /// The language to reply in (`en`, `es`, `pt`, or `fr`), to check how replies read in it.
+    #[clap(long, default_value = "en")]
+    locale: Locale,
+    /// An address to which sending should fail, as if the transaction couldn't be built. May be
+    /// given more than once.
+    #[clap(long)]
+    fail: Vec<String>,
+    /// Also dispense to addresses on another chain by withdrawing over IBC, written as
+    /// `<address prefix>=<channel>` (e.g. `osmo=channel-0`). May be given more than once.
+    #[clap(long)]
+    ibc_chain: Vec<Counterparty>,
+    /// Print the transcript of every step, not just the failing ones.
+    #[clap(short, long)]
+    verbose: bool,
+    /// The script to run: one JSON object per line, each a message
+    /// (`{"user": 1, "content": "...", "expect": ["..."]}`), an edit to an earlier message by its
+    /// step number (`{"edit": 0, "content": "...", "expect": ["..."]}`), or a pause
+    /// (`{"sleep": "2s"}`). Blank lines and lines starting with `#` are ignored.
+    script: PathBuf,
+    /// The amounts to send for each response, written as typed values 1.87penumbra, 12cubes, etc.
+    #[clap(default_value = "1penumbra")]
+    values: Vec<Value>,
+}
+
+/// A step in a simulation script.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Step {
+    /// A new message.
+    Message {
+        /// The author's user id.
+        user: u64,
+        /// The author's role ids.
+        #[serde(default)]
+        roles: Vec<u64>,
+        /// The text of the message.
+        content: String,
+        #[serde(flatten)]
+        expect: Expect,
+    },
+    /// An edit to the message posted in an earlier step.
+    Edit {
+        /// The number of the step which posted the message, counting from zero.
+        edit: usize,
+        /// The new text of the message.
+        content: String,
+        #[serde(flatten)]
+        expect: Expect,
+    },
+    /// A pause, written as a duration (e.g. "2s").
+    Sleep { sleep: String },
+}
+
+/// What the faucet should say in answer to a message.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Expect {
+    /// Text which should appear somewhere in what the faucet says.
+    #[serde(default)]
+    expect: Vec<String>,
+    /// Whether the faucet should say nothing at all.
+    #[serde(default)]
+    silent: bool,
+}
+
+impl Expect {
+    /// Check what the faucet said against the expectation, returning what didn't match.
+    fn check(&self, transcript: &[String]) -> Vec<String> {
+        let said = transcript.join("\n");
+        let mut mismatches = self
+            .expect
+            .iter()
+            .filter(|expected| !said.contains(expected.as_str()))
+            .map(|expected| format!("expected {:?}", expected))
+            .collect::<Vec<_>>();
+        if self.silent && !transcript.is_empty() {
+            mismatches.push("expected no reply".to_string());
+        }
+        mismatches
+    }
+}
+
+impl Simulate {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let script = std::fs::read_to_string(&self.script)
+            .with_context(|| format!("could not read script {}", self.script.display()))?;
+        let steps = script
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(number, line)| {
+                serde_json::from_str::<Step>(line)
+                    .with_context(|| format!("could not parse line {} of script", number + 1))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // Start from a clean slate every time, so that runs are repeatable
+        let store_dir =
+            std::env::temp_dir().join(format!("galileo-simulate-{}", std::process::id()));
+        let store = Store::load(&store_dir)?;
+        let result = self.run(steps, store).await;
+        if let Err(e) = std::fs::remove_dir_all(&store_dir) {
+            tracing::warn!(error = ?e, "failed to remove simulation store");
+        }
+        result
+    }
+
+    async fn run(&self, steps: Vec<Step>, store: Store) -> anyhow::Result<()> {
+        let lifecycle = Lifecycle::default();
+        let (requests, _control, responder) = Responder::new(
+            MockSender::new(self.fail.iter().cloned().collect::<HashSet<_>>()),
+            self.max_addresses,
+            self.values.clone(),
+            None,
+            Menu::default(),
+            Vec::new(),
+            Vec::new(),
+            None,
+            Counterparties::new(self.ibc_chain.clone()),
+            store.clone(),
+            lifecycle.clone(),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = responder.run().await {
+                tracing::error!(error = ?e, "responder failed");
+            }
+        });
+
+        let intake = Intake::new(
+            RateLimit::new(self.rate_limit),
+            ReplyLimits::new(
+                self.reply_limit,
+                self.first_time_reply_limit,
+                self.role_reply_limit.clone(),
+            ),
+            Overrides::default(),
+            self.locale,
+            Counterparties::new(self.ibc_chain.clone()),
+            store,
+            self.dm_receipts,
+            Vec::new(),
+            lifecycle,
+            Routes::new(requests),
+            None,
+            None,
+            None,
+            Vec::new(),
+            None,
+        );
+
+        let start = Instant::now();
+        let mut messages = Vec::<Option<Incoming>>::new();
+        let mut failures = 0;
+        for (number, step) in steps.into_iter().enumerate() {
+            let (message, edited, expect) = match step {
+                Step::Message {
+                    user,
+                    roles,
+                    content,
+                    expect,
+                } => {
+                    let message = Incoming {
+                        id: MessageId(number as u64 + 1),
+                        channel_id: ChannelId(1),
+                        server_id: None,
+                        author_id: UserId(user),
+                        author_name: format!("user-{}", user),
+                        author_roles: roles.into_iter().map(RoleId).collect(),
+                        content,
+                    };
+                    (message, false, expect)
+                }
+                Step::Edit {
+                    edit,
+                    content,
+                    expect,
+                } => {
+                    let original = messages.get(edit).cloned().flatten().with_context(|| {
+                        format!("step {} edits step {}, which isn't a message", number, edit)
+                    })?;
+                    (
+                        Incoming {
+                            content,
+                            ..original
+                        },
+                        true,
+                        expect,
+                    )
+                }
+                Step::Sleep { sleep } => {
+                    tokio::time::sleep(humantime::parse_duration(&sleep)?).await;
+                    messages.push(None);
+                    continue;
+                }
+            };
+            messages.push(Some(message.clone()));
+
+            let chat = MockChat::default();
+            let label = format!(
+                "[{}] step {}: user {}{}: {}",
+                humantime::format_duration(Duration::from_secs(start.elapsed().as_secs())),
+                number,
+                message.author_id,
+                if edited { " (edited)" } else { "" },
+                message.content,
+            );
+            intake.handle(&chat, message, edited).await;
+
+            let transcript = chat.transcript();
+            let mismatches = expect.check(&transcript);
+            if self.verbose || !mismatches.is_empty() {
+                println!("{}", label);
+                for reaction in chat.reactions() {
+                    println!("    + reacted {}", reaction.default_emoji());
+                }
+                for until in chat.cooldowns() {
+                    println!(
+                        "    + cooled down until {}",
+                        until.format("%Y-%m-%d %H:%M:%S UTC")
+                    );
+                }
+                for said in transcript.iter() {
+                    for line in said.lines() {
+                        println!("    > {}", line);
+                    }
+                }
+            }
+            for mismatch in mismatches.iter() {
+                println!("  FAILED: {}", mismatch);
+            }
+            failures += mismatches.len();
+        }
+
+        if failures > 0 {
+            anyhow::bail!("{} expectation(s) failed", failures);
+        }
+        println!("All expectations met.");
+        Ok(())
+    }
+}
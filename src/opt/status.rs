@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::admin;
+
+#[derive(Debug, Clone, Parser)]
+pub struct Status {
+    /// The directory the running bot keeps its data in [default: platform appdata directory], in
+    /// which to find its admin socket.
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+    /// The path to the admin socket, if the bot was given another with `--admin-socket`.
+    #[clap(long, conflicts_with = "data_dir")]
+    socket: Option<PathBuf>,
+    /// Print the status as JSON, for scripts.
+    #[clap(long)]
+    json: bool,
+}
+
+impl Status {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        let socket = self.socket.unwrap_or_else(|| {
+            self.data_dir
+                .unwrap_or_else(super::default_data_dir)
+                .join("galileo")
+                .join(admin::SOCKET_FILE)
+        });
+        let status = admin::query(&socket).await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&status)?);
+        } else {
+            print!("{}", status.summary());
+        }
+        Ok(())
+    }
+}
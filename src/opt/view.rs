@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use penumbra_keys::FullViewingKey;
+use url::Url;
+
+use crate::{view, Wallet};
+
+#[derive(Debug, Clone, Parser)]
+pub struct View {
+    #[clap(subcommand)]
+    command: ViewCommand,
+}
+
+#[derive(Debug, Clone, Parser)]
+enum ViewCommand {
+    /// Synchronize the faucet wallet's view database into a file, to ship with a deployment and
+    /// import with `serve --import-snapshot`, so that the bot only has to sync the blocks since.
+    ExportSnapshot(ExportSnapshot),
+}
+
+impl View {
+    pub async fn exec(self) -> anyhow::Result<()> {
+        match self.command {
+            ViewCommand::ExportSnapshot(export) => export.exec().await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+struct ExportSnapshot {
+    /// Path to the custody file holding the faucet's spend key [default: `custody.json` in the
+    /// platform appdata directory]. Only its full viewing key is used.
+    #[clap(long, conflicts_with = "fvk")]
+    custody_file: Option<PathBuf>,
+    /// The full viewing key of the faucet's wallet, instead of reading it from a custody file.
+    #[clap(long)]
+    fvk: Option<FullViewingKey>,
+    /// The URL of the pd gRPC endpoint on the remote node.
+    #[clap(short, long, default_value = "http://testnet.penumbra.zone:8080")]
+    node: Url,
+    /// Path to which to write the snapshot. If it already exists, it is brought up to date instead.
+    /// Ship it together with its `-wal` file, if one is left beside it.
+    #[clap(long, short)]
+    output: PathBuf,
+}
+
+impl ExportSnapshot {
+    async fn exec(self) -> anyhow::Result<()> {
+        let fvk = match self.fvk {
+            Some(fvk) => fvk,
+            None => {
+                let custody_file = self
+                    .custody_file
+                    .unwrap_or_else(|| super::default_data_dir().join("custody.json"));
+                Wallet::load(&custody_file)
+                    .with_context(|| format!("could not load {}", custody_file.display()))?
+                    .spend_key
+                    .full_viewing_key()
+                    .clone()
+            }
+        };
+
+        tracing::info!(output = %self.output.display(), "starting sync into snapshot");
+        let mut view = view::on_disk(&fvk, self.node, &self.output).await?;
+        view::sync(&mut view, &fvk).await?;
+        tracing::info!(
+            output = %self.output.display(),
+            "snapshot is synchronized: import it with `serve --import-snapshot`"
+        );
+        Ok(())
+    }
+}
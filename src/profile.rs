@@ -0,0 +1,167 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use anyhow::Context;
+use futures::{future::BoxFuture, FutureExt};
+use penumbra_asset::Value;
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::{
+    custody, id, registry,
+    responder::{BatchSchedule, Budget, Control, Counterparties, Menu, RequestQueue},
+    sender::Memo,
+    view, Lifecycle, Responder, Sender, Store, Wallet,
+};
+
+/// Another chain to faucet from the same process (say, a preview devnet alongside the testnet),
+/// with its own nodes, wallet, and values, for the requests made in the channels mapped to it.
+///
+/// Written as `<name>:<setting>=<value>,...`, where the settings are `channel` (a channel id),
+/// `node` (a URL), `value` (like `100penumbra`), each of which may be given more than once, and
+/// `custody-file` (a path).
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// The name of the profile, for logs.
+    pub name: String,
+    /// The channels whose requests are dispensed on this chain.
+    pub channels: Vec<id::ChannelId>,
+    /// The nodes of this chain, in order of preference.
+    pub nodes: Vec<Url>,
+    /// The custody file of the wallet to dispense from on this chain.
+    pub custody_file: PathBuf,
+    /// The values to send for each request.
+    pub values: Vec<Value>,
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, settings) = s
+            .split_once(':')
+            .context("profile must be written as <name>:<setting>=<value>,...")?;
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            anyhow::bail!("profile must have a name");
+        }
+        let (mut channels, mut nodes, mut custody_file, mut values) =
+            (Vec::new(), Vec::new(), None, Vec::new());
+        for setting in settings.split(',') {
+            let (setting, value) = setting.split_once('=').with_context(|| {
+                format!("setting must be written as <setting>=<value>: {}", setting)
+            })?;
+            let value = value.trim();
+            match setting.trim() {
+                "channel" => {
+                    channels.push(id::ChannelId(value.parse().context("invalid channel id")?))
+                }
+                "node" => nodes.push(value.parse().context("invalid node URL")?),
+                "custody-file" => custody_file = Some(PathBuf::from(value)),
+                "value" => {
+                    let value: Value = value.parse().context("invalid value")?;
+                    if value.amount.value() == 0 {
+                        anyhow::bail!("all values must be non-zero");
+                    }
+                    values.push(value);
+                }
+                setting => anyhow::bail!(
+                    "unknown setting {} (expected channel, node, custody-file, or value)",
+                    setting
+                ),
+            }
+        }
+        if channels.is_empty() || nodes.is_empty() || values.is_empty() {
+            anyhow::bail!(
+                "profile {} needs at least one channel, node, and value",
+                name
+            );
+        }
+        let custody_file =
+            custody_file.with_context(|| format!("profile {} needs a custody-file", name))?;
+        Ok(Profile {
+            name,
+            channels,
+            nodes,
+            custody_file,
+            values,
+        })
+    }
+}
+
+impl Profile {
+    /// Load this chain's wallet and synchronize a view of it, returning the queues for the
+    /// requests made in its channels and for administrative control, and the responder which
+    /// dispenses them (to run alongside the main one).
+    ///
+    /// Budgets, per-user caps, and batches apply as on the main chain, counting what's sent on
+    /// every chain together. Menus, delegation tokens, and IBC withdrawals name the main chain's
+    /// assets and channels, so are the main chain's alone: requests for them in this chain's
+    /// channels are sent its usual values.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start(
+        &self,
+        check_interval: Duration,
+        max_failures: usize,
+        max_addresses: usize,
+        budgets: Vec<Budget>,
+        user_caps: Vec<Budget>,
+        batch: Option<BatchSchedule>,
+        memo: Memo,
+        dry_run: bool,
+        store: Store,
+        lifecycle: Lifecycle,
+    ) -> anyhow::Result<(
+        RequestQueue,
+        mpsc::Sender<Control>,
+        BoxFuture<'static, anyhow::Result<()>>,
+    )> {
+        let wallet = Wallet::load(&self.custody_file).with_context(|| {
+            format!(
+                "could not load {} for profile {}",
+                self.custody_file.display(),
+                self.name
+            )
+        })?;
+        let fvk = wallet.spend_key.full_viewing_key().clone();
+        tracing::info!(profile = %self.name, "starting sync of profile wallet");
+        let mut view = view::failover(&fvk, self.nodes.clone(), check_interval, max_failures)
+            .await
+            .with_context(|| format!("could not start the view for profile {}", self.name))?;
+        view::sync(&mut view, &fvk).await?;
+        tracing::info!(
+            profile = %self.name,
+            address = %fvk.payment_address(0.into()).0,
+            "profile wallet sync complete"
+        );
+        let problems = registry::check(&mut view, &self.values).await?;
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "the values for profile {} don't match its chain's assets:\n- {}",
+                self.name,
+                problems.join("\n- ")
+            );
+        }
+        tokio::spawn(registry::watch(
+            view.clone(),
+            self.values.clone(),
+            registry::INTERVAL,
+        ));
+
+        let custody = custody::local(&wallet);
+        let sender = Sender::new(0, fvk, view, custody, memo, dry_run);
+        let (requests, control, responder) = Responder::new(
+            sender,
+            max_addresses,
+            self.values.clone(),
+            None,
+            Menu::default(),
+            budgets,
+            user_caps,
+            batch,
+            Counterparties::default(),
+            store,
+            lifecycle,
+        );
+        Ok((requests, control, responder.run().boxed()))
+    }
+}
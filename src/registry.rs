@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use penumbra_asset::{asset, Value};
+use penumbra_view::ViewClient;
+
+use crate::notice;
+
+/// How often to check the values against the chain's assets again, to catch an upgrade changing
+/// them while the faucet runs.
+pub const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Find what's wrong with sending the values on the chain the view follows: assets the chain
+/// doesn't know, or whose display exponent on the chain differs from the one the values were
+/// written with (so that `100penumbra` would no longer mean what it says).
+pub async fn check<V: ViewClient>(view: &mut V, values: &[Value]) -> anyhow::Result<Vec<String>> {
+    let chain = view.assets().await?;
+    let known = asset::Cache::with_known_assets();
+    let mut problems = Vec::new();
+    for value in values {
+        let written = value.format(&known);
+        let metadata = match chain.get(&value.asset_id) {
+            Some(metadata) => metadata,
+            None => {
+                problems.push(format!("`{}` isn't an asset on this chain", written));
+                continue;
+            }
+        };
+        if let Some(expected) = known.get(&value.asset_id) {
+            let (expected, actual) = (
+                expected.default_unit().exponent(),
+                metadata.default_unit().exponent(),
+            );
+            if expected != actual {
+                problems.push(format!(
+                    "`{}` has display exponent {} on this chain, not {}, so the amount sent \
+                    isn't what was configured",
+                    written, actual, expected
+                ));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Check the values against the chain's assets at the given interval in the background, telling
+/// administrators whenever what's wrong changes.
+pub async fn watch<V: ViewClient>(mut view: V, values: Vec<Value>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // The values were checked at startup, so begin with the next check
+    interval.tick().await;
+    let mut reported = Vec::new();
+    loop {
+        interval.tick().await;
+        let problems = match check(&mut view, &values).await {
+            Ok(problems) => problems,
+            Err(e) => {
+                tracing::warn!(error = ?e, "failed to check values against the chain's assets");
+                continue;
+            }
+        };
+        if problems != reported {
+            if problems.is_empty() {
+                notice::send("The configured values match the chain's assets again.");
+            } else {
+                notice::send(format!(
+                    "The configured values no longer match the chain's assets (after an \
+                    upgrade?):\n- {}",
+                    problems.join("\n- ")
+                ));
+            }
+            reported = problems;
+        }
+    }
+}
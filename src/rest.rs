@@ -0,0 +1,166 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::metrics;
+
+/// The counter of Discord REST calls made, by operation.
+pub const CALLS: &str = "galileo_discord_rest_calls_total";
+
+/// The counter of time spent waiting for a turn to make a Discord REST call, in milliseconds, by
+/// operation.
+pub const WAIT: &str = "galileo_discord_rest_wait_milliseconds_total";
+
+/// The counter of Discord REST calls which were rate limited, and retried, by operation.
+pub const RATE_LIMITED: &str = "galileo_discord_rest_rate_limited_total";
+
+/// How long a call can wait for its turn before it's worth mentioning in the logs.
+const SLOW_WAIT: Duration = Duration::from_secs(5);
+
+/// How long to back off the first time a call is rate limited, doubling each time it's rate limited
+/// again, up to the maximum.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest to back off after being rate limited.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How many times to try a call which keeps being rate limited before giving up on it.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// The limit on simultaneous Discord REST calls, once one is set.
+static PERMITS: Mutex<Option<Arc<Semaphore>>> = Mutex::new(None);
+
+/// When every call can go ahead again, after one was rate limited.
+static PAUSED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Allow at most this many Discord REST calls (replies, reactions, DMs, and so on) at once, so
+/// that a burst of them (such as when catch-up completes) doesn't get the bot's IP banned.
+pub fn limit(concurrency: usize) {
+    *PERMITS.lock().unwrap() = Some(Arc::new(Semaphore::new(concurrency)));
+}
+
+/// Wait for a turn to make a Discord REST call, which lasts until the returned permit is dropped.
+/// If no limit was set, there's only waiting while backing off after a call was rate limited.
+pub async fn permit(operation: &'static str) -> Option<OwnedSemaphorePermit> {
+    let labels = [("operation", operation.to_string())];
+    metrics::increment(CALLS, &labels);
+    let permits = PERMITS.lock().unwrap().clone();
+
+    let start = Instant::now();
+    // The semaphore is never closed
+    let permit = match permits {
+        Some(permits) => permits.acquire_owned().await.ok(),
+        None => None,
+    };
+    let paused_until = *PAUSED_UNTIL.lock().unwrap();
+    if let Some(paused_until) = paused_until {
+        tokio::time::sleep_until(paused_until.into()).await;
+    }
+
+    let waited = start.elapsed();
+    metrics::add(WAIT, &labels, waited.as_millis() as u64);
+    if waited >= SLOW_WAIT {
+        tracing::info!(operation, ?waited, "waited for a turn to call Discord");
+    }
+    permit
+}
+
+/// Make a Discord REST call in turn (see [`permit`]), retrying it if Discord says it was rate
+/// limited. Every Discord REST call the bot makes goes through here.
+///
+/// Serenity already waits out the limits it knows about before each call, and when Discord answers
+/// 429 with a `Retry-After` header it sleeps for that long and retries by itself. So a 429 only
+/// reaches us without one (as from Cloudflare, in front of Discord), and serenity drops the body
+/// of the response, so there's no `retry_after` left to read either: we back off exponentially
+/// instead. Every call backs off, not just this one, since they all count against the same limits.
+pub async fn call<T, F, Fut>(operation: &'static str, mut call: F) -> serenity::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = serenity::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        let result = {
+            let _permit = permit(operation).await;
+            call().await
+        };
+        match result {
+            Err(e) if is_rate_limited(&e) && attempt < MAX_ATTEMPTS => {
+                let backoff = backoff(attempt);
+                tracing::warn!(
+                    operation,
+                    ?backoff,
+                    attempt,
+                    "rate limited by Discord, backing off"
+                );
+                metrics::increment(RATE_LIMITED, &[("operation", operation.to_string())]);
+                pause(backoff);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// How long to back off after the given attempt at a call was rate limited.
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .checked_mul(1 << (attempt - 1).min(31))
+        .map_or(MAX_BACKOFF, |backoff| backoff.min(MAX_BACKOFF))
+}
+
+/// Hold back every Discord REST call for the given time (unless they're held back longer already).
+fn pause(wait: Duration) {
+    let until = Instant::now() + wait;
+    let mut paused_until = PAUSED_UNTIL.lock().unwrap();
+    if paused_until.map_or(true, |paused_until| paused_until < until) {
+        *paused_until = Some(until);
+    }
+}
+
+/// Whether the error is Discord saying we're making calls too fast.
+fn is_rate_limited(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(e) => e.status_code().map(|status| status.as_u16()) == Some(429),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        assert_eq!(backoff(1), INITIAL_BACKOFF);
+        assert_eq!(backoff(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(3), INITIAL_BACKOFF * 4);
+        assert_eq!(backoff(MAX_ATTEMPTS), INITIAL_BACKOFF * 32);
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let attempts = AtomicU32::new(0);
+        let result: serenity::Result<()> = call("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(serenity::Error::Other("not a rate limit"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn successes_are_returned() {
+        let result = call("test", || async { Ok(7) }).await;
+        assert_eq!(result.unwrap(), 7);
+    }
+}
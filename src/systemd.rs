@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use sd_notify::NotifyState;
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::{responder::Control, view::SyncProgress};
+
+/// Tell systemd (when it started us as a `Type=notify` service) how we're doing, logging rather
+/// than failing if we can't. Outside of systemd, this does nothing.
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        tracing::debug!(error = ?e, "failed to notify systemd");
+    }
+}
+
+/// Tell systemd we're ready: synchronized, and taking requests.
+pub fn ready() {
+    notify(&[NotifyState::Ready, NotifyState::Status("dispensing")]);
+}
+
+/// Tell systemd we're stopping: no longer taking requests, and finishing those in flight.
+pub fn stopping() {
+    notify(&[NotifyState::Stopping, NotifyState::Status("stopping")]);
+}
+
+/// Show how far the initial sync has got as our status in systemd, until it's done.
+pub fn sync_status(mut progress: watch::Receiver<SyncProgress>) {
+    tokio::spawn(async move {
+        loop {
+            let current = *progress.borrow_and_update();
+            notify(&[NotifyState::Status(&current.summary())]);
+            if current.done || progress.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Ping systemd's watchdog (if the service has `WatchdogSec=` set) for as long as the responder
+/// keeps answering, at half the watchdog's timeout, so that systemd restarts the bot if the
+/// responder gets stuck. A dispense in progress holds up the answer, so the timeout should be
+/// longer than the slowest dispense.
+pub fn watchdog(control: mpsc::Sender<Control>) {
+    let mut usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut usec) {
+        return;
+    }
+    let period = Duration::from_micros(usec) / 2;
+    tracing::info!(period = %humantime::format_duration(period), "pinging systemd watchdog");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let (reply, answered) = oneshot::channel();
+            if control.send(Control::Ping(reply)).await.is_err() || answered.await.is_err() {
+                // The responder is gone, so there's nothing left to vouch for
+                return;
+            }
+            notify(&[NotifyState::Watchdog]);
+        }
+    });
+}
@@ -0,0 +1,7 @@
+pub use galileo_core::transport::*;
+
+mod discord;
+pub use discord::{DirectMessage, InteractionReply, Reply};
+
+mod matrix;
+pub use matrix::MatrixReply;
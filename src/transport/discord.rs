@@ -0,0 +1,165 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use serenity::{
+    cache::Cache,
+    client::Context,
+    http::Http,
+    model::{
+        application::interaction::modal::ModalSubmitInteraction,
+        channel::Message,
+        id::{GuildId, UserId},
+    },
+    prelude::Mentionable,
+};
+
+use super::Transport;
+use crate::{responder::Response, rest};
+
+/// Reply to the message containing the request with a summary of what happened.
+pub struct Reply {
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    message: Message,
+    guild_id: GuildId,
+}
+
+impl Reply {
+    pub fn new(http: Arc<Http>, cache: Arc<Cache>, message: Message, guild_id: GuildId) -> Self {
+        Reply {
+            http,
+            cache,
+            message,
+            guild_id,
+        }
+    }
+
+    /// Reply to a message received in the given context.
+    pub fn from_context(ctx: &Context, message: &Message, guild_id: GuildId) -> Self {
+        Self::new(
+            ctx.http.clone(),
+            ctx.cache.clone(),
+            message.clone(),
+            guild_id,
+        )
+    }
+}
+
+#[async_trait]
+impl Transport for Reply {
+    fn name(&self) -> &'static str {
+        "discord-reply"
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        // Mention the administrator role(s) of the server if an error occurred
+        let mention_admins =
+            if response.failed().is_empty() && response.failed_withdrawals().is_empty() {
+                None
+            } else {
+                Some(
+                    self.cache
+                        .guild_roles(self.guild_id)
+                        .iter()
+                        .flat_map(IntoIterator::into_iter)
+                        .filter(|(_, r)| r.permissions.administrator())
+                        .map(|(&id, _)| id.mention().to_string())
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                )
+            };
+        let summary = response.summary(mention_admins);
+        rest::call("reply", || {
+            self.message.reply_ping(self.http.clone(), summary.clone())
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// Answer an address submitted through the claim button with a summary of what happened, which
+/// only the person who submitted it can see.
+pub struct InteractionReply {
+    http: Arc<Http>,
+    interaction: Arc<ModalSubmitInteraction>,
+    /// Set once anything has been said in answer to the interaction.
+    answered: Arc<AtomicBool>,
+}
+
+impl InteractionReply {
+    pub fn new(
+        http: Arc<Http>,
+        interaction: Arc<ModalSubmitInteraction>,
+        answered: Arc<AtomicBool>,
+    ) -> Self {
+        InteractionReply {
+            http,
+            interaction,
+            answered,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for InteractionReply {
+    fn name(&self) -> &'static str {
+        "discord-interaction"
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        // Administrators can't see the reply, so there's no point mentioning them
+        let summary = response.plain_summary();
+        rest::call("followup", || {
+            self.interaction
+                .create_followup_message(self.http.as_ref(), |followup| {
+                    followup.content(&summary).ephemeral(true)
+                })
+        })
+        .await?;
+        self.answered.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Send the user a receipt by direct message for each address they were sent tokens.
+pub struct DirectMessage {
+    http: Arc<Http>,
+    user_id: UserId,
+}
+
+impl DirectMessage {
+    pub fn new(http: Arc<Http>, user_id: UserId) -> Self {
+        DirectMessage { http, user_id }
+    }
+}
+
+#[async_trait]
+impl Transport for DirectMessage {
+    fn name(&self) -> &'static str {
+        "discord-dm"
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        if response.succeeded().is_empty() {
+            return Ok(());
+        }
+        let channel =
+            rest::call("dm", || self.user_id.create_dm_channel(self.http.as_ref())).await?;
+        for (address, receipt) in response.succeeded() {
+            let message = receipt.message(address, response.locale());
+            let result = rest::call("dm", || {
+                channel.send_message(self.http.as_ref(), |m| m.content(&message))
+            })
+            .await;
+            if let Err(e) = result {
+                // Users can disable DMs from server members, so this isn't unusual
+                tracing::debug!(error = ?e, "failed to send receipt");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+
+use super::Transport;
+use crate::{matrix::Client, responder::Response};
+
+/// Reply to the Matrix message containing the request with a summary of what happened.
+pub struct MatrixReply {
+    client: Client,
+    room_id: String,
+    event_id: String,
+}
+
+impl MatrixReply {
+    pub fn new(client: Client, room_id: String, event_id: String) -> Self {
+        MatrixReply {
+            client,
+            room_id,
+            event_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for MatrixReply {
+    fn name(&self) -> &'static str {
+        "matrix-reply"
+    }
+
+    async fn deliver(&self, response: &Response) -> anyhow::Result<()> {
+        self.client
+            .reply(&self.room_id, &self.event_id, response.plain_summary())
+            .await?;
+        Ok(())
+    }
+}
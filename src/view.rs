@@ -0,0 +1,466 @@
+use std::{
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use futures::TryStreamExt;
+use penumbra_asset::asset;
+use penumbra_keys::FullViewingKey;
+use penumbra_proto::view::v1alpha1::{
+    view_protocol_service_client::ViewProtocolServiceClient,
+    view_protocol_service_server::ViewProtocolServiceServer,
+};
+use penumbra_view::{ViewClient, ViewService};
+use tokio::sync::{oneshot, watch};
+use tonic::{
+    body::BoxBody,
+    codegen::{http, BoxFuture, Service},
+    transport::Channel,
+};
+use url::Url;
+
+use crate::{node, notice};
+
+/// How long to wait before first trying to reconnect a view to its node, doubling with each failure
+/// in a row, up to the node check interval.
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Why a view gave up on reaching its node, once one has, and who's waiting to hear about it.
+static GAVE_UP: Mutex<(Option<String>, Vec<oneshot::Sender<String>>)> =
+    Mutex::new((None, Vec::new()));
+
+/// A view service running in-process, which we talk to by doing gRPC with ourselves.
+pub type LocalView = ViewProtocolServiceClient<ViewProtocolServiceServer<ViewService>>;
+
+/// A view service running in-process which fails over between nodes, which we talk to by doing
+/// gRPC with ourselves.
+pub type FailoverView = ViewProtocolServiceClient<Failover>;
+
+/// Start an in-process view service for the given full viewing key, with in-memory storage,
+/// synchronizing from the given node.
+pub async fn in_memory(fvk: &FullViewingKey, node: Url) -> anyhow::Result<LocalView> {
+    Ok(ViewProtocolServiceClient::new(
+        start(fvk, node, None).await?,
+    ))
+}
+
+/// Start an in-process view service like [`in_memory`], but keeping its state in the database at
+/// the given path, so that it picks up where it left off (or where a snapshot did).
+pub async fn on_disk(fvk: &FullViewingKey, node: Url, path: &Path) -> anyhow::Result<LocalView> {
+    Ok(ViewProtocolServiceClient::new(
+        start(fvk, node, Some(path)).await?,
+    ))
+}
+
+async fn start(
+    fvk: &FullViewingKey,
+    node: Url,
+    path: Option<&Path>,
+) -> anyhow::Result<ViewProtocolServiceServer<ViewService>> {
+    // Without a path, the database is kept in memory
+    let path = path
+        .map(|path| camino::Utf8PathBuf::try_from(path.to_path_buf()))
+        .transpose()
+        .context("view database path must be valid UTF-8")?;
+    let view_storage = penumbra_view::Storage::load_or_initialize(path, fvk, node.clone()).await?;
+    let view_service = ViewService::new(view_storage, node).await?;
+
+    Ok(ViewProtocolServiceServer::new(view_service))
+}
+
+/// Start an in-process view service like [`in_memory`], using the first healthy node of those
+/// given, and checking its health at the given interval: if it becomes unhealthy, a new view
+/// service is synchronized from another healthy node and swapped in. Likewise, if the chain is
+/// reset, the view service is replaced by one synchronized with the new chain, and if its
+/// connection to the node is cut off (as when the node restarts), by one which reconnects.
+///
+/// After the given number of failures in a row to reach a node or reconnect, the view gives up,
+/// which [`gave_up`] reports.
+pub async fn failover(
+    fvk: &FullViewingKey,
+    nodes: Vec<Url>,
+    check_interval: Duration,
+    max_failures: usize,
+) -> anyhow::Result<FailoverView> {
+    failover_on_disk(fvk, nodes, check_interval, max_failures, None).await
+}
+
+/// Start a view service which fails over between nodes like [`failover`], keeping its state in the
+/// database at the given path, if any, so that reconnecting resumes from where it left off.
+/// Replacements after the chain is reset are kept in memory, since they start from scratch anyway.
+pub async fn failover_on_disk(
+    fvk: &FullViewingKey,
+    nodes: Vec<Url>,
+    check_interval: Duration,
+    max_failures: usize,
+    path: Option<&Path>,
+) -> anyhow::Result<FailoverView> {
+    let active = match node::first_healthy(&nodes).await {
+        Some(node) => node,
+        None => {
+            tracing::warn!("no node is healthy, trying the first one anyway");
+            nodes
+                .first()
+                .context("at least one node is required")?
+                .clone()
+        }
+    };
+    node::set_active(&nodes, &active);
+    let chain_id = node::status(&active)
+        .await
+        .map(|status| status.chain_id)
+        .unwrap_or_default();
+
+    let failover = Failover {
+        current: Arc::new(RwLock::new(start(fvk, active.clone(), path).await?)),
+    };
+
+    tokio::spawn(monitor(
+        fvk.clone(),
+        nodes,
+        active,
+        chain_id,
+        failover.clone(),
+        Reconnect {
+            check_interval,
+            max_failures,
+            path: path.map(Path::to_path_buf),
+        },
+    ));
+
+    Ok(ViewProtocolServiceClient::new(failover))
+}
+
+/// How a view reconnects to its node (or another) when it loses it.
+struct Reconnect {
+    /// How often to check the health of the active node and the view.
+    check_interval: Duration,
+    /// How many failures in a row to reach a node or reconnect before giving up.
+    max_failures: usize,
+    /// The database to resume from when reconnecting, if the view is kept on disk.
+    path: Option<PathBuf>,
+}
+
+/// Periodically check the health of the active node, failing over to another if it's unhealthy,
+/// reconnecting if the view lost its connection to it, and reinitializing the view if the chain
+/// was reset. Gives up once too many checks in a row fail.
+async fn monitor(
+    fvk: FullViewingKey,
+    nodes: Vec<Url>,
+    mut active: Url,
+    mut chain_id: String,
+    failover: Failover,
+    reconnect: Reconnect,
+) {
+    let path = reconnect.path.as_deref();
+    let mut failures = 0;
+    loop {
+        // Check again soon after a failure, backing off to the usual interval
+        let wait = match failures {
+            0 => reconnect.check_interval,
+            failures => backoff(failures, reconnect.check_interval),
+        };
+        tokio::time::sleep(wait).await;
+        if failures >= reconnect.max_failures {
+            let reason = format!(
+                "the faucet's view failed to reach a node {} times in a row",
+                failures
+            );
+            tracing::error!(node = %active, "{}", reason);
+            give_up(reason);
+            return;
+        }
+
+        match node::status(&active).await {
+            Ok(status) if !status.catching_up => {
+                // The view is for a chain which no longer exists, so it can't be used any more
+                if !chain_id.is_empty() && status.chain_id != chain_id {
+                    notice::send(format!(
+                        "Chain reset detected: the node is now on chain `{}` (was `{}`); reinitializing the faucet's view",
+                        status.chain_id, chain_id
+                    ));
+                    match replace(&fvk, &active, &failover, None).await {
+                        Ok(()) => notice::send("Faucet's view reinitialized for the new chain"),
+                        Err(e) => {
+                            tracing::error!(error = ?e, "failed to reinitialize view");
+                            failures += 1;
+                            continue;
+                        }
+                    }
+                }
+                chain_id = status.chain_id;
+
+                // The node is fine, but if it restarted, the view's connection to it was cut off
+                let mut view = ViewProtocolServiceClient::new(failover.clone());
+                if let Err(e) = ViewClient::status(&mut view, fvk.account_group_id()).await {
+                    tracing::warn!(node = %active, error = ?e, "view lost its node, reconnecting");
+                    match replace(&fvk, &active, &failover, path).await {
+                        Ok(()) => tracing::info!(node = %active, "view reconnected"),
+                        Err(e) => {
+                            tracing::error!(node = %active, error = ?e, "failed to reconnect view");
+                            failures += 1;
+                            continue;
+                        }
+                    }
+                }
+                failures = 0;
+                continue;
+            }
+            Ok(_) => tracing::warn!(node = %active, "active node is catching up"),
+            Err(e) => tracing::warn!(node = %active, error = ?e, "active node is unreachable"),
+        }
+        failures += 1;
+        if nodes.len() < 2 {
+            continue;
+        }
+        tracing::warn!(node = %active, "active node is unhealthy, failing over");
+
+        let others = nodes
+            .iter()
+            .filter(|node| **node != active)
+            .cloned()
+            .collect::<Vec<_>>();
+        let next = if let Some(next) = node::first_healthy(&others).await {
+            next
+        } else {
+            tracing::error!("no other node is healthy, staying with the active node");
+            continue;
+        };
+
+        match replace(&fvk, &next, &failover, path).await {
+            Ok(()) => {
+                node::set_active(&nodes, &next);
+                active = next;
+                // Pick up the chain id on the next check, in case it changed while we failed over
+                chain_id = String::new();
+                failures = 0;
+            }
+            Err(e) => {
+                tracing::error!(node = %next, error = ?e, "failed to fail over to node");
+            }
+        }
+    }
+}
+
+/// Replace the view service with a new one synchronized from the given node, resuming from the
+/// database at the given path, if any.
+async fn replace(
+    fvk: &FullViewingKey,
+    node: &Url,
+    failover: &Failover,
+    path: Option<&Path>,
+) -> anyhow::Result<()> {
+    // Synchronize the replacement fully before swapping it in, so that the faucet never
+    // dispenses from a view that's behind the chain
+    let server = start(fvk, node.clone(), path).await?;
+    sync(&mut ViewProtocolServiceClient::new(server.clone()), fvk).await?;
+    *failover.current.write().unwrap() = server;
+    Ok(())
+}
+
+/// How long to wait before trying to reconnect again after the given number of failures in a row:
+/// a second at first, doubling each time, up to the given maximum.
+pub fn backoff(failures: usize, max: Duration) -> Duration {
+    RECONNECT_BACKOFF
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(max)
+}
+
+/// Report that a view gave up on reaching its node, to whoever's waiting in [`gave_up`].
+fn give_up(reason: String) {
+    let mut gave_up = GAVE_UP.lock().unwrap();
+    for waiting in gave_up.1.drain(..) {
+        let _ = waiting.send(reason.clone());
+    }
+    gave_up.0.get_or_insert(reason);
+}
+
+/// Wait for a view to give up on reaching its node (see [`failover`]), returning why.
+pub async fn gave_up() -> String {
+    let waiting = {
+        let mut gave_up = GAVE_UP.lock().unwrap();
+        if let Some(reason) = &gave_up.0 {
+            return reason.clone();
+        }
+        let (tx, rx) = oneshot::channel();
+        gave_up.1.push(tx);
+        rx
+    };
+    // The sender is only dropped after sending
+    waiting.await.unwrap_or_default()
+}
+
+/// An in-process view service which can be replaced by another without disturbing its clients.
+#[derive(Clone)]
+pub struct Failover {
+    current: Arc<RwLock<ViewProtocolServiceServer<ViewService>>>,
+}
+
+impl Service<http::Request<BoxBody>> for Failover {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        // Requests already underway carry on with the service they started with
+        let mut server = self.current.read().unwrap().clone();
+        Box::pin(async move { server.call(request).await })
+    }
+}
+
+/// Connect to an external view service (such as `pclientd`) over gRPC.
+pub async fn remote(url: Url) -> anyhow::Result<ViewProtocolServiceClient<Channel>> {
+    ViewProtocolServiceClient::connect(url.to_string())
+        .await
+        .with_context(|| format!("could not connect to view service at {}", url))
+}
+
+/// How often to log how far synchronizing has got.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How far a view has synchronized with the chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    /// The height the view has synchronized up to.
+    pub sync_height: u64,
+    /// The latest height of the chain, as far as the view knows.
+    pub latest_height: u64,
+    /// How much longer synchronizing will take, at the rate it's going, once that's known.
+    pub eta: Option<Duration>,
+    /// Whether synchronizing is done.
+    pub done: bool,
+}
+
+impl SyncProgress {
+    /// How far along synchronizing is, as a percentage.
+    pub fn percent(&self) -> f64 {
+        if self.done || self.latest_height == 0 {
+            return if self.done { 100.0 } else { 0.0 };
+        }
+        (self.sync_height as f64 / self.latest_height as f64 * 100.0).min(100.0)
+    }
+
+    /// A line describing the progress, for people to read.
+    pub fn summary(&self) -> String {
+        if self.done {
+            return format!("Synchronized to height {}.", self.sync_height);
+        }
+        let mut summary = format!(
+            "Synchronizing: height {} of {} ({:.1}%)",
+            self.sync_height,
+            self.latest_height,
+            self.percent()
+        );
+        if let Some(eta) = self.eta {
+            summary.push_str(&format!(
+                ", about {} to go",
+                humantime::format_duration(Duration::from_secs(eta.as_secs()))
+            ));
+        }
+        summary
+    }
+}
+
+/// Wait for the view service to synchronize with the chain, logging its progress periodically.
+pub async fn sync<V: ViewClient>(view: &mut V, fvk: &FullViewingKey) -> anyhow::Result<()> {
+    sync_reporting(view, fvk, &watch::channel(SyncProgress::default()).0).await
+}
+
+/// Wait for the view service to synchronize with the chain like [`sync`], also publishing its
+/// progress as it goes.
+pub async fn sync_reporting<V: ViewClient>(
+    view: &mut V,
+    fvk: &FullViewingKey,
+    progress: &watch::Sender<SyncProgress>,
+) -> anyhow::Result<()> {
+    let mut updates = ViewClient::status_stream(view, fvk.account_group_id()).await?;
+    let start = Instant::now();
+    let mut start_height = None;
+    let mut last_logged = Instant::now();
+    let mut current = SyncProgress::default();
+    while let Some(update) = updates.try_next().await? {
+        let start_height = *start_height.get_or_insert(update.sync_height);
+        let synced = update.sync_height.saturating_sub(start_height);
+        let remaining = update
+            .latest_known_block_height
+            .saturating_sub(update.sync_height);
+        current = SyncProgress {
+            sync_height: update.sync_height,
+            latest_height: update.latest_known_block_height,
+            eta: (synced > 0).then(|| start.elapsed().mul_f64(remaining as f64 / synced as f64)),
+            done: false,
+        };
+        progress.send_replace(current);
+        if last_logged.elapsed() >= PROGRESS_INTERVAL {
+            last_logged = Instant::now();
+            tracing::info!(
+                sync_height = current.sync_height,
+                latest_height = current.latest_height,
+                "{}",
+                current.summary()
+            );
+        }
+    }
+    current.done = true;
+    current.eta = None;
+    progress.send_replace(current);
+    Ok(())
+}
+
+/// The total unspent balance of each asset in the wallet, as far as the view has synchronized.
+pub async fn balances<V: ViewClient>(
+    view: &mut V,
+    fvk: &FullViewingKey,
+) -> anyhow::Result<Vec<(asset::Id, u128)>> {
+    let notes = view
+        .unspent_notes_by_asset_and_address(fvk.account_group_id())
+        .await?;
+    Ok(notes
+        .into_iter()
+        .map(|(asset_id, by_address)| {
+            let total = by_address
+                .values()
+                .flatten()
+                .map(|record| record.note.amount().value())
+                .sum();
+            (asset_id, total)
+        })
+        .collect())
+}
+
+/// Check the wallet's balances at the given interval in the background, returning a receiver
+/// which always holds the latest (or nothing, before the first check succeeds).
+pub fn watch_balances<V>(
+    mut view: V,
+    fvk: FullViewingKey,
+    interval: Duration,
+) -> watch::Receiver<Option<Vec<(asset::Id, u128)>>>
+where
+    V: ViewClient + Send + 'static,
+{
+    let (sender, receiver) = watch::channel(None);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match balances(&mut view, &fvk).await {
+                Ok(balances) => {
+                    if sender.send(Some(balances)).is_err() {
+                        // No one is watching any more
+                        return;
+                    }
+                }
+                Err(e) => tracing::warn!(error = ?e, "failed to check balances"),
+            }
+        }
+    });
+    receiver
+}
@@ -1,7 +1,16 @@
 use anyhow::Context;
-use penumbra_keys::keys::SpendKey;
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use penumbra_keys::keys::{SeedPhrase, SpendKey};
+use rand::{rngs::OsRng, RngCore};
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{fs::OpenOptions, path::Path, str::FromStr};
+
+/// The environment variable holding the passphrase for an encrypted custody file.
+pub const PASSPHRASE_VAR: &str = "GALILEO_CUSTODY_PASSPHRASE";
 
 /// A wallet file storing a single spend authority.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,11 +18,35 @@ pub struct Wallet {
     pub spend_key: SpendKey,
 }
 
+/// The contents of a custody file, encrypted with a key derived from a passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Encrypted {
+    /// The salt for deriving the key from the passphrase with Argon2id, in hex.
+    salt: String,
+    /// The ChaCha20-Poly1305 nonce, in hex.
+    nonce: String,
+    /// The encrypted custody file, in hex.
+    ciphertext: String,
+}
+
 impl Wallet {
     /// Read the wallet data from the provided path.
+    ///
+    /// If the custody file is encrypted (see [`Wallet::encrypt`]), the passphrase is read from the
+    /// `GALILEO_CUSTODY_PASSPHRASE` environment variable.
     pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
-        let custody_json: serde_json::Value =
+        let mut custody_json: serde_json::Value =
             serde_json::from_slice(std::fs::read(path)?.as_slice())?;
+        if let Some(encrypted) = custody_json.get("encrypted") {
+            let encrypted: Encrypted = serde_json::from_value(encrypted.clone())?;
+            let passphrase = std::env::var(PASSPHRASE_VAR).with_context(|| {
+                format!(
+                    "custody file is encrypted, but {} is not set",
+                    PASSPHRASE_VAR
+                )
+            })?;
+            custody_json = serde_json::from_slice(&decrypt(&encrypted, &passphrase)?)?;
+        }
         let sk_str = match custody_json["spend_key"].as_str() {
             Some(s) => s,
             None => {
@@ -26,4 +59,107 @@ impl Wallet {
             .context(format!("Could not create SpendKey from string: {}", sk_str))?;
         Ok(Self { spend_key })
     }
+
+    /// Parse a spend key, or a seed phrase (such as the one a devnet's genesis allocation is made
+    /// to), in which case the first account's spend key is used.
+    pub fn from_key(key: &str) -> anyhow::Result<Self> {
+        let key = key.trim();
+        let spend_key = match SpendKey::from_str(key) {
+            Ok(spend_key) => spend_key,
+            Err(_) => {
+                let seed_phrase =
+                    SeedPhrase::from_str(key).context("not a spend key or a seed phrase")?;
+                SpendKey::from_seed_phrase_bip39(seed_phrase, 0)
+            }
+        };
+        Ok(Self { spend_key })
+    }
+
+    /// Make sure there's a custody file at the given path holding the key: write a new
+    /// (unencrypted) one if there's none, or check that the existing one holds the same key.
+    pub fn provision(path: impl AsRef<Path>, key: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let wallet = Self::from_key(key)?;
+        if path.exists() {
+            if Self::load(path)?.spend_key.to_string() != wallet.spend_key.to_string() {
+                anyhow::bail!(
+                    "custody file {} holds a different key; remove it to start from the new one",
+                    path.display()
+                );
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(path)
+            .with_context(|| format!("could not create {}", path.display()))?;
+        serde_json::to_writer_pretty(
+            file,
+            &serde_json::json!({ "spend_key": wallet.spend_key.to_string() }),
+        )?;
+        tracing::info!(path = %path.display(), "created custody file from key");
+        Ok(())
+    }
+
+    /// Encrypt the custody file at the given path with the passphrase, returning the contents of
+    /// the encrypted custody file.
+    pub fn encrypt(path: impl AsRef<std::path::Path>, passphrase: &str) -> anyhow::Result<String> {
+        let plaintext = std::fs::read(path)?;
+        // Make sure we're not encrypting garbage (or an already-encrypted file)
+        let custody_json: serde_json::Value = serde_json::from_slice(&plaintext)?;
+        if custody_json.get("spend_key").is_none() {
+            anyhow::bail!("'spend_key' field not found in custody JSON file");
+        }
+
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?)
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("could not encrypt custody file"))?;
+
+        let encrypted = Encrypted {
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        Ok(serde_json::to_string_pretty(
+            &serde_json::json!({ "encrypted": encrypted }),
+        )?)
+    }
+}
+
+/// Derive the encryption key for a custody file from its passphrase.
+fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| anyhow::anyhow!("could not derive key from passphrase: {}", e))?;
+    Ok(key)
+}
+
+/// Decrypt the contents of an encrypted custody file.
+fn decrypt(encrypted: &Encrypted, passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    let salt = hex::decode(&encrypted.salt).context("invalid salt")?;
+    let nonce = hex::decode(&encrypted.nonce).context("invalid nonce")?;
+    let ciphertext = hex::decode(&encrypted.ciphertext).context("invalid ciphertext")?;
+    if nonce.len() != 12 {
+        anyhow::bail!("invalid nonce length");
+    }
+
+    ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("could not decrypt custody file: wrong passphrase?"))
 }